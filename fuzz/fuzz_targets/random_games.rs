@@ -0,0 +1,22 @@
+// Plays random games, each input byte selects the next move.
+// cargo +nightly fuzz run random_games
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use std::sync::{Mutex, OnceLock};
+use tiny_chess::engine::{self, Game};
+
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+// reused, as new_game() allocates a large transposition table
+static GAME: OnceLock<Mutex<Game>> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    let mut g = GAME
+        .get_or_init(|| Mutex::new(engine::new_game()))
+        .lock()
+        .unwrap();
+    common::play_random_game(&mut g, data.iter().map(|&b| b as usize));
+});
@@ -0,0 +1,295 @@
+// The analysis of a finished game: each position is searched to a fixed depth, and each
+// move is judged by its centipawn loss, the difference between the score of the best move
+// and the score after the move of the game. Without threads, see handle.rs for the GUI.
+// The positions are searched by a Backend, the own engine or e.g. an external UCI engine.
+
+use crate::engine::{self, Game, GameState, Move, SearchInfo, SearchObserver};
+use alloc::vec::Vec;
+
+// scores are clamped, so a missed mate costs as much as a lost queen with a pawn
+const MAX_SCORE: i64 = 1000;
+
+// the values of VOID_ID..=KING_ID in pawns, for sacrifices
+const PIECE_VALUES: [i64; 7] = [0, 1, 3, 3, 5, 9, 0];
+
+// The judgments of the moves, with the NAG of the move list and of PGN: a blunder "??",
+// a mistake "?" and an inaccuracy "?!" by their loss, a good move "!" is the best move
+// after a mistake of the opponent, and a brilliant move "!!" is a best move sacrificing
+// a piece.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Judgment {
+    Brilliant,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgment {
+    pub fn nag(self) -> u8 {
+        match self {
+            Judgment::Good => 1,
+            Judgment::Mistake => 2,
+            Judgment::Brilliant => 3,
+            Judgment::Blunder => 4,
+            Judgment::Inaccuracy => 6,
+        }
+    }
+}
+
+// the centipawn losses of the judgments, and the loss of the opponent for a good move
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Thresholds {
+    pub inaccuracy: i64,
+    pub mistake: i64,
+    pub blunder: i64,
+    pub good: i64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+            good: 100,
+        }
+    }
+}
+
+// the judgment of a move by its loss alone
+pub fn judgment(loss: i64, t: &Thresholds) -> Option<Judgment> {
+    if loss >= t.blunder {
+        Some(Judgment::Blunder)
+    } else if loss >= t.mistake {
+        Some(Judgment::Mistake)
+    } else if loss >= t.inaccuracy {
+        Some(Judgment::Inaccuracy)
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AnalyzedMove {
+    pub mv: Move,    // the move of the game
+    pub best: Move,  // the best move of the search
+    pub white: bool, // mv is a move of White
+    pub score: i64,  // after mv, in centipawns from White's point of view
+    pub loss: i64,   // in centipawns, 0 for the best move
+}
+
+impl AnalyzedMove {
+    pub fn is_best(&self) -> bool {
+        let (m, b) = (&self.mv, &self.best);
+        (m.src, m.dst, m.promote_to) == (b.src, b.dst, b.promote_to)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub depth: i64,
+    pub move_counter: u16,        // of the start position, for the move numbers
+    pub moves: Vec<AnalyzedMove>, // the moves leading to the analyzed position
+}
+
+impl Report {
+    // The judgment of the move with index i. A sacrifice leaves a piece where the best reply
+    // captures it for less, the sacrifice of a pawn is not brilliant.
+    pub fn judgment(&self, i: usize, t: &Thresholds) -> Option<Judgment> {
+        let m = &self.moves[i];
+        if !m.is_best() {
+            return judgment(m.loss, t);
+        }
+        let value = |f: engine::FigureID| PIECE_VALUES[f.unsigned_abs() as usize];
+        let sacrifice = self.moves.get(i + 1).is_some_and(|reply| {
+            reply.best.dst == m.mv.dst
+                && m.mv.piece.abs() != engine::PAWN_ID
+                && value(m.mv.piece) > value(m.mv.captured)
+                && value(reply.best.piece) < value(m.mv.piece)
+        });
+        if sacrifice {
+            Some(Judgment::Brilliant)
+        } else if i > 0 && self.moves[i - 1].loss >= t.good {
+            Some(Judgment::Good)
+        } else {
+            None
+        }
+    }
+
+    // the average centipawn loss of the moves of White or Black
+    pub fn average_loss(&self, white: bool) -> i64 {
+        let losses: Vec<i64> = self
+            .moves
+            .iter()
+            .filter(|m| m.white == white)
+            .map(|m| m.loss)
+            .collect();
+        losses.iter().sum::<i64>() / (losses.len() as i64).max(1)
+    }
+
+    pub fn count(&self, white: bool, judgment: Judgment, t: &Thresholds) -> usize {
+        let judged = (0..self.moves.len()).filter(|&i| self.moves[i].white == white);
+        judged
+            .filter(|&i| self.judgment(i, t) == Some(judgment))
+            .count()
+    }
+
+    // the indices of the at most count moves with the largest loss, at least mistakes,
+    // in the order of the game
+    pub fn critical_moments(&self, count: usize, t: &Thresholds) -> Vec<usize> {
+        let mut moments: Vec<usize> = (0..self.moves.len())
+            .filter(|&i| self.moves[i].loss >= t.mistake)
+            .collect();
+        moments.sort_by_key(|&i| -self.moves[i].loss);
+        moments.truncate(count);
+        moments.sort();
+        moments
+    }
+}
+
+// Follows analyze_game(), all methods do nothing by default.
+pub trait AnalysisObserver {
+    // called before the search of the position after ply moves, with the number of moves
+    fn on_position(&mut self, _ply: usize, _plies: usize) {}
+    // polled during the searches, true cancels the analysis
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+}
+
+impl AnalysisObserver for () {}
+
+// Searches the positions of the analysis, which are never over. None when the search
+// was canceled by the observer or failed.
+pub trait Backend {
+    fn search(
+        &mut self,
+        g: &mut Game,
+        depth: i64,
+        observer: &mut dyn AnalysisObserver,
+    ) -> Option<SearchInfo>;
+}
+
+// the own engine, searching with the options of the analyzed game
+pub struct BuiltIn;
+
+// ends reply() after the iteration of the given depth
+struct DepthLimit<'a> {
+    depth: i64,
+    info: Option<SearchInfo>, // of the last completed iteration
+    observer: &'a mut dyn AnalysisObserver,
+}
+
+impl SearchObserver for DepthLimit<'_> {
+    fn on_depth_complete(&mut self, info: &SearchInfo) {
+        self.info = Some(info.clone());
+    }
+    fn should_stop(&mut self) -> bool {
+        self.info.as_ref().is_some_and(|i| i.depth >= self.depth) || self.observer.should_stop()
+    }
+}
+
+impl Backend for BuiltIn {
+    fn search(
+        &mut self,
+        g: &mut Game,
+        depth: i64,
+        observer: &mut dyn AnalysisObserver,
+    ) -> Option<SearchInfo> {
+        let mut limit = DepthLimit {
+            depth,
+            info: None,
+            observer,
+        };
+        engine::reply(g, &mut limit);
+        if limit.observer.should_stop() {
+            return None;
+        }
+        limit.info
+    }
+}
+
+// the clamped score of the position for the active player and the best move,
+// None when the search was canceled
+fn search(
+    g: &mut Game,
+    depth: i64,
+    backend: &mut dyn Backend,
+    observer: &mut dyn AnalysisObserver,
+) -> Option<(i64, Option<Move>)> {
+    match engine::state(g) {
+        GameState::Ongoing => {}
+        GameState::Checkmate(_) => return Some((-MAX_SCORE, None)),
+        GameState::VariantWin(winner) => {
+            let active = 1 - g.move_counter as i64 % 2 * 2;
+            let score = if winner == active {
+                MAX_SCORE
+            } else {
+                -MAX_SCORE
+            };
+            return Some((score, None));
+        }
+        _ => return Some((0, None)),
+    }
+    let info = backend.search(g, depth, observer)?;
+    Some((
+        info.score.clamp(-MAX_SCORE, MAX_SCORE),
+        Some(info.best_move),
+    ))
+}
+
+// Searches the positions of the moves leading to the current position of g to the given
+// depth, or until MoveTime, which is set to its maximum. None when canceled by observer.
+pub fn analyze_game(g: &Game, depth: i64, observer: &mut dyn AnalysisObserver) -> Option<Report> {
+    analyze_game_with(g, depth, &mut BuiltIn, observer)
+}
+
+// analyze_game() with the searches of another backend, None when it fails as well
+pub fn analyze_game_with(
+    g: &Game,
+    depth: i64,
+    backend: &mut dyn Backend,
+    observer: &mut dyn AnalysisObserver,
+) -> Option<Report> {
+    let mut g = g.clone();
+    let _ = engine::set_option(&mut g, "MoveTime", "9000");
+    // the analysis runs next to the engine of the game, a small table is enough for its depth
+    let _ = engine::set_option(&mut g, "Hash", "8");
+    let plies = engine::game_ply(&g);
+    engine::go_to_ply(&mut g, 0);
+    let move_counter = g.move_counter;
+    // for each position the score for the active player and the best move
+    let mut searched = Vec::new();
+    for ply in 0..=plies {
+        observer.on_position(ply, plies);
+        searched.push(search(&mut g, depth, backend, observer)?);
+        if ply < plies {
+            engine::redo_move(&mut g);
+        }
+    }
+    let moves = engine::move_list(&g);
+    let moves = (0..plies).map(|ply| {
+        let (before, best) = searched[ply];
+        let after = searched[ply + 1].0;
+        let mv = moves[ply];
+        let white = (move_counter as usize + ply).is_multiple_of(2);
+        let mut analyzed = AnalyzedMove {
+            mv,
+            best: best.unwrap_or(mv),
+            white,
+            score: if white { -after } else { after },
+            loss: (before + after).max(0),
+        };
+        if analyzed.is_best() {
+            analyzed.loss = 0;
+        }
+        analyzed
+    });
+    Some(Report {
+        depth,
+        move_counter,
+        moves: moves.collect(),
+    })
+}
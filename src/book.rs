@@ -0,0 +1,318 @@
+// An opening book in the file format of Polyglot: entries of 16 bytes in big endian, the
+// key of the position (u64), the move (u16), its weight (u16) and a learn value (u32),
+// sorted by the key. The engine plays a move of the book with a probability of its weight.
+//
+// The keys are those of Polyglot, see engine::polyglot_key(), so the books of other programs
+// can be used, and the books of this engine by them. The moves are encoded as by Polyglot:
+// the destination in bits 0-5, the source in bits 6-11 (square a1 = 0, b1 = 1 ... h8 = 63),
+// the promotion in bits 12-14, and castling as the king capturing its rook, i.e. e1h1 for
+// O-O.
+//
+// A book is built from the games of a PGN collection, filtered by their result, the
+// ratings of the players and the number of plies. The book editor of the GUI changes the
+// weights, adds moves and deletes lines.
+
+use crate::engine::{self, Game, Move, MoveKind, PgnError, Position};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use core::fmt;
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32, // unused by the engine, kept when a book is saved again
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BookError {
+    Size(usize), // the file size is not a multiple of the entry size
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BookError::Size(n) => write!(f, "a book of {} bytes is not a Polyglot book", n),
+        }
+    }
+}
+
+impl core::error::Error for BookError {}
+
+// Polyglot square of an engine position, see the board layout in engine.rs
+fn square(p: Position) -> u16 {
+    let (file, row) = (7 - p as u16 % 8, p as u16 / 8);
+    row * 8 + file
+}
+
+// the move in the encoding of Polyglot
+pub fn encode(m: &Move) -> u16 {
+    let dst = match m.kind {
+        MoveKind::ShortCastling => m.dst - 1, // to the h file
+        MoveKind::LongCastling => m.dst + 2,  // to the a file
+        _ => m.dst,
+    };
+    let promotion = if m.promote_to == engine::VOID_ID {
+        0
+    } else {
+        m.promote_to.unsigned_abs() as u16 - 1 // the knight is 1, the queen 4
+    };
+    promotion << 12 | square(m.src) << 6 | square(dst)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+    entries: Vec<Entry>, // sorted by key, the highest weight first
+}
+
+impl Book {
+    pub fn new(mut entries: Vec<Entry>) -> Self {
+        entries.sort_by_key(|e| (e.key, core::cmp::Reverse(e.weight)));
+        Book { entries }
+    }
+
+    fn sort(&mut self) {
+        self.entries
+            .sort_by_key(|e| (e.key, core::cmp::Reverse(e.weight)));
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::Size(bytes.len()));
+        }
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|b| Entry {
+                key: u64::from_be_bytes(b[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes([b[8], b[9]]),
+                weight: u16::from_be_bytes([b[10], b[11]]),
+                learn: u32::from_be_bytes(b[12..16].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Book::new(entries))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for e in &self.entries {
+            bytes.extend_from_slice(&e.key.to_be_bytes());
+            bytes.extend_from_slice(&e.mv.to_be_bytes());
+            bytes.extend_from_slice(&e.weight.to_be_bytes());
+            bytes.extend_from_slice(&e.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    // the number of positions
+    pub fn positions(&self) -> usize {
+        let mut keys: Vec<u64> = self.entries.iter().map(|e| e.key).collect();
+        keys.dedup();
+        keys.len()
+    }
+
+    fn position(&self, key: u64) -> &[Entry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+
+    // the legal moves of the book in the position of g with their weights, the highest
+    // weight first
+    pub fn moves(&self, g: &Game) -> Vec<(Move, u16)> {
+        let legal: Vec<Move> = engine::legal_moves(g).collect();
+        self.position(engine::polyglot_key(g))
+            .iter()
+            .filter_map(|e| {
+                let m = legal.iter().find(|m| encode(m) == e.mv)?;
+                Some((*m, e.weight))
+            })
+            .collect()
+    }
+
+    // A move with a probability of its weight, random is any random number. None when the
+    // position is not in the book, or all its moves have the weight 0.
+    pub fn pick(&self, g: &Game, random: u64) -> Option<Move> {
+        let moves = self.moves(g);
+        let total: u64 = moves.iter().map(|&(_, w)| w as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut r = random % total;
+        for (m, w) in moves {
+            if r < w as u64 {
+                return Some(m);
+            }
+            r -= w as u64;
+        }
+        None
+    }
+
+    // the weight of the move m in the position of g, which is added when it is new
+    pub fn set_weight(&mut self, g: &Game, m: &Move, weight: u16) {
+        let (key, mv) = (engine::polyglot_key(g), encode(m));
+        match self.entries.iter_mut().find(|e| e.key == key && e.mv == mv) {
+            Some(e) => e.weight = weight,
+            None => self.entries.push(Entry {
+                key,
+                mv,
+                weight,
+                learn: 0,
+            }),
+        }
+        self.sort();
+    }
+
+    // Delete the move m of the position of g with its line: the positions after it, which
+    // are not reached from the start position of g without it.
+    pub fn remove(&mut self, g: &Game, m: &Move) {
+        let mut line = engine::new_game_with_hash(1);
+        engine::set_position(&mut line, g);
+        let (key, mv) = (engine::polyglot_key(g), encode(m));
+        self.entries.retain(|e| (e.key, e.mv) != (key, mv));
+        let token = engine::do_move(&mut line, m.src, m.dst, m.promote_to, false);
+        let mut removed = BTreeSet::new();
+        self.reached(&mut line, &mut removed);
+        engine::undo_move(&mut line, token);
+        engine::go_to_ply(&mut line, 0);
+        let mut kept = BTreeSet::new();
+        self.reached(&mut line, &mut kept);
+        self.entries
+            .retain(|e| !removed.contains(&e.key) || kept.contains(&e.key));
+    }
+
+    // the keys of the positions of the book reached from the position of g
+    fn reached(&self, g: &mut Game, keys: &mut BTreeSet<u64>) {
+        let key = engine::polyglot_key(g);
+        if self.position(key).is_empty() || !keys.insert(key) {
+            return;
+        }
+        for (m, _) in self.moves(g) {
+            let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+            self.reached(g, keys);
+            engine::undo_move(g, token);
+        }
+    }
+}
+
+// which moves of a game are counted
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Results {
+    All, // the moves of both players
+    #[default]
+    NoLosses, // not the moves of the losing player
+    Wins, // only the moves of the winning player, no draws
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Filter {
+    pub plies: usize,     // the moves of a game after this many plies are not counted
+    pub min_rating: u32,  // of both players, by the tags WhiteElo and BlackElo
+    pub results: Results, // unfinished games are always skipped
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            plies: 20,
+            min_rating: 0,
+            results: Results::default(),
+        }
+    }
+}
+
+// Collects the moves of games, a move gets 2 points for each win and 1 point for each
+// draw or loss of the player who made it. The points become the weights of the book.
+pub struct Builder {
+    game: Game, // for the moves of the added games
+    filter: Filter,
+    points: BTreeMap<(u64, u16), u32>,
+}
+
+impl Builder {
+    pub fn new(filter: Filter) -> Self {
+        Builder {
+            game: engine::new_game_with_hash(1),
+            filter,
+            points: BTreeMap::new(),
+        }
+    }
+
+    // the main line of a game, Ok(false) when the filter skips it
+    pub fn add_game(&mut self, pgn: &str) -> Result<bool, PgnError> {
+        let tags = engine::set_pgn(&mut self.game, pgn)?;
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(n, _)| n == name)
+                .map_or("", |(_, v)| v.as_str())
+        };
+        let rating = |name: &str| tag(name).parse::<u32>().unwrap_or(0);
+        if rating("WhiteElo").min(rating("BlackElo")) < self.filter.min_rating {
+            return Ok(false);
+        }
+        // the points of White and Black
+        let points = match tag("Result") {
+            "1-0" => [2, 1],
+            "0-1" => [1, 2],
+            "1/2-1/2" => [1, 1],
+            _ => return Ok(false),
+        };
+        let counted = |color: usize| match self.filter.results {
+            Results::All => true,
+            Results::NoLosses => points[color] >= points[1 - color],
+            Results::Wins => points[color] > points[1 - color],
+        };
+        let counted = [counted(0), counted(1)];
+        if counted == [false, false] {
+            return Ok(false);
+        }
+        let moves = engine::move_list(&self.game);
+        engine::go_to_ply(&mut self.game, 0);
+        for m in moves.iter().take(self.filter.plies) {
+            let color = self.game.move_counter as usize % 2;
+            if counted[color] {
+                let key = (engine::polyglot_key(&self.game), encode(m));
+                *self.points.entry(key).or_default() += points[color];
+            }
+            engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false);
+        }
+        Ok(true)
+    }
+
+    // the games of a PGN collection, invalid games are skipped. The number of added games.
+    pub fn add_pgn(&mut self, text: &str) -> usize {
+        let added = engine::split_pgn(text)
+            .into_iter()
+            .filter(|game| matches!(self.add_game(game), Ok(true)));
+        added.count()
+    }
+
+    // the weights are scaled down when the points exceed u16
+    pub fn book(&self) -> Book {
+        let max = self.points.values().copied().max().unwrap_or(0) as u64;
+        let scale = |p: u32| {
+            if max <= u16::MAX as u64 {
+                p as u16
+            } else {
+                (p as u64 * u16::MAX as u64 / max).max(1) as u16
+            }
+        };
+        let entries = self
+            .points
+            .iter()
+            .map(|(&(key, mv), &p)| Entry {
+                key,
+                mv,
+                weight: scale(p),
+                learn: 0,
+            })
+            .collect();
+        Book::new(entries)
+    }
+}
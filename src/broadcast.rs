@@ -0,0 +1,181 @@
+// Live output of the current game for streams, i.e. for the text and browser sources of
+// OBS. The files in a directory are replaced on each change:
+//
+//   live.fen    the FEN of the position
+//   live.pgn    the game up to the position
+//   live.txt    the last move with its number, i.e. "12... Nf6"
+//   live.json   all of them: {"fen": ..., "pgn": ..., "lastMove": ...}
+//
+// The Endpoint serves the same over HTTP on localhost, GET /fen, /pgn, /move and /json,
+// for tools which poll a URL. Needs std.
+
+use crate::engine::{self, Game};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 8090;
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50); // to notice a stop
+const READ_TIMEOUT: Duration = Duration::from_secs(5); // for clients which send no request
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Live {
+    pub fen: String,
+    pub pgn: String,
+    pub last_move: String, // empty at the start of the game
+}
+
+impl Live {
+    // the position of g, with the PGN of the game from the frontend, which knows the players
+    pub fn new(g: &Game, pgn: String) -> Self {
+        let ply = engine::game_ply(g);
+        let last_move = match ply.checked_sub(1) {
+            Some(i) => {
+                let m = engine::move_list(g)[i];
+                let counter = g.move_counter as usize - 1; // of the last move
+                format!("{}{} {}", counter / 2 + 1, [".", "..."][counter % 2], m)
+            }
+            None => String::new(),
+        };
+        Live {
+            fen: engine::fen(g),
+            pgn,
+            last_move,
+        }
+    }
+
+    pub fn json(&self) -> String {
+        format!(
+            "{{\"fen\": {}, \"pgn\": {}, \"lastMove\": {}}}",
+            json_string(&self.fen),
+            json_string(&self.pgn),
+            json_string(&self.last_move)
+        )
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// a file is written under another name first, so a reader never sees half of it
+fn replace(dir: &Path, name: &str, text: &str) -> io::Result<()> {
+    let path = dir.join(name);
+    let partial = dir.join(format!(".{}.partial", name));
+    fs::write(&partial, text)?;
+    fs::rename(&partial, &path)
+}
+
+pub fn write_files(dir: &Path, live: &Live) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    replace(dir, "live.fen", &live.fen)?;
+    replace(dir, "live.pgn", &live.pgn)?;
+    replace(dir, "live.txt", &live.last_move)?;
+    replace(dir, "live.json", &live.json())
+}
+
+fn respond(stream: &mut TcpStream, live: &Mutex<Live>) -> io::Result<()> {
+    let mut reader = BufReader::new(&mut *stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    // the headers are read, unread bytes would reset the connection at the close
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = line.split_whitespace().nth(1).unwrap_or_default();
+    let live = live.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let (status, kind, body) = match path {
+        "/fen" => ("200 OK", "text/plain", live.fen),
+        "/pgn" => ("200 OK", "application/x-chess-pgn", live.pgn),
+        "/move" => ("200 OK", "text/plain", live.last_move),
+        "/json" => ("200 OK", "application/json", live.json()),
+        _ => ("404 Not Found", "text/plain", "Not found".to_owned()),
+    };
+    // overlays are pages of other origins
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: {}; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Cache-Control: no-store\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        kind,
+        body.len(),
+        body
+    )
+}
+
+pub struct Endpoint {
+    live: Arc<Mutex<Live>>,
+    closed: Arc<AtomicBool>,
+    port: u16,
+}
+
+impl Endpoint {
+    // serve on the port of localhost, 0 chooses a free one
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        let endpoint = Endpoint {
+            live: Arc::new(Mutex::new(Live::default())),
+            closed: Arc::new(AtomicBool::new(false)),
+            port: listener.local_addr()?.port(),
+        };
+        let (live, closed) = (Arc::clone(&endpoint.live), Arc::clone(&endpoint.closed));
+        thread::spawn(move || {
+            while !closed.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    // each connection in its own thread, a slow client does not block the others
+                    Ok((mut stream, _)) => {
+                        let live = Arc::clone(&live);
+                        thread::spawn(move || {
+                            let _ = stream
+                                .set_nonblocking(false)
+                                .and_then(|_| stream.set_read_timeout(Some(READ_TIMEOUT)))
+                                .and_then(|_| respond(&mut stream, &live));
+                        });
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL)
+                    }
+                    Err(_) => return,
+                }
+            }
+        });
+        Ok(endpoint)
+    }
+
+    pub fn update(&self, live: Live) {
+        *self.live.lock().unwrap_or_else(|e| e.into_inner()) = live;
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+// the server thread ends at its next poll
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
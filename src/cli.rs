@@ -0,0 +1,691 @@
+// Subcommands of the tiny-chess binary, which drive the engine from shell scripts and CI
+// without opening a window:
+//
+//   tiny-chess analyze [--fen FEN] [--depth N]
+//   tiny-chess selfplay [--games N] [--tc MINUTES+INCREMENT] [--pgn FILE]
+//   tiny-chess perft [--fen FEN] [--depth N]
+//   tiny-chess epd FILE [--time MS]
+//   tiny-chess book FILE [--out FILE] [--plies N] [--min-rating N] [--results all|nolosses|wins]
+//   tiny-chess match [--engine1 ENGINE] [--engine2 ENGINE] [--options1 NAME=VALUE,...]
+//                    [--options2 NAME=VALUE,...] [--games N] [--tc MINUTES+INCREMENT]
+//                    [--book FILE] [--pgn FILE] [--sprt elo0=0,elo1=5,alpha=0.05,beta=0.05]
+//   tiny-chess --serve [ADDRESS]
+//
+// The output is plain text, the lines of analyze look like the info lines of UCI.
+
+use crate::book::{self, Results};
+use crate::eco;
+use crate::engine::{self, Game, GameState, SearchInfo, SearchObserver};
+use crate::epd;
+use crate::tournament::{self, Adjudication, Decision, Sprt};
+use std::fmt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "Usage:
+  tiny-chess analyze [--fen FEN] [--depth N]
+  tiny-chess selfplay [--games N] [--tc MINUTES+INCREMENT] [--pgn FILE]
+  tiny-chess perft [--fen FEN] [--depth N]
+  tiny-chess epd FILE [--time MS]
+  tiny-chess book FILE [--out FILE] [--plies N] [--min-rating N] [--results all|nolosses|wins]
+  tiny-chess match [--engine1 ENGINE] [--engine2 ENGINE] [--options1 NAME=VALUE,...]
+                   [--options2 NAME=VALUE,...] [--games N] [--tc MINUTES+INCREMENT]
+                   [--book FILE] [--pgn FILE] [--sprt elo0=0,elo1=5,alpha=0.05,beta=0.05]
+ENGINE is builtin or the path of a UCI engine, the book a PGN or EPD file. With --sprt,
+the match ends when the test accepts a hypothesis, or after --games, which defaults
+to 100000. The book is built from the games of a PGN file, book.bin by default.
+  tiny-chess --serve [ADDRESS]
+serves a JSON API over HTTP and WebSocket at ADDRESS, 127.0.0.1:8080 by default.";
+
+// a game of selfplay is a draw after this many plies
+const MAX_PLIES: usize = 400;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cli {
+    Analyze {
+        fen: Option<String>,
+        depth: i64,
+    },
+    Selfplay {
+        games: usize,
+        tc: (Duration, Duration), // the time for the game and the increment per move
+        pgn: Option<String>,      // the file for the games
+    },
+    Perft {
+        fen: Option<String>,
+        depth: u32,
+    },
+    // search each position of a test suite for time milliseconds
+    Epd {
+        path: String,
+        time: u32,
+    },
+    // a book of the games of a PGN file, see book.rs
+    Book {
+        path: String,
+        out: String,
+        filter: book::Filter,
+    },
+    // a match between two engines, builtin or the path of a UCI engine
+    Match {
+        engines: [String; 2],
+        options: [Vec<(String, String)>; 2],
+        games: usize,
+        tc: (Duration, Duration),
+        book: Option<String>, // a PGN file, or an EPD file by its extension
+        pgn: Option<String>,
+        sprt: Option<Sprt>, // for the first engine
+    },
+    // the API of server.rs, needs the server feature
+    Serve {
+        address: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownFlag(String),
+    MissingValue(String),         // of the flag
+    InvalidValue(String, String), // the flag and its value
+    Fen(engine::FenError),
+    Engine(String),        // an engine of a match could not be started
+    Feature(&'static str), // the command needs a feature of Cargo.toml
+    Io(std::io::Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::UnknownCommand(c) => write!(f, "unknown command {}", c),
+            CliError::UnknownFlag(flag) => write!(f, "unknown flag {}", flag),
+            CliError::MissingValue(flag) => write!(f, "{} needs a value", flag),
+            CliError::InvalidValue(flag, value) => write!(f, "invalid value {} of {}", value, flag),
+            CliError::Fen(e) => e.fmt(f),
+            CliError::Engine(e) => f.write_str(e),
+            CliError::Feature(feature) => write!(f, "built without the {} feature", feature),
+            CliError::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+// a time control like "1+0.1", the minutes for the game and the seconds per move
+pub fn parse_tc(tc: &str) -> Option<(Duration, Duration)> {
+    let (minutes, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+    let minutes: f64 = minutes.trim().parse().ok()?;
+    let increment: f64 = increment.trim().parse().ok()?;
+    Some((
+        Duration::try_from_secs_f64(minutes * 60.0).ok()?,
+        Duration::try_from_secs_f64(increment).ok()?,
+    ))
+}
+
+// options like "Hash=64,MoveTime=500"
+pub fn parse_options(options: &str) -> Option<Vec<(String, String)>> {
+    let options = options.split(',').filter(|o| !o.trim().is_empty());
+    let option = |o: &str| {
+        let (name, value) = o.split_once('=')?;
+        Some((name.trim().to_owned(), value.trim().to_owned()))
+    };
+    options.map(option).collect()
+}
+
+// the parameters of the SPRT like "elo0=0,elo1=5", the missing ones by default
+pub fn parse_sprt(sprt: &str) -> Option<Sprt> {
+    let mut parameters = Sprt::default();
+    for (name, value) in parse_options(sprt)? {
+        let value = value.parse().ok()?;
+        match name.as_str() {
+            "elo0" => parameters.elo0 = value,
+            "elo1" => parameters.elo1 = value,
+            "alpha" => parameters.alpha = value,
+            "beta" => parameters.beta = value,
+            _ => return None,
+        }
+    }
+    let probability = |p: f64| p > 0.0 && p < 0.5;
+    let valid = parameters.elo0 < parameters.elo1
+        && probability(parameters.alpha)
+        && probability(parameters.beta);
+    valid.then_some(parameters)
+}
+
+// the command and its flags, without the name of the binary
+pub fn parse_args(args: &[String]) -> Result<Cli, CliError> {
+    let (command, mut flags) = args
+        .split_first()
+        .ok_or_else(|| CliError::UnknownCommand(String::new()))?;
+    if command == "--serve" {
+        let address = match flags {
+            [] => "127.0.0.1:8080".to_owned(),
+            [address] => address.clone(),
+            [_, flag, ..] => return Err(CliError::UnknownFlag(flag.clone())),
+        };
+        return Ok(Cli::Serve { address });
+    }
+    // the file of epd and book precedes the flags
+    let mut path = None;
+    if command == "epd" || command == "book" {
+        let (file, rest) = flags
+            .split_first()
+            .ok_or_else(|| CliError::MissingValue(command.clone()))?;
+        path = Some(file.clone());
+        flags = rest;
+    }
+    let mut flags = flags.iter();
+    let mut values: Vec<(&str, &str)> = Vec::new();
+    while let Some(flag) = flags.next() {
+        let value = flags
+            .next()
+            .ok_or_else(|| CliError::MissingValue(flag.clone()))?;
+        values.push((flag, value));
+    }
+    let known = match command.as_str() {
+        "analyze" => &["--fen", "--depth"][..],
+        "selfplay" => &["--games", "--tc", "--pgn"][..],
+        "perft" => &["--fen", "--depth"][..],
+        "epd" => &["--time"][..],
+        "book" => &["--out", "--plies", "--min-rating", "--results"][..],
+        "match" => &[
+            "--engine1",
+            "--engine2",
+            "--options1",
+            "--options2",
+            "--games",
+            "--tc",
+            "--book",
+            "--pgn",
+            "--sprt",
+        ][..],
+        _ => return Err(CliError::UnknownCommand(command.clone())),
+    };
+    if let Some((flag, _)) = values.iter().find(|(flag, _)| !known.contains(flag)) {
+        return Err(CliError::UnknownFlag(flag.to_string()));
+    }
+    let value = |name: &str| values.iter().rev().find(|(f, _)| *f == name).map(|v| v.1);
+    let invalid = |name: &str, v: &str| CliError::InvalidValue(name.to_owned(), v.to_owned());
+    let number = |name: &str, default: u32| match value(name) {
+        Some(v) => v.parse().map_err(|_| invalid(name, v)),
+        None => Ok(default),
+    };
+    let fen = value("--fen").map(str::to_owned);
+    let tc = || match value("--tc") {
+        Some(v) => parse_tc(v).ok_or_else(|| invalid("--tc", v)),
+        None => Ok((Duration::from_secs(60), Duration::from_millis(100))),
+    };
+    let options = |name: &str| match value(name) {
+        Some(v) => parse_options(v).ok_or_else(|| invalid(name, v)),
+        None => Ok(Vec::new()),
+    };
+    let engine = |name: &str| value(name).unwrap_or("builtin").to_owned();
+    let sprt = match value("--sprt") {
+        Some(v) => Some(parse_sprt(v).ok_or_else(|| invalid("--sprt", v))?),
+        None => None,
+    };
+    Ok(match command.as_str() {
+        "analyze" => Cli::Analyze {
+            fen,
+            depth: number("--depth", 10)? as i64,
+        },
+        "selfplay" => Cli::Selfplay {
+            games: number("--games", 10)? as usize,
+            tc: tc()?,
+            pgn: value("--pgn").map(str::to_owned),
+        },
+        "match" => Cli::Match {
+            engines: [engine("--engine1"), engine("--engine2")],
+            options: [options("--options1")?, options("--options2")?],
+            games: number("--games", if sprt.is_some() { 100_000 } else { 10 })? as usize,
+            tc: tc()?,
+            book: value("--book").map(str::to_owned),
+            pgn: value("--pgn").map(str::to_owned),
+            sprt,
+        },
+        "perft" => Cli::Perft {
+            fen,
+            depth: number("--depth", 5)?,
+        },
+        "book" => Cli::Book {
+            path: path.unwrap_or_default(),
+            out: value("--out").unwrap_or("book.bin").to_owned(),
+            filter: book::Filter {
+                plies: number("--plies", 20)? as usize,
+                min_rating: number("--min-rating", 0)?,
+                results: match value("--results") {
+                    None | Some("nolosses") => Results::NoLosses,
+                    Some("all") => Results::All,
+                    Some("wins") => Results::Wins,
+                    Some(v) => return Err(invalid("--results", v)),
+                },
+            },
+        },
+        _ => Cli::Epd {
+            path: path.unwrap_or_default(),
+            time: number("--time", 1000)?,
+        },
+    })
+}
+
+pub fn perft(g: &mut Game, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves: Vec<engine::Move> = engine::legal_moves(g).collect();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for m in moves {
+        let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+        nodes += perft(g, depth - 1);
+        engine::undo_move(g, token);
+    }
+    nodes
+}
+
+// the score in UCI notation, i.e. "cp 31" or "mate -3", see uci::parse_info()
+pub fn uci_score(score: i64) -> String {
+    let king = engine::KING_VALUE as i64;
+    if score > engine::KING_VALUE_DIV_2 as i64 {
+        format!("mate {}", (king - score) / 2 + 1)
+    } else if score < -engine::KING_VALUE_DIV_2 as i64 {
+        format!("mate -{}", (king + score + 1) / 2)
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+// the reply of the engine as played, with the flags for check and mate, None in a
+// finished game
+fn reply_move(g: &mut Game, result: &engine::SearchResult) -> Option<engine::Move> {
+    let mut moves = engine::legal_moves(g);
+    let m = moves.find(|m| {
+        (m.src as i64, m.dst as i64, m.promote_to) == (result.src, result.dst, result.promote_to)
+    })?;
+    let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    let m = token.mv;
+    engine::undo_move(g, token);
+    Some(m)
+}
+
+// the text of a finished game, None while it goes on
+fn game_over(g: &Game) -> Option<GameState> {
+    Some(engine::state(g)).filter(|s| *s != GameState::Ongoing)
+}
+
+fn new_game(fen: &Option<String>) -> Result<Game, CliError> {
+    let mut g = engine::new_game();
+    if let Some(fen) = fen {
+        engine::set_fen(&mut g, fen).map_err(CliError::Fen)?;
+    }
+    Ok(g)
+}
+
+// prints each iteration, and ends the search after the iteration of the given depth
+struct Printer<'a> {
+    out: &'a mut dyn Write,
+    depth: i64,
+    done: bool,
+}
+
+impl SearchObserver for Printer<'_> {
+    fn on_depth_complete(&mut self, info: &SearchInfo) {
+        let pv: Vec<String> = info.pv.iter().map(|m| m.uci().to_string()).collect();
+        let _ = writeln!(
+            self.out,
+            "info depth {} seldepth {} score {} nodes {} time {} pv {}",
+            info.depth,
+            info.seldepth,
+            uci_score(info.score),
+            info.nodes,
+            info.time.as_millis(),
+            pv.join(" ")
+        );
+        self.done = info.depth >= self.depth;
+    }
+    fn should_stop(&mut self) -> bool {
+        self.done
+    }
+}
+
+// search to the depth, or until MoveTime, which is set to its maximum
+fn analyze(fen: &Option<String>, depth: i64, out: &mut dyn Write) -> Result<(), CliError> {
+    let mut g = new_game(fen)?;
+    if let Some(text) = game_over(&g) {
+        writeln!(out, "{}", text)?;
+        return Ok(());
+    }
+    let _ = engine::set_option(&mut g, "MoveTime", "9000");
+    let mut printer = Printer {
+        out,
+        depth,
+        done: false,
+    };
+    let result = engine::reply(&mut g, &mut printer);
+    match reply_move(&mut g, &result) {
+        Some(m) => writeln!(out, "bestmove {}", m.uci())?,
+        None => writeln!(out, "bestmove (none)")?,
+    }
+    Ok(())
+}
+
+// The games start with the lines of the ECO table in turn, as the engine would play the
+// same game each time. The result is written for each game, the total at the end.
+fn selfplay(
+    games: usize,
+    (time, increment): (Duration, Duration),
+    pgn: &Option<String>,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let openings: Vec<Vec<&str>> = eco::openings().map(|(_, moves)| moves).collect();
+    let mut g = engine::new_game();
+    let mut results = [0; 3]; // won by White, drawn and won by Black
+    let mut file = match pgn {
+        Some(path) => Some(std::fs::File::create(path)?),
+        None => None,
+    };
+    for game in 0..games {
+        engine::reset_game(&mut g);
+        for san in openings
+            .get(game % openings.len().max(1))
+            .into_iter()
+            .flatten()
+        {
+            let Ok(m) = engine::parse_san(&g, san) else {
+                break;
+            };
+            engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+        }
+        let mut clocks = [time; 2];
+        let (reason, score) = loop {
+            if let Some(state) = game_over(&g) {
+                break (state.to_string(), engine::pgn_result(&g));
+            }
+            if engine::game_ply(&g) >= MAX_PLIES {
+                break ("Draw by the move limit.".to_owned(), "1/2-1/2");
+            }
+            let active = g.move_counter as usize % 2;
+            engine::set_time_left(&mut g, Some((clocks[active], increment)));
+            let start = Instant::now();
+            let result = engine::reply(&mut g, &mut ());
+            let elapsed = start.elapsed();
+            if elapsed > clocks[active] {
+                if active == 0 {
+                    break ("White lost on time.".to_owned(), "0-1");
+                }
+                break ("Black lost on time.".to_owned(), "1-0");
+            }
+            clocks[active] = clocks[active] - elapsed + increment;
+            engine::do_move(
+                &mut g,
+                result.src as i8,
+                result.dst as i8,
+                result.promote_to,
+                false,
+            );
+        };
+        engine::set_time_left(&mut g, None);
+        let index = match score {
+            "1-0" => 0,
+            "0-1" => 2,
+            _ => 1,
+        };
+        results[index] += 1;
+        let moves = engine::game_ply(&g).div_ceil(2);
+        writeln!(
+            out,
+            "Game {}: {} {} {} moves",
+            game + 1,
+            score,
+            reason,
+            moves
+        )?;
+        if let Some(file) = &mut file {
+            let round = (game + 1).to_string();
+            let tags = [
+                ("Event", "Tiny chess selfplay"),
+                ("Round", round.as_str()),
+                ("White", "Tiny chess"),
+                ("Black", "Tiny chess"),
+                ("Result", score),
+            ];
+            writeln!(file, "{}", engine::pgn(&g, &tags))?;
+        }
+    }
+    writeln!(
+        out,
+        "White wins {}, draws {}, Black wins {}",
+        results[0], results[1], results[2]
+    )?;
+    Ok(())
+}
+
+// the node counts of the depths up to depth
+fn run_perft(fen: &Option<String>, depth: u32, out: &mut dyn Write) -> Result<(), CliError> {
+    let mut g = new_game(fen)?;
+    let start = Instant::now();
+    for d in 1..=depth {
+        let nodes = perft(&mut g, d);
+        let ms = start.elapsed().as_millis();
+        writeln!(out, "perft {} nodes {} time {}", d, nodes, ms)?;
+    }
+    Ok(())
+}
+
+// Each position is searched for the given time, its line tells the move of the engine
+// and whether it solves the test. Invalid lines are reported and not counted.
+fn run_epd(path: &str, time: u32, out: &mut dyn Write) -> Result<(), CliError> {
+    let mut g = engine::new_game();
+    let invalid = || CliError::InvalidValue("--time".to_owned(), time.to_string());
+    engine::set_option(&mut g, "MoveTime", &time.to_string()).map_err(|_| invalid())?;
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let (mut solved, mut total) = (0, 0);
+    let start = Instant::now();
+    for (line, test) in epd::read_epd(file)? {
+        let test = match test.and_then(|t| epd::set_test(&mut g, &t).map(|()| t)) {
+            Ok(test) => test,
+            Err(e) => {
+                writeln!(out, "line {}: {}", line, e)?;
+                continue;
+            }
+        };
+        let result = engine::reply(&mut g, &mut ());
+        let id = if test.id.is_empty() {
+            format!("line {}", line)
+        } else {
+            test.id.clone()
+        };
+        total += 1;
+        match reply_move(&mut g, &result) {
+            Some(m) if epd::is_solution(&g, &test, &m) => {
+                solved += 1;
+                writeln!(out, "{}: {} solved", id, m)?;
+            }
+            Some(m) => {
+                let expected = if test.best.is_empty() {
+                    format!("am {}", test.avoid.join(" "))
+                } else {
+                    format!("bm {}", test.best.join(" "))
+                };
+                writeln!(out, "{}: {} failed, {}", id, m, expected)?;
+            }
+            None => writeln!(out, "{}: no move", id)?,
+        }
+    }
+    let percent = 100.0 * solved as f64 / total.max(1) as f64;
+    let secs = start.elapsed().as_secs();
+    writeln!(
+        out,
+        "Solved {} of {} ({:.1} %) in {} s",
+        solved, total, percent, secs
+    )?;
+    Ok(())
+}
+
+// the book of the games of a PGN file, the games skipped by the filter are not counted
+fn run_book(
+    path: &str,
+    out_path: &str,
+    filter: &book::Filter,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let mut builder = book::Builder::new(*filter);
+    let games = builder.add_pgn(&std::fs::read_to_string(path)?);
+    let book = builder.book();
+    std::fs::write(out_path, book.to_bytes())?;
+    writeln!(
+        out,
+        "{} positions and {} moves of {} games written to {}",
+        book.positions(),
+        book.entries().len(),
+        games,
+        out_path
+    )?;
+    Ok(())
+}
+
+// The engines play the games with alternating colors, the results of the games are
+// written as they finish and the table of both engines at the end. The SPRT reports its
+// log-likelihood ratio after each game, and decides after both colors of an opening.
+fn run_match(
+    engines: &[String; 2],
+    options: &[Vec<(String, String)>; 2],
+    m: &tournament::Match,
+    pgn: &Option<String>,
+    sprt: &Option<Sprt>,
+    out: &mut dyn Write,
+) -> Result<(), CliError> {
+    let mut names = engines.clone().map(|e| match e.as_str() {
+        "builtin" => "Tiny chess".to_owned(),
+        path => std::path::Path::new(path)
+            .file_stem()
+            .map_or(e.clone(), |s| s.to_string_lossy().into_owned()),
+    });
+    if names[0] == names[1] {
+        names[0].push_str(" 1");
+        names[1].push_str(" 2");
+    }
+    let player = |i: usize| {
+        let player = match engines[i].as_str() {
+            "builtin" => tournament::Engine::built_in(&names[i], &options[i]),
+            path => tournament::Engine::external(&names[i], path, &options[i]),
+        };
+        player.map_err(CliError::Engine)
+    };
+    let mut players = [player(0)?, player(1)?];
+    let mut file = match pgn {
+        Some(path) => Some(std::fs::File::create(path)?),
+        None => None,
+    };
+    let mut error = Ok(());
+    let mut round = 0;
+    let mut results = [0; 3]; // the wins, draws and losses of the first engine
+    let mut decision = Decision::Continue;
+    let mut on_game = |record: &tournament::GameRecord| {
+        let (white, black) = (&names[record.white], &names[1 - record.white]);
+        round += 1;
+        results[(2.0 - 2.0 * record.points(0)) as usize] += 1;
+        let mut result = writeln!(
+            out,
+            "Game {}: {} - {} {} {}",
+            round, white, black, record.result, record.reason
+        )
+        .and_then(|()| match &mut file {
+            Some(file) => writeln!(file, "{}", record.pgn),
+            None => Ok(()),
+        });
+        if let Some(sprt) = sprt {
+            let llr = sprt.llr(results[0], results[1], results[2]);
+            let (lower, upper) = sprt.bounds();
+            if round % 2 == 0 {
+                decision = sprt.decision(llr);
+            }
+            let line = writeln!(out, "LLR {:.2} [{:.2}, {:.2}]", llr, lower, upper);
+            result = result.and(line);
+        }
+        if error.is_ok() {
+            error = result;
+        }
+        error.is_ok() && decision == Decision::Continue
+    };
+    let records = tournament::play_match(m, &mut players, &mut on_game);
+    error?;
+    let names = [names[0].as_str(), names[1].as_str()];
+    write!(out, "{}", tournament::results_table(names, &records))?;
+    if let Some(sprt) = sprt {
+        match decision {
+            Decision::AcceptH0 => writeln!(out, "H0 accepted (elo0 {})", sprt.elo0)?,
+            Decision::AcceptH1 => writeln!(out, "H1 accepted (elo1 {})", sprt.elo1)?,
+            Decision::Continue => writeln!(out, "No hypothesis accepted")?,
+        }
+    }
+    Ok(())
+}
+
+pub fn run(cli: &Cli, out: &mut dyn Write) -> Result<(), CliError> {
+    match cli {
+        Cli::Analyze { fen, depth } => analyze(fen, *depth, out),
+        Cli::Selfplay { games, tc, pgn } => selfplay(*games, *tc, pgn, out),
+        Cli::Perft { fen, depth } => run_perft(fen, *depth, out),
+        Cli::Epd { path, time } => run_epd(path, *time, out),
+        Cli::Book {
+            path,
+            out: out_path,
+            filter,
+        } => run_book(path, out_path, filter, out),
+        Cli::Match {
+            engines,
+            options,
+            games,
+            tc,
+            book,
+            pgn,
+            sprt,
+        } => {
+            let openings = match book {
+                Some(path) if path.ends_with(".epd") => {
+                    tournament::epd_openings(&std::fs::read_to_string(path)?)
+                }
+                Some(path) => tournament::pgn_openings(&std::fs::read_to_string(path)?),
+                None => Vec::new(),
+            };
+            let m = tournament::Match {
+                games: *games,
+                tc: *tc,
+                openings,
+                adjudication: Adjudication::default(),
+            };
+            run_match(engines, options, &m, pgn, sprt, out)
+        }
+        #[cfg(feature = "server")]
+        Cli::Serve { address } => Ok(crate::server::serve(address, out)?),
+        #[cfg(not(feature = "server"))]
+        Cli::Serve { .. } => Err(CliError::Feature("server")),
+    }
+}
+
+// the exit code of the command, errors are printed with the usage to stderr
+pub fn main(args: &[String]) -> i32 {
+    let result = parse_args(args).and_then(|cli| run(&cli, &mut std::io::stdout()));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("tiny-chess: {}", e);
+            if !matches!(
+                e,
+                CliError::Fen(_) | CliError::Engine(_) | CliError::Feature(_) | CliError::Io(_)
+            ) {
+                eprintln!("{}", USAGE);
+            }
+            2
+        }
+    }
+}
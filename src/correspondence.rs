@@ -0,0 +1,98 @@
+// Conditional moves of correspondence games. A line like "if e4 then c5 Nf3 d6" is entered
+// while the opponent is to move: it starts with a move of the opponent, followed by the
+// answer, and so on. As long as the opponent follows the line, the answers are played at
+// once. A line is dropped when the game leaves it, or when its last answer was played.
+
+use crate::engine::{self, Game, Move, SanError};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Conditional {
+    pub ply: usize,         // of the first move, see engine::game_ply()
+    pub moves: Vec<String>, // in UCI notation, the moves of the opponent and the answers
+    pub san: String,        // for the display, i.e. "e4 c5 Nf3 d6"
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineError {
+    NoAnswer,              // a move of the opponent alone
+    Move(usize, SanError), // the number of the move in the line, from 1
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LineError::NoAnswer => f.write_str("a move of the opponent needs an answer"),
+            LineError::Move(i, e) => write!(f, "move {} of the line: {}", i, e),
+        }
+    }
+}
+
+impl core::error::Error for LineError {}
+
+// the moves in SAN from the position of g. The words "if", "then" and "and", move
+// numbers and commas are skipped.
+pub fn parse_line(g: &Game, text: &str) -> Result<Conditional, LineError> {
+    let mut line = engine::new_game_with_hash(1);
+    engine::set_position(&mut line, g);
+    let words = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(','))
+        .filter(|w| !w.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        .filter(|w| {
+            !["if", "then", "and"]
+                .iter()
+                .any(|k| k.eq_ignore_ascii_case(w))
+        });
+    let (mut moves, mut san) = (Vec::new(), Vec::new());
+    for word in words {
+        let m = engine::parse_san(&line, word).map_err(|e| LineError::Move(moves.len() + 1, e))?;
+        engine::do_move(&mut line, m.src, m.dst, m.promote_to, false);
+        // with the check and the disambiguation of the played move
+        let played = engine::move_list(&line)[engine::game_ply(&line) - 1];
+        moves.push(played.uci().to_string());
+        san.push(played.to_string());
+    }
+    if moves.len() < 2 {
+        return Err(LineError::NoAnswer);
+    }
+    Ok(Conditional {
+        ply: engine::game_ply(g),
+        moves,
+        san: san.join(" "),
+    })
+}
+
+// The answer to play now, when the opponent has just followed a line. Only at the end of
+// the game, not while browsing it. The lines which the game has left are removed, and a
+// line with its last answer.
+pub fn answer(g: &Game, lines: &mut Vec<Conditional>) -> Option<Move> {
+    let played = engine::move_list(g);
+    let ply = engine::game_ply(g);
+    if ply < played.len() {
+        return None;
+    }
+    let mut result = None;
+    lines.retain(|line| {
+        let Some(done) = ply.checked_sub(line.ply).filter(|&d| d < line.moves.len()) else {
+            return false;
+        };
+        let followed = played[line.ply..ply]
+            .iter()
+            .zip(&line.moves)
+            .all(|(m, uci)| m.uci().to_string() == *uci);
+        if !followed {
+            return false;
+        }
+        // the opponent is to move after an even number of moves
+        if done % 2 == 1 && result.is_none() {
+            result = engine::parse_uci_move(g, &line.moves[done]).ok();
+            return done + 1 < line.moves.len();
+        }
+        true
+    });
+    result
+}
@@ -0,0 +1,180 @@
+// A database of the games of a PGN file, e.g. a download of a tournament or of the games
+// of a player. Only the tags are read to list and search the games, the moves of a game
+// are parsed with engine::set_pgn() when it is loaded onto the board.
+//
+// An opening tree collects the moves of the games of one player in each position, with
+// the results of the player, transpositions are joined by engine::position_key().
+
+use crate::engine::{self, Game, PgnError};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::ops::Range;
+
+// the tags of a game for the list
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub white: String,
+    pub black: String,
+    pub eco: String,
+    pub result: String,
+    pub date: String,
+    pub event: String,
+    text: Range<usize>, // of the game in Database::text
+}
+
+pub struct Database {
+    text: String,
+    pub games: Vec<Header>,
+}
+
+impl Database {
+    pub fn new(text: String) -> Self {
+        let mut games = Vec::new();
+        for game in engine::split_pgn(&text) {
+            let start = game.as_ptr() as usize - text.as_ptr() as usize;
+            let mut header = Header {
+                text: start..start + game.len(),
+                ..Header::default()
+            };
+            let tags = game.lines().map(str::trim).filter(|l| !l.is_empty());
+            for (name, value) in tags.map_while(engine::pgn_tag) {
+                let field = match name.as_str() {
+                    "White" => &mut header.white,
+                    "Black" => &mut header.black,
+                    "ECO" => &mut header.eco,
+                    "Result" => &mut header.result,
+                    "Date" => &mut header.date,
+                    "Event" => &mut header.event,
+                    _ => continue,
+                };
+                *field = value;
+            }
+            games.push(header);
+        }
+        Database { text, games }
+    }
+
+    // the PGN of a game
+    pub fn pgn(&self, index: usize) -> &str {
+        &self.text[self.games[index].text.clone()]
+    }
+
+    // the game onto g, with its tags
+    pub fn load(&self, g: &mut Game, index: usize) -> Result<Vec<(String, String)>, PgnError> {
+        engine::set_pgn(g, self.pgn(index))
+    }
+
+    // the indices of the games with each word of the query in one of the tags of the list,
+    // ignoring case, i.e. "carlsen 1-0" or "B90 2023"
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        (0..self.games.len())
+            .filter(|&i| {
+                let h = &self.games[i];
+                let fields = [&h.white, &h.black, &h.eco, &h.result, &h.date, &h.event];
+                let fields: Vec<String> = fields.iter().map(|f| f.to_lowercase()).collect();
+                words
+                    .iter()
+                    .all(|w| fields.iter().any(|f| f.contains(w.as_str())))
+            })
+            .collect()
+    }
+}
+
+// a move of an opening tree, the results are of the player
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeMove {
+    pub uci: String,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl TreeMove {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // the points of the player per game, from 0 to 1
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+}
+
+pub struct OpeningTree {
+    game: Game, // for the moves of the added games
+    positions: BTreeMap<u64, Vec<TreeMove>>,
+}
+
+impl Default for OpeningTree {
+    fn default() -> Self {
+        OpeningTree {
+            game: engine::new_game_with_hash(1),
+            positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl OpeningTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The first plies of the main line of a game, when the player is named in the White or
+    // Black tag. Ok(false) for the games of others and for unfinished games.
+    pub fn add_game(&mut self, pgn: &str, player: &str, plies: usize) -> Result<bool, PgnError> {
+        let tags = engine::set_pgn(&mut self.game, pgn)?;
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(n, _)| n == name)
+                .map_or("", |(_, v)| v.as_str())
+        };
+        let white = if tag("White") == player {
+            true
+        } else if tag("Black") == player {
+            false
+        } else {
+            return Ok(false);
+        };
+        let (wins, draws, losses) = match (tag("Result"), white) {
+            ("1-0", true) | ("0-1", false) => (1, 0, 0),
+            ("1/2-1/2", _) => (0, 1, 0),
+            ("1-0", false) | ("0-1", true) => (0, 0, 1),
+            _ => return Ok(false),
+        };
+        let moves = engine::move_list(&self.game);
+        engine::go_to_ply(&mut self.game, 0);
+        for m in moves.iter().take(plies) {
+            let list = self
+                .positions
+                .entry(engine::position_key(&self.game))
+                .or_default();
+            let uci = m.uci().to_string();
+            let i = match list.iter().position(|t| t.uci == uci) {
+                Some(i) => i,
+                None => {
+                    list.push(TreeMove {
+                        uci,
+                        ..TreeMove::default()
+                    });
+                    list.len() - 1
+                }
+            };
+            list[i].wins += wins;
+            list[i].draws += draws;
+            list[i].losses += losses;
+            list.sort_by_key(|t| Reverse(t.games()));
+            engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false);
+        }
+        Ok(true)
+    }
+
+    // the moves of the current position of g, the most played first
+    pub fn moves(&self, g: &Game) -> &[TreeMove] {
+        self.positions
+            .get(&engine::position_key(g))
+            .map_or(&[], |list| list.as_slice())
+    }
+}
@@ -0,0 +1,165 @@
+// Diagrams of a position as SVG, i.e. for blogs and handouts: the board with the pieces
+// of the bundled "Outline" set, with coordinates and the last move if wanted. A square
+// is 45 units like the viewBox of the pieces, the size scales the whole diagram. The
+// frontend rasterizes the SVG for PNG images and animated GIFs.
+
+use crate::engine::{self, Game, Position};
+use crate::engine::{BISHOP_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, VOID_ID};
+use alloc::format;
+use alloc::string::String;
+
+const SQUARE: i32 = 45;
+const MARGIN: i32 = 20; // for the coordinates
+const SQUARES: [&str; 2] = ["#ffffff", "#cdcdcd"]; // light and dark, like the board of the app
+const LAST_MOVE: &str = "#e69f00";
+
+// the pieces by their id, "wn" is the white knight
+const PIECES: [(&str, &str); 12] = [
+    ("wp", include_str!("pieces/wp.svg")),
+    ("wn", include_str!("pieces/wn.svg")),
+    ("wb", include_str!("pieces/wb.svg")),
+    ("wr", include_str!("pieces/wr.svg")),
+    ("wq", include_str!("pieces/wq.svg")),
+    ("wk", include_str!("pieces/wk.svg")),
+    ("bp", include_str!("pieces/bp.svg")),
+    ("bn", include_str!("pieces/bn.svg")),
+    ("bb", include_str!("pieces/bb.svg")),
+    ("br", include_str!("pieces/br.svg")),
+    ("bq", include_str!("pieces/bq.svg")),
+    ("bk", include_str!("pieces/bk.svg")),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub size: u32, // the width and height in pixels
+    pub coordinates: bool,
+    pub last_move: bool,
+    pub flipped: bool, // Black at the bottom
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            size: 400,
+            coordinates: true,
+            last_move: true,
+            flipped: false,
+        }
+    }
+}
+
+fn piece_id(f: i64) -> usize {
+    let kind = match f.abs() {
+        PAWN_ID => 0,
+        KNIGHT_ID => 1,
+        BISHOP_ID => 2,
+        ROOK_ID => 3,
+        QUEEN_ID => 4,
+        _ => 5,
+    };
+    if f > 0 {
+        kind
+    } else {
+        kind + 6
+    }
+}
+
+// the content of the svg element of a piece
+fn piece_content(svg: &str) -> &str {
+    let start = svg
+        .find("<svg")
+        .and_then(|i| svg[i..].find('>').map(|j| i + j + 1));
+    let end = svg.rfind("</svg>");
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => svg[start..end].trim(),
+        _ => "",
+    }
+}
+
+// the top left corner of square p in units
+fn corner(p: Position, style: &Style) -> (i32, i32) {
+    let (file, rank) = (7 - p as i32 % 8, p as i32 / 8); // a1 is (0, 0)
+    let margin = if style.coordinates { MARGIN } else { 0 };
+    if style.flipped {
+        (margin + (7 - file) * SQUARE, rank * SQUARE)
+    } else {
+        (margin + file * SQUARE, (7 - rank) * SQUARE)
+    }
+}
+
+pub fn svg(g: &Game, style: &Style) -> String {
+    let board = engine::get_board(g);
+    let extent = 8 * SQUARE + if style.coordinates { MARGIN } else { 0 };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{0}\" viewBox=\"0 0 {1} {1}\">\n",
+        style.size, extent
+    );
+    svg.push_str("<defs>\n");
+    for (i, &(id, piece)) in PIECES.iter().enumerate() {
+        // only the pieces on the board
+        if board.iter().any(|&f| f != VOID_ID && piece_id(f) == i) {
+            svg.push_str(&format!("<g id=\"{}\">{}</g>\n", id, piece_content(piece)));
+        }
+    }
+    svg.push_str("</defs>\n");
+    svg.push_str(&format!(
+        "<rect width=\"{0}\" height=\"{0}\" fill=\"{1}\"/>\n",
+        extent, SQUARES[0]
+    ));
+    for p in 0..64 {
+        let (x, y) = corner(p, style);
+        if (p / 8 + p % 8) % 2 == 1 {
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\"/>\n",
+                x, y, SQUARE, SQUARES[1]
+            ));
+        }
+    }
+    let last = engine::game_ply(g)
+        .checked_sub(1)
+        .filter(|_| style.last_move);
+    if let Some(ply) = last {
+        let m = engine::move_list(g)[ply];
+        for p in [m.src, m.dst] {
+            let (x, y) = corner(p, style);
+            svg.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{2}\" height=\"{2}\" fill=\"{3}\" fill-opacity=\"0.4\"/>\n",
+                x, y, SQUARE, LAST_MOVE
+            ));
+        }
+    }
+    for (p, &f) in board.iter().enumerate() {
+        if f != VOID_ID {
+            let (x, y) = corner(p as Position, style);
+            let id = PIECES[piece_id(f)].0;
+            svg.push_str(&format!(
+                "<use href=\"#{}\" transform=\"translate({} {})\"/>\n",
+                id, x, y
+            ));
+        }
+    }
+    if style.coordinates {
+        let text = "font-family=\"sans-serif\" font-size=\"14\" text-anchor=\"middle\"";
+        for i in 0..8 {
+            // the files below the board and the ranks to the left
+            let (x, _) = corner(7 - i, style);
+            let (_, y) = corner(7 + 8 * i, style);
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" {}>{}</text>\n",
+                x + SQUARE / 2,
+                8 * SQUARE + 15,
+                text,
+                (b'a' + i as u8) as char
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" {}>{}</text>\n",
+                MARGIN / 2,
+                y + SQUARE / 2 + 5,
+                text,
+                i + 1
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
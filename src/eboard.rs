@@ -0,0 +1,188 @@
+// Electronic chess boards. A driver reports the pieces on the physical board, sync()
+// compares them with the game: the board shows the game, or a legal move of the side to
+// move, or it differs, e.g. while a piece is lifted or after a wrong move. The drivers
+// read the board in their own threads, the events are polled with Driver::try_event()
+// like the events of the engine in handle.rs. Needs std.
+//
+// With the "eboard" feature the Chessnut Air is connected by Bluetooth LE, see Chessnut.
+
+use crate::engine::{self, Board, Game, Move, Position};
+use crate::engine::{BISHOP_ID, KING_ID, KNIGHT_ID, PAWN_ID, QUEEN_ID, ROOK_ID, VOID_ID};
+#[cfg(feature = "eboard")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "eboard")]
+use std::sync::{mpsc, Arc};
+#[cfg(feature = "eboard")]
+use std::thread;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Connected(String),      // the name of the board
+    Board(Box<Board>),      // the pieces on the board, when they change
+    Closed(Option<String>), // with the error that ended the connection, if any
+}
+
+pub trait Driver {
+    fn try_event(&self) -> Option<Event>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardSync {
+    InSync,
+    Move(Move),             // a legal move of the side to move was made on the board
+    Differs(Vec<Position>), // the squares which differ from the game
+}
+
+pub fn sync(g: &Game, board: &Board) -> BoardSync {
+    if *board == engine::get_board(g) {
+        return BoardSync::InSync;
+    }
+    match engine::legal_moves(g).find(|m| engine::board_after(g, m) == *board) {
+        Some(m) => BoardSync::Move(m),
+        None => BoardSync::Differs(
+            engine::diff(&engine::get_board(g), board)
+                .iter()
+                .map(|c| c.pos)
+                .collect(),
+        ),
+    }
+}
+
+// the pieces of the codes of the Chessnut Air, 0 is an empty square
+const CHESSNUT_PIECES: [i64; 13] = [
+    VOID_ID, -QUEEN_ID, -KING_ID, -BISHOP_ID, -PAWN_ID, -KNIGHT_ID, ROOK_ID, PAWN_ID, -ROOK_ID,
+    BISHOP_ID, KNIGHT_ID, QUEEN_ID, KING_ID,
+];
+
+// The board data of the Chessnut Air: the header 0x01 0x24, then 32 bytes with a square
+// in each nibble, the low nibble first, from h8 to a8 and down to a1.
+pub fn chessnut_board(data: &[u8]) -> Option<Board> {
+    if data.len() < 34 || data[..2] != [0x01, 0x24] {
+        return None;
+    }
+    let mut board = [VOID_ID; 64];
+    for (i, byte) in data[2..34].iter().enumerate() {
+        for (j, code) in [byte & 0x0f, byte >> 4].into_iter().enumerate() {
+            let k = i * 2 + j; // h8 is 0, a1 is 63
+            board[(7 - k / 8) * 8 + k % 8] = *CHESSNUT_PIECES.get(code as usize)?;
+        }
+    }
+    Some(board)
+}
+
+#[cfg(feature = "eboard")]
+mod chessnut {
+    use super::{chessnut_board, Event};
+    use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter, WriteType};
+    use btleplug::platform::Manager;
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    const NAME: &str = "Chessnut"; // the start of the advertised name
+    const WRITE: Uuid = Uuid::from_u128(0x1b7e8272_2877_41c3_b46e_cf057c562023);
+    const BOARD: Uuid = Uuid::from_u128(0x1b7e8262_2877_41c3_b46e_cf057c562023);
+    const INIT: [u8; 3] = [0x21, 0x01, 0x00]; // starts the board data
+    const INTERVAL: Duration = Duration::from_millis(500); // of the scan and to notice a close
+
+    fn text(e: btleplug::Error) -> String {
+        e.to_string()
+    }
+
+    // scan for the board, then pass its board data until the connection ends or is closed
+    pub async fn run(events: &mpsc::Sender<Event>, closed: &AtomicBool) -> Result<(), String> {
+        let manager = Manager::new().await.map_err(text)?;
+        let central = manager.adapters().await.map_err(text)?.into_iter().next();
+        let central = central.ok_or("No Bluetooth adapter")?;
+        central
+            .start_scan(ScanFilter::default())
+            .await
+            .map_err(text)?;
+        let (peripheral, name) = 'scan: loop {
+            if closed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            for p in central.peripherals().await.map_err(text)? {
+                let properties = p.properties().await.map_err(text)?;
+                if let Some(name) = properties.and_then(|p| p.local_name) {
+                    if name.starts_with(NAME) {
+                        break 'scan (p, name);
+                    }
+                }
+            }
+            tokio::time::sleep(INTERVAL).await;
+        };
+        central.stop_scan().await.map_err(text)?;
+        peripheral.connect().await.map_err(text)?;
+        peripheral.discover_services().await.map_err(text)?;
+        let characteristic = |uuid| {
+            let characteristics = peripheral.characteristics();
+            let c = characteristics.into_iter().find(|c| c.uuid == uuid);
+            c.ok_or_else(|| format!("{} is no Chessnut Air", name))
+        };
+        let (board, write) = (characteristic(BOARD)?, characteristic(WRITE)?);
+        peripheral.subscribe(&board).await.map_err(text)?;
+        let mut notifications = peripheral.notifications().await.map_err(text)?;
+        peripheral
+            .write(&write, &INIT, WriteType::WithResponse)
+            .await
+            .map_err(text)?;
+        let _ = events.send(Event::Connected(name.clone()));
+        let mut last = None;
+        while !closed.load(Ordering::Relaxed) {
+            match tokio::time::timeout(INTERVAL, notifications.next()).await {
+                Ok(Some(n)) if n.uuid == BOARD => {
+                    if let Some(b) = chessnut_board(&n.value).filter(|&b| last != Some(b)) {
+                        last = Some(b);
+                        let _ = events.send(Event::Board(Box::new(b)));
+                    }
+                }
+                Ok(Some(_)) | Err(_) => {}
+                Ok(None) => return Err(format!("{} disconnected", name)),
+            }
+        }
+        let _ = peripheral.disconnect().await;
+        Ok(())
+    }
+}
+
+// the Chessnut Air, the first board found by a Bluetooth scan
+#[cfg(feature = "eboard")]
+pub struct Chessnut {
+    events: mpsc::Receiver<Event>,
+    closed: Arc<AtomicBool>, // ends the scan or the connection
+}
+
+#[cfg(feature = "eboard")]
+impl Chessnut {
+    pub fn connect() -> Self {
+        let (sender, events) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let stop = Arc::clone(&closed);
+        thread::spawn(move || {
+            let result = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|e| e.to_string())
+                .and_then(|runtime| runtime.block_on(chessnut::run(&sender, &stop)));
+            let _ = sender.send(Event::Closed(result.err()));
+        });
+        Chessnut { events, closed }
+    }
+}
+
+#[cfg(feature = "eboard")]
+impl Driver for Chessnut {
+    fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+#[cfg(feature = "eboard")]
+impl Drop for Chessnut {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
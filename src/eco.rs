@@ -0,0 +1,65 @@
+// The names of the chess openings with their ECO code (Encyclopaedia of Chess Openings),
+// from a small table of the well known lines in eco.tsv: the code, the name, and the moves
+// in SAN, separated by tabs.
+
+use crate::engine::{self, Game};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+const TABLE: &str = include_str!("eco.tsv");
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub eco: &'static str,  // i.e. "B90"
+    pub name: &'static str, // i.e. "Sicilian Defense: Najdorf Variation"
+}
+
+// the entries of the table with their moves, without the move numbers
+pub fn openings() -> impl Iterator<Item = (Opening, Vec<&'static str>)> {
+    TABLE.lines().filter_map(|line| {
+        let mut fields = line.split('\t');
+        let opening = Opening {
+            eco: fields.next()?,
+            name: fields.next()?,
+        };
+        let moves = fields
+            .next()?
+            .split_whitespace()
+            .filter(|s| !s.ends_with('.'))
+            .collect();
+        Some((opening, moves))
+    })
+}
+
+// a move in SAN without the suffix for check or mate
+fn without_check(san: &str) -> &str {
+    san.trim_end_matches(['+', '#'])
+}
+
+// The opening of the moves leading to the current position, the entry with the longest
+// line. None for games not starting from the initial position or outside the table.
+pub fn opening(g: &Game) -> Option<Opening> {
+    let mut start = g.clone();
+    engine::go_to_ply(&mut start, 0);
+    if engine::fen(&start) != engine::START_FEN {
+        return None;
+    }
+    let played: Vec<_> = engine::move_list(g)
+        .iter()
+        .take(engine::game_ply(g))
+        .map(|m| m.to_string())
+        .collect();
+    let mut best: Option<(Opening, usize)> = None;
+    for (opening, moves) in openings() {
+        if moves.len() <= played.len()
+            && moves
+                .iter()
+                .zip(&played)
+                .all(|(a, b)| without_check(a) == without_check(b))
+            && best.is_none_or(|(_, len)| moves.len() > len)
+        {
+            best = Some((opening, moves.len()));
+        }
+    }
+    best.map(|(opening, _)| opening)
+}
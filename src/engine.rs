@@ -22,13 +22,50 @@
 // #![allow(non_upper_case_globals)]
 
 //use bitintr::Popcnt;
+use crate::book::Book;
+use crate::persona;
+use crate::variant::{self, Variant};
+use alloc::sync::Arc;
+use core::cmp::max;
+use core::fmt;
 use core::ops::Range;
+use core::time::Duration;
 use num_traits::sign::signum;
-use std::cmp::max;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::time::{Duration, Instant};
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+use std::time::Instant;
+// std::time::Instant panics in the browser
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+use web_time::Instant;
+
+// without std the engine needs only alloc, see lib.rs
+#[cfg(not(feature = "std"))]
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+#[cfg(not(feature = "std"))]
+macro_rules! println {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! print {
+    ($($arg:tt)*) => {{
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+// the repetition history -- BTreeMap is available without std
+#[cfg(feature = "std")]
+type HistoryMap = std::collections::HashMap<BitBuffer192, i32>;
+#[cfg(not(feature = "std"))]
+type HistoryMap = alloc::collections::BTreeMap<BitBuffer192, i32>;
 
 // ### our own primitive bitset type
 #[derive(Copy, Clone, Debug)]
@@ -50,7 +87,7 @@ impl BitSet {
 
     fn insert<T>(&mut self, index: T)
     where
-        u64: std::ops::Shl<T, Output = u64>,
+        u64: core::ops::Shl<T, Output = u64>,
     {
         self.0 |= 1 << index;
     }
@@ -62,9 +99,9 @@ impl BitSet {
     }
     */
 
-    fn _remove<T>(&mut self, index: T)
+    fn remove<T>(&mut self, index: T)
     where
-        u64: std::ops::Shl<T, Output = u64>,
+        u64: core::ops::Shl<T, Output = u64>,
     {
         self.0 &= !(1 << index);
     }
@@ -78,7 +115,7 @@ impl BitSet {
 
     fn contains<T>(&self, index: T) -> bool
     where
-        u64: std::ops::Shl<T, Output = u64>,
+        u64: core::ops::Shl<T, Output = u64>,
     {
         (self.0 & (1 << index)) != 0
     }
@@ -119,16 +156,32 @@ impl Default for BitSet {
 
 #[allow(dead_code)]
 fn _print_variable_type<K>(_: &K) {
-    println!("{}", std::any::type_name::<K>())
+    println!("{}", core::any::type_name::<K>())
+}
+
+// A node of the variation tree of a game, ROOT_NODE is the start position.
+// The moves of the game are the path from the root to the current node.
+#[derive(Clone, Debug, Default)]
+struct Node {
+    mv: Move, // the move leading to this node, a default move for the root
+    parent: NodeId,
+    children: Vec<NodeId>, // the first one continues the main line
+    next: NodeId,          // the child visited last for redo_move(), ROOT_NODE for none
+    comment: String,       // after the move, see node_comment()
+    nags: Vec<u8>,         // see node_nags()
 }
 
+// Cloning a game is cheap, the clone gets its transposition table only when it searches,
+// see TransTable. So the GUI can fork the current position while the live game continues.
 //#[derive(Default)]
-//#[derive(Clone)]
+#[derive(Clone)]
 pub struct Game {
     table_put: i64, // some fields like this are only for statistics and debugging
     table_col: i64,
     max_cup: i64,
     ab_call: i64,
+    nodes: u64, // abeta() calls of the current reply(), for SearchObserver::on_node_batch()
+    sel_depth: i64, // the largest ply of abeta() in the current reply(), for SearchInfo
     score_hash_succ: i64,
     floor_hash_succ: i64,
     hash_succ: i64,
@@ -137,28 +190,39 @@ pub struct Game {
     re_eval_skip: i64,
     max_delta_len: i64,
     is_endgame: bool,
-    start_time: std::time::Instant,
-    tt: Vec<TTE>,
+    start_time: Duration, // time stamp from now()
+    #[cfg(feature = "std")]
+    epoch: Instant,
+    #[cfg(not(feature = "std"))]
+    clock: fn() -> Duration,
+    tt: TransTable,
     debug_list: Vec<String>,
-    history: HashMap<BitBuffer192, i32>,
+    history: HistoryMap,
     board: Board,
     has_moved: HasMoved,
-    move_chain: [i8; 64], // large enough to avoid IF index-in-range test
+    move_chain: [i8; 64],   // large enough to avoid IF index-in-range test
+    played: Vec<UndoToken>, // the moves leading to the current position, for take_back()
+    tree: Vec<Node>,        // the moves and variations of the game, see Node
+    node: NodeId,           // the current position in tree
     freedom: Freedom,
-    pawn_path: [Path; 2],
-    knight_path: Path,
-    bishop_path: Path,
-    rook_path: Path,
-    king_path: Path,
+    pawn_path: [Box<Path>; 2], // the paths are on the heap, they are most of the size of a Game
+    knight_path: Box<Path>,
+    bishop_path: Box<Path>,
+    rook_path: Box<Path>,
+    king_path: Box<Path>,
     to_100: u8,
-    pub secs_per_move: f32,
-    time_0: std::time::Duration,
-    _time_1: std::time::Duration,
-    time_2: std::time::Duration,
-    time_3: std::time::Duration,
-    time_4: std::time::Duration,
+    options: Vec<OptionValue>,               // values of OPTIONS
+    time_left: Option<(Duration, Duration)>, // clock of the active player and increment
+    time_0: Duration,
+    _time_1: Duration,
+    time_2: Duration,
+    time_3: Duration,
+    time_4: Duration,
     pub move_counter: u16,
     pjm: i8,
+    variant: &'static dyn Variant, // the rules, see variant.rs
+    personality: usize,            // index of PERSONALITIES, from the option Personality
+    book: Option<Arc<Book>>,       // the opening book, see set_book()
 }
 
 pub fn print_move_list(g: &Game) {
@@ -190,7 +254,17 @@ const _JUST_TEST: usize = if cfg!(feature = "salewskiChessDebug") {
     7
 };
 
+// the start position of the variant of g
 pub fn reset_game(g: &mut Game) {
+    clear_game(g);
+    let fen = g.variant.start_fen();
+    if fen != START_FEN {
+        set_fen(g, fen).expect("valid start position of the variant");
+    }
+}
+
+// the standard start position, without a history
+fn clear_game(g: &mut Game) {
     g.debug_list.clear();
     g.history.clear();
     g.board = SETUP;
@@ -199,14 +273,349 @@ pub fn reset_game(g: &mut Game) {
                             // g.move_chain.iter_mut().for_each(|m| *m = 0)
     g.move_counter = 0;
     g.pjm = -1;
+    g.to_100 = 0;
     g.has_moved = BitSet::new();
+    g.played.clear();
+    g.tree.clear();
+    g.tree.push(Node::default());
+    g.node = ROOT_NODE;
+    g.history.insert(encode_board(g, COLOR_WHITE), 1);
 }
 
-pub fn new_game() -> Game {
-    if cfg!(debug_assertions) {
-        println!("compiled in debug mode");
+// Copy the position and its history from another game, i.e. from a clone used by the GUI.
+// The transposition table and the options of g are kept, the clock of set_time_left()
+// is copied.
+pub fn set_position(g: &mut Game, from: &Game) {
+    g.board = from.board;
+    g.has_moved = from.has_moved;
+    g.move_chain = from.move_chain;
+    g.move_counter = from.move_counter;
+    g.pjm = from.pjm;
+    g.to_100 = from.to_100;
+    g.history = from.history.clone();
+    g.debug_list = from.debug_list.clone();
+    g.played = from.played.clone();
+    g.tree = from.tree.clone();
+    g.node = from.node;
+    g.time_left = from.time_left;
+    g.variant = from.variant;
+}
+
+// start a new game of the variant
+pub fn set_variant(g: &mut Game, variant: &'static dyn Variant) {
+    g.variant = variant;
+    reset_game(g);
+}
+
+pub fn variant(g: &Game) -> &'static dyn Variant {
+    g.variant
+}
+
+// the persistent part of a game, used for serialization with serde: the moves are played
+// again from the start position, so the position and its history are those of the game
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct GameSnapshot {
+    start: String,      // FEN of the start position
+    moves: Vec<String>, // in UCI notation, including the taken back moves, see move_list()
+    ply: usize,         // the played moves, see game_ply()
+    secs_per_move: f32,
+    variant: String, // the name
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Game {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut start = self.clone();
+        go_to_ply(&mut start, 0);
+        let snapshot = GameSnapshot {
+            start: fen(&start),
+            moves: move_list(self)
+                .iter()
+                .map(|m| m.uci().to_string())
+                .collect(),
+            ply: game_ply(self),
+            secs_per_move: secs_per_move(self),
+            variant: self.variant.name().to_string(),
+        };
+        serde::Serialize::serialize(&snapshot, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Game {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let s: GameSnapshot = serde::Deserialize::deserialize(deserializer)?;
+        let variant = variant::by_name(&s.variant)
+            .ok_or_else(|| D::Error::custom(format!("unknown variant {}", s.variant)))?;
+        let mut g = new_game_without_table(); // a table only when the game is searched
+        set_variant(&mut g, variant);
+        set_fen(&mut g, &s.start).map_err(|e| D::Error::custom(format!("invalid FEN: {}", e)))?;
+        for uci in &s.moves {
+            let m = parse_uci_move(&g, uci)
+                .map_err(|e| D::Error::custom(format!("invalid move {}: {}", uci, e)))?;
+            do_move(&mut g, m.src, m.dst, m.promote_to, false);
+        }
+        go_to_ply(&mut g, s.ply);
+        let ms = (s.secs_per_move * 1000.0 + 0.5) as i64;
+        set_option(&mut g, "MoveTime", &ms.to_string())
+            .map_err(|_| D::Error::custom("invalid time per move"))?;
+        Ok(g)
+    }
+}
+
+// ### engine options, shared by the GUI settings and a UCI frontend
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionType {
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Check {
+        default: bool,
+    },
+    String {
+        default: &'static str,
+    },
+    Combo {
+        default: &'static str,
+        values: &'static [&'static str],
+    }, // one of values
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: &'static str,
+    pub kind: OptionType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OptionValue {
+    Spin(i64),
+    Check(bool),
+    String(String),
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionValue::Spin(v) => write!(f, "{}", v),
+            OptionValue::Check(v) => write!(f, "{}", v),
+            OptionValue::String(v) => f.write_str(v),
+        }
+    }
+}
+
+impl OptionType {
+    pub fn default_value(&self) -> OptionValue {
+        match *self {
+            OptionType::Spin { default, .. } => OptionValue::Spin(default),
+            OptionType::Check { default } => OptionValue::Check(default),
+            OptionType::String { default } | OptionType::Combo { default, .. } => {
+                OptionValue::String(default.to_string())
+            }
+        }
+    }
+
+    pub fn parse(&self, value: &str) -> Result<OptionValue, OptionError> {
+        let value = value.trim();
+        match *self {
+            OptionType::Spin { min, max, .. } => match value.parse::<i64>() {
+                Ok(v) if (min..=max).contains(&v) => Ok(OptionValue::Spin(v)),
+                _ => Err(OptionError::InvalidValue),
+            },
+            OptionType::Check { .. } => match value.parse::<bool>() {
+                Ok(v) => Ok(OptionValue::Check(v)),
+                _ => Err(OptionError::InvalidValue),
+            },
+            OptionType::String { .. } => Ok(OptionValue::String(value.to_string())),
+            // case is ignored, as for the names
+            OptionType::Combo { values, .. } => values
+                .iter()
+                .find(|v| v.eq_ignore_ascii_case(value))
+                .map(|v| OptionValue::String(v.to_string()))
+                .ok_or(OptionError::InvalidValue),
+        }
+    }
+}
+
+const MB: usize = 1024 * 1024;
+const DEFAULT_HASH_MB: i64 = (TTE_SIZE * core::mem::size_of::<TTE>() / MB) as i64;
+const OPTION_HASH: usize = 0;
+const OPTION_MOVE_TIME: usize = 1;
+const OPTION_PERSONA: usize = 2;
+const OPTION_PERSONALITY: usize = 3;
+
+pub const OPTIONS: [EngineOption; 4] = [
+    // size of the transposition table in MB, rounded down to a power of two entries
+    EngineOption {
+        name: "Hash",
+        kind: OptionType::Spin {
+            default: DEFAULT_HASH_MB,
+            min: 1,
+            max: 4096,
+        },
+    },
+    // time for a reply in milliseconds, the search may take longer in rare cases
+    EngineOption {
+        name: "MoveTime",
+        kind: OptionType::Spin {
+            default: 1500,
+            min: 100,
+            max: 9000,
+        },
+    },
+    // a weak strategy instead of the search, see persona.rs
+    EngineOption {
+        name: "Persona",
+        kind: OptionType::Combo {
+            default: persona::FULL,
+            values: &persona::PERSONAS,
+        },
+    },
+    // the weights of the evaluation, see PERSONALITIES
+    EngineOption {
+        name: "Personality",
+        kind: OptionType::Combo {
+            default: PERSONALITY_NAMES[0],
+            values: &PERSONALITY_NAMES,
+        },
+    },
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OptionError {
+    UnknownOption,
+    InvalidValue,
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OptionError::UnknownOption => "unknown option",
+            OptionError::InvalidValue => "invalid option value",
+        })
+    }
+}
+
+impl core::error::Error for OptionError {}
+
+// option names are case insensitive, as in UCI
+fn option_index(name: &str) -> Option<usize> {
+    OPTIONS
+        .iter()
+        .position(|o| o.name.eq_ignore_ascii_case(name.trim()))
+}
+
+pub fn set_option(g: &mut Game, name: &str, value: &str) -> Result<(), OptionError> {
+    let i = option_index(name).ok_or(OptionError::UnknownOption)?;
+    g.options[i] = OPTIONS[i].kind.parse(value)?;
+    // a game without a table gets one with the new size by its next reply()
+    if i == OPTION_HASH && !g.tt.is_empty() && hash_entries(g) != g.tt.len() {
+        g.tt = TransTable(Vec::new()); // free the old table first
+        alloc_table(g);
+    }
+    if i == OPTION_PERSONALITY {
+        let name = string_option(g, i);
+        g.personality = PERSONALITY_NAMES.iter().position(|&n| n == name).unwrap();
+    }
+    Ok(())
+}
+
+// the entries of the transposition table for the option Hash, a power of two
+fn hash_entries(g: &Game) -> usize {
+    let entries = spin_option(g, OPTION_HASH) as usize * MB / core::mem::size_of::<TTE>();
+    1 << (usize::BITS - 1 - entries.max(1).leading_zeros())
+}
+
+fn alloc_table(g: &mut Game) {
+    if g.tt.is_empty() {
+        g.tt = TransTable(vec![Default::default(); hash_entries(g)]);
     }
-    #[cfg(debug_assertions)]
+}
+
+pub fn get_option<'a>(g: &'a Game, name: &str) -> Option<&'a OptionValue> {
+    Some(&g.options[option_index(name)?])
+}
+
+// The engine plays the moves of the book while the position is in it, instead of the
+// search. Like the options, the book is kept by set_position().
+pub fn set_book(g: &mut Game, book: Option<Arc<Book>>) {
+    g.book = book;
+}
+
+fn spin_option(g: &Game, i: usize) -> i64 {
+    match g.options[i] {
+        OptionValue::Spin(v) => v,
+        _ => unreachable!(),
+    }
+}
+
+fn string_option(g: &Game, i: usize) -> &str {
+    match &g.options[i] {
+        OptionValue::String(v) => v,
+        _ => unreachable!(),
+    }
+}
+
+fn secs_per_move(g: &Game) -> f32 {
+    spin_option(g, OPTION_MOVE_TIME) as f32 / 1000.0
+}
+// ###
+
+// Play with a chess clock: the time left for the active player and the increment per
+// move. reply() then plans its time from the clock instead of the option MoveTime,
+// None switches back to MoveTime.
+pub fn set_time_left(g: &mut Game, time_left: Option<(Duration, Duration)>) {
+    g.time_left = time_left;
+}
+
+pub fn time_left(g: &Game) -> Option<(Duration, Duration)> {
+    g.time_left
+}
+
+// the planned time for the next move in seconds
+fn move_time(g: &Game) -> f32 {
+    match g.time_left {
+        None => secs_per_move(g),
+        Some((left, increment)) => {
+            let t = left.as_secs_f32() / 30.0 + increment.as_secs_f32() * 0.8;
+            t.min(left.as_secs_f32() / 4.0).max(0.01)
+        }
+    }
+}
+
+// the hard limit for one iteration of the search, but never more than a third of the clock
+fn max_time(g: &Game) -> f32 {
+    match g.time_left {
+        None => move_time(g) * 5.0,
+        Some((left, _)) => (move_time(g) * 5.0).min(left.as_secs_f32() / 3.0),
+    }
+}
+
+pub fn new_game() -> Game {
+    new_game_with_hash(DEFAULT_HASH_MB)
+}
+
+// A new game with a transposition table of hash_mb MB, like new_game() with the option
+// Hash set, but without allocating the default table first. The size is clamped to the
+// range of the option. A table of 1 MB is enough for a game used for the rules only.
+pub fn new_game_with_hash(hash_mb: i64) -> Game {
+    let mut g = new_game_without_table();
+    let OptionType::Spin { min, max, .. } = OPTIONS[OPTION_HASH].kind else {
+        unreachable!()
+    };
+    g.options[OPTION_HASH] = OptionValue::Spin(hash_mb.clamp(min, max));
+    alloc_table(&mut g);
+    g
+}
+
+// a new game with the default options, its first reply() allocates the table, like the
+// one of a clone, see TransTable
+fn new_game_without_table() -> Game {
+    #[cfg(all(debug_assertions, feature = "salewskiChessDebug"))]
     {
         println!("compiled in debug mode");
     }
@@ -222,7 +631,10 @@ pub fn new_game() -> Game {
 
     // Default::default() does not work, e.g. Duration has no default value!
     let mut g = Game {
-        secs_per_move: 1.5,
+        options: OPTIONS.iter().map(|o| o.kind.default_value()).collect(),
+        personality: 0,
+        book: None,
+        time_left: None,
         time_0: Duration::new(0, 0),
         _time_1: Duration::new(0, 0),
         time_2: Duration::new(0, 0),
@@ -232,6 +644,8 @@ pub fn new_game() -> Game {
         table_col: 0,
         max_cup: 0,
         ab_call: 0,
+        nodes: 0,
+        sel_depth: 0,
         score_hash_succ: 0,
         floor_hash_succ: 0,
         hash_succ: 0,
@@ -240,37 +654,30 @@ pub fn new_game() -> Game {
         re_eval_skip: 0,
         max_delta_len: 0,
         is_endgame: false,
-        start_time: Instant::now(),
-        tt: vec![Default::default(); TTE_SIZE],
+        start_time: Duration::ZERO,
+        #[cfg(feature = "std")]
+        epoch: Instant::now(),
+        #[cfg(not(feature = "std"))]
+        clock: || Duration::ZERO,
+        tt: TransTable(Vec::new()),
         debug_list: Vec::new(),
-        history: HashMap::new(),
+        history: HistoryMap::new(),
         board: SETUP,
         has_moved: BitSet::new(),
         move_chain: [0; 64],
+        played: Vec::new(),
+        tree: vec![Node::default()],
+        node: ROOT_NODE,
         freedom: [[0; 64]; 13],
-        pawn_path: [[[Gnu {
-            pos: 0,
-            nxt_dir_idx: 0,
-        }; 64]; 64]; 2],
-        knight_path: [[Gnu {
-            pos: 0,
-            nxt_dir_idx: 0,
-        }; 64]; 64],
-        bishop_path: [[Gnu {
-            pos: 0,
-            nxt_dir_idx: 0,
-        }; 64]; 64],
-        rook_path: [[Gnu {
-            pos: 0,
-            nxt_dir_idx: 0,
-        }; 64]; 64],
-        king_path: [[Gnu {
-            pos: 0,
-            nxt_dir_idx: 0,
-        }; 64]; 64],
+        pawn_path: [new_path(), new_path()],
+        knight_path: new_path(),
+        bishop_path: new_path(),
+        rook_path: new_path(),
+        king_path: new_path(),
         to_100: 0,
         move_counter: 0,
         pjm: -1,
+        variant: variant::STANDARD,
     };
     init_pawn(&mut g, COLOR_WHITE);
     init_pawn(&mut g, COLOR_BLACK);
@@ -279,80 +686,103 @@ pub fn new_game() -> Game {
     init_king(&mut g);
     init_rook(&mut g);
 
-    //set_board(&mut g, VOID_ID, BF, B8);
-    //set_board(&mut g, VOID_ID, BG, B8);
+    //set_square(&mut g, VOID_ID, BF, B8);
+    //set_square(&mut g, VOID_ID, BG, B8);
     if false {
         g.board = [0; 64];
-        set_board(&mut g, B_KING, BC, B3);
-        set_board(&mut g, W_KING, BD, B6);
-        set_board(&mut g, B_BISHOP, BC, B2);
-        set_board(&mut g, B_BISHOP, BE, B5);
+        set_square(&mut g, B_KING, BC, B3);
+        set_square(&mut g, W_KING, BD, B6);
+        set_square(&mut g, B_BISHOP, BC, B2);
+        set_square(&mut g, B_BISHOP, BE, B5);
     }
 
     if false {
         g.board = [0; 64];
-        //set_board(&mut g, B_KING, BE, B8);
-        //set_board(&mut g, W_KING, BE, B1);
-        //set_board(&mut g, B_PAWN, BE, B7);
-        //set_board(&mut g, W_PAWN, BD, B4);
-
-        set_board(&mut g, W_ROOK, BA, B1);
-        set_board(&mut g, W_ROOK, BH, B1);
-        set_board(&mut g, W_BISHOP, BB, B2);
-        set_board(&mut g, W_QUEEN, BC, B2);
-        set_board(&mut g, W_KING, BD, B2);
-        set_board(&mut g, W_PAWN, BF, B2);
-        set_board(&mut g, W_PAWN, BG, B2);
-        set_board(&mut g, W_PAWN, BH, B2);
-        set_board(&mut g, W_PAWN, BA, B3);
-        set_board(&mut g, W_BISHOP, BD, B3);
-        set_board(&mut g, W_PAWN, BE, B3);
-        set_board(&mut g, W_KNIGHT, BF, B3);
-        set_board(&mut g, B_PAWN, BG, B4);
-        set_board(&mut g, W_PAWN, BC, B5);
-        set_board(&mut g, W_PAWN, BD, B5); // !!!
-        set_board(&mut g, B_PAWN, BE, B5); // !!!
-        set_board(&mut g, B_PAWN, BF, B5);
-        set_board(&mut g, B_KNIGHT, BC, B6);
-        set_board(&mut g, B_QUEEN, BF, B6);
-        set_board(&mut g, B_ROOK, BG, B6);
-        set_board(&mut g, B_PAWN, BA, B7);
-        set_board(&mut g, B_PAWN, BB, B7);
-        set_board(&mut g, B_PAWN, BC, B7);
-        set_board(&mut g, B_PAWN, BH, B7);
-        set_board(&mut g, B_ROOK, BA, B8);
-        set_board(&mut g, B_BISHOP, BC, B8);
-        set_board(&mut g, B_KING, BE, B8);
-        set_board(&mut g, B_BISHOP, BF, B8);
+        //set_square(&mut g, B_KING, BE, B8);
+        //set_square(&mut g, W_KING, BE, B1);
+        //set_square(&mut g, B_PAWN, BE, B7);
+        //set_square(&mut g, W_PAWN, BD, B4);
+
+        set_square(&mut g, W_ROOK, BA, B1);
+        set_square(&mut g, W_ROOK, BH, B1);
+        set_square(&mut g, W_BISHOP, BB, B2);
+        set_square(&mut g, W_QUEEN, BC, B2);
+        set_square(&mut g, W_KING, BD, B2);
+        set_square(&mut g, W_PAWN, BF, B2);
+        set_square(&mut g, W_PAWN, BG, B2);
+        set_square(&mut g, W_PAWN, BH, B2);
+        set_square(&mut g, W_PAWN, BA, B3);
+        set_square(&mut g, W_BISHOP, BD, B3);
+        set_square(&mut g, W_PAWN, BE, B3);
+        set_square(&mut g, W_KNIGHT, BF, B3);
+        set_square(&mut g, B_PAWN, BG, B4);
+        set_square(&mut g, W_PAWN, BC, B5);
+        set_square(&mut g, W_PAWN, BD, B5); // !!!
+        set_square(&mut g, B_PAWN, BE, B5); // !!!
+        set_square(&mut g, B_PAWN, BF, B5);
+        set_square(&mut g, B_KNIGHT, BC, B6);
+        set_square(&mut g, B_QUEEN, BF, B6);
+        set_square(&mut g, B_ROOK, BG, B6);
+        set_square(&mut g, B_PAWN, BA, B7);
+        set_square(&mut g, B_PAWN, BB, B7);
+        set_square(&mut g, B_PAWN, BC, B7);
+        set_square(&mut g, B_PAWN, BH, B7);
+        set_square(&mut g, B_ROOK, BA, B8);
+        set_square(&mut g, B_BISHOP, BC, B8);
+        set_square(&mut g, B_KING, BE, B8);
+        set_square(&mut g, B_BISHOP, BF, B8);
     }
 
     if false {
-        set_board(&mut g, VOID_ID, BF, B1);
-        set_board(&mut g, VOID_ID, BH, B1);
-        set_board(&mut g, VOID_ID, BC, B2);
-        set_board(&mut g, VOID_ID, BD, B2);
-        set_board(&mut g, VOID_ID, BE, B2);
-        set_board(&mut g, VOID_ID, BG, B2);
-        set_board(&mut g, W_PAWN, BC, B2); // ***
-        set_board(&mut g, W_BISHOP, BD, B3);
-        set_board(&mut g, W_KNIGHT, BF, B3);
-        set_board(&mut g, W_PAWN, BD, B4);
-        set_board(&mut g, W_PAWN, BE, B5);
-        set_board(&mut g, W_ROOK, BG, B3);
-        set_board(&mut g, VOID_ID, BG, B1);
-
-        set_board(&mut g, VOID_ID, BB, B8);
-        set_board(&mut g, VOID_ID, BD, B8);
-        set_board(&mut g, VOID_ID, BG, B8);
-        set_board(&mut g, VOID_ID, BE, B7);
-        set_board(&mut g, B_KNIGHT, BC, B6);
-        set_board(&mut g, B_PAWN, BE, B6);
-        set_board(&mut g, B_KNIGHT, BH, B6);
-        set_board(&mut g, B_QUEEN, BH, B3); // ***
+        set_square(&mut g, VOID_ID, BF, B1);
+        set_square(&mut g, VOID_ID, BH, B1);
+        set_square(&mut g, VOID_ID, BC, B2);
+        set_square(&mut g, VOID_ID, BD, B2);
+        set_square(&mut g, VOID_ID, BE, B2);
+        set_square(&mut g, VOID_ID, BG, B2);
+        set_square(&mut g, W_PAWN, BC, B2); // ***
+        set_square(&mut g, W_BISHOP, BD, B3);
+        set_square(&mut g, W_KNIGHT, BF, B3);
+        set_square(&mut g, W_PAWN, BD, B4);
+        set_square(&mut g, W_PAWN, BE, B5);
+        set_square(&mut g, W_ROOK, BG, B3);
+        set_square(&mut g, VOID_ID, BG, B1);
+
+        set_square(&mut g, VOID_ID, BB, B8);
+        set_square(&mut g, VOID_ID, BD, B8);
+        set_square(&mut g, VOID_ID, BG, B8);
+        set_square(&mut g, VOID_ID, BE, B7);
+        set_square(&mut g, B_KNIGHT, BC, B6);
+        set_square(&mut g, B_PAWN, BE, B6);
+        set_square(&mut g, B_KNIGHT, BH, B6);
+        set_square(&mut g, B_QUEEN, BH, B3); // ***
     }
     g
 }
 
+// a monotonic time stamp for the time management of the search
+#[cfg(feature = "std")]
+pub(crate) fn now(g: &Game) -> Duration {
+    g.epoch.elapsed()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn now(g: &Game) -> Duration {
+    (g.clock)()
+}
+
+fn elapsed(g: &Game, since: Duration) -> Duration {
+    now(g).saturating_sub(since)
+}
+
+// Without std there is no Instant, so the application has to provide a monotonic clock,
+// e.g. from a hardware timer. The default clock always returns zero, so reply()
+// then searches up to MAX_DEPTH.
+#[cfg(not(feature = "std"))]
+pub fn set_clock(g: &mut Game, clock: fn() -> Duration) {
+    g.clock = clock;
+}
+
 fn reset_statistics(g: &mut Game) {
     g.table_put = 0;
     g.table_col = 0;
@@ -386,13 +816,13 @@ type BitBuffer192 = [u8; bit_buffer_size()];
 
 const MAX_DEPTH: usize = 15; // other values should work as well
 
-const VOID_ID: i64 = 0;
-const PAWN_ID: i64 = 1;
-const KNIGHT_ID: i64 = 2;
-const BISHOP_ID: i64 = 3;
-const ROOK_ID: i64 = 4;
-const QUEEN_ID: i64 = 5;
-const KING_ID: i64 = 6;
+pub const VOID_ID: i64 = 0;
+pub const PAWN_ID: i64 = 1;
+pub const KNIGHT_ID: i64 = 2;
+pub const BISHOP_ID: i64 = 3;
+pub const ROOK_ID: i64 = 4;
+pub const QUEEN_ID: i64 = 5;
+pub const KING_ID: i64 = 6;
 const ARRAY_BASE_6: i64 = 6;
 const W_PAWN: i64 = PAWN_ID;
 const W_KNIGHT: i64 = KNIGHT_ID;
@@ -452,6 +882,46 @@ pub const KING_VALUE: i16 = 18000; // more than the summed value of all other pi
 pub const KING_VALUE_DIV_2: i16 = KING_VALUE / 2;
 pub const SURE_CHECKMATE: i16 = KING_VALUE / 2; // still more than the summed value of all other pieces, but less than value of a king
 
+// The weights of an engine personality in percent, 100 for the plain evaluation. The
+// material counts less in the opening for a gambit player, and king_attack is a bonus in
+// centipawns for each piece near the king of the opponent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Personality {
+    opening_material: i32,
+    freedom: i32,  // the piece square tables, with the pawn advancement
+    mobility: i32, // the estimation of the quiescence search
+    king_attack: i32,
+}
+
+const PERSONALITY_NAMES: [&str; 4] = ["Balanced", "Aggressive", "Positional", "Gambit"];
+const PERSONALITIES: [Personality; 4] = [
+    Personality {
+        opening_material: 100,
+        freedom: 100,
+        mobility: 100,
+        king_attack: 0,
+    },
+    Personality {
+        opening_material: 100,
+        freedom: 100,
+        mobility: 100,
+        king_attack: 12,
+    },
+    Personality {
+        opening_material: 100,
+        freedom: 150,
+        mobility: 150,
+        king_attack: 0,
+    },
+    Personality {
+        opening_material: 75,
+        freedom: 125,
+        mobility: 125,
+        king_attack: 6,
+    },
+];
+const OPENING_PIECES: usize = 24; // more pieces on the board are the opening
+
 const FIGURE_VALUE: [i16; KING_ID as usize + 1] = [
     VOID_VALUE,
     PAWN_VALUE,
@@ -506,14 +976,14 @@ const B8: usize = 7;
 const POS_RANGE: Range<i8> = 0..64;
 const POS_RANGE_US: Range<usize> = 0..64;
 
-type Color = i64;
-const COLOR_BLACK: i64 = -1;
-const COLOR_WHITE: i64 = 1;
+pub type Color = i64;
+pub const COLOR_BLACK: i64 = -1;
+pub const COLOR_WHITE: i64 = 1;
 type ColorIndex = i8; //0 .. 1
-type Position = i8; //0 .. 63
+pub type Position = i8; //0 .. 63
 type Col = i8; //0 .. 7
 type Row = i8; //0 .. 7
-type FigureID = i64;
+pub type FigureID = i64;
 pub type Board = [FigureID; 64];
 type Freedom = [[i16; 64]; 13]; // VOID_ID..KING_ID; Maybe we should call it happyness
 
@@ -538,6 +1008,20 @@ struct Gnu {
 
 type Path = [[Gnu; 64]; 64];
 
+// an empty Path, allocated without a copy on the stack
+fn new_path() -> Box<Path> {
+    let rows = vec![
+        [Gnu {
+            pos: 0,
+            nxt_dir_idx: 0
+        }; 64];
+        64
+    ];
+    rows.into_boxed_slice()
+        .try_into()
+        .unwrap_or_else(|_| unreachable!())
+}
+
 const IGNORE_MARKER_LOW_INT16: i16 = i16::MIN;
 const INVALID_SCORE: i16 = i16::MIN;
 const LOWEST_SCORE: i16 = -i16::MAX; // allows inverting the sign
@@ -552,16 +1036,16 @@ const STATE_CAN_CAPTURE_KING: i32 = 4;
 #[derive(Copy, Clone, Debug, Default)]
 pub struct KK {
     // source figure, destination figure, source index, destination index
-    s: i16, // score
-    sf: i8,
-    df: i8,
-    si: i8,
+    pub(crate) s: i16, // score
+    pub(crate) sf: i8,
+    pub(crate) df: i8,
+    pub(crate) si: i8,
     pub di: i8,
     eval_depth: i8,
     promote_to: i8, // we may use this to indicate pawn to queen/knight promotion
 }
 
-type KKS = Vec<KK>;
+pub(crate) type Kks = Vec<KK>;
 
 #[derive(Copy, Clone, Default)]
 struct Guide1 {
@@ -584,7 +1068,7 @@ type HashLine2 = [Guide2; MAX_DEPTH + 1];
 struct HashResult {
     score: HashLine1, // exact values
     floor: HashLine2, // lower bounds
-    kks: KKS,
+    kks: Kks,
     pri: i64,
     king_pos: i8,
     queen_pos: i8,
@@ -614,6 +1098,30 @@ fn lift_i16(a: &mut i16, b: i16) {
 }
 
 const TTE_SIZE: usize = 1024 * 1024 * 2; // must be a power of 2
+
+// The transposition table. A clone gets no table, the first reply() of the clone
+// allocates one with the size of the option Hash, see alloc_table(). So copies of a
+// game for the rules, e.g. to replay its moves, are cheap.
+struct TransTable(Vec<TTE>);
+
+impl Clone for TransTable {
+    fn clone(&self) -> Self {
+        TransTable(Vec::new())
+    }
+}
+
+impl core::ops::Deref for TransTable {
+    type Target = Vec<TTE>;
+    fn deref(&self) -> &Vec<TTE> {
+        &self.0
+    }
+}
+
+impl core::ops::DerefMut for TransTable {
+    fn deref_mut(&mut self) -> &mut Vec<TTE> {
+        &mut self.0
+    }
+}
 const TT_TRY: i32 = 5;
 
 fn odd(i: i8) -> bool {
@@ -684,23 +1192,26 @@ fn rows_to_go(p: Position, c: Color) -> i8 {
     }
 }
 
+// FNV-1a, needs no std and is stable across runs
+fn fnv_hash(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 fn board_hash(b: Board) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    Hash::hash_slice(&b, &mut hasher);
-    hasher.finish()
+    fnv_hash(b.iter().flat_map(|f| f.to_le_bytes()))
 }
 
 fn bit_buffer_hash(key: &BitBuffer192) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    Hash::hash_slice(&key[0..CORE_BIT_BUFFER_SIZE], &mut hasher);
-    hasher.finish()
+    fnv_hash(key[0..CORE_BIT_BUFFER_SIZE].iter().copied())
 }
 
 fn get_tte<'a>(g: &'a mut Game, key: BitBuffer192) -> isize {
-    debug_assert!(g.tt.len() == TTE_SIZE);
+    debug_assert!(g.tt.len().is_power_of_two());
     let h0 = bit_buffer_hash(&key);
     for i in 0..(TT_TRY + 1) {
-        let h = (h0.wrapping_add(i as u64)) as usize & ((TTE_SIZE - 1) as usize);
+        let h = (h0.wrapping_add(i as u64)) as usize & (g.tt.len() - 1);
         if g.tt[h].key[0..CORE_BIT_BUFFER_SIZE] == key[0..CORE_BIT_BUFFER_SIZE] {
             if BIT_BUFFER_SIZE == HASH_BIT_BUFFER_SIZE {
                 let bh = board_hash(g.board).to_le_bytes();
@@ -720,7 +1231,7 @@ fn debug_inc(x: &mut i64) {
 }
 
 fn put_tte(g: &mut Game, key: BitBuffer192, mut res: HashResult, pri: i64, hash_pos: isize) {
-    debug_assert!(g.tt.len() == TTE_SIZE);
+    debug_assert!(g.tt.len().is_power_of_two());
     debug_inc(&mut g.table_put);
     if hash_pos >= 0 {
         res.pri = pri;
@@ -729,7 +1240,7 @@ fn put_tte(g: &mut Game, key: BitBuffer192, mut res: HashResult, pri: i64, hash_
     }
     let h0 = bit_buffer_hash(&key);
     for i in 0..(TT_TRY + 1) {
-        let h = (h0.wrapping_add(i as u64)) as usize & ((TTE_SIZE - 1) as usize);
+        let h = (h0.wrapping_add(i as u64)) as usize & (g.tt.len() - 1);
         if g.tt[h].res.pri < pri {
             res.pri = pri;
             g.tt[h].res = res;
@@ -812,7 +1323,7 @@ fn _check(g: &Game) {
 /*
 fn simpleWriteToBitBuffer(g: &Game, c: Color) -> BitBuffer192 {
     let mut result: BitBuffer192 = [0; 32];
-    debug_assert!(std::mem::size_of_val(&result) == 32);
+    debug_assert!(core::mem::size_of_val(&result) == 32);
     let mut empty: u8 = KING_ID as u8;
     if c == COLOR_BLACK {
         // encode the color of active player in empty squares
@@ -851,7 +1362,15 @@ fn much_faster_write_to_bit_buffer(g: &Game, c: Color) -> BitBuffer192 {
     let mut shift: usize;
     let mut bpos: usize = 0; // bype position in collector
     let mut bp; // board position
-    debug_assert!(std::mem::size_of_val(&result) == BIT_BUFFER_SIZE); // 24 byte size should be enough
+    debug_assert!(core::mem::size_of_val(&result) == BIT_BUFFER_SIZE); // 24 byte size should be enough
+    let bits: usize = g
+        .board
+        .iter()
+        .map(|&f| L[(ARRAY_BASE_6 + f) as usize])
+        .sum();
+    if bits + 3 > 22 * 8 {
+        return hashed_key(g, c);
+    }
 
     // for color encoding, we assume a board position (-1), which is empty for white and has a pawn for black.
     if c == COLOR_WHITE {
@@ -887,6 +1406,27 @@ fn much_faster_write_to_bit_buffer(g: &Game, c: Color) -> BitBuffer192 {
     result
 }
 
+// more than 32 pieces, as in Horde, do not fit into the huffman code, the key is a hash of
+// the board then. The last byte, always zero for the huffman code, marks such keys.
+fn hashed_key(g: &Game, c: Color) -> BitBuffer192 {
+    let mut result: BitBuffer192 = [0; BIT_BUFFER_SIZE];
+    let bytes = || {
+        g.board
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .chain(c.to_le_bytes())
+    };
+    result[0..8].copy_from_slice(&fnv_hash(bytes()).to_le_bytes());
+    result[8..16].copy_from_slice(&fnv_hash(bytes().rev()).to_le_bytes());
+    result[16..22]
+        .copy_from_slice(&fnv_hash(core::iter::once(1).chain(bytes())).to_le_bytes()[0..6]);
+    result[23] = 1;
+    if BIT_BUFFER_SIZE == HASH_BIT_BUFFER_SIZE {
+        result[CORE_BIT_BUFFER_SIZE..].copy_from_slice(&board_hash(g.board).to_le_bytes());
+    }
+    result
+}
+
 fn encode_board(g: &Game, c: Color) -> BitBuffer192 {
     //return simpleWriteToBitBuffer(g, c);
     return much_faster_write_to_bit_buffer(g, c);
@@ -1112,7 +1652,7 @@ fn wanted(kk: KK) -> bool {
     kk.sf * kk.df < (kk.s > 0) as i8
 }
 
-fn walk_rook(g: &Game, kk: KK, s: &mut KKS) {
+fn walk_rook(g: &Game, kk: KK, s: &mut Kks) {
     let mut i: i64 = 0;
     let mut kk = kk;
     while {
@@ -1135,7 +1675,7 @@ fn walk_rook(g: &Game, kk: KK, s: &mut KKS) {
     }
 }
 
-fn walk_bishop(g: &Game, kk: KK, s: &mut KKS) {
+fn walk_bishop(g: &Game, kk: KK, s: &mut Kks) {
     let mut i: i64 = 0;
     let mut kk = kk;
     while {
@@ -1158,7 +1698,7 @@ fn walk_bishop(g: &Game, kk: KK, s: &mut KKS) {
     }
 }
 
-fn walk_king(g: &Game, kk: KK, s: &mut KKS) {
+fn walk_king(g: &Game, kk: KK, s: &mut Kks) {
     let mut kk = kk;
     for i in 0..(7 + 1) {
         if {
@@ -1175,7 +1715,7 @@ fn walk_king(g: &Game, kk: KK, s: &mut KKS) {
     }
 }
 
-fn walk_knight(g: &Game, kk: KK, s: &mut KKS) {
+fn walk_knight(g: &Game, kk: KK, s: &mut Kks) {
     let mut kk = kk;
     for i in 0..(7 + 1) {
         if {
@@ -1193,7 +1733,7 @@ fn walk_knight(g: &Game, kk: KK, s: &mut KKS) {
 }
 
 // now we generate all possible ep captures -- before performing the actual move, we have to check ep_pos value
-fn walk_pawn(g: &Game, kk: KK, s: &mut KKS, gen_always_ep: bool) {
+fn walk_pawn(g: &Game, kk: KK, s: &mut Kks, gen_always_ep: bool) {
     let mut kk = kk;
     let col_idx = (kk.sf + 1) / 2;
     for i in 0..2 {
@@ -1258,17 +1798,47 @@ fn walk_pawn(g: &Game, kk: KK, s: &mut KKS, gen_always_ep: bool) {
 }
 
 #[derive(Debug, Default, Copy, Clone)]
-pub struct Move {
+pub struct SearchResult {
     pub src: i64,
     pub dst: i64,
     pub score: i64,
     control: ChessSquares,
-    promote_to: i64,
+    pub promote_to: i64,
     state: State,
 }
 
-// result is for White
-fn plain_evaluate_board(g: &Game) -> i16 {
+impl SearchResult {
+    // the reply of another engine, see uci.rs
+    pub fn new(m: &Move, score: i64) -> Self {
+        SearchResult {
+            src: m.src as i64,
+            dst: m.dst as i64,
+            score,
+            promote_to: m.promote_to,
+            state: STATE_PLAYING,
+            ..Default::default()
+        }
+    }
+}
+
+// the pseudo moves of the figure at kk.si, without castlings
+pub(crate) fn walk_figure(g: &Game, kk: KK, s: &mut Kks, gen_always_ep: bool) {
+    match kk.sf.abs() as i64 {
+        PAWN_ID => walk_pawn(g, kk, s, gen_always_ep),
+        KNIGHT_ID => walk_knight(g, kk, s),
+        BISHOP_ID => walk_bishop(g, kk, s),
+        ROOK_ID => walk_rook(g, kk, s),
+        QUEEN_ID => {
+            walk_bishop(g, kk, s);
+            walk_rook(g, kk, s)
+        }
+        KING_ID => walk_king(g, kk, s),
+        _ => {}
+    }
+}
+
+// result is for White, see Variant::evaluate()
+pub(crate) fn plain_evaluate_board(g: &Game) -> i16 {
     let mut result: i16 = 0;
     for (p, f) in g.board.iter().enumerate() {
         // if f != VOID_ID -- does not increase performance
@@ -1296,9 +1866,133 @@ fn plain_evaluate_board(g: &Game) -> i16 {
             result += 2;
         }
     }
+    if g.personality != 0 {
+        result += personality_bonus(g);
+    }
     result
 }
 
+// the difference of the weighted terms of the personality to the plain evaluation
+fn personality_bonus(g: &Game) -> i16 {
+    let w = PERSONALITIES[g.personality];
+    let (mut material, mut freedom) = (0, 0);
+    let mut kings = [None; 2]; // Black, White
+    for (p, &f) in g.board.iter().enumerate() {
+        if f.abs() == KING_ID {
+            kings[(f > 0) as usize] = Some(p as Position);
+        } else {
+            material += FIGURE_VALUE[f.unsigned_abs() as usize] as i32 * signum(f) as i32;
+        }
+        freedom += g.freedom[(ARRAY_BASE_6 + f) as usize][p] as i32 * signum(f) as i32;
+    }
+    let mut bonus = freedom * (w.freedom - 100) / 100;
+    if g.board.iter().filter(|&&f| f != VOID_ID).count() > OPENING_PIECES {
+        bonus += material * (w.opening_material - 100) / 100;
+    }
+    if w.king_attack != 0 {
+        for (p, &f) in g.board.iter().enumerate() {
+            if f.abs() == PAWN_ID || f.abs() == KING_ID || f == VOID_ID {
+                continue;
+            }
+            // the king of the opponent within two squares
+            if let Some(k) = kings[(f < 0) as usize] {
+                let p = p as Position;
+                if (col(p) - col(k)).abs() <= 2 && (row(p) - row(k)).abs() <= 2 {
+                    bonus += w.king_attack * signum(f) as i32;
+                }
+            }
+        }
+    }
+    bonus as i16
+}
+
+// A quick static evaluation in centipawns from White's point of view, without search,
+// i.e. for an evaluation bar while the engine is idle.
+pub fn evaluate(g: &Game) -> i64 {
+    g.variant.evaluate(g) as i64
+}
+
+// the material balance in pawns from White's point of view: 1 for a pawn, 3 for knights
+// and bishops, 5 for a rook and 9 for a queen
+pub fn material_balance(g: &Game) -> i64 {
+    let pieces = g.board.iter().filter(|f| f.abs() != KING_ID);
+    pieces
+        .map(|&f| signum(f) * (FIGURE_VALUE[f.unsigned_abs() as usize] / PAWN_VALUE) as i64)
+        .sum()
+}
+
+// the pieces captured by the moves leading to the current position, with their color
+pub fn captured_pieces(g: &Game) -> Vec<FigureID> {
+    let captured = g.played.iter().map(|t| t.mv.captured);
+    captured.filter(|&f| f != VOID_ID).collect()
+}
+
+// the contributions of the evaluation terms for one side, in centipawns
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalTerms {
+    pub material: i64,       // without the king
+    pub pst: i64,            // piece square tables (freedom) of all figures but pawns
+    pub pawn_structure: i64, // pawn advancement, we have no other pawn terms
+    pub king_safety: i64,    // penalty for lost castling rights
+    pub mobility: i64,       // one point per pseudo legal move
+}
+
+impl EvalTerms {
+    pub fn sum(&self) -> i64 {
+        self.material + self.pst + self.pawn_structure + self.king_safety + self.mobility
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalBreakdown {
+    pub white: EvalTerms,
+    pub black: EvalTerms,
+}
+
+impl EvalBreakdown {
+    // the evaluation from White's point of view
+    pub fn total(&self) -> i64 {
+        self.white.sum() - self.black.sum()
+    }
+}
+
+// The static evaluation split into its terms, to explain why the engine likes a position.
+// Mobility is not part of plain_evaluate_board(), the search uses it only as an estimation.
+pub fn evaluate_detailed(g: &Game) -> EvalBreakdown {
+    let mut r = EvalBreakdown::default();
+    for (p, &f) in g.board.iter().enumerate() {
+        if f == VOID_ID {
+            continue;
+        }
+        let t = if f > 0 { &mut r.white } else { &mut r.black };
+        let freedom = g.freedom[(ARRAY_BASE_6 + f) as usize][p] as i64;
+        match f.abs() {
+            PAWN_ID => t.pawn_structure += freedom,
+            _ => t.pst += freedom,
+        }
+        if f.abs() != KING_ID {
+            t.material += FIGURE_VALUE[f.unsigned_abs() as usize] as i64;
+        }
+        t.mobility += pseudo_moves(g, p as i64).len() as i64;
+    }
+    for (t, k, r0, r7) in [
+        (&mut r.white, WK3, WR0, WR7),
+        (&mut r.black, BK59, BR56, BR63),
+    ] {
+        if g.has_moved.contains(k) {
+            t.king_safety -= 4;
+        } else {
+            t.king_safety -=
+                2 * (g.has_moved.contains(r0) as i64 + g.has_moved.contains(r7) as i64);
+        }
+    }
+    debug_assert!(
+        r.total() - r.white.mobility + r.black.mobility
+            == (plain_evaluate_board(g) - personality_bonus(g)) as i64
+    );
+    r
+}
+
 /*
 discard """
 https://chessprogramming.wikispaces.com/Alpha-Beta
@@ -1460,15 +2154,44 @@ fn in_check(game: &Game, square_index: usize, color: Color) -> bool {
 }
 */
 
-fn king_pos(g: &Game, c: Color) -> i8 {
-    let k = KING_ID * c as i64;
-    for (i, f) in g.board.iter().enumerate() {
-        if *f == k {
-            return i as i8;
+// the positions of all figures of color c attacking position si on board b
+fn attackers(g: &Game, b: &Board, si: Position, c: Color) -> ChessSquares {
+    let mut result = ChessSquares::new();
+    let si = si as usize;
+    for (path, f) in [(&g.knight_path, W_KNIGHT), (&g.king_path, W_KING)] {
+        for el in path[si].iter().take_while(|el| el.pos >= 0) {
+            if b[el.pos as usize] == f * c {
+                result.insert(el.pos);
+            }
+        }
+    }
+    for el in &g.pawn_path[col_idx(opp_color(c)) as usize][si][0..2] {
+        if el.pos >= 0 && b[el.pos as usize] == W_PAWN * c {
+            result.insert(el.pos);
+        }
+    }
+    for (path, f) in [(&g.bishop_path, W_BISHOP), (&g.rook_path, W_ROOK)] {
+        let mut i = 0;
+        while path[si][i].pos >= 0 {
+            let el = path[si][i];
+            let t = b[el.pos as usize];
+            if t == VOID_ID {
+                i += 1;
+            } else {
+                if t == f * c || t == W_QUEEN * c {
+                    result.insert(el.pos);
+                }
+                i = el.nxt_dir_idx as usize;
+            }
         }
     }
-    debug_assert!(false);
-    0
+    result
+}
+
+// None in variants without a king, i.e. for White in Horde
+fn king_pos(g: &Game, c: Color) -> Option<Position> {
+    let k = KING_ID * c as i64;
+    g.board.iter().position(|&f| f == k).map(|i| i as Position)
 }
 
 const V_RATIO: i64 = 8;
@@ -1529,6 +2252,7 @@ fn pmq(a: i64, b: i64) -> i64 {
 //
 fn abeta(
     g: &mut Game,
+    observer: &mut dyn SearchObserver,
     color: Color,
     v_depth: i64,
     cup: i64,
@@ -1536,17 +2260,31 @@ fn abeta(
     beta: i64,
     old_list_len: i64,
     ep_pos: i8,
-) -> Move {
-    let mut result = Move {
+) -> SearchResult {
+    let mut result = SearchResult {
         state: STATE_NO_VALID_MOVE,
         score: LOWEST_SCORE as i64,
         ..Default::default()
     };
-    if g.start_time.elapsed() > g.time_4 {
+    if elapsed(g, g.start_time) > g.time_4 {
         return result; // invalid due to hard time contraints.
     }
     debug_assert!(alpha_0 < beta);
+    if g.variant.winner(g, &g.board) == Some(opp_color(color)) {
+        // the last move has won, like a checkmate
+        result.state = STATE_CHECKMATE;
+        result.score = -KING_VALUE as i64 + cup - 1;
+        return result;
+    }
     debug_inc(&mut g.ab_call);
+    g.nodes += 1;
+    if g.nodes.is_multiple_of(NODE_BATCH) {
+        observer.on_node_batch(g.nodes);
+        // a stop request is a hard cut, when the first iteration has given a valid move
+        if g.time_4 != Duration::MAX && observer.should_stop() {
+            g.time_4 = Duration::ZERO;
+        }
+    }
     debug_assert!(MAX_DEPTH == 15);
     debug_assert!(V_RATIO == 8);
     let depth_0: usize = max(v_depth / V_RATIO, 0) as usize; // starting at depth_0 == 0 we do only captures
@@ -1555,8 +2293,9 @@ fn abeta(
     if depth_0 > 0 {
         lift(&mut g.max_cup, cup);
     }
+    lift(&mut g.sel_depth, cup);
     debug_assert!(cup >= 0);
-    debug_assert!(std::mem::size_of::<KK>() == 8);
+    debug_assert!(core::mem::size_of::<KK>() == 8);
     debug_assert!(old_list_len >= 0);
     debug_assert!((-1..63).contains(&ep_pos));
     let mut hash_res: HashResult;
@@ -1614,6 +2353,7 @@ fn abeta(
         hash_res = HashResult::default();
         init_hr(&mut hash_res);
         hash_res.queen_pos = -1;
+        hash_res.king_pos = -1;
     }
 
     //when false: // possible, but makes not much sense
@@ -1639,7 +2379,7 @@ fn abeta(
     let mut evaluation: i16 = LOWEST_SCORE;
     if depth_0 == 0 {
         // null move estimation for quiescence search
-        evaluation = plain_evaluate_board(&g) * color as i16 - old_list_len as i16;
+        evaluation = g.variant.evaluate(g) * color as i16 - old_list_len as i16;
         if evaluation as i64 >= beta {
             result.score = beta;
             debug_inc(&mut g.null_move_succ_1);
@@ -1662,23 +2402,12 @@ fn abeta(
             kk.si = si as i8;
             kk.sf = *sf as i8;
             match sf.abs() {
-                PAWN_ID => walk_pawn(&g, kk, &mut s, true),
-                KNIGHT_ID => walk_knight(&g, kk, &mut s),
-                BISHOP_ID => walk_bishop(&g, kk, &mut s),
-                ROOK_ID => walk_rook(&g, kk, &mut s),
-                QUEEN_ID => {
-                    walk_bishop(&g, kk, &mut s);
-                    walk_rook(&g, kk, &mut s);
-                    hash_res.queen_pos = kk.si
-                }
-                KING_ID => {
-                    walk_king(&g, kk, &mut s);
-                    hash_res.king_pos = kk.si
-                }
+                QUEEN_ID => hash_res.queen_pos = kk.si,
+                KING_ID => hash_res.king_pos = kk.si,
                 _ => {}
             }
+            g.variant.walk(g, kk, &mut s, true);
         }
-        debug_assert!(hash_res.pop_cnt <= 32); // for regular games
         for el in &s {
             if !is_a_pawn(el.sf) || odd(el.si - el.di) {
                 attacs += 1;
@@ -1778,7 +2507,7 @@ fn abeta(
     if CHECK_EXTEND && !hash_res.tested_for_check && depth_0 > 1 {
         hash_res.in_check = (hash_res.queen_pos >= 0
             && queen_in_check(&g, hash_res.queen_pos, color))
-            || in_check(&g, hash_res.king_pos, color, false);
+            || hash_res.king_pos >= 0 && in_check(g, hash_res.king_pos, color, false);
         // this field is optional information
         hash_res.tested_for_check = true;
     }
@@ -1787,7 +2516,9 @@ fn abeta(
         (hash_res.kks.len() as i64 + attacs + hash_res.control.0.count_ones() as i64) as i16;
     if depth_0 == 0 {
         // more detailed null move estimation for quiescence search. NOTE: Take attacs into account?
-        evaluation += hash_res_kks_len; // we may do a more fine grained board control evaluation?
+        // we may do a more fine grained board control evaluation?
+        evaluation +=
+            (hash_res_kks_len as i32 * PERSONALITIES[g.personality].mobility / 100) as i16;
         if cfg!(feature = "salewskiChessDebug") {
             lift(
                 &mut g.max_delta_len,
@@ -1820,13 +2551,15 @@ fn abeta(
             continue;
         }
         if cup == 0 {
-            if (eval_cnt > 0 && g.start_time.elapsed() > g.time_3)
-                || (eval_cnt > 1 && g.start_time.elapsed() > g.time_2)
+            if (eval_cnt > 0 && elapsed(g, g.start_time) > g.time_3)
+                || (eval_cnt > 1 && elapsed(g, g.start_time) > g.time_2)
             {
-                println!(
-                    "time break, eval count: {} {} {}",
-                    eval_cnt, hash_res_kks_high, el.eval_depth
-                );
+                if cfg!(feature = "salewskiChessDebug") {
+                    println!(
+                        "time break, eval count: {} {} {}",
+                        eval_cnt, hash_res_kks_high, el.eval_depth
+                    );
+                }
                 //debug_assert!(eval_cnt as usize + 1 == hash_res_kks_high); // no, not always
                 if false && cfg!(feature = "salewskiChessDebug") {
                     println!("{:?}", hash_res.kks);
@@ -1836,7 +2569,7 @@ fn abeta(
                 break;
             }
         }
-        let mut m: Move = Default::default();
+        let mut m: SearchResult = Default::default();
         if el.eval_depth >= depth_0 as i8 {
             // this move was already evaluated, but was not good enough, no beta cutoff
             valid_move_found = true; // list contains only valid moves, as we delete or skip the invalid ones
@@ -1974,7 +2707,9 @@ fn abeta(
             } else if is_a_pawnelsf && base_row(el.di) {
                 g.board[el.di as usize] = el.promote_to as i64;
             }
-            let pawn_jump = is_a_pawnelsf && (elsieldi == 16 || elsieldi == -16);
+            let pawn_jump = is_a_pawnelsf
+                && (elsieldi == 16 || elsieldi == -16)
+                && rows_to_go(el.si, color) == 6;
             if pawn_jump {
                 nep_pos = (el.si + el.di) / 2; // fast unsigned div
             } else {
@@ -2006,6 +2741,7 @@ fn abeta(
             }
             m = abeta(
                 g,
+                observer,
                 opp_color(color),
                 v_depth + v_depth_inc + sdi[el.sf.abs() as usize] + ddi[el.df.abs() as usize],
                 cup + 1,
@@ -2107,7 +2843,7 @@ fn abeta(
         hash_res_kks_high += 1; // the number of up to date positions, which we have to sort later
     }
     if depth_0 > 0 && !valid_move_found {
-        if in_check(&g, hash_res.king_pos, color, false) {
+        if hash_res.king_pos >= 0 && in_check(g, hash_res.king_pos, color, false) {
             result.state = STATE_CHECKMATE;
             result.score = -KING_VALUE as i64 + cup as i64 - 1;
         } else {
@@ -2155,16 +2891,16 @@ fn abeta(
     result
 }
 
-fn _str_2_board_pos(s: String) -> Position {
-    debug_assert!(s.len() == 2);
-    let s0 = s.as_bytes()[0] as char;
-    let s0 = s0.to_ascii_lowercase();
-    let s1 = s.as_bytes()[1];
-    //debug_assert!(s0 in {'a' .. 'h'})
-    //debug_assert!(s1 in {'1' .. '8'})
-    let c = 7 - (s0 as i8 - 'a' as i8);
-    let r = s1 as i8 - '1' as i8;
-    return c + r * 8;
+// i.e. "e4" -- case is ignored
+pub fn parse_square(s: &str) -> Result<Position, MoveError> {
+    match s.as_bytes() {
+        [c, r] if (b'a'..=b'h').contains(&c.to_ascii_lowercase()) && (b'1'..=b'8').contains(r) => {
+            let c = 7 - (c.to_ascii_lowercase() - b'a') as i8;
+            let r = (r - b'1') as i8;
+            Ok(c + r * 8)
+        }
+        _ => Err(MoveError::InvalidSquare),
+    }
 }
 
 fn _check_mate_in(score: i64) -> i64 {
@@ -2175,16 +2911,23 @@ fn _check_mate_in(score: i64) -> i64 {
     }
 }
 
-fn alphabeta(g: &mut Game, color: Color, depth: i64, ep_pos: i8) -> Move {
-    debug_assert!((0.1..10.0).contains(&g.secs_per_move));
+fn alphabeta(
+    g: &mut Game,
+    observer: &mut dyn SearchObserver,
+    color: Color,
+    depth: i64,
+    ep_pos: i8,
+) -> SearchResult {
+    debug_assert!(g.time_left.is_some() || (0.1..10.0).contains(&secs_per_move(g)));
     //g.time_0 = Duration::from_secs_f32(g.secs_per_move * 0.7);
-    g.time_2 = Duration::from_secs_f32(g.secs_per_move * 1.5);
-    g.time_3 = Duration::from_secs_f32(g.secs_per_move * 2.5);
+    g.time_2 = Duration::from_secs_f32((move_time(g) * 1.5).min(max_time(g)));
+    g.time_3 = Duration::from_secs_f32((move_time(g) * 2.5).min(max_time(g)));
     //g.time_4 = Duration::from_secs_f32(g.secs_per_move * 5.0);
-    g.start_time = Instant::now();
+    g.start_time = now(g);
     reset_statistics(g);
     let result = abeta(
         g,
+        observer,
         color,
         depth * V_RATIO + V_RATIO / 2,
         0,
@@ -2203,230 +2946,2786 @@ fn alphabeta(g: &mut Game, color: Color, depth: i64, ep_pos: i8) -> Move {
     result
 }
 
-const FLAG_PLAIN: i32 = 0;
-const FLAG_CAPTURE: i32 = 1;
-const FLAG_EP: i32 = 2;
-const FLAG_PROMOTION: i32 = 3;
-const FLAG_PROCAP: i32 = 4;
+// the special kinds of moves -- plain moves and captures are MoveKind::Plain
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MoveKind {
+    #[default]
+    Plain,
+    PawnJump,
+    EnPassant,
+    Promotion,
+    ShortCastling,
+    LongCastling,
+}
+
+// a fully described move, as used by the GUI and for move lists.
+// piece, captured and promote_to carry the color as sign, like the board entries.
+// For en passant captured is the pawn removed from the board.
+// check, mate and the SAN disambiguation hints are set by do_move() only.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move {
+    pub src: Position,
+    pub dst: Position,
+    pub piece: FigureID,
+    pub captured: FigureID,
+    pub promote_to: FigureID,
+    pub kind: MoveKind,
+    pub check: bool,
+    pub mate: bool,
+    file_hint: bool, // SAN disambiguation, i.e. Nbd7 or R1e2
+    rank_hint: bool,
+}
 
-pub fn do_move(g: &mut Game, p0: Position, p1: Position, silent: bool) -> i32 {
-    p(g.board);
-    let mut result: i32 = 0;
-    if !is_void_at(&g, p1) {
-        result = FLAG_CAPTURE;
-    }
-    if !silent {
-        g.has_moved.insert(p0 as usize);
-        g.pjm = -1;
-        if is_a_pawn_at(&g, p0) && (p0 - p1).abs() == 16 {
-            g.pjm = (p0 + p1) / 2;
-        }
-        if is_a_pawn_at(&g, p0) || !is_void_at(&g, p1) {
-            // test for castlings as well?
-            g.to_100 = 0;
-        } else {
-            g.to_100 += 1;
-        }
+impl Move {
+    pub fn is_capture(&self) -> bool {
+        self.captured != VOID_ID
     }
-    if (p1 - p0).abs() == 2 && is_a_king_at(&g, p0) {
-        if col(p1) == 1 {
-            g.board[p0 as usize - 1] = g.board[p0 as usize - 3];
-            g.board[p0 as usize - 3] = VOID_ID;
-        } else {
-            g.board[p0 as usize + 1] = g.board[p0 as usize + 4];
-            g.board[p0 as usize + 4] = VOID_ID;
-        }
-    } else if base_row(p1) && is_a_pawn_at(&g, p0) {
-        g.board[p0 as usize] *= QUEEN_ID;
-        result = if result == FLAG_CAPTURE {
-            FLAG_PROCAP
-        } else {
-            FLAG_PROMOTION
-        }
-    } else if is_a_pawn_at(&g, p0) && is_void_at(&g, p1) && odd(p1 - p0) {
-        result = FLAG_EP;
-        g.board[(p1 as i64 - g.board[p0 as usize] * 8) as usize] = VOID_ID;
+
+    // UCI long algebraic notation, i.e. "g1f3" or "e7e8q"
+    pub fn uci(&self) -> Uci {
+        Uci(*self)
     }
-    g.board[p1 as usize] = g.board[p0 as usize];
-    g.board[p0 as usize] = VOID_ID;
-    if !silent {
-        if is_a_pawn_at(&g, p1) || result != FLAG_PLAIN {
-            g.history.clear();
-        } else {
-            let new_state = encode_board(&g, signum(g.board[p1 as usize]) as Color);
-            *g.history.entry(new_state).or_insert(0) += 1;
-        }
+
+    // the move in the given notation, Display of Move is Notation::San
+    pub fn notation(&self, n: Notation) -> Notated {
+        self.localized(n, &SAN_FIG_STR)
     }
-    //when defined(salewskiChessDebug):
-    if true {
-        if !silent {
-            g.debug_list.push(move_to_str(&g, p0, p1, result));
-            //println!("--");
-        }
+
+    // the move in the given notation with the piece letters of a language, indexed by
+    // KNIGHT_ID..=KING_ID, figurine notation ignores them
+    pub fn localized(&self, n: Notation, letters: &'static [&'static str; 7]) -> Notated {
+        Notated(*self, n, letters)
     }
-    p(g.board);
-    g.move_counter += (!silent) as u16;
-    result
 }
 
-pub fn tag(g: &mut Game, si: i64) -> KKS {
-    let mut kk: KK = Default::default();
+pub const SAN_FIG_STR: [&str; 7] = ["", "", "N", "B", "R", "Q", "K"];
+const FIGURINE_STR: [&str; 7] = [
+    "", "", "\u{2658}", "\u{2657}", "\u{2656}", "\u{2655}", "\u{2654}",
+];
+
+// the notations of moves for display, PGN is always written in SAN
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Notation {
+    #[default]
+    San, // standard algebraic notation, i.e. "Nxf7+", "exd6", "e8=Q" or "O-O-O"
+    Long,     // long algebraic notation with the source square, i.e. "Ng5xf7+" or "e2-e4"
+    Figurine, // SAN with piece symbols, i.e. "♘xf7+"
+}
+
+pub struct Notated(Move, Notation, &'static [&'static str; 7]);
+
+impl fmt::Display for Notated {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (m, n) = (&self.0, self.1);
+        let figures = if n == Notation::Figurine {
+            &FIGURINE_STR
+        } else {
+            self.2
+        };
+        match m.kind {
+            MoveKind::ShortCastling => f.write_str("O-O")?,
+            MoveKind::LongCastling => f.write_str("O-O-O")?,
+            _ => {
+                if n == Notation::Long {
+                    f.write_str(figures[m.piece.unsigned_abs() as usize])?;
+                    f.write_str(&square_str(m.src))?;
+                    f.write_str(if m.is_capture() { "x" } else { "-" })?;
+                } else if m.piece.abs() == PAWN_ID {
+                    if m.is_capture() {
+                        write!(f, "{}x", file_char(m.src))?;
+                    }
+                } else {
+                    f.write_str(figures[m.piece.unsigned_abs() as usize])?;
+                    if m.file_hint {
+                        write!(f, "{}", file_char(m.src))?;
+                    }
+                    if m.rank_hint {
+                        write!(f, "{}", row_str(row(m.src)))?;
+                    }
+                    if m.is_capture() {
+                        f.write_str("x")?;
+                    }
+                }
+                f.write_str(&square_str(m.dst))?;
+                if m.promote_to != VOID_ID {
+                    write!(f, "={}", figures[m.promote_to.unsigned_abs() as usize])?;
+                }
+            }
+        }
+        if m.mate {
+            f.write_str("#")
+        } else if m.check {
+            f.write_str("+")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.notation(Notation::San).fmt(f)
+    }
+}
+
+pub struct Uci(Move);
+
+impl fmt::Display for Uci {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = &self.0;
+        write!(f, "{}{}", square_str(m.src), square_str(m.dst))?;
+        if m.promote_to != VOID_ID {
+            f.write_str(&SAN_FIG_STR[m.promote_to.unsigned_abs() as usize].to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+// describe the move from src to dst for the current board, without doing it.
+// For pawns reaching the base row, promote_to == VOID_ID means promotion to a queen.
+pub fn new_move(g: &Game, src: Position, dst: Position, promote_to: FigureID) -> Move {
+    let piece = g.board[src as usize];
+    let mut m = Move {
+        src,
+        dst,
+        piece,
+        captured: g.board[dst as usize],
+        ..Default::default()
+    };
+    if is_a_king_at(g, src) && (dst - src).abs() == 2 {
+        m.kind = if col(dst) == 1 {
+            MoveKind::ShortCastling
+        } else {
+            MoveKind::LongCastling
+        };
+    } else if is_a_pawn_at(g, src) {
+        if base_row(dst) {
+            m.kind = MoveKind::Promotion;
+            let id = if promote_to == VOID_ID {
+                QUEEN_ID
+            } else {
+                promote_to.abs()
+            };
+            m.promote_to = id * signum(piece);
+        } else if (dst - src).abs() == 16 {
+            m.kind = MoveKind::PawnJump;
+        } else if is_void_at(g, dst) && odd(dst - src) {
+            m.kind = MoveKind::EnPassant;
+            m.captured = -piece;
+        }
+    }
+    m
+}
+
+// SAN needs the source file and/or row when other figures of same kind can reach dst
+fn set_san_hints(m: &mut Move, moves: &[Move]) {
+    if m.piece.abs() == PAWN_ID || m.piece.abs() == KING_ID {
+        return;
+    }
+    let mut ambiguous = false;
+    let mut same_col = false;
+    let mut same_row = false;
+    for el in moves {
+        if el.src != m.src && el.dst == m.dst && el.piece == m.piece {
+            ambiguous = true;
+            same_col |= col(el.src) == col(m.src);
+            same_row |= row(el.src) == row(m.src);
+        }
+    }
+    if ambiguous {
+        m.file_hint = !same_col || same_row;
+        m.rank_hint = same_col;
+    }
+}
+
+fn gives_check(g: &Game, m: &Move) -> bool {
+    let b = board_after(g, m);
+    let c = opp_color(signum(m.piece) as Color);
+    match b.iter().position(|&f| f == KING_ID * c) {
+        Some(k) => attackers(g, &b, k as Position, opp_color(c)).0 != 0,
+        None => false,
+    }
+}
+
+fn has_valid_move(g: &Game, c: Color) -> bool {
+    for si in POS_RANGE {
+        if g.board[si as usize] * c > 0 && !tag(g, si as i64).is_empty() {
+            return true;
+        }
+    }
+    false
+}
+
+// everything needed by undo_move() to restore the game state from before do_move()
+#[derive(Clone, Debug)]
+pub struct UndoToken {
+    pub mv: Move,
+    has_moved: HasMoved,
+    pjm: i8,
+    to_100: u8,
+    silent: bool,
+    history: Option<HistoryMap>, // the old history, when do_move() had to clear it
+    new_node: bool,              // do_move() added the move to the variation tree
+    next: NodeId,                // the old next of the current node
+}
+
+// silent is used for tests of legality, board is modified, but no game state or history.
+pub fn do_move(
+    g: &mut Game,
+    p0: Position,
+    p1: Position,
+    promote_to: FigureID,
+    silent: bool,
+) -> UndoToken {
+    p(g.board);
+    let mut m = new_move(g, p0, p1, promote_to);
+    let mut token = UndoToken {
+        mv: m,
+        has_moved: g.has_moved,
+        pjm: g.pjm,
+        to_100: g.to_100,
+        silent,
+        history: None,
+        new_node: false,
+        next: ROOT_NODE,
+    };
+    let is_pawn_move = m.piece.abs() == PAWN_ID;
+    if !silent {
+        let moves: Vec<Move> = legal_moves(g).collect();
+        set_san_hints(&mut m, &moves);
+        g.has_moved.insert(p0 as usize);
+        g.pjm = -1;
+        // not for a double step from the first rank, as in Horde
+        if m.kind == MoveKind::PawnJump && rows_to_go(p0, signum(m.piece)) == 6 {
+            g.pjm = (p0 + p1) / 2;
+        }
+        if is_pawn_move || m.is_capture() {
+            // test for castlings as well?
+            g.to_100 = 0;
+        } else {
+            g.to_100 += 1;
+        }
+    }
+    g.board = board_after(g, &m);
+    if !silent {
+        let opp = -signum(m.piece) as Color;
+        if is_pawn_move || m.is_capture() {
+            token.history = Some(core::mem::take(&mut g.history));
+        }
+        // same encoding as in abeta(), so the search can detect repetitions of game positions
+        let new_state = encode_board(g, opp);
+        *g.history.entry(new_state).or_insert(0) += 1;
+        m.check = king_pos(g, opp).is_some_and(|k| in_check(g, k, opp, true));
+        m.mate = m.check && !has_valid_move(g, opp);
+        g.debug_list.push(m.to_string());
+    }
+    p(g.board);
+    g.move_counter += (!silent) as u16;
+    token.mv = m;
+    if !silent {
+        // a known move follows its line, another one starts a new variation
+        token.next = g.tree[g.node].next;
+        let known = g.tree[g.node].children.iter().copied().find(|&c| {
+            let c = g.tree[c].mv;
+            (c.src, c.dst, c.promote_to) == (m.src, m.dst, m.promote_to)
+        });
+        let child = known.unwrap_or_else(|| {
+            g.tree.push(Node {
+                mv: m,
+                parent: g.node,
+                ..Default::default()
+            });
+            let child = g.tree.len() - 1;
+            g.tree[g.node].children.push(child);
+            token.new_node = true;
+            child
+        });
+        g.tree[g.node].next = child;
+        g.node = child;
+        g.played.push(token.clone());
+    }
+    token
+}
+
+// take back the last move done by do_move(), tokens must be used in reverse order.
+// The variation tree is restored too, so a move which was only tried leaves no trace.
+pub fn undo_move(g: &mut Game, token: UndoToken) {
+    undo(g, token, false);
+}
+
+// keep leaves the move in the variation tree, for take_back()
+fn undo(g: &mut Game, token: UndoToken, keep: bool) {
+    let m = token.mv;
+    if !token.silent {
+        let node = g.node;
+        g.node = g.tree[node].parent;
+        if !keep {
+            // a new node is the last one of the tree, and a leaf
+            if token.new_node && node == g.tree.len() - 1 && g.tree[node].children.is_empty() {
+                g.tree.pop();
+                g.tree[g.node].children.pop();
+            }
+            g.tree[g.node].next = token.next;
+        }
+        let new_state = encode_board(g, -signum(m.piece) as Color);
+        match token.history {
+            Some(history) => g.history = history,
+            None => {
+                if let Some(reps) = g.history.get_mut(&new_state) {
+                    *reps -= 1;
+                    if *reps <= 0 {
+                        g.history.remove(&new_state);
+                    }
+                }
+            }
+        }
+        g.debug_list.pop();
+        g.played.pop();
+        g.move_counter -= 1;
+        g.has_moved = token.has_moved;
+        g.pjm = token.pjm;
+        g.to_100 = token.to_100;
+    }
+    let p0 = m.src as usize;
+    let p1 = m.dst as usize;
+    g.board[p0] = m.piece;
+    g.board[p1] = m.captured;
+    match m.kind {
+        MoveKind::ShortCastling => {
+            g.board[p0 - 3] = g.board[p0 - 1];
+            g.board[p0 - 1] = VOID_ID;
+        }
+        MoveKind::LongCastling => {
+            g.board[p0 + 4] = g.board[p0 + 1];
+            g.board[p0 + 1] = VOID_ID;
+        }
+        MoveKind::EnPassant => {
+            g.board[p1] = VOID_ID;
+            g.board[(p1 as i64 - m.piece * 8) as usize] = m.captured;
+        }
+        _ => {}
+    }
+}
+
+// ### navigation in the moves of the game, i.e. for a move list of the GUI.
+// Playing a move after take_back() keeps the taken back moves: the next one of them
+// continues the line, another move starts a variation.
+
+// take back the last move of the game, it can be played again with redo_move()
+pub fn take_back(g: &mut Game) -> Option<Move> {
+    let token = g.played.last()?.clone();
+    let m = token.mv;
+    undo(g, token, true);
+    Some(m)
+}
+
+// play the move taken back last
+pub fn redo_move(g: &mut Game) -> Option<Move> {
+    let next = g.tree[g.node].next;
+    if next == ROOT_NODE {
+        return None;
+    }
+    let m = g.tree[next].mv;
+    Some(do_move(g, m.src, m.dst, m.promote_to, false).mv)
+}
+
+// the moves played since the start position or set_fen(), followed by the taken back ones
+pub fn move_list(g: &Game) -> Vec<Move> {
+    let mut moves: Vec<Move> = g.played.iter().map(|t| t.mv).collect();
+    let mut node = g.tree[g.node].next;
+    while node != ROOT_NODE {
+        moves.push(g.tree[node].mv);
+        node = g.tree[node].next;
+    }
+    moves
+}
+
+// the number of moves of move_list() leading to the current position
+pub fn game_ply(g: &Game) -> usize {
+    g.played.len()
+}
+
+// go to the position after the first ply moves of move_list()
+pub fn go_to_ply(g: &mut Game, ply: usize) {
+    while game_ply(g) > ply && take_back(g).is_some() {}
+    while game_ply(g) < ply && redo_move(g).is_some() {}
+}
+
+// ### the variation tree of the game, i.e. for PGN with variations.
+// The children of a node are the moves played in its position, the first one is the
+// main line and the others are variations.
+
+pub type NodeId = usize;
+pub const ROOT_NODE: NodeId = 0;
+
+// the node of the current position
+pub fn current_node(g: &Game) -> NodeId {
+    g.node
+}
+
+// the move leading to node, a default move for ROOT_NODE
+pub fn node_move(g: &Game, node: NodeId) -> Move {
+    g.tree[node].mv
+}
+
+pub fn node_parent(g: &Game, node: NodeId) -> NodeId {
+    g.tree[node].parent
+}
+
+pub fn node_children(g: &Game, node: NodeId) -> &[NodeId] {
+    &g.tree[node].children
+}
+
+// the comment after the move of node, the one of ROOT_NODE precedes the first move
+pub fn node_comment(g: &Game, node: NodeId) -> &str {
+    &g.tree[node].comment
+}
+
+pub fn set_node_comment(g: &mut Game, node: NodeId, comment: &str) {
+    g.tree[node].comment = comment.to_string();
+}
+
+// the numeric annotation glyphs of the move of node, see NAG_SYMBOLS
+pub fn node_nags(g: &Game, node: NodeId) -> &[u8] {
+    &g.tree[node].nags
+}
+
+pub fn set_node_nags(g: &mut Game, node: NodeId, nags: &[u8]) {
+    g.tree[node].nags = nags.to_vec();
+}
+
+// Is node reached from the root, i.e. no node of a deleted variation? The ids of the
+// nodes of a deleted variation are not reused, see delete_variation().
+fn is_attached(g: &Game, node: NodeId) -> bool {
+    let mut n = node;
+    while n != ROOT_NODE {
+        let Some(parent) = g.tree.get(n).map(|n| n.parent) else {
+            return false;
+        };
+        if !g.tree[parent].children.contains(&n) {
+            return false;
+        }
+        n = parent;
+    }
+    true
+}
+
+// go to the position of node, by taking back and playing moves, nothing for a node of a
+// deleted variation
+pub fn go_to_node(g: &mut Game, node: NodeId) {
+    if !is_attached(g, node) {
+        return;
+    }
+    let mut path = vec![node]; // from node up to the root
+    while let Some(&n) = path.last().filter(|&&n| n != ROOT_NODE) {
+        path.push(g.tree[n].parent);
+    }
+    while !path.contains(&g.node) {
+        take_back(g);
+    }
+    let known = path.iter().position(|&n| n == g.node).unwrap();
+    for &n in path[..known].iter().rev() {
+        let m = g.tree[n].mv;
+        do_move(g, m.src, m.dst, m.promote_to, false);
+    }
+}
+
+// the first node of the variation containing the current position, None in the main line
+fn variation_start(g: &Game) -> Option<NodeId> {
+    let mut node = g.node;
+    while node != ROOT_NODE {
+        let parent = g.tree[node].parent;
+        if g.tree[parent].children[0] != node {
+            return Some(node);
+        }
+        node = parent;
+    }
+    None
+}
+
+// make the variation containing the current position the main line at its branch point,
+// false in the main line
+pub fn promote_variation(g: &mut Game) -> bool {
+    let Some(node) = variation_start(g) else {
+        return false;
+    };
+    let parent = g.tree[node].parent;
+    let children = &mut g.tree[parent].children;
+    children.retain(|&c| c != node);
+    children.insert(0, node);
+    true
+}
+
+// remove the variation containing the current position and go to its branch point,
+// false in the main line
+pub fn delete_variation(g: &mut Game) -> bool {
+    let Some(node) = variation_start(g) else {
+        return false;
+    };
+    let parent = g.tree[node].parent;
+    go_to_node(g, parent);
+    let p = &mut g.tree[parent];
+    p.children.retain(|&c| c != node);
+    if p.next == node {
+        p.next = p.children[0];
+    }
+    // The nodes of the variation keep their place, so the ids of the other nodes are
+    // unchanged, but they are detached and emptied. They are not saved, PGN and the
+    // serialization of a game contain only the nodes reached from the root.
+    let mut deleted = vec![node];
+    while let Some(n) = deleted.pop() {
+        let n = &mut g.tree[n];
+        deleted.append(&mut n.children);
+        n.comment = String::new();
+        n.nags = Vec::new();
+    }
+    true
+}
+// ###
+
+// all moves of the figure at si, including castlings, but the own king may be left in check
+fn pseudo_moves(g: &Game, si: i64) -> Kks {
+    let mut kk: KK = Default::default();
     kk.sf = g.board[si as usize] as i8;
     let color = signum(kk.sf as i64) as Color;
     kk.si = si as i8;
     kk.s = 1; // generate all moves, not only captures
     let mut s: Vec<KK> = Vec::with_capacity(32);
-    match kk.sf.abs() as i64 {
-        PAWN_ID => walk_pawn(&g, kk, &mut s, false),
-        KNIGHT_ID => walk_knight(&g, kk, &mut s),
-        BISHOP_ID => walk_bishop(&g, kk, &mut s),
-        ROOK_ID => walk_rook(&g, kk, &mut s),
-        QUEEN_ID => {
-            walk_bishop(&g, kk, &mut s);
-            walk_rook(&g, kk, &mut s)
+    g.variant.walk(g, kk, &mut s, false);
+    if si == 3 || si == 3 + 7 * 8 {
+        const // king, void, void, void, rook, kingDelta+2
+      Q: [[usize; 6]; 2] = [[3, 2, 1, 1, 0, 0], [3, 4, 5, 6, 7, 4]];
+        let k = W_KING * color;
+        let r = W_ROOK * color;
+        for i in 0..(1 + 1) {
+            // castlings both sides
+            let mut q = Q[i];
+            if color == COLOR_BLACK {
+                for j in 0..(4 + 1) {
+                    q[j as usize] += 7 * 8;
+                }
+            }
+            if g.board[q[0]] == k
+                && g.board[q[1]] == 0
+                && g.board[q[2]] == 0
+                && g.board[q[3]] == 0
+                && g.board[q[4]] == r
+                && !g.has_moved.contains(q[0])
+                && !g.has_moved.contains(q[4])
+            {
+                if !(in_check(&g, q[0] as i8, color, true)
+                    || in_check(&g, q[1] as i8, color, true)
+                    || in_check(&g, q[2] as i8, color, true))
+                {
+                    kk.di = (q[0] + q[5] - 2) as i8;
+                    s.push(kk);
+                }
+            }
+        }
+    }
+    s
+}
+
+// the board after move m, the game itself is not touched
+pub fn board_after(g: &Game, m: &Move) -> Board {
+    let mut b = g.board;
+    let p0 = m.src as usize;
+    let p1 = m.dst as usize;
+    match m.kind {
+        MoveKind::ShortCastling => {
+            b[p0 - 1] = b[p0 - 3];
+            b[p0 - 3] = VOID_ID;
+        }
+        MoveKind::LongCastling => {
+            b[p0 + 1] = b[p0 + 4];
+            b[p0 + 4] = VOID_ID;
+        }
+        MoveKind::Promotion => b[p0] = m.promote_to,
+        MoveKind::EnPassant => b[(p1 as i64 - m.piece * 8) as usize] = VOID_ID,
+        _ => {}
+    }
+    b[p1] = b[p0];
+    b[p0] = VOID_ID;
+    b
+}
+
+// does move m not leave the own king in check
+fn is_legal(g: &Game, m: &Move) -> bool {
+    let b = board_after(g, m);
+    let c = signum(m.piece) as Color;
+    match b.iter().position(|&f| f == KING_ID * c) {
+        Some(k) => attackers(g, &b, k as Position, opp_color(c)).0 == 0,
+        None => true,
+    }
+}
+
+// the valid moves of the figure at si, mostly used for highlighting in the GUI
+pub fn tag(g: &Game, si: i64) -> Kks {
+    if !(0..64).contains(&si) {
+        return Vec::new();
+    }
+    let mut s = pseudo_moves(g, si);
+    s.retain(|&el| is_legal(g, &new_move(g, el.si, el.di, el.promote_to as FigureID)));
+    s
+}
+
+// is position si attacked by a figure of color c
+pub fn is_square_attacked(g: &Game, si: Position, c: Color) -> bool {
+    POS_RANGE.contains(&si) && attackers(g, &g.board, si, c).0 != 0
+}
+
+// the same for a board of the search, see Variant::winner()
+pub(crate) fn is_attacked_on(g: &Game, board: &Board, si: Position, c: Color) -> bool {
+    attackers(g, board, si, c).0 != 0
+}
+
+// the sorted positions of all figures of both colors attacking (or defending) si
+pub fn attackers_of(g: &Game, si: Position) -> Vec<Position> {
+    if !POS_RANGE.contains(&si) {
+        return Vec::new();
+    }
+    let s = attackers(g, &g.board, si, COLOR_WHITE).0 | attackers(g, &g.board, si, COLOR_BLACK).0;
+    POS_RANGE.filter(|&p| BitSet(s).contains(p)).collect()
+}
+
+pub fn is_in_check(g: &Game, c: Color) -> bool {
+    king_pos(g, c).is_some_and(|k| attackers(g, &g.board, k, opp_color(c)).0 != 0)
+}
+
+// the sorted positions of the figures giving check to the king of the active player
+pub fn checkers(g: &Game) -> Vec<Position> {
+    let c = active_color(g);
+    let Some(k) = king_pos(g, c) else {
+        return Vec::new();
+    };
+    let s = attackers(g, &g.board, k, opp_color(c));
+    POS_RANGE.filter(|&p| s.contains(p)).collect()
+}
+
+// the sorted positions of the figures of the active player which are pinned to their king,
+// i.e. removing the figure from the board would expose the king to an attack
+pub fn pinned_pieces(g: &Game) -> Vec<Position> {
+    let c = active_color(g);
+    let Some(k) = king_pos(g, c) else {
+        return Vec::new();
+    };
+    let checks = attackers(g, &g.board, k, opp_color(c)).0;
+    POS_RANGE
+        .filter(|&p| {
+            if p == k || g.board[p as usize] * c <= 0 {
+                return false;
+            }
+            let mut b = g.board;
+            b[p as usize] = VOID_ID;
+            attackers(g, &b, k, opp_color(c)).0 & !checks != 0
+        })
+        .collect()
+}
+
+pub(crate) fn active_color(g: &Game) -> Color {
+    -(g.move_counter as Color % 2) * 2 + 1
+}
+
+pub fn move_is_valid2(g: &Game, si: i64, di: i64) -> bool {
+    check_move(g, si, di).is_ok()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    InvalidSquare,
+    NoPiece,
+    NotYourPiece,
+    WrongPattern,      // the piece can not move this way
+    BlockedPath,       // a piece is in the way, or on the destination square
+    LeavesKingInCheck, // or moves the king into check
+    Pinned,            // the piece is pinned to its king
+    InvalidPromotion,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MoveError::InvalidSquare => "invalid square",
+            MoveError::NoPiece => "no piece to move",
+            MoveError::NotYourPiece => "not your piece",
+            MoveError::WrongPattern => "the piece can not move this way",
+            MoveError::BlockedPath => "the path is blocked",
+            MoveError::LeavesKingInCheck => "your king would be in check",
+            MoveError::Pinned => "the piece is pinned to your king",
+            MoveError::InvalidPromotion => "invalid promotion",
+        })
+    }
+}
+
+impl core::error::Error for MoveError {}
+
+fn check_move(g: &Game, si: i64, di: i64) -> Result<(), MoveError> {
+    if !(0..64).contains(&si) || !(0..64).contains(&di) {
+        return Err(MoveError::InvalidSquare);
+    }
+    match signum(g.board[si as usize]) as Color {
+        0 => Err(MoveError::NoPiece),
+        c if c != active_color(g) => Err(MoveError::NotYourPiece),
+        _ if tag(g, si).iter().any(|&it| it.di == di as i8) => Ok(()),
+        _ => Err(invalid_move_reason(g, si, di)),
+    }
+}
+
+// why the figure of the active player at si can not move to di
+fn invalid_move_reason(g: &Game, si: i64, di: i64) -> MoveError {
+    let f = g.board[si as usize];
+    let c = signum(f) as Color;
+    if pseudo_moves(g, si).iter().any(|el| el.di == di as i8) {
+        if f.abs() != KING_ID && !is_in_check(g, c) && pinned_pieces(g).contains(&(si as i8)) {
+            return MoveError::Pinned;
+        }
+        return MoveError::LeavesKingInCheck;
+    }
+    if g.board[di as usize] * c > 0 {
+        return MoveError::BlockedPath;
+    }
+    let on_path = |path: &Path| {
+        path[si as usize]
+            .iter()
+            .take_while(|el| el.pos >= 0)
+            .any(|el| el.pos as i64 == di)
+    };
+    let blocked = match f.abs() {
+        PAWN_ID => di == si + 8 * c || di == si + 16 * c && rows_to_go(si as i8, c) == 6,
+        BISHOP_ID => on_path(&g.bishop_path),
+        ROOK_ID => on_path(&g.rook_path),
+        QUEEN_ID => on_path(&g.bishop_path) || on_path(&g.rook_path),
+        KING_ID if (di - si).abs() == 2 && row(si as i8) == row(di as i8) => {
+            // castling: are the rights lost, is a square occupied, or is the king attacked
+            let i = CASTLING_SQUARES.iter().position(|&(k, r, _)| {
+                k == si as usize && (r as i64 - si).signum() == (di - si).signum()
+            });
+            match i {
+                Some(i) if castling_rights(g)[i] => {
+                    let r = CASTLING_SQUARES[i].1 as i64;
+                    let d = (r - si).signum();
+                    if (1..(r - si).abs()).any(|j| g.board[(si + j * d) as usize] != VOID_ID) {
+                        return MoveError::BlockedPath;
+                    }
+                    return MoveError::LeavesKingInCheck;
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    if blocked {
+        MoveError::BlockedPath
+    } else {
+        MoveError::WrongPattern
+    }
+}
+
+// do_move() for untrusted input, e.g. from the GUI or a library user
+pub fn play_move(
+    g: &mut Game,
+    src: Position,
+    dst: Position,
+    promote_to: FigureID,
+) -> Result<UndoToken, MoveError> {
+    check_move(g, src as i64, dst as i64)?;
+    if ![VOID_ID, KNIGHT_ID, BISHOP_ID, ROOK_ID, QUEEN_ID].contains(&promote_to.abs()) {
+        return Err(MoveError::InvalidPromotion);
+    }
+    Ok(do_move(g, src, dst, promote_to, false))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SanError {
+    Syntax,
+    IllegalMove,
+    AmbiguousMove,
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SanError::Syntax => "invalid move notation",
+            SanError::IllegalMove => "invalid move",
+            SanError::AmbiguousMove => "ambiguous move",
+        })
+    }
+}
+
+impl core::error::Error for SanError {}
+
+// Parse a move in standard algebraic notation, i.e. "Nbd7", "exd6", "e8=Q+" or "O-O".
+// Unneeded disambiguation, a missing 'x', "0-0" and annotations like "!?" are accepted.
+// A promotion without piece defaults to a queen.
+pub fn parse_san(g: &Game, san: &str) -> Result<Move, SanError> {
+    let s = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let moves: Vec<Move> = legal_moves(g).collect();
+    let castling = match s {
+        "O-O" | "0-0" => Some(MoveKind::ShortCastling),
+        "O-O-O" | "0-0-0" => Some(MoveKind::LongCastling),
+        _ => None,
+    };
+    if let Some(kind) = castling {
+        return moves
+            .into_iter()
+            .find(|m| m.kind == kind)
+            .ok_or(SanError::IllegalMove);
+    }
+    let mut chars: Vec<char> = s.chars().filter(|c| !"x:-=".contains(*c)).collect();
+    let mut id = PAWN_ID;
+    if let Some(p) = chars.first().and_then(|c| "NBRQK".find(*c)) {
+        id = p as FigureID + KNIGHT_ID;
+        chars.remove(0);
+    }
+    let mut promote_to = VOID_ID;
+    if id == PAWN_ID && chars.len() > 2 {
+        if let Some(p) = "NBRQ".find(chars[chars.len() - 1].to_ascii_uppercase()) {
+            promote_to = p as FigureID + KNIGHT_ID;
+            chars.pop();
+        }
+    }
+    if chars.len() < 2 || chars.len() > 4 {
+        return Err(SanError::Syntax);
+    }
+    let dst: String = chars.split_off(chars.len() - 2).into_iter().collect();
+    let dst = parse_square(&dst).map_err(|_| SanError::Syntax)?;
+    let mut file = None;
+    let mut rank = None;
+    for c in chars {
+        match c {
+            'a'..='h' => file = Some(c),
+            '1'..='8' => rank = Some(c),
+            _ => return Err(SanError::Syntax),
+        }
+    }
+    let found: Vec<Move> = moves
+        .into_iter()
+        .filter(|m| {
+            m.piece.abs() == id
+                && m.dst == dst
+                && file.is_none_or(|f| file_char(m.src) == f)
+                && rank.is_none_or(|r| row_str(row(m.src)) == r)
+                && (m.promote_to.abs() == promote_to
+                    || promote_to == VOID_ID && m.promote_to.abs() == QUEEN_ID)
+        })
+        .collect();
+    match found.len() {
+        0 => Err(SanError::IllegalMove),
+        1 => Ok(found[0]),
+        _ => Err(SanError::AmbiguousMove),
+    }
+}
+
+// Parse a move in UCI long algebraic notation, i.e. "g1f3", "e1g1" or "e7e8q".
+// This is the inverse of Move::uci(). A promotion without piece defaults to a queen.
+pub fn parse_uci_move(g: &Game, uci: &str) -> Result<Move, SanError> {
+    let s = uci.trim();
+    if !s.is_ascii() || s.len() < 4 || s.len() > 5 {
+        return Err(SanError::Syntax);
+    }
+    let src = parse_square(&s[0..2]).map_err(|_| SanError::Syntax)?;
+    let dst = parse_square(&s[2..4]).map_err(|_| SanError::Syntax)?;
+    let promote_to = match s[4..].to_ascii_lowercase().as_str() {
+        "" => VOID_ID,
+        "n" => KNIGHT_ID,
+        "b" => BISHOP_ID,
+        "r" => ROOK_ID,
+        "q" => QUEEN_ID,
+        _ => return Err(SanError::Syntax),
+    };
+    legal_moves_from(g, src)
+        .find(|m| {
+            m.dst == dst
+                && (m.promote_to.abs() == promote_to
+                    || promote_to == VOID_ID && m.promote_to.abs() == QUEEN_ID)
+        })
+        .ok_or(SanError::IllegalMove)
+}
+
+// all valid moves of the active player, with all four possible promotions.
+// check is set for each move, but mate is only detected by do_move().
+pub fn legal_moves(g: &Game) -> impl Iterator<Item = Move> {
+    let color = active_color(g);
+    let mut moves: Vec<Move> = Vec::with_capacity(64);
+    for si in POS_RANGE {
+        if g.board[si as usize] * color <= 0 {
+            continue;
+        }
+        for el in pseudo_moves(g, si as i64) {
+            if is_a_pawn(el.sf) && base_row(el.di) {
+                // walk_pawn() generates knight and queen promotions only
+                if el.promote_to.abs() == QUEEN_ID as i8 {
+                    for id in [QUEEN_ID, ROOK_ID, BISHOP_ID, KNIGHT_ID] {
+                        moves.push(new_move(g, si, el.di, id));
+                    }
+                }
+            } else {
+                moves.push(new_move(g, si, el.di, VOID_ID));
+            }
+        }
+    }
+    moves.retain(|m| is_legal(g, m));
+    for i in 0..moves.len() {
+        let mut m = moves[i];
+        set_san_hints(&mut m, &moves);
+        m.check = gives_check(g, &m);
+        moves[i] = m;
+    }
+    moves.into_iter()
+}
+
+// the valid moves of the figure at position si
+pub fn legal_moves_from(g: &Game, si: Position) -> impl Iterator<Item = Move> {
+    legal_moves(g).filter(move |m| m.src == si)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameState {
+    Ongoing,
+    Checkmate(Color),  // color of the winner
+    VariantWin(Color), // by a rule of the variant, see Variant::winner()
+    Stalemate,
+    DrawFiftyMove,
+    DrawRepetition,
+    DrawMaterial,
+}
+
+impl GameState {
+    // i.e. "checkmate" or "fifty-move rule"
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameState::Ongoing => "ongoing",
+            GameState::Checkmate(_) => "checkmate",
+            GameState::VariantWin(_) => "variant win",
+            GameState::Stalemate => "stalemate",
+            GameState::DrawFiftyMove => "fifty-move rule",
+            GameState::DrawRepetition => "threefold repetition",
+            GameState::DrawMaterial => "insufficient material",
+        }
+    }
+}
+
+// the result as a sentence, i.e. "Checkmate, white wins."
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let winner = |c: &Color| if *c == COLOR_WHITE { "white" } else { "black" };
+        match self {
+            GameState::Ongoing => f.write_str("The game goes on."),
+            GameState::Checkmate(c) => write!(f, "Checkmate, {} wins.", winner(c)),
+            GameState::VariantWin(c) => write!(f, "Variant win, {} wins.", winner(c)),
+            GameState::Stalemate => f.write_str("Stalemate, draw."),
+            _ => write!(f, "Draw by {}.", self.name()),
+        }
+    }
+}
+
+// no pawns, rooks or queens, and at most a single knight or only bishops on squares of same color
+pub(crate) fn insufficient_material(g: &Game) -> bool {
+    let mut minors = 0;
+    let mut knights = 0;
+    let mut bishop_squares = [false; 2];
+    for (p, f) in g.board.iter().enumerate() {
+        match f.abs() {
+            PAWN_ID | ROOK_ID | QUEEN_ID => return false,
+            KNIGHT_ID => {
+                minors += 1;
+                knights += 1;
+            }
+            BISHOP_ID => {
+                minors += 1;
+                bishop_squares[(col(p as Position) + row(p as Position)) as usize % 2] = true;
+            }
+            _ => {}
+        }
+    }
+    minors <= 1 || knights == 0 && !(bishop_squares[0] && bishop_squares[1])
+}
+
+// the state of the game, from the view of the active player
+pub fn state(g: &Game) -> GameState {
+    let color = active_color(g);
+    if let Some(winner) = g.variant.winner(g, &g.board) {
+        return GameState::VariantWin(winner);
+    }
+    if !has_valid_move(g, color) {
+        if king_pos(g, color).is_some_and(|k| in_check(g, k, color, true)) {
+            return GameState::Checkmate(opp_color(color));
+        }
+        return GameState::Stalemate;
+    }
+    if g.variant.insufficient_material(g) {
+        return GameState::DrawMaterial;
+    }
+    if g.to_100 >= 100 {
+        return GameState::DrawFiftyMove;
+    }
+    if g.history
+        .get(&encode_board(g, color))
+        .is_some_and(|&n| n >= 3)
+    {
+        return GameState::DrawRepetition;
+    }
+    GameState::Ongoing
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount,
+    InvalidBoard,
+    InvalidPiece(char),
+    InvalidSideToMove,
+    InvalidCastling,
+    InvalidEnPassant,
+    InvalidNumber,
+    Setup(SetupError),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount => f.write_str("FEN needs 4 to 6 fields"),
+            FenError::InvalidBoard => f.write_str("invalid board in FEN"),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece '{}' in FEN", c),
+            FenError::InvalidSideToMove => f.write_str("invalid side to move in FEN"),
+            FenError::InvalidCastling => f.write_str("invalid castling rights in FEN"),
+            FenError::InvalidEnPassant => f.write_str("invalid en passant square in FEN"),
+            FenError::InvalidNumber => f.write_str("invalid move number in FEN"),
+            FenError::Setup(e) => e.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for FenError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SetupError {
+    InvalidPiece,
+    InvalidSideToMove,
+    MissingKing,
+    PawnOnBackRank,
+    OpponentInCheck,
+    InvalidCastling,
+    InvalidEnPassant,
+}
+
+impl fmt::Display for SetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SetupError::InvalidPiece => "invalid piece",
+            SetupError::InvalidSideToMove => "invalid side to move",
+            SetupError::MissingKing => "each side needs exactly one king",
+            SetupError::PawnOnBackRank => "pawns can not be on the first or last rank",
+            SetupError::OpponentInCheck => "the side not to move is in check",
+            SetupError::InvalidCastling => {
+                "castling needs king and rook on their initial positions"
+            }
+            SetupError::InvalidEnPassant => "no pawn did a double step to the en passant square",
+        })
+    }
+}
+
+impl core::error::Error for SetupError {}
+
+const FEN_FIGURES: &str = "kqrbnp.PNBRQK";
+
+// (king, rook) positions for the castling rights K, Q, k and q
+const CASTLING_SQUARES: [(usize, usize, char); 4] = [
+    (WK3, WR0, 'K'),
+    (WK3, WR7, 'Q'),
+    (BK59, BR56, 'k'),
+    (BK59, BR63, 'q'),
+];
+
+// Zobrist keys from a fixed seed, so position keys are stable across runs and platforms
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const ZOBRIST_SIZE: usize = 13 * 64 + 1 + 4 + 8; // figures, black to move, castling rights, e.p. column
+const ZOBRIST_BLACK: usize = 13 * 64;
+const ZOBRIST_CASTLING: usize = ZOBRIST_BLACK + 1;
+const ZOBRIST_EP: usize = ZOBRIST_CASTLING + 4;
+
+const ZOBRIST: [u64; ZOBRIST_SIZE] = {
+    let mut keys = [0; ZOBRIST_SIZE];
+    let mut state = 0x5a1e_3519_c4e5_5eed;
+    let mut i = 0;
+    while i < ZOBRIST_SIZE {
+        let (s, k) = splitmix64(state);
+        state = s;
+        keys[i] = k;
+        i += 1;
+    }
+    keys
+};
+
+// the Random64 keys of Polyglot: 12 * 64 figures, castling rights K Q k q, e.p. file a..h,
+// and White to move
+const POLYGLOT: [u64; 781] = [
+    0x9D39247E33776D41,
+    0x2AF7398005AAA5C7,
+    0x44DB015024623547,
+    0x9C15F73E62A76AE2,
+    0x75834465489C0C89,
+    0x3290AC3A203001BF,
+    0x0FBBAD1F61042279,
+    0xE83A908FF2FB60CA,
+    0x0D7E765D58755C10,
+    0x1A083822CEAFE02D,
+    0x9605D5F0E25EC3B0,
+    0xD021FF5CD13A2ED5,
+    0x40BDF15D4A672E32,
+    0x011355146FD56395,
+    0x5DB4832046F3D9E5,
+    0x239F8B2D7FF719CC,
+    0x05D1A1AE85B49AA1,
+    0x679F848F6E8FC971,
+    0x7449BBFF801FED0B,
+    0x7D11CDB1C3B7ADF0,
+    0x82C7709E781EB7CC,
+    0xF3218F1C9510786C,
+    0x331478F3AF51BBE6,
+    0x4BB38DE5E7219443,
+    0xAA649C6EBCFD50FC,
+    0x8DBD98A352AFD40B,
+    0x87D2074B81D79217,
+    0x19F3C751D3E92AE1,
+    0xB4AB30F062B19ABF,
+    0x7B0500AC42047AC4,
+    0xC9452CA81A09D85D,
+    0x24AA6C514DA27500,
+    0x4C9F34427501B447,
+    0x14A68FD73C910841,
+    0xA71B9B83461CBD93,
+    0x03488B95B0F1850F,
+    0x637B2B34FF93C040,
+    0x09D1BC9A3DD90A94,
+    0x3575668334A1DD3B,
+    0x735E2B97A4C45A23,
+    0x18727070F1BD400B,
+    0x1FCBACD259BF02E7,
+    0xD310A7C2CE9B6555,
+    0xBF983FE0FE5D8244,
+    0x9F74D14F7454A824,
+    0x51EBDC4AB9BA3035,
+    0x5C82C505DB9AB0FA,
+    0xFCF7FE8A3430B241,
+    0x3253A729B9BA3DDE,
+    0x8C74C368081B3075,
+    0xB9BC6C87167C33E7,
+    0x7EF48F2B83024E20,
+    0x11D505D4C351BD7F,
+    0x6568FCA92C76A243,
+    0x4DE0B0F40F32A7B8,
+    0x96D693460CC37E5D,
+    0x42E240CB63689F2F,
+    0x6D2BDCDAE2919661,
+    0x42880B0236E4D951,
+    0x5F0F4A5898171BB6,
+    0x39F890F579F92F88,
+    0x93C5B5F47356388B,
+    0x63DC359D8D231B78,
+    0xEC16CA8AEA98AD76,
+    0x5355F900C2A82DC7,
+    0x07FB9F855A997142,
+    0x5093417AA8A7ED5E,
+    0x7BCBC38DA25A7F3C,
+    0x19FC8A768CF4B6D4,
+    0x637A7780DECFC0D9,
+    0x8249A47AEE0E41F7,
+    0x79AD695501E7D1E8,
+    0x14ACBAF4777D5776,
+    0xF145B6BECCDEA195,
+    0xDABF2AC8201752FC,
+    0x24C3C94DF9C8D3F6,
+    0xBB6E2924F03912EA,
+    0x0CE26C0B95C980D9,
+    0xA49CD132BFBF7CC4,
+    0xE99D662AF4243939,
+    0x27E6AD7891165C3F,
+    0x8535F040B9744FF1,
+    0x54B3F4FA5F40D873,
+    0x72B12C32127FED2B,
+    0xEE954D3C7B411F47,
+    0x9A85AC909A24EAA1,
+    0x70AC4CD9F04F21F5,
+    0xF9B89D3E99A075C2,
+    0x87B3E2B2B5C907B1,
+    0xA366E5B8C54F48B8,
+    0xAE4A9346CC3F7CF2,
+    0x1920C04D47267BBD,
+    0x87BF02C6B49E2AE9,
+    0x092237AC237F3859,
+    0xFF07F64EF8ED14D0,
+    0x8DE8DCA9F03CC54E,
+    0x9C1633264DB49C89,
+    0xB3F22C3D0B0B38ED,
+    0x390E5FB44D01144B,
+    0x5BFEA5B4712768E9,
+    0x1E1032911FA78984,
+    0x9A74ACB964E78CB3,
+    0x4F80F7A035DAFB04,
+    0x6304D09A0B3738C4,
+    0x2171E64683023A08,
+    0x5B9B63EB9CEFF80C,
+    0x506AACF489889342,
+    0x1881AFC9A3A701D6,
+    0x6503080440750644,
+    0xDFD395339CDBF4A7,
+    0xEF927DBCF00C20F2,
+    0x7B32F7D1E03680EC,
+    0xB9FD7620E7316243,
+    0x05A7E8A57DB91B77,
+    0xB5889C6E15630A75,
+    0x4A750A09CE9573F7,
+    0xCF464CEC899A2F8A,
+    0xF538639CE705B824,
+    0x3C79A0FF5580EF7F,
+    0xEDE6C87F8477609D,
+    0x799E81F05BC93F31,
+    0x86536B8CF3428A8C,
+    0x97D7374C60087B73,
+    0xA246637CFF328532,
+    0x043FCAE60CC0EBA0,
+    0x920E449535DD359E,
+    0x70EB093B15B290CC,
+    0x73A1921916591CBD,
+    0x56436C9FE1A1AA8D,
+    0xEFAC4B70633B8F81,
+    0xBB215798D45DF7AF,
+    0x45F20042F24F1768,
+    0x930F80F4E8EB7462,
+    0xFF6712FFCFD75EA1,
+    0xAE623FD67468AA70,
+    0xDD2C5BC84BC8D8FC,
+    0x7EED120D54CF2DD9,
+    0x22FE545401165F1C,
+    0xC91800E98FB99929,
+    0x808BD68E6AC10365,
+    0xDEC468145B7605F6,
+    0x1BEDE3A3AEF53302,
+    0x43539603D6C55602,
+    0xAA969B5C691CCB7A,
+    0xA87832D392EFEE56,
+    0x65942C7B3C7E11AE,
+    0xDED2D633CAD004F6,
+    0x21F08570F420E565,
+    0xB415938D7DA94E3C,
+    0x91B859E59ECB6350,
+    0x10CFF333E0ED804A,
+    0x28AED140BE0BB7DD,
+    0xC5CC1D89724FA456,
+    0x5648F680F11A2741,
+    0x2D255069F0B7DAB3,
+    0x9BC5A38EF729ABD4,
+    0xEF2F054308F6A2BC,
+    0xAF2042F5CC5C2858,
+    0x480412BAB7F5BE2A,
+    0xAEF3AF4A563DFE43,
+    0x19AFE59AE451497F,
+    0x52593803DFF1E840,
+    0xF4F076E65F2CE6F0,
+    0x11379625747D5AF3,
+    0xBCE5D2248682C115,
+    0x9DA4243DE836994F,
+    0x066F70B33FE09017,
+    0x4DC4DE189B671A1C,
+    0x51039AB7712457C3,
+    0xC07A3F80C31FB4B4,
+    0xB46EE9C5E64A6E7C,
+    0xB3819A42ABE61C87,
+    0x21A007933A522A20,
+    0x2DF16F761598AA4F,
+    0x763C4A1371B368FD,
+    0xF793C46702E086A0,
+    0xD7288E012AEB8D31,
+    0xDE336A2A4BC1C44B,
+    0x0BF692B38D079F23,
+    0x2C604A7A177326B3,
+    0x4850E73E03EB6064,
+    0xCFC447F1E53C8E1B,
+    0xB05CA3F564268D99,
+    0x9AE182C8BC9474E8,
+    0xA4FC4BD4FC5558CA,
+    0xE755178D58FC4E76,
+    0x69B97DB1A4C03DFE,
+    0xF9B5B7C4ACC67C96,
+    0xFC6A82D64B8655FB,
+    0x9C684CB6C4D24417,
+    0x8EC97D2917456ED0,
+    0x6703DF9D2924E97E,
+    0xC547F57E42A7444E,
+    0x78E37644E7CAD29E,
+    0xFE9A44E9362F05FA,
+    0x08BD35CC38336615,
+    0x9315E5EB3A129ACE,
+    0x94061B871E04DF75,
+    0xDF1D9F9D784BA010,
+    0x3BBA57B68871B59D,
+    0xD2B7ADEEDED1F73F,
+    0xF7A255D83BC373F8,
+    0xD7F4F2448C0CEB81,
+    0xD95BE88CD210FFA7,
+    0x336F52F8FF4728E7,
+    0xA74049DAC312AC71,
+    0xA2F61BB6E437FDB5,
+    0x4F2A5CB07F6A35B3,
+    0x87D380BDA5BF7859,
+    0x16B9F7E06C453A21,
+    0x7BA2484C8A0FD54E,
+    0xF3A678CAD9A2E38C,
+    0x39B0BF7DDE437BA2,
+    0xFCAF55C1BF8A4424,
+    0x18FCF680573FA594,
+    0x4C0563B89F495AC3,
+    0x40E087931A00930D,
+    0x8CFFA9412EB642C1,
+    0x68CA39053261169F,
+    0x7A1EE967D27579E2,
+    0x9D1D60E5076F5B6F,
+    0x3810E399B6F65BA2,
+    0x32095B6D4AB5F9B1,
+    0x35CAB62109DD038A,
+    0xA90B24499FCFAFB1,
+    0x77A225A07CC2C6BD,
+    0x513E5E634C70E331,
+    0x4361C0CA3F692F12,
+    0xD941ACA44B20A45B,
+    0x528F7C8602C5807B,
+    0x52AB92BEB9613989,
+    0x9D1DFA2EFC557F73,
+    0x722FF175F572C348,
+    0x1D1260A51107FE97,
+    0x7A249A57EC0C9BA2,
+    0x04208FE9E8F7F2D6,
+    0x5A110C6058B920A0,
+    0x0CD9A497658A5698,
+    0x56FD23C8F9715A4C,
+    0x284C847B9D887AAE,
+    0x04FEABFBBDB619CB,
+    0x742E1E651C60BA83,
+    0x9A9632E65904AD3C,
+    0x881B82A13B51B9E2,
+    0x506E6744CD974924,
+    0xB0183DB56FFC6A79,
+    0x0ED9B915C66ED37E,
+    0x5E11E86D5873D484,
+    0xF678647E3519AC6E,
+    0x1B85D488D0F20CC5,
+    0xDAB9FE6525D89021,
+    0x0D151D86ADB73615,
+    0xA865A54EDCC0F019,
+    0x93C42566AEF98FFB,
+    0x99E7AFEABE000731,
+    0x48CBFF086DDF285A,
+    0x7F9B6AF1EBF78BAF,
+    0x58627E1A149BBA21,
+    0x2CD16E2ABD791E33,
+    0xD363EFF5F0977996,
+    0x0CE2A38C344A6EED,
+    0x1A804AADB9CFA741,
+    0x907F30421D78C5DE,
+    0x501F65EDB3034D07,
+    0x37624AE5A48FA6E9,
+    0x957BAF61700CFF4E,
+    0x3A6C27934E31188A,
+    0xD49503536ABCA345,
+    0x088E049589C432E0,
+    0xF943AEE7FEBF21B8,
+    0x6C3B8E3E336139D3,
+    0x364F6FFA464EE52E,
+    0xD60F6DCEDC314222,
+    0x56963B0DCA418FC0,
+    0x16F50EDF91E513AF,
+    0xEF1955914B609F93,
+    0x565601C0364E3228,
+    0xECB53939887E8175,
+    0xBAC7A9A18531294B,
+    0xB344C470397BBA52,
+    0x65D34954DAF3CEBD,
+    0xB4B81B3FA97511E2,
+    0xB422061193D6F6A7,
+    0x071582401C38434D,
+    0x7A13F18BBEDC4FF5,
+    0xBC4097B116C524D2,
+    0x59B97885E2F2EA28,
+    0x99170A5DC3115544,
+    0x6F423357E7C6A9F9,
+    0x325928EE6E6F8794,
+    0xD0E4366228B03343,
+    0x565C31F7DE89EA27,
+    0x30F5611484119414,
+    0xD873DB391292ED4F,
+    0x7BD94E1D8E17DEBC,
+    0xC7D9F16864A76E94,
+    0x947AE053EE56E63C,
+    0xC8C93882F9475F5F,
+    0x3A9BF55BA91F81CA,
+    0xD9A11FBB3D9808E4,
+    0x0FD22063EDC29FCA,
+    0xB3F256D8ACA0B0B9,
+    0xB03031A8B4516E84,
+    0x35DD37D5871448AF,
+    0xE9F6082B05542E4E,
+    0xEBFAFA33D7254B59,
+    0x9255ABB50D532280,
+    0xB9AB4CE57F2D34F3,
+    0x693501D628297551,
+    0xC62C58F97DD949BF,
+    0xCD454F8F19C5126A,
+    0xBBE83F4ECC2BDECB,
+    0xDC842B7E2819E230,
+    0xBA89142E007503B8,
+    0xA3BC941D0A5061CB,
+    0xE9F6760E32CD8021,
+    0x09C7E552BC76492F,
+    0x852F54934DA55CC9,
+    0x8107FCCF064FCF56,
+    0x098954D51FFF6580,
+    0x23B70EDB1955C4BF,
+    0xC330DE426430F69D,
+    0x4715ED43E8A45C0A,
+    0xA8D7E4DAB780A08D,
+    0x0572B974F03CE0BB,
+    0xB57D2E985E1419C7,
+    0xE8D9ECBE2CF3D73F,
+    0x2FE4B17170E59750,
+    0x11317BA87905E790,
+    0x7FBF21EC8A1F45EC,
+    0x1725CABFCB045B00,
+    0x964E915CD5E2B207,
+    0x3E2B8BCBF016D66D,
+    0xBE7444E39328A0AC,
+    0xF85B2B4FBCDE44B7,
+    0x49353FEA39BA63B1,
+    0x1DD01AAFCD53486A,
+    0x1FCA8A92FD719F85,
+    0xFC7C95D827357AFA,
+    0x18A6A990C8B35EBD,
+    0xCCCB7005C6B9C28D,
+    0x3BDBB92C43B17F26,
+    0xAA70B5B4F89695A2,
+    0xE94C39A54A98307F,
+    0xB7A0B174CFF6F36E,
+    0xD4DBA84729AF48AD,
+    0x2E18BC1AD9704A68,
+    0x2DE0966DAF2F8B1C,
+    0xB9C11D5B1E43A07E,
+    0x64972D68DEE33360,
+    0x94628D38D0C20584,
+    0xDBC0D2B6AB90A559,
+    0xD2733C4335C6A72F,
+    0x7E75D99D94A70F4D,
+    0x6CED1983376FA72B,
+    0x97FCAACBF030BC24,
+    0x7B77497B32503B12,
+    0x8547EDDFB81CCB94,
+    0x79999CDFF70902CB,
+    0xCFFE1939438E9B24,
+    0x829626E3892D95D7,
+    0x92FAE24291F2B3F1,
+    0x63E22C147B9C3403,
+    0xC678B6D860284A1C,
+    0x5873888850659AE7,
+    0x0981DCD296A8736D,
+    0x9F65789A6509A440,
+    0x9FF38FED72E9052F,
+    0xE479EE5B9930578C,
+    0xE7F28ECD2D49EECD,
+    0x56C074A581EA17FE,
+    0x5544F7D774B14AEF,
+    0x7B3F0195FC6F290F,
+    0x12153635B2C0CF57,
+    0x7F5126DBBA5E0CA7,
+    0x7A76956C3EAFB413,
+    0x3D5774A11D31AB39,
+    0x8A1B083821F40CB4,
+    0x7B4A38E32537DF62,
+    0x950113646D1D6E03,
+    0x4DA8979A0041E8A9,
+    0x3BC36E078F7515D7,
+    0x5D0A12F27AD310D1,
+    0x7F9D1A2E1EBE1327,
+    0xDA3A361B1C5157B1,
+    0xDCDD7D20903D0C25,
+    0x36833336D068F707,
+    0xCE68341F79893389,
+    0xAB9090168DD05F34,
+    0x43954B3252DC25E5,
+    0xB438C2B67F98E5E9,
+    0x10DCD78E3851A492,
+    0xDBC27AB5447822BF,
+    0x9B3CDB65F82CA382,
+    0xB67B7896167B4C84,
+    0xBFCED1B0048EAC50,
+    0xA9119B60369FFEBD,
+    0x1FFF7AC80904BF45,
+    0xAC12FB171817EEE7,
+    0xAF08DA9177DDA93D,
+    0x1B0CAB936E65C744,
+    0xB559EB1D04E5E932,
+    0xC37B45B3F8D6F2BA,
+    0xC3A9DC228CAAC9E9,
+    0xF3B8B6675A6507FF,
+    0x9FC477DE4ED681DA,
+    0x67378D8ECCEF96CB,
+    0x6DD856D94D259236,
+    0xA319CE15B0B4DB31,
+    0x073973751F12DD5E,
+    0x8A8E849EB32781A5,
+    0xE1925C71285279F5,
+    0x74C04BF1790C0EFE,
+    0x4DDA48153C94938A,
+    0x9D266D6A1CC0542C,
+    0x7440FB816508C4FE,
+    0x13328503DF48229F,
+    0xD6BF7BAEE43CAC40,
+    0x4838D65F6EF6748F,
+    0x1E152328F3318DEA,
+    0x8F8419A348F296BF,
+    0x72C8834A5957B511,
+    0xD7A023A73260B45C,
+    0x94EBC8ABCFB56DAE,
+    0x9FC10D0F989993E0,
+    0xDE68A2355B93CAE6,
+    0xA44CFE79AE538BBE,
+    0x9D1D84FCCE371425,
+    0x51D2B1AB2DDFB636,
+    0x2FD7E4B9E72CD38C,
+    0x65CA5B96B7552210,
+    0xDD69A0D8AB3B546D,
+    0x604D51B25FBF70E2,
+    0x73AA8A564FB7AC9E,
+    0x1A8C1E992B941148,
+    0xAAC40A2703D9BEA0,
+    0x764DBEAE7FA4F3A6,
+    0x1E99B96E70A9BE8B,
+    0x2C5E9DEB57EF4743,
+    0x3A938FEE32D29981,
+    0x26E6DB8FFDF5ADFE,
+    0x469356C504EC9F9D,
+    0xC8763C5B08D1908C,
+    0x3F6C6AF859D80055,
+    0x7F7CC39420A3A545,
+    0x9BFB227EBDF4C5CE,
+    0x89039D79D6FC5C5C,
+    0x8FE88B57305E2AB6,
+    0xA09E8C8C35AB96DE,
+    0xFA7E393983325753,
+    0xD6B6D0ECC617C699,
+    0xDFEA21EA9E7557E3,
+    0xB67C1FA481680AF8,
+    0xCA1E3785A9E724E5,
+    0x1CFC8BED0D681639,
+    0xD18D8549D140CAEA,
+    0x4ED0FE7E9DC91335,
+    0xE4DBF0634473F5D2,
+    0x1761F93A44D5AEFE,
+    0x53898E4C3910DA55,
+    0x734DE8181F6EC39A,
+    0x2680B122BAA28D97,
+    0x298AF231C85BAFAB,
+    0x7983EED3740847D5,
+    0x66C1A2A1A60CD889,
+    0x9E17E49642A3E4C1,
+    0xEDB454E7BADC0805,
+    0x50B704CAB602C329,
+    0x4CC317FB9CDDD023,
+    0x66B4835D9EAFEA22,
+    0x219B97E26FFC81BD,
+    0x261E4E4C0A333A9D,
+    0x1FE2CCA76517DB90,
+    0xD7504DFA8816EDBB,
+    0xB9571FA04DC089C8,
+    0x1DDC0325259B27DE,
+    0xCF3F4688801EB9AA,
+    0xF4F5D05C10CAB243,
+    0x38B6525C21A42B0E,
+    0x36F60E2BA4FA6800,
+    0xEB3593803173E0CE,
+    0x9C4CD6257C5A3603,
+    0xAF0C317D32ADAA8A,
+    0x258E5A80C7204C4B,
+    0x8B889D624D44885D,
+    0xF4D14597E660F855,
+    0xD4347F66EC8941C3,
+    0xE699ED85B0DFB40D,
+    0x2472F6207C2D0484,
+    0xC2A1E7B5B459AEB5,
+    0xAB4F6451CC1D45EC,
+    0x63767572AE3D6174,
+    0xA59E0BD101731A28,
+    0x116D0016CB948F09,
+    0x2CF9C8CA052F6E9F,
+    0x0B090A7560A968E3,
+    0xABEEDDB2DDE06FF1,
+    0x58EFC10B06A2068D,
+    0xC6E57A78FBD986E0,
+    0x2EAB8CA63CE802D7,
+    0x14A195640116F336,
+    0x7C0828DD624EC390,
+    0xD74BBE77E6116AC7,
+    0x804456AF10F5FB53,
+    0xEBE9EA2ADF4321C7,
+    0x03219A39EE587A30,
+    0x49787FEF17AF9924,
+    0xA1E9300CD8520548,
+    0x5B45E522E4B1B4EF,
+    0xB49C3B3995091A36,
+    0xD4490AD526F14431,
+    0x12A8F216AF9418C2,
+    0x001F837CC7350524,
+    0x1877B51E57A764D5,
+    0xA2853B80F17F58EE,
+    0x993E1DE72D36D310,
+    0xB3598080CE64A656,
+    0x252F59CF0D9F04BB,
+    0xD23C8E176D113600,
+    0x1BDA0492E7E4586E,
+    0x21E0BD5026C619BF,
+    0x3B097ADAF088F94E,
+    0x8D14DEDB30BE846E,
+    0xF95CFFA23AF5F6F4,
+    0x3871700761B3F743,
+    0xCA672B91E9E4FA16,
+    0x64C8E531BFF53B55,
+    0x241260ED4AD1E87D,
+    0x106C09B972D2E822,
+    0x7FBA195410E5CA30,
+    0x7884D9BC6CB569D8,
+    0x0647DFEDCD894A29,
+    0x63573FF03E224774,
+    0x4FC8E9560F91B123,
+    0x1DB956E450275779,
+    0xB8D91274B9E9D4FB,
+    0xA2EBEE47E2FBFCE1,
+    0xD9F1F30CCD97FB09,
+    0xEFED53D75FD64E6B,
+    0x2E6D02C36017F67F,
+    0xA9AA4D20DB084E9B,
+    0xB64BE8D8B25396C1,
+    0x70CB6AF7C2D5BCF0,
+    0x98F076A4F7A2322E,
+    0xBF84470805E69B5F,
+    0x94C3251F06F90CF3,
+    0x3E003E616A6591E9,
+    0xB925A6CD0421AFF3,
+    0x61BDD1307C66E300,
+    0xBF8D5108E27E0D48,
+    0x240AB57A8B888B20,
+    0xFC87614BAF287E07,
+    0xEF02CDD06FFDB432,
+    0xA1082C0466DF6C0A,
+    0x8215E577001332C8,
+    0xD39BB9C3A48DB6CF,
+    0x2738259634305C14,
+    0x61CF4F94C97DF93D,
+    0x1B6BACA2AE4E125B,
+    0x758F450C88572E0B,
+    0x959F587D507A8359,
+    0xB063E962E045F54D,
+    0x60E8ED72C0DFF5D1,
+    0x7B64978555326F9F,
+    0xFD080D236DA814BA,
+    0x8C90FD9B083F4558,
+    0x106F72FE81E2C590,
+    0x7976033A39F7D952,
+    0xA4EC0132764CA04B,
+    0x733EA705FAE4FA77,
+    0xB4D8F77BC3E56167,
+    0x9E21F4F903B33FD9,
+    0x9D765E419FB69F6D,
+    0xD30C088BA61EA5EF,
+    0x5D94337FBFAF7F5B,
+    0x1A4E4822EB4D7A59,
+    0x6FFE73E81B637FB3,
+    0xDDF957BC36D8B9CA,
+    0x64D0E29EEA8838B3,
+    0x08DD9BDFD96B9F63,
+    0x087E79E5A57D1D13,
+    0xE328E230E3E2B3FB,
+    0x1C2559E30F0946BE,
+    0x720BF5F26F4D2EAA,
+    0xB0774D261CC609DB,
+    0x443F64EC5A371195,
+    0x4112CF68649A260E,
+    0xD813F2FAB7F5C5CA,
+    0x660D3257380841EE,
+    0x59AC2C7873F910A3,
+    0xE846963877671A17,
+    0x93B633ABFA3469F8,
+    0xC0C0F5A60EF4CDCF,
+    0xCAF21ECD4377B28C,
+    0x57277707199B8175,
+    0x506C11B9D90E8B1D,
+    0xD83CC2687A19255F,
+    0x4A29C6465A314CD1,
+    0xED2DF21216235097,
+    0xB5635C95FF7296E2,
+    0x22AF003AB672E811,
+    0x52E762596BF68235,
+    0x9AEBA33AC6ECC6B0,
+    0x944F6DE09134DFB6,
+    0x6C47BEC883A7DE39,
+    0x6AD047C430A12104,
+    0xA5B1CFDBA0AB4067,
+    0x7C45D833AFF07862,
+    0x5092EF950A16DA0B,
+    0x9338E69C052B8E7B,
+    0x455A4B4CFE30E3F5,
+    0x6B02E63195AD0CF8,
+    0x6B17B224BAD6BF27,
+    0xD1E0CCD25BB9C169,
+    0xDE0C89A556B9AE70,
+    0x50065E535A213CF6,
+    0x9C1169FA2777B874,
+    0x78EDEFD694AF1EED,
+    0x6DC93D9526A50E68,
+    0xEE97F453F06791ED,
+    0x32AB0EDB696703D3,
+    0x3A6853C7E70757A7,
+    0x31865CED6120F37D,
+    0x67FEF95D92607890,
+    0x1F2B1D1F15F6DC9C,
+    0xB69E38A8965C6B65,
+    0xAA9119FF184CCCF4,
+    0xF43C732873F24C13,
+    0xFB4A3D794A9A80D2,
+    0x3550C2321FD6109C,
+    0x371F77E76BB8417E,
+    0x6BFA9AAE5EC05779,
+    0xCD04F3FF001A4778,
+    0xE3273522064480CA,
+    0x9F91508BFFCFC14A,
+    0x049A7F41061A9E60,
+    0xFCB6BE43A9F2FE9B,
+    0x08DE8A1C7797DA9B,
+    0x8F9887E6078735A1,
+    0xB5B4071DBFC73A66,
+    0x230E343DFBA08D33,
+    0x43ED7F5A0FAE657D,
+    0x3A88A0FBBCB05C63,
+    0x21874B8B4D2DBC4F,
+    0x1BDEA12E35F6A8C9,
+    0x53C065C6C8E63528,
+    0xE34A1D250E7A8D6B,
+    0xD6B04D3B7651DD7E,
+    0x5E90277E7CB39E2D,
+    0x2C046F22062DC67D,
+    0xB10BB459132D0A26,
+    0x3FA9DDFB67E2F199,
+    0x0E09B88E1914F7AF,
+    0x10E8B35AF3EEAB37,
+    0x9EEDECA8E272B933,
+    0xD4C718BC4AE8AE5F,
+    0x81536D601170FC20,
+    0x91B534F885818A06,
+    0xEC8177F83F900978,
+    0x190E714FADA5156E,
+    0xB592BF39B0364963,
+    0x89C350C893AE7DC1,
+    0xAC042E70F8B383F2,
+    0xB49B52E587A1EE60,
+    0xFB152FE3FF26DA89,
+    0x3E666E6F69AE2C15,
+    0x3B544EBE544C19F9,
+    0xE805A1E290CF2456,
+    0x24B33C9D7ED25117,
+    0xE74733427B72F0C1,
+    0x0A804D18B7097475,
+    0x57E3306D881EDB4F,
+    0x4AE7D6A36EB5DBCB,
+    0x2D8D5432157064C8,
+    0xD1E649DE1E7F268B,
+    0x8A328A1CEDFE552C,
+    0x07A3AEC79624C7DA,
+    0x84547DDC3E203C94,
+    0x990A98FD5071D263,
+    0x1A4FF12616EEFC89,
+    0xF6F7FD1431714200,
+    0x30C05B1BA332F41C,
+    0x8D2636B81555A786,
+    0x46C9FEB55D120902,
+    0xCCEC0A73B49C9921,
+    0x4E9D2827355FC492,
+    0x19EBB029435DCB0F,
+    0x4659D2B743848A2C,
+    0x963EF2C96B33BE31,
+    0x74F85198B05A2E7D,
+    0x5A0F544DD2B1FB18,
+    0x03727073C2E134B1,
+    0xC7F6AA2DE59AEA61,
+    0x352787BAA0D7C22F,
+    0x9853EAB63B5E0B35,
+    0xABBDCDD7ED5C0860,
+    0xCF05DAF5AC8D77B0,
+    0x49CAD48CEBF4A71E,
+    0x7A4C10EC2158C4A6,
+    0xD9E92AA246BF719E,
+    0x13AE978D09FE5557,
+    0x730499AF921549FF,
+    0x4E4B705B92903BA4,
+    0xFF577222C14F0A3A,
+    0x55B6344CF97AAFAE,
+    0xB862225B055B6960,
+    0xCAC09AFBDDD2CDB4,
+    0xDAF8E9829FE96B5F,
+    0xB5FDFC5D3132C498,
+    0x310CB380DB6F7503,
+    0xE87FBB46217A360E,
+    0x2102AE466EBB1148,
+    0xF8549E1A3AA5E00D,
+    0x07A69AFDCC42261A,
+    0xC4C118BFE78FEAAE,
+    0xF9F4892ED96BD438,
+    0x1AF3DBE25D8F45DA,
+    0xF5B4B0B0D2DEEEB4,
+    0x962ACEEFA82E1C84,
+    0x046E3ECAAF453CE9,
+    0xF05D129681949A4C,
+    0x964781CE734B3C84,
+    0x9C2ED44081CE5FBD,
+    0x522E23F3925E319E,
+    0x177E00F9FC32F791,
+    0x2BC60A63A6F3B3F2,
+    0x222BBFAE61725606,
+    0x486289DDCC3D6780,
+    0x7DC7785B8EFDFC80,
+    0x8AF38731C02BA980,
+    0x1FAB64EA29A2DDF7,
+    0xE4D9429322CD065A,
+    0x9DA058C67844F20C,
+    0x24C0E332B70019B0,
+    0x233003B5A6CFE6AD,
+    0xD586BD01C5C217F6,
+    0x5E5637885F29BC2B,
+    0x7EBA726D8C94094B,
+    0x0A56A5F0BFE39272,
+    0xD79476A84EE20D06,
+    0x9E4C1269BAA4BF37,
+    0x17EFEE45B0DEE640,
+    0x1D95B0A5FCF90BC6,
+    0x93CBE0B699C2585D,
+    0x65FA4F227A2B6D79,
+    0xD5F9E858292504D5,
+    0xC2B5A03F71471A6F,
+    0x59300222B4561E00,
+    0xCE2F8642CA0712DC,
+    0x7CA9723FBB2E8988,
+    0x2785338347F2BA08,
+    0xC61BB3A141E50E8C,
+    0x150F361DAB9DEC26,
+    0x9F6A419D382595F4,
+    0x64A53DC924FE7AC9,
+    0x142DE49FFF7A7C3D,
+    0x0C335248857FA9E7,
+    0x0A9C32D5EAE45305,
+    0xE6C42178C4BBB92E,
+    0x71F1CE2490D20B07,
+    0xF1BCC3D275AFE51A,
+    0xE728E8C83C334074,
+    0x96FBF83A12884624,
+    0x81A1549FD6573DA5,
+    0x5FA7867CAF35E149,
+    0x56986E2EF3ED091B,
+    0x917F1DD5F8886C61,
+    0xD20D8C88C8FFE65F,
+    0x31D71DCE64B2C310,
+    0xF165B587DF898190,
+    0xA57E6339DD2CF3A0,
+    0x1EF6E6DBB1961EC9,
+    0x70CC73D90BC26E24,
+    0xE21A6B35DF0C3AD7,
+    0x003A93D8B2806962,
+    0x1C99DED33CB890A1,
+    0xCF3145DE0ADD4289,
+    0xD0E4427A5514FB72,
+    0x77C621CC9FB3A483,
+    0x67A34DAC4356550B,
+    0xF8D626AAAF278509,
+];
+
+// can the active player capture en passant
+fn ep_possible(g: &Game) -> bool {
+    if g.pjm < 0 {
+        return false;
+    }
+    let c = active_color(g);
+    let pawn = g.pjm as i64 - c * 8; // the pawn which did the double step
+    [pawn - 1, pawn + 1]
+        .iter()
+        .any(|&p| row(p as Position) == row(pawn as Position) && g.board[p as usize] == W_PAWN * c)
+}
+
+// The Zobrist key of the current position, including active player, castling rights and
+// en passant (only when possible). Can be used for opening books or game databases.
+pub fn position_key(g: &Game) -> u64 {
+    let mut key = 0;
+    for (p, f) in g.board.iter().enumerate() {
+        if *f != VOID_ID {
+            key ^= ZOBRIST[(ARRAY_BASE_6 + f) as usize * 64 + p];
+        }
+    }
+    if active_color(g) == COLOR_BLACK {
+        key ^= ZOBRIST[ZOBRIST_BLACK];
+    }
+    for (i, right) in castling_rights(g).iter().enumerate() {
+        if *right {
+            key ^= ZOBRIST[ZOBRIST_CASTLING + i];
+        }
+    }
+    if ep_possible(g) {
+        key ^= ZOBRIST[ZOBRIST_EP + col(g.pjm) as usize];
+    }
+    key
+}
+
+// The key of Polyglot books, with its Random64 keys and the squares a1 = 0 ... h8 = 63.
+// The start position is 0x463b96181691fc9c.
+pub fn polyglot_key(g: &Game) -> u64 {
+    let mut key = 0;
+    for (p, f) in g.board.iter().enumerate() {
+        if *f != VOID_ID {
+            // bp, wp, bn, wn ... bk, wk
+            let kind = (f.unsigned_abs() as usize - 1) * 2 + (*f > 0) as usize;
+            key ^= POLYGLOT
+                [kind * 64 + row(p as Position) as usize * 8 + 7 - col(p as Position) as usize];
         }
-        KING_ID => walk_king(&g, kk, &mut s),
-        _ => {}
     }
-    if si == 3 || si == 3 + 7 * 8 {
-        const // king, void, void, void, rook, kingDelta+2
-      Q: [[usize; 6]; 2] = [[3, 2, 1, 1, 0, 0], [3, 4, 5, 6, 7, 4]];
-        let k = W_KING * color;
-        let r = W_ROOK * color;
-        for i in 0..(1 + 1) {
-            // castlings both sides
-            let mut q = Q[i];
-            if color == COLOR_BLACK {
-                for j in 0..(4 + 1) {
-                    q[j as usize] += 7 * 8;
-                }
+    for (i, right) in castling_rights(g).iter().enumerate() {
+        if *right {
+            key ^= POLYGLOT[768 + i];
+        }
+    }
+    if ep_possible(g) {
+        key ^= POLYGLOT[772 + 7 - col(g.pjm) as usize];
+    }
+    if active_color(g) == COLOR_WHITE {
+        key ^= POLYGLOT[780];
+    }
+    key
+}
+
+// castling rights K, Q, k and q -- king and rook are still on their initial positions
+pub fn castling_rights(g: &Game) -> [bool; 4] {
+    let mut result = [false; 4];
+    for (i, (k, r, _)) in CASTLING_SQUARES.iter().enumerate() {
+        let c = if *k == WK3 { COLOR_WHITE } else { COLOR_BLACK };
+        result[i] = g.board[*k] == W_KING * c
+            && g.board[*r] == W_ROOK * c
+            && !g.has_moved.contains(*k)
+            && !g.has_moved.contains(*r);
+    }
+    result
+}
+
+// the Forsyth-Edwards Notation of the current position
+pub fn fen(g: &Game) -> String {
+    let mut result = String::new();
+    for r in (0..8).rev() {
+        let mut empty = 0;
+        for c in (0..8).rev() {
+            let f = g.board[c + r * 8];
+            if f == VOID_ID {
+                empty += 1;
+                continue;
             }
-            if g.board[q[0]] == k
-                && g.board[q[1]] == 0
-                && g.board[q[2]] == 0
-                && g.board[q[3]] == 0
-                && g.board[q[4]] == r
-                && !g.has_moved.contains(q[0])
-                && !g.has_moved.contains(q[4])
+            if empty > 0 {
+                result.push_str(&empty.to_string());
+                empty = 0;
+            }
+            result.push(FEN_FIGURES.as_bytes()[(ARRAY_BASE_6 + f) as usize] as char);
+        }
+        if empty > 0 {
+            result.push_str(&empty.to_string());
+        }
+        if r > 0 {
+            result.push('/');
+        }
+    }
+    result.push_str(if active_color(g) == COLOR_WHITE {
+        " w "
+    } else {
+        " b "
+    });
+    let mut castling = String::new();
+    for (i, (_, _, ch)) in CASTLING_SQUARES.iter().enumerate() {
+        if castling_rights(g)[i] {
+            castling.push(*ch);
+        }
+    }
+    if castling.is_empty() {
+        castling.push('-');
+    }
+    result.push_str(&castling);
+    result.push(' ');
+    if g.pjm >= 0 {
+        result.push_str(&square_str(g.pjm));
+    } else {
+        result.push('-');
+    }
+    result.push_str(&format!(" {} {}", g.to_100, g.move_counter / 2 + 1));
+    result
+}
+
+// one king of each color and no pawns on the first or last rank, see Variant::check_setup()
+pub(crate) fn check_setup(pieces: &Board) -> Result<(), SetupError> {
+    if pieces.iter().filter(|&&f| f == W_KING).count() != 1
+        || pieces.iter().filter(|&&f| f == B_KING).count() != 1
+    {
+        return Err(SetupError::MissingKing);
+    }
+    if (0..8).chain(56..64).any(|p| pieces[p].abs() == PAWN_ID) {
+        return Err(SetupError::PawnOnBackRank);
+    }
+    Ok(())
+}
+
+// Set up an arbitrary position. rights are the castling rights K, Q, k and q, and ep
+// is the en passant square behind a pawn which just did a double step.
+// The game is only modified when the position is valid.
+pub fn set_board(
+    g: &mut Game,
+    pieces: &Board,
+    side_to_move: Color,
+    rights: [bool; 4],
+    ep: Option<Position>,
+) -> Result<(), SetupError> {
+    if pieces.iter().any(|f| f.abs() > KING_ID) {
+        return Err(SetupError::InvalidPiece);
+    }
+    if side_to_move != COLOR_WHITE && side_to_move != COLOR_BLACK {
+        return Err(SetupError::InvalidSideToMove);
+    }
+    g.variant.check_setup(pieces)?;
+    let opp = opp_color(side_to_move);
+    if let Some(king) = pieces.iter().position(|&f| f == W_KING * opp) {
+        if attackers(g, pieces, king as Position, side_to_move).0 != 0 {
+            return Err(SetupError::OpponentInCheck);
+        }
+    }
+    let mut has_moved = BitSet::new();
+    for (i, &(k, r, _)) in CASTLING_SQUARES.iter().enumerate() {
+        let c = if k == WK3 { COLOR_WHITE } else { COLOR_BLACK };
+        if !rights[i] {
+            has_moved.insert(k);
+            has_moved.insert(r);
+        } else if pieces[k] != W_KING * c || pieces[r] != W_ROOK * c {
+            return Err(SetupError::InvalidCastling);
+        }
+    }
+    for (i, &(k, _, _)) in CASTLING_SQUARES.iter().enumerate() {
+        if rights[i] {
+            has_moved.remove(k);
+        }
+    }
+    let pjm = match ep {
+        None => -1,
+        Some(p) => {
+            // the pawn of the opponent is in front of p, seen from the active player
+            let pawn = p as i64 - side_to_move * 8;
+            let from = p as i64 + side_to_move * 8;
+            if !POS_RANGE.contains(&p)
+                || row(p) != if side_to_move == COLOR_WHITE { 5 } else { 2 }
+                || pieces[p as usize] != VOID_ID
+                || pieces[from as usize] != VOID_ID
+                || pieces[pawn as usize] != W_PAWN * opp
             {
-                if !(in_check(&g, q[0] as i8, color, true)
-                    || in_check(&g, q[1] as i8, color, true)
-                    || in_check(&g, q[2] as i8, color, true))
-                {
-                    kk.di = (q[0] + q[5] - 2) as i8;
-                    s.push(kk);
+                return Err(SetupError::InvalidEnPassant);
+            }
+            p
+        }
+    };
+    clear_game(g);
+    g.board = *pieces;
+    g.has_moved = has_moved;
+    g.pjm = pjm;
+    g.move_counter = (side_to_move == COLOR_BLACK) as u16;
+    g.history.clear();
+    g.history.insert(encode_board(g, side_to_move), 1);
+    Ok(())
+}
+
+// set up the position from a FEN string. The game is only modified when the FEN is valid.
+// The halfmove clock and move number are optional.
+pub fn set_fen(g: &mut Game, fen: &str) -> Result<(), FenError> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if !(4..=6).contains(&fields.len()) {
+        return Err(FenError::WrongFieldCount);
+    }
+    let mut board: Board = [VOID_ID; 64];
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::InvalidBoard);
+    }
+    for (i, rank) in ranks.iter().enumerate() {
+        let r = 7 - i;
+        let mut file = 0;
+        for ch in rank.chars() {
+            if let Some(n) = ch.to_digit(10) {
+                if n == 0 {
+                    return Err(FenError::InvalidBoard); // "80" would pass as 8 empty squares
+                }
+                file += n as usize;
+            } else {
+                let f = match FEN_FIGURES.find(ch) {
+                    Some(p) if ch != '.' => p as FigureID - ARRAY_BASE_6,
+                    _ => return Err(FenError::InvalidPiece(ch)),
+                };
+                if file > 7 {
+                    return Err(FenError::InvalidBoard);
                 }
+                board[7 - file + r * 8] = f;
+                file += 1;
             }
         }
+        if file != 8 {
+            return Err(FenError::InvalidBoard);
+        }
     }
-    let backup = g.board;
-    for el in &mut s {
-        do_move(g, si as i8, el.di, true);
-        if in_check(&g, king_pos(&g, color), color, true) {
-            el.s = 0
+    let color = match fields[1] {
+        "w" => COLOR_WHITE,
+        "b" => COLOR_BLACK,
+        _ => return Err(FenError::InvalidSideToMove),
+    };
+    let mut rights = [false; 4];
+    if fields[2] != "-" {
+        for ch in fields[2].chars() {
+            match CASTLING_SQUARES.iter().position(|el| el.2 == ch) {
+                Some(i) => rights[i] = true,
+                None => return Err(FenError::InvalidCastling),
+            }
         }
-        g.board = backup;
     }
-    s.retain(|&el| el.s != 0);
-    return s;
+    let ep = match fields[3] {
+        "-" => None,
+        sq => Some(parse_square(sq).map_err(|_| FenError::InvalidEnPassant)?),
+    };
+    let to_100 = match fields.get(4) {
+        Some(n) => n.parse::<u8>().map_err(|_| FenError::InvalidNumber)?,
+        None => 0,
+    };
+    let move_number = match fields.get(5) {
+        Some(n) => n.parse::<u16>().map_err(|_| FenError::InvalidNumber)?,
+        None => 1,
+    };
+    if move_number == 0 || move_number > 5000 {
+        return Err(FenError::InvalidNumber);
+    }
+    set_board(g, &board, color, rights, ep).map_err(FenError::Setup)?;
+    g.to_100 = to_100;
+    g.move_counter += (move_number - 1) * 2;
+    Ok(())
 }
 
-pub fn move_is_valid2(g: &mut Game, si: i64, di: i64) -> bool {
-    let next = -(g.move_counter as Color % 2) * 2 + 1;
-    signum(g.board[si as usize]) as Color == next && tag(g, si).iter().any(|&it| it.di == di as i8)
+// Extended Position Description: the first four FEN fields followed by operations
+// like `bm Qg6; id "WAC.001";`. The opcodes bm, am, id and ce are decoded, all
+// other operations are kept as written. hmvc and fmvn set the move counters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Epd {
+    pub best_moves: Vec<Move>,
+    pub avoid_moves: Vec<Move>,
+    pub id: Option<String>,
+    pub centipawns: Option<i64>,
+    pub other: Vec<(String, String)>,
 }
 
-const FIG_STR: [&str; 7] = ["  ", "  ", "N_", "B_", "R_", "Q_", "K_"];
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    Fen(FenError),
+    Syntax,
+    Move(SanError),
+}
 
-fn col_str(c: Col) -> char {
-    char::from_u32('H' as u32 - c as u32).unwrap()
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::Fen(e) => e.fmt(f),
+            EpdError::Syntax => f.write_str("invalid operation in EPD"),
+            EpdError::Move(e) => write!(f, "{} in EPD", e),
+        }
+    }
 }
 
-fn row_str(c: Col) -> char {
-    char::from_u32('1' as u32 + c as u32).unwrap()
+impl core::error::Error for EpdError {}
+
+// split the operations at semicolons outside of quoted strings
+fn epd_operations(s: &str) -> Result<Vec<(String, String)>, EpdError> {
+    let mut result = Vec::new();
+    let mut op = String::new();
+    let mut quoted = false;
+    for ch in s.chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                let t = op.trim();
+                let (code, operands) = t.split_once(char::is_whitespace).unwrap_or((t, ""));
+                if code.is_empty() {
+                    return Err(EpdError::Syntax);
+                }
+                result.push((code.to_string(), operands.trim().to_string()));
+                op.clear();
+                continue;
+            }
+            _ => {}
+        }
+        op.push(ch);
+    }
+    if quoted || !op.trim().is_empty() {
+        return Err(EpdError::Syntax);
+    }
+    Ok(result)
 }
 
-pub fn get_board(g: &Game) -> Board {
-    return g.board;
+// Set up the position of an EPD line and return its operations.
+// bm and am moves are given in SAN for that position.
+pub fn set_epd(g: &mut Game, line: &str) -> Result<Epd, EpdError> {
+    let mut rest = line.trim_start();
+    let mut fields = Vec::new();
+    for _ in 0..4 {
+        let (field, r) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        fields.push(field);
+        rest = r.trim_start();
+    }
+    let ops = epd_operations(rest)?;
+    let counter = |code: &str, default: &str| match ops.iter().find(|op| op.0 == code) {
+        Some(op) => op.1.clone(),
+        None => default.to_string(),
+    };
+    let fen = format!(
+        "{} {} {}",
+        fields.join(" "),
+        counter("hmvc", "0"),
+        counter("fmvn", "1")
+    );
+    set_fen(g, &fen).map_err(EpdError::Fen)?;
+    let mut epd = Epd::default();
+    for (code, operands) in ops {
+        match code.as_str() {
+            "bm" | "am" => {
+                let mut moves = Vec::new();
+                for san in operands.split_whitespace() {
+                    moves.push(parse_san(g, san).map_err(EpdError::Move)?);
+                }
+                if code == "bm" {
+                    epd.best_moves = moves;
+                } else {
+                    epd.avoid_moves = moves;
+                }
+            }
+            "id" => epd.id = Some(operands.trim_matches('"').to_string()),
+            "ce" => epd.centipawns = Some(operands.parse().map_err(|_| EpdError::Syntax)?),
+            _ => epd.other.push((code, operands)),
+        }
+    }
+    Ok(epd)
 }
 
-// call this after do_move()
-pub fn move_to_str(g: &Game, si: Position, di: Position, flag: i32) -> String {
-    //when true: // move_is_valid(si, di): // avoid unnecessary expensive test
-    let mut result: String;
-    if true {
-        if g.board[di as usize].abs() == KING_ID && (di - si).abs() == 2 {
-            result = String::from(if col(di) == 1 { "o-o" } else { "o-o-o" });
+// the EPD line of the current position with the given operations
+pub fn epd(g: &Game, epd: &Epd) -> String {
+    let fen = fen(g);
+    let mut result: Vec<&str> = fen.split(' ').take(4).collect();
+    let mut ops = Vec::new();
+    for (code, moves) in [("bm", &epd.best_moves), ("am", &epd.avoid_moves)] {
+        if !moves.is_empty() {
+            let sans: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+            ops.push(format!("{} {};", code, sans.join(" ")));
+        }
+    }
+    if let Some(ce) = epd.centipawns {
+        ops.push(format!("ce {};", ce));
+    }
+    if let Some(id) = &epd.id {
+        ops.push(format!("id \"{}\";", id));
+    }
+    for (code, operands) in &epd.other {
+        if operands.is_empty() {
+            ops.push(format!("{};", code));
         } else {
-            result = String::from(FIG_STR[g.board[di as usize].abs() as usize]);
-            result.push(col_str(col(si)));
-            result.push(row_str(row(si)));
-            result.push(if flag == FLAG_CAPTURE || flag == FLAG_PROCAP {
-                'x'
-            } else {
-                '-'
-            });
-            result.push(col_str(col(di)));
-            result.push(row_str(row(di)));
-            if flag == FLAG_EP || flag == FLAG_PROCAP {
-                result.push_str(" e.p.");
+            ops.push(format!("{} {};", code, operands));
+        }
+    }
+    result.extend(ops.iter().map(|op| op.as_str()));
+    result.join(" ")
+}
+
+pub(crate) const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The result of the game for PGN: "1-0", "0-1", "1/2-1/2", or "*" while it is ongoing
+pub fn pgn_result(g: &Game) -> &'static str {
+    match state(g) {
+        GameState::Ongoing => "*",
+        GameState::Checkmate(COLOR_WHITE) | GameState::VariantWin(COLOR_WHITE) => "1-0",
+        GameState::Checkmate(_) | GameState::VariantWin(_) => "0-1",
+        _ => "1/2-1/2",
+    }
+}
+
+// the numeric annotation glyphs of PGN with a symbol, the others are written as "$n"
+pub const NAG_SYMBOLS: [(u8, &str); 6] = [
+    (1, "!"),
+    (2, "?"),
+    (3, "!!"),
+    (4, "??"),
+    (5, "!?"),
+    (6, "?!"),
+];
+
+// a PGN comment as tokens for the line wrapping of pgn(), comments can not be nested
+fn comment_tokens(tokens: &mut Vec<String>, comment: &str) {
+    let text = comment.replace('}', ")");
+    let mut words: Vec<String> = text.split_whitespace().map(String::from).collect();
+    if let Some(w) = words.first_mut() {
+        w.insert(0, '{');
+    }
+    if let Some(w) = words.last_mut() {
+        w.push('}');
+    }
+    tokens.extend(words);
+}
+
+// the movetext of the line starting with the move of node, the other moves of a position
+// follow the move of the line as variations in parentheses
+fn line_tokens(g: &Game, mut node: NodeId, mut counter: u16, tokens: &mut Vec<String>) {
+    let mut numbered = false; // a move of Black needs its number after a comment or variation
+    loop {
+        let n = &g.tree[node];
+        if counter.is_multiple_of(2) {
+            tokens.push(format!("{}.", counter / 2 + 1));
+        } else if !numbered {
+            tokens.push(format!("{}...", counter / 2 + 1));
+        }
+        tokens.push(n.mv.to_string());
+        tokens.extend(n.nags.iter().map(|nag| format!("${}", nag)));
+        comment_tokens(tokens, &n.comment);
+        numbered = n.comment.is_empty();
+        let siblings = &g.tree[n.parent].children;
+        if siblings[0] == node {
+            for &v in &siblings[1..] {
+                let mut variation = Vec::new();
+                line_tokens(g, v, counter, &mut variation);
+                variation[0].insert(0, '(');
+                variation.last_mut().unwrap().push(')');
+                tokens.extend(variation);
+                numbered = false;
             }
         }
-        if in_check(
-            &g,
-            king_pos(&g, (-signum(g.board[di as usize])) as Color),
-            (-signum(g.board[di as usize])) as Color,
-            true,
-        ) {
-            result.push_str(" +");
+        counter += 1;
+        match n.children.first() {
+            Some(&child) => node = child,
+            None => break,
         }
-    } else {
-        result = String::from("invalid move");
     }
+}
+
+// The game in PGN, with all its moves from the start position, the variations and their
+// comments and NAGs, wherever the current position is. tags replace or extend the seven tag
+// roster, i.e. [("White", "Stefan")], and a Result tag overrides pgn_result() of the end of
+// the main line, i.e. for a resignation or a loss on time.
+pub fn pgn(g: &Game, tags: &[(&str, &str)]) -> String {
+    let mut start = g.clone();
+    go_to_ply(&mut start, 0);
+    let start_fen = fen(&start);
+    let mut end = start.clone(); // the end of the main line
+    let mut node = ROOT_NODE;
+    while let Some(&child) = g.tree[node].children.first() {
+        node = child;
+    }
+    go_to_node(&mut end, node);
+    let mut roster = vec![
+        ("Event", "?"),
+        ("Site", "?"),
+        ("Date", "????.??.??"),
+        ("Round", "?"),
+        ("White", "?"),
+        ("Black", "?"),
+        ("Result", pgn_result(&end)),
+    ];
+    if g.variant.name() != variant::STANDARD.name() {
+        roster.push(("Variant", g.variant.name()));
+    }
+    if start_fen != g.variant.start_fen() {
+        roster.push(("SetUp", "1"));
+        roster.push(("FEN", start_fen.as_str()));
+    }
+    for &(name, value) in tags {
+        match roster.iter_mut().find(|(n, _)| *n == name) {
+            Some(tag) => tag.1 = value,
+            None => roster.push((name, value)),
+        }
+    }
+    let mut result = String::new();
+    for (name, value) in &roster {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+        result.push_str(&format!("[{} \"{}\"]\n", name, value));
+    }
+    result.push('\n');
+    let mut tokens = Vec::new();
+    comment_tokens(&mut tokens, &g.tree[ROOT_NODE].comment);
+    if let Some(&first) = g.tree[ROOT_NODE].children.first() {
+        line_tokens(g, first, start.move_counter, &mut tokens);
+    }
+    tokens.push(roster[6].1.to_string());
+    // the movetext is wrapped before 80 columns
+    let mut len = 0;
+    for t in tokens {
+        if len > 0 && len + 1 + t.len() >= 80 {
+            result.push('\n');
+            len = 0;
+        } else if len > 0 {
+            result.push(' ');
+            len += 1;
+        }
+        result.push_str(&t);
+        len += t.len();
+    }
+    result.push('\n');
     result
 }
 
-pub fn _m_2_str(g: &Game, si: Position, di: Position) -> String {
-    let mut result: String;
-    let mut flag: i32 = 0;
-    if !is_void_at(&g, di) {
-        flag = FLAG_CAPTURE;
+// the analysis board of lichess with the game, or with the position when no move was played
+pub fn lichess_analysis_url(g: &Game) -> String {
+    if g.tree[ROOT_NODE].children.is_empty() {
+        return format!("https://lichess.org/analysis/{}", fen(g).replace(' ', "_"));
     }
-    if base_row(di) && is_a_pawn_at(&g, si) {
-        flag = if flag == FLAG_CAPTURE {
-            FLAG_PROCAP
-        } else {
-            FLAG_PROMOTION
+    let mut url = String::from("https://lichess.org/analysis/pgn/");
+    for b in pgn(g, &[]).bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                url.push(b as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", b)),
         }
-    } else if is_a_pawn_at(&g, si) && is_void_at(&g, di) && odd(di - si) {
-        flag = FLAG_EP
     }
-    if true {
-        // move_is_valid(si, di): // avoid unnecessary expensive test
-        if g.board[di as usize].abs() == KING_ID && (di - si).abs() == 2 {
-            result = String::from(if col(di) == 1 { "o-o" } else { "o-o-o" });
+    url
+}
+
+// The compact game string: the moves leading to the current position as their index in
+// the sorted legal moves, one byte each, in URL-safe base64 without padding. A game of
+// another variant than standard chess starts with the byte 0xfe and the index of the
+// variant in variant::VARIANTS. A game from another position than the start position of
+// its variant continues with the byte 0xff and its FEN, terminated by 0.
+// "1. e4 e5 2. Nf3" is 4 characters, a game of 80 moves 214.
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const COMPACT_VARIANT: u8 = 0xfe;
+const COMPACT_FEN: u8 = 0xff;
+
+fn sorted_moves(g: &Game) -> Vec<Move> {
+    let mut moves: Vec<Move> = legal_moves(g).collect();
+    moves.sort_by_key(|m| (m.src, m.dst, m.promote_to));
+    moves
+}
+
+pub fn compact(g: &Game) -> String {
+    let mut start = g.clone();
+    go_to_ply(&mut start, 0);
+    let mut bytes = Vec::new();
+    if g.variant.name() != variant::STANDARD.name() {
+        let v = variant::VARIANTS
+            .iter()
+            .position(|v| v.name() == g.variant.name());
+        bytes.extend([COMPACT_VARIANT, v.unwrap_or_default() as u8]);
+    }
+    let start_fen = fen(&start);
+    if start_fen != g.variant.start_fen() {
+        bytes.push(COMPACT_FEN);
+        bytes.extend(start_fen.bytes());
+        bytes.push(0);
+    }
+    for m in &move_list(g)[..game_ply(g)] {
+        let moves = sorted_moves(&start);
+        let i = moves
+            .iter()
+            .position(|l| (l.src, l.dst, l.promote_to) == (m.src, m.dst, m.promote_to));
+        bytes.push(i.unwrap_or_default() as u8); // at most 218 legal moves
+        do_move(&mut start, m.src, m.dst, m.promote_to, false);
+    }
+    let mut result = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().fold(0u32, |n, &b| n << 8 | b as u32) << (8 * (3 - chunk.len()));
+        for i in 0..=chunk.len() {
+            result.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    result
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompactError {
+    Base64,
+    Variant(u8), // the index of an unknown variant
+    Fen(FenError),
+    Move(usize), // the ply of an invalid move index
+}
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompactError::Base64 => f.write_str("invalid characters in game string"),
+            CompactError::Variant(i) => write!(f, "unknown variant {} in game string", i),
+            CompactError::Fen(e) => e.fmt(f),
+            CompactError::Move(ply) => write!(f, "invalid move {} in game string", ply + 1),
+        }
+    }
+}
+
+impl core::error::Error for CompactError {}
+
+// Read a game string of compact(), the game is at its end in the variant of the string, and
+// is only modified when the string is valid. Whitespace is ignored, i.e. of a line break.
+pub fn set_compact(g: &mut Game, s: &str) -> Result<(), CompactError> {
+    let mut bytes = Vec::new();
+    let (mut n, mut bits) = (0u32, 0);
+    for c in s.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let digit = BASE64
+            .iter()
+            .position(|&b| b == c)
+            .ok_or(CompactError::Base64)?;
+        n = n << 6 | digit as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    let mut game = g.clone();
+    let mut moves = &bytes[..];
+    let mut variant = variant::STANDARD;
+    if let [COMPACT_VARIANT, i, rest @ ..] = moves {
+        variant = *variant::VARIANTS
+            .get(*i as usize)
+            .ok_or(CompactError::Variant(*i))?;
+        moves = rest;
+    }
+    set_variant(&mut game, variant);
+    if let Some((&COMPACT_FEN, rest)) = moves.split_first() {
+        let end = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(CompactError::Base64)?;
+        let fen = core::str::from_utf8(&rest[..end]).map_err(|_| CompactError::Base64)?;
+        set_fen(&mut game, fen).map_err(CompactError::Fen)?;
+        moves = &rest[end + 1..];
+    }
+    for (ply, &i) in moves.iter().enumerate() {
+        let m = *sorted_moves(&game)
+            .get(i as usize)
+            .ok_or(CompactError::Move(ply))?;
+        do_move(&mut game, m.src, m.dst, m.promote_to, false);
+    }
+    set_position(g, &game);
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PgnError {
+    Tag,
+    Fen(FenError),
+    Move(String, SanError),
+    Variation,
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Tag => f.write_str("invalid tag pair in PGN"),
+            PgnError::Fen(e) => e.fmt(f),
+            PgnError::Move(san, e) => write!(f, "{} in PGN: {}", e, san),
+            PgnError::Variation => f.write_str("unbalanced variation in PGN"),
+        }
+    }
+}
+
+impl core::error::Error for PgnError {}
+
+// a tag pair like [White "Stefan"], with the escapes \" and \\ in the value
+pub(crate) fn pgn_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped || c != '\\' {
+            result.push(c);
+            escaped = false;
         } else {
-            result = String::from(FIG_STR[g.board[si as usize].abs() as usize]);
-            result.push(col_str(col(si)));
-            result.push(row_str(row(si)));
-            result.push(if flag == FLAG_CAPTURE || flag == FLAG_PROCAP {
-                'x'
-            } else {
-                '-'
-            });
-            result.push(col_str(col(di)));
-            result.push(row_str(row(di)));
-            if flag == FLAG_EP || flag == FLAG_PROCAP {
-                result.push_str(" e.p.");
+            escaped = true;
+        }
+    }
+    Some((name.to_string(), result))
+}
+
+// append a comment of the PGN to the comment of the current position
+fn add_comment(g: &mut Game, text: &str) {
+    let comment = &mut g.tree[g.node].comment;
+    if !text.trim().is_empty() && !comment.is_empty() {
+        comment.push(' ');
+    }
+    comment.push_str(text.trim());
+}
+
+// Read the first game of a PGN text with its comments, NAGs and variations, and return
+// its tags. The game is at the end of the main line, and is only modified when the PGN
+// is valid. The Variant tag sets the variant of the game. Move numbers are optional, and the annotations "!", "?" etc. become NAGs.
+pub fn set_pgn(g: &mut Game, pgn: &str) -> Result<Vec<(String, String)>, PgnError> {
+    let mut lines = pgn
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| l.is_empty())
+        .peekable();
+    let mut tags = Vec::new();
+    while let Some(line) = lines.next_if(|l| l.starts_with('[')) {
+        tags.push(pgn_tag(line).ok_or(PgnError::Tag)?);
+    }
+    let movetext = lines.collect::<Vec<&str>>().join("\n");
+    let mut game = g.clone();
+    // unknown variants like "From Position" are standard chess
+    game.variant = tags
+        .iter()
+        .find(|(name, _)| name == "Variant")
+        .and_then(|(_, name)| variant::by_name(name))
+        .unwrap_or(variant::STANDARD);
+    reset_game(&mut game);
+    if let Some((_, fen)) = tags.iter().find(|(name, _)| name == "FEN") {
+        set_fen(&mut game, fen).map_err(PgnError::Fen)?;
+    }
+    let mut variations = Vec::new(); // the nodes to continue with after a variation
+    let mut chars = movetext.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => {
+                let text: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                add_comment(&mut game, &text);
+            }
+            ';' => {
+                let text: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                add_comment(&mut game, &text);
+            }
+            // a variation replaces the last move
+            '(' if game.node != ROOT_NODE => {
+                variations.push(game.node);
+                let parent = game.tree[game.node].parent;
+                go_to_node(&mut game, parent);
+            }
+            ')' => {
+                let node = variations.pop().ok_or(PgnError::Variation)?;
+                go_to_node(&mut game, node);
+            }
+            '$' => {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                match digits.parse::<u8>() {
+                    Ok(nag) if game.node != ROOT_NODE => game.tree[game.node].nags.push(nag),
+                    _ => return Err(PgnError::Move(format!("${}", digits), SanError::Syntax)),
+                }
+            }
+            '(' => return Err(PgnError::Variation),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !"{}();$".contains(*c))
+                {
+                    token.push(c);
+                }
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                    break;
+                }
+                // a move number like "12." or "12...", maybe without space before the move
+                let san = match token.rfind('.') {
+                    Some(i) => &token[i + 1..],
+                    None => &token,
+                };
+                if san.is_empty() {
+                    continue;
+                }
+                let (san, symbol) = san.split_at(san.find(['!', '?']).unwrap_or(san.len()));
+                let m = parse_san(&game, san).map_err(|e| PgnError::Move(token.clone(), e))?;
+                do_move(&mut game, m.src, m.dst, m.promote_to, false);
+                if let Some(&(nag, _)) = NAG_SYMBOLS.iter().find(|(_, s)| *s == symbol) {
+                    game.tree[game.node].nags.push(nag);
+                }
             }
-            // works only after doing the move
-            //if in_check(king_pos((-signum(board[di])).Color), (-signum(board[di])).Color):
-            //  result.add(" +")
         }
-    } else {
-        result = String::from("invalid move");
     }
-    result
+    if !variations.is_empty() {
+        return Err(PgnError::Variation);
+    }
+    set_position(g, &game);
+    Ok(tags)
+}
+
+// The games of a PGN text with several games, each starting with its tags
+pub fn split_pgn(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let (mut start, mut offset, mut in_moves) = (0, 0, false);
+    for line in pgn.split_inclusive('\n') {
+        let tag = line.trim_start().starts_with('[');
+        if tag && in_moves {
+            games.push(&pgn[start..offset]);
+            start = offset;
+            in_moves = false;
+        } else if !tag && !line.trim().is_empty() {
+            in_moves = true;
+        }
+        offset += line.len();
+    }
+    if !pgn[start..].trim().is_empty() {
+        games.push(&pgn[start..]);
+    }
+    games
+}
+
+fn col_str(c: Col) -> char {
+    char::from_u32('H' as u32 - c as u32).unwrap()
+}
+
+fn row_str(c: Col) -> char {
+    char::from_u32('1' as u32 + c as u32).unwrap()
+}
+
+fn file_char(p: Position) -> char {
+    col_str(col(p)).to_ascii_lowercase()
+}
+
+// i.e. "e4"
+pub fn square_str(p: Position) -> String {
+    format!("{}{}", file_char(p), row_str(row(p)))
+}
+
+pub fn get_board(g: &Game) -> Board {
+    g.board
+}
+
+// a square with a different figure in two boards
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SquareChange {
+    pub pos: Position,
+    pub old: FigureID,
+    pub new: FigureID,
+}
+
+// The changed squares from prev to next, i.e. two for a plain move, three for en passant
+// and four for castling. Lets the GUI update and animate only what has changed.
+pub fn diff(prev: &Board, next: &Board) -> Vec<SquareChange> {
+    POS_RANGE
+        .filter(|&p| prev[p as usize] != next[p as usize])
+        .map(|p| SquareChange {
+            pos: p,
+            old: prev[p as usize],
+            new: next[p as usize],
+        })
+        .collect()
 }
 
 // Endgame = no pawns, weaker side has no queen, no rook and not two bishops.
@@ -2487,15 +5786,107 @@ fn setup_endgame(g: &mut Game) -> bool {
     return true;
 }
 
-pub fn reply(g: &mut Game) -> Move {
+const NODE_BATCH: u64 = 4096;
+
+// the state of the search after a completed iteration
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: i64,
+    pub best_move: Move,
+    pub pv: Vec<Move>, // the principal variation, starting with best_move
+    pub score: i64,    // from the view of the active player
+    pub nodes: u64,
+    pub time: Duration,
+    pub seldepth: i64, // the deepest ply searched, with captures and extensions
+    pub hashfull: u32, // the permille of the transposition table used by this search
+}
+
+impl SearchInfo {
+    // nodes per second
+    pub fn nps(&self) -> u64 {
+        (self.nodes as u128 * 1000 / self.time.as_millis().max(1)) as u64
+    }
+}
+
+// like the UCI hashfull, the permille of the first 1000 entries of the transposition table
+// written by the current reply(), which resets the priorities of all entries
+fn hashfull(g: &Game) -> u32 {
+    let sample = &g.tt[..g.tt.len().min(1000)];
+    let used = sample.iter().filter(|e| e.res.pri != i64::MIN).count();
+    (used * 1000 / sample.len()) as u32
+}
+
+// Hooks into the search of reply(), so the GUI, a UCI adapter or tests can follow
+// the search without the engine knowing about channels or egui.
+// All methods do nothing by default, and () is an observer ignoring everything.
+pub trait SearchObserver {
+    // called after each iteration of the iterative deepening
+    fn on_depth_complete(&mut self, _info: &SearchInfo) {}
+    // called when an iteration finds a new best move
+    fn on_new_pv(&mut self, _info: &SearchInfo) {}
+    // called each NODE_BATCH searched nodes with the total node count
+    fn on_node_batch(&mut self, _nodes: u64) {}
+    // polled with on_node_batch() and after each iteration, true ends the search early
+    fn should_stop(&mut self) -> bool {
+        false
+    }
+}
+
+impl SearchObserver for () {}
+
+// The principal variation of a search result, read from the transposition table: the
+// best move, followed by the move of the deepest exact score of each next position.
+// Ends with the first position without such a move, or after max_len moves.
+fn principal_variation(g: &mut Game, best: &SearchResult, max_len: usize) -> Vec<Move> {
+    let mut tokens: Vec<UndoToken> = Vec::new();
+    let (mut si, mut di, mut promote_to) = (best.src, best.dst, best.promote_to);
+    while tokens.len() < max_len && check_move(g, si, di).is_ok() {
+        tokens.push(do_move(
+            g,
+            si as Position,
+            di as Position,
+            promote_to,
+            false,
+        ));
+        let hash_pos = get_tte(g, encode_board(g, active_color(g)));
+        if hash_pos < 0 {
+            break;
+        }
+        let res = &g.tt[hash_pos as usize].res;
+        match res.score.iter().rev().find(|s| s.s != INVALID_SCORE) {
+            Some(s) => (si, di, promote_to) = (s.si as i64, s.di as i64, s.promote_to as i64),
+            None => break,
+        }
+    }
+    let pv = tokens.iter().map(|t| t.mv).collect();
+    while let Some(token) = tokens.pop() {
+        undo_move(g, token);
+    }
+    pv
+}
+
+pub fn reply(g: &mut Game, observer: &mut dyn SearchObserver) -> SearchResult {
     //let back_move
-    let mut move_result = Move {
+    let mut move_result = SearchResult {
         state: STATE_NO_VALID_MOVE,
         score: LOWEST_SCORE as i64,
         ..Default::default()
     };
     let color = ((g.move_counter as i64 + 1) % 2) * 2 - 1;
-    let mut result: Move = Default::default();
+    if let Some(book) = g.book.clone() {
+        let random = position_key(g) ^ now(g).as_nanos() as u64;
+        if let Some(m) = book.pick(g, random.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 16) {
+            return SearchResult::new(&m, 0);
+        }
+    }
+    let persona = string_option(g, OPTION_PERSONA).to_string();
+    if let Some(m) = persona::choose(g, &persona) {
+        let token = do_move(g, m.src, m.dst, m.promote_to, true);
+        let score = evaluate(g) * color;
+        undo_move(g, token);
+        return SearchResult::new(&m, score);
+    }
+    let mut result: SearchResult = Default::default();
     //println!("{:?}", g.freedom);
     if cfg!(feature = "salewskiChessDebug") {
         for i in 0..13 {
@@ -2504,42 +5895,71 @@ pub fn reply(g: &mut Game) -> Move {
         }
     }
     let mut depth = 0;
-    let start_time = Instant::now();
-    g.time_0 = Duration::from_secs_f32(g.secs_per_move * 0.7);
+    alloc_table(g); // before the clock starts, a large table takes a while
+    let start_time = now(g);
+    g.time_0 = Duration::from_secs_f32(move_time(g) * 0.7);
+    // the debug output goes to stdout, where the CLI writes its results, see cli.rs
+    let debug = cfg!(feature = "salewskiChessDebug");
     if setup_endgame(g) {
-        println!("endgame");
+        if debug {
+            println!("endgame");
+        }
         g.is_endgame = true;
     }
     for i in 0..13 {
         pf(g.freedom[i]);
     }
-    for el in &mut g.tt {
+    for el in g.tt.iter_mut() {
         el.res.pri = i64::MIN
     }
-    println!("--");
+    if debug {
+        println!("--");
+    }
     g.time_4 = Duration::MAX;
+    g.nodes = 0;
+    g.sel_depth = 0;
+    let mut best_move: Option<Move> = None;
     while depth < MAX_DEPTH {
         depth += 1;
-        result = alphabeta(g, color as i64, depth as i64, g.pjm);
+        result = alphabeta(g, observer, color, depth as i64, g.pjm);
         if result.score != LOWEST_SCORE as i64 {
             move_result = result;
-            g.time_4 = Duration::from_secs_f32(g.secs_per_move * 5.0);
+            g.time_4 = Duration::from_secs_f32(max_time(g));
         } else {
             assert!(move_result.score != LOWEST_SCORE as i64);
-            println!("--- hard cut");
+            if debug {
+                println!("--- hard cut");
+            }
             return move_result;
         }
-        println!(
-            "Depth: {} {} score {} ({:.2} s)",
-            depth,
-            _m_2_str(g, result.src as i8, result.dst as i8),
-            result.score,
-            start_time.elapsed().as_millis() as f64 * 1e-3
-        );
+        let info = SearchInfo {
+            depth: depth as i64,
+            best_move: new_move(g, result.src as i8, result.dst as i8, result.promote_to),
+            pv: principal_variation(g, &result, depth),
+            score: result.score,
+            nodes: g.nodes,
+            time: elapsed(g, start_time),
+            seldepth: g.sel_depth,
+            hashfull: hashfull(g),
+        };
+        if debug {
+            println!(
+                "Depth: {} {} score {} ({:.2} s)",
+                depth,
+                info.best_move.uci(),
+                info.score,
+                info.time.as_millis() as f64 * 1e-3
+            );
+        }
+        observer.on_depth_complete(&info);
+        if best_move != Some(info.best_move) {
+            best_move = Some(info.best_move);
+            observer.on_new_pv(&info);
+        }
         if result.score.abs() > SURE_CHECKMATE as i64 {
             break;
         }
-        if start_time.elapsed() > g.time_0 {
+        if elapsed(g, start_time) > g.time_0 || observer.should_stop() {
             break;
         }
     }
@@ -2550,7 +5970,7 @@ fn board_pos(col: usize, row: usize) -> usize {
     col + row * 8
 }
 
-fn set_board(g: &mut Game, f: FigureID, c: usize, r: usize) {
+fn set_square(g: &mut Game, f: FigureID, c: usize, r: usize) {
     g.board[c + r * 8] = f
 }
 
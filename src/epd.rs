@@ -0,0 +1,103 @@
+// Test suites in the Extended Position Description, like Win at Chess (WAC), the Strategic
+// Test Suite (STS) or the Arasan suite: the first four fields of a FEN, followed by
+// operations like bm Qg6+; am Nxe5; id "WAC.001";
+// A position is solved by one of the best moves bm, and by any move except the avoid moves
+// am. The moves are in SAN. Needs std for reading the file.
+
+use crate::engine::{self, FenError, Game, Move, SanError};
+use std::fmt;
+use std::io::{self, BufRead};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Test {
+    pub id: String, // empty without an id operation
+    pub fen: String,
+    pub best: Vec<String>,  // bm
+    pub avoid: Vec<String>, // am
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EpdError {
+    Fields,
+    NoMoves, // neither bm nor am
+    Fen(FenError),
+    Move(String, SanError),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EpdError::Fields => f.write_str("missing fields in EPD"),
+            EpdError::NoMoves => f.write_str("no bm or am in EPD"),
+            EpdError::Fen(e) => e.fmt(f),
+            EpdError::Move(san, e) => write!(f, "{} in EPD: {}", e, san),
+        }
+    }
+}
+
+impl std::error::Error for EpdError {}
+
+// a line of a suite, the moves are checked by set_test()
+pub fn parse_epd(line: &str) -> Result<Test, EpdError> {
+    let mut rest = line.trim();
+    let mut fields = Vec::new();
+    for _ in 0..4 {
+        let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if field.is_empty() {
+            return Err(EpdError::Fields);
+        }
+        fields.push(field);
+        rest = tail.trim_start();
+    }
+    let mut test = Test {
+        fen: fields.join(" "),
+        ..Default::default()
+    };
+    for operation in rest.split(';') {
+        let (opcode, operands) = operation.trim().split_once(' ').unwrap_or((operation, ""));
+        let moves = || operands.split_whitespace().map(String::from).collect();
+        match opcode.trim() {
+            "bm" => test.best = moves(),
+            "am" => test.avoid = moves(),
+            "id" => test.id = operands.trim().trim_matches('"').to_string(),
+            _ => {}
+        }
+    }
+    if test.best.is_empty() && test.avoid.is_empty() {
+        return Err(EpdError::NoMoves);
+    }
+    Ok(test)
+}
+
+// The tests of a suite with their line numbers, blank lines and comments starting with
+// # are skipped
+pub fn read_epd(reader: impl BufRead) -> io::Result<Vec<(usize, Result<Test, EpdError>)>> {
+    let mut tests = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        tests.push((i + 1, parse_epd(&line)));
+    }
+    Ok(tests)
+}
+
+// Set up the position of the test. g is only modified when all moves are legal.
+pub fn set_test(g: &mut Game, t: &Test) -> Result<(), EpdError> {
+    let mut check = g.clone();
+    engine::set_fen(&mut check, &t.fen).map_err(EpdError::Fen)?;
+    for san in t.best.iter().chain(&t.avoid) {
+        engine::parse_san(&check, san).map_err(|e| EpdError::Move(san.clone(), e))?;
+    }
+    engine::set_fen(g, &t.fen).map_err(EpdError::Fen)
+}
+
+// m in the position g of the test is a best move, and no avoid move
+pub fn is_solution(g: &Game, t: &Test, m: &Move) -> bool {
+    let is = |san: &String| {
+        engine::parse_san(g, san)
+            .is_ok_and(|s| (s.src, s.dst, s.promote_to.abs()) == (m.src, m.dst, m.promote_to.abs()))
+    };
+    (t.best.is_empty() || t.best.iter().any(is)) && !t.avoid.iter().any(is)
+}
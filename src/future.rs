@@ -0,0 +1,117 @@
+// An async friendly search, i.e. for servers: search() returns a future which is
+// resolved by a search thread. Cancelling or dropping the future stops the search.
+// No executor is needed by the engine, any async runtime can await the future.
+// Not on wasm32, where std::thread::spawn() panics.
+
+use crate::engine::{self, Game, SearchInfo, SearchObserver, SearchResult};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Limits {
+    // default is the option "MoveTime" of the position, a time out of its range of
+    // 100 ms to 9 s is clamped to that range
+    pub move_time: Option<Duration>,
+    pub depth: Option<i64>, // iterations of the iterative deepening
+}
+
+#[derive(Default)]
+struct State {
+    result: Option<SearchResult>,
+    done: bool, // the search thread ended, with a result or by a panic
+    waker: Option<Waker>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    stop: AtomicBool,
+}
+
+// ends the search of the thread which owns it, also when the search panics
+struct Done(Arc<Shared>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.done = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct SearchFuture {
+    shared: Arc<Shared>,
+}
+
+struct Limiter<'a> {
+    stop: &'a AtomicBool,
+    depth: Option<i64>,
+    done: i64, // completed iterations
+}
+
+impl SearchObserver for Limiter<'_> {
+    fn on_depth_complete(&mut self, info: &SearchInfo) {
+        self.done = info.depth;
+    }
+    fn should_stop(&mut self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.depth.is_some_and(|d| self.done >= d)
+    }
+}
+
+// Search the position in a new thread. The search uses a clone of position, which
+// allocates a transposition table of the size of the option "Hash", see engine::TransTable.
+pub fn search(position: &Game, limits: Limits) -> SearchFuture {
+    let shared = Arc::new(Shared::default());
+    let mut g = position.clone();
+    if let Some(t) = limits.move_time {
+        let ms = t.as_millis().clamp(100, 9000); // range of option "MoveTime"
+        engine::set_option(&mut g, "MoveTime", &ms.to_string()).unwrap();
+    }
+    let done = Done(shared.clone());
+    thread::spawn(move || {
+        let s = &done.0;
+        let mut limiter = Limiter {
+            stop: &s.stop,
+            depth: limits.depth,
+            done: 0,
+        };
+        let result = engine::reply(&mut g, &mut limiter);
+        s.state.lock().unwrap().result = Some(result);
+    });
+    SearchFuture { shared }
+}
+
+impl SearchFuture {
+    // end the search early, the future then resolves to the best move found so far
+    pub fn cancel(&self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// None when the search thread panicked
+impl Future for SearchFuture {
+    type Output = Option<SearchResult>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<SearchResult>> {
+        let mut state = self.shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.done {
+            Poll::Ready(state.result.take())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for SearchFuture {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
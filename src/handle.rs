@@ -0,0 +1,417 @@
+// An engine running in its own thread, controlled by commands and reporting events.
+// The GUI keeps its own (cheap) clone of the game for the rules, so it never has to wait
+// for a mutex held by the search. Needs std for threads and channels.
+//
+// EngineHandle::external() runs an external UCI engine instead, see uci.rs, and
+// AnalysisHandle::external() analyzes with one.
+//
+// In the browser (wasm32) there are no threads, so the commands are queued and executed
+// by the next try_event() or wait_event(). A search then blocks the caller for up to
+// its move time, but the interface of the handles is the same.
+
+use crate::analysis::{self, AnalysisObserver, Backend, Report};
+use crate::book::Book;
+use crate::engine::{self, Game, SearchInfo, SearchObserver, SearchResult};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::uci;
+#[cfg(target_arch = "wasm32")]
+use std::cell::RefCell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+// a position is sent once per search, so the size of a Game in the enum does not matter
+#[allow(clippy::large_enum_variant)]
+pub enum Command {
+    SetPosition(Game), // copied with engine::set_position(), a clone has no table
+    SetOption(String, String),
+    SetBook(Option<Arc<Book>>), // see engine::set_book()
+    Go,
+    Stop,
+}
+
+#[derive(Debug)]
+pub enum Event {
+    Info(SearchInfo),
+    // the nodes searched since Go, at most each PROGRESS_INTERVAL
+    Progress { nodes: u64, time: Duration },
+    // the reply for the position with the given engine::position_key()
+    BestMove { key: u64, result: SearchResult },
+    OptionError(engine::OptionError),
+    // the external engine failed, it does not answer any more
+    Error(String),
+}
+
+pub struct EngineHandle {
+    // each command is sent with the number of stop requests so far
+    commands: Option<mpsc::Sender<(Command, u64)>>,
+    events: mpsc::Receiver<Event>,
+    stops: Arc<AtomicU64>,
+    #[cfg(not(target_arch = "wasm32"))]
+    thread: Option<thread::JoinHandle<()>>,
+    #[cfg(target_arch = "wasm32")]
+    worker: RefCell<Worker>,
+}
+
+// the part of the engine thread which runs in the caller on wasm32
+#[cfg(target_arch = "wasm32")]
+struct Worker {
+    g: Game,
+    commands: mpsc::Receiver<(Command, u64)>,
+    events: mpsc::Sender<Event>,
+}
+
+// reports the search progress, and stops the search when Stop was sent after Go
+struct Reporter<'a> {
+    events: &'a mpsc::Sender<Event>,
+    stops: &'a AtomicU64,
+    stops_at_go: u64,
+    start: Instant,
+    progress: Instant, // of the last Progress event
+}
+
+impl SearchObserver for Reporter<'_> {
+    fn on_depth_complete(&mut self, info: &SearchInfo) {
+        let _ = self.events.send(Event::Info(info.clone()));
+    }
+    fn on_node_batch(&mut self, nodes: u64) {
+        if self.progress.elapsed() >= PROGRESS_INTERVAL {
+            self.progress = Instant::now();
+            let time = self.start.elapsed();
+            let _ = self.events.send(Event::Progress { nodes, time });
+        }
+    }
+    fn should_stop(&mut self) -> bool {
+        self.stops.load(Ordering::Relaxed) != self.stops_at_go
+    }
+}
+
+// the command loop of the engine thread
+fn execute(
+    g: &mut Game,
+    c: Command,
+    stops_at_go: u64,
+    events: &mpsc::Sender<Event>,
+    stops: &AtomicU64,
+) {
+    match c {
+        Command::SetPosition(from) => engine::set_position(g, &from),
+        Command::SetOption(name, value) => {
+            if let Err(e) = engine::set_option(g, &name, &value) {
+                let _ = events.send(Event::OptionError(e));
+            }
+        }
+        Command::SetBook(book) => engine::set_book(g, book),
+        Command::Go => {
+            let mut reporter = Reporter {
+                events,
+                stops,
+                stops_at_go,
+                start: Instant::now(),
+                progress: Instant::now(),
+            };
+            let result = engine::reply(g, &mut reporter);
+            let key = engine::position_key(g);
+            let _ = events.send(Event::BestMove { key, result });
+        }
+        Command::Stop => {} // handled by send(), the search polls the count
+    }
+}
+
+impl EngineHandle {
+    // start the engine thread, which owns g with its large transposition table
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(mut g: Game) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let stops = Arc::new(AtomicU64::new(0));
+        let stop_count = stops.clone();
+        let thread = thread::spawn(move || {
+            for (c, stops_at_go) in command_rx {
+                execute(&mut g, c, stops_at_go, &event_tx, &stop_count);
+            }
+        });
+        EngineHandle {
+            commands: Some(commands),
+            events,
+            stops,
+            thread: Some(thread),
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(g: Game) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        EngineHandle {
+            commands: Some(commands),
+            events,
+            stops: Arc::new(AtomicU64::new(0)),
+            worker: RefCell::new(Worker {
+                g,
+                commands: command_rx,
+                events: event_tx,
+            }),
+        }
+    }
+
+    // Start the UCI engine of the executable at path, which answers the commands like the
+    // own engine. A failing handshake arrives as Event::Error.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn external(path: &str) -> io::Result<Self> {
+        let mut external = uci::Engine::spawn(path)?;
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+        let stops = Arc::new(AtomicU64::new(0));
+        let stop_count = stops.clone();
+        let thread = thread::spawn(move || {
+            let result = external
+                .handshake()
+                .and_then(|()| run_external(&mut external, command_rx, &event_tx, &stop_count));
+            if let Err(e) = result {
+                let _ = event_tx.send(Event::Error(e));
+            }
+        });
+        Ok(EngineHandle {
+            commands: Some(commands),
+            events,
+            stops,
+            thread: Some(thread),
+        })
+    }
+
+    // executes the queued commands, a Stop sent after a queued Go still stops its search
+    #[cfg(target_arch = "wasm32")]
+    fn run(&self) {
+        let mut worker = self.worker.borrow_mut();
+        let Worker {
+            g,
+            commands,
+            events,
+        } = &mut *worker;
+        while let Ok((c, stops_at_go)) = commands.try_recv() {
+            execute(g, c, stops_at_go, events, &self.stops);
+        }
+    }
+
+    // Stop takes effect immediately, all other commands are processed in order
+    // when the engine is idle.
+    pub fn send(&self, c: Command) {
+        if let Command::Stop = c {
+            self.stops.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(commands) = &self.commands {
+            let _ = commands.send((c, self.stops.load(Ordering::Relaxed)));
+        }
+    }
+
+    pub fn try_event(&self) -> Option<Event> {
+        #[cfg(target_arch = "wasm32")]
+        self.run();
+        self.events.try_recv().ok()
+    }
+
+    // blocks until the next event
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait_event(&self) -> Option<Event> {
+        self.events.recv().ok()
+    }
+
+    // without a thread no event can arrive after the queued commands are executed
+    #[cfg(target_arch = "wasm32")]
+    pub fn wait_event(&self) -> Option<Event> {
+        self.try_event()
+    }
+}
+
+// the command loop of an external engine, which quits when the handle is dropped
+#[cfg(not(target_arch = "wasm32"))]
+fn run_external(
+    external: &mut uci::Engine,
+    commands: mpsc::Receiver<(Command, u64)>,
+    events: &mpsc::Sender<Event>,
+    stops: &AtomicU64,
+) -> Result<(), String> {
+    // a copy of the game for the rules and the moves of the pv
+    let mut g = engine::new_game_with_hash(1);
+    for (c, stops_at_go) in commands {
+        match c {
+            Command::SetPosition(from) => engine::set_position(&mut g, &from),
+            // the time per move is sent with each go command
+            Command::SetOption(name, value) if name.eq_ignore_ascii_case("MoveTime") => {
+                if let Err(e) = engine::set_option(&mut g, &name, &value) {
+                    let _ = events.send(Event::OptionError(e));
+                }
+            }
+            Command::SetOption(name, value) => match external.option(&name) {
+                Some(name) => {
+                    let command = format!("setoption name {} value {}", name, value);
+                    external.send(&command)?;
+                }
+                None => {
+                    let e = engine::OptionError::UnknownOption;
+                    let _ = events.send(Event::OptionError(e));
+                }
+            },
+            Command::SetBook(_) => {} // an external engine has its own book option
+            Command::Go => {
+                let go = uci::go_command(&g);
+                let mut score = 0;
+                let m = external.go(
+                    &mut g,
+                    &go,
+                    &mut |info| {
+                        score = info.score;
+                        let _ = events.send(Event::Info(info));
+                    },
+                    &mut || stops.load(Ordering::Relaxed) != stops_at_go,
+                )?;
+                let result = SearchResult::new(&m, score);
+                let key = engine::position_key(&g);
+                let _ = events.send(Event::BestMove { key, result });
+            }
+            Command::Stop => {} // handled by send(), the search polls the count
+        }
+    }
+    Ok(())
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        self.stops.fetch_add(1, Ordering::Relaxed);
+        self.commands = None; // ends the command loop of the thread
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub enum AnalysisEvent {
+    Progress { ply: usize, plies: usize }, // the search of the position after ply moves
+    Done(Report),
+    // the external engine failed, the analysis ends
+    Error(String),
+}
+
+// A post-game analysis in its own thread, see analysis::analyze_game().
+// Dropping the handle cancels the analysis.
+pub struct AnalysisHandle {
+    events: mpsc::Receiver<AnalysisEvent>,
+    cancel: Arc<AtomicBool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    thread: Option<thread::JoinHandle<()>>,
+    // on wasm32 the game is analyzed by the first try_event()
+    #[cfg(target_arch = "wasm32")]
+    pending: RefCell<Option<(Game, i64, mpsc::Sender<AnalysisEvent>)>>,
+}
+
+struct Progress<'a> {
+    events: &'a mpsc::Sender<AnalysisEvent>,
+    cancel: &'a AtomicBool,
+}
+
+impl AnalysisObserver for Progress<'_> {
+    fn on_position(&mut self, ply: usize, plies: usize) {
+        let _ = self.events.send(AnalysisEvent::Progress { ply, plies });
+    }
+    fn should_stop(&mut self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+fn analyze(
+    g: &Game,
+    depth: i64,
+    backend: &mut dyn Backend,
+    events: &mpsc::Sender<AnalysisEvent>,
+    cancel: &AtomicBool,
+) {
+    let mut progress = Progress { events, cancel };
+    if let Some(report) = analysis::analyze_game_with(g, depth, backend, &mut progress) {
+        let _ = events.send(AnalysisEvent::Done(report));
+    }
+}
+
+impl AnalysisHandle {
+    // analyze the moves leading to the current position of g
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(g: &Game, depth: i64) -> Self {
+        let g = g.clone();
+        let (event_tx, events) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceled = cancel.clone();
+        let thread = thread::spawn(move || {
+            analyze(&g, depth, &mut analysis::BuiltIn, &event_tx, &canceled);
+        });
+        AnalysisHandle {
+            events,
+            cancel,
+            thread: Some(thread),
+        }
+    }
+
+    // Analyze with the UCI engine of the executable at path, which searches each position
+    // to the given depth. A failure of the engine arrives as AnalysisEvent::Error.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn external(g: &Game, depth: i64, path: &str) -> io::Result<Self> {
+        let mut external = uci::Engine::spawn(path)?;
+        let g = g.clone();
+        let (event_tx, events) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceled = cancel.clone();
+        let thread = thread::spawn(move || {
+            if let Err(e) = external.handshake() {
+                let _ = event_tx.send(AnalysisEvent::Error(e));
+                return;
+            }
+            analyze(&g, depth, &mut external, &event_tx, &canceled);
+            if let Some(e) = external.error() {
+                let _ = event_tx.send(AnalysisEvent::Error(e.to_owned()));
+            }
+        });
+        Ok(AnalysisHandle {
+            events,
+            cancel,
+            thread: Some(thread),
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(g: &Game, depth: i64) -> Self {
+        let (event_tx, events) = mpsc::channel();
+        AnalysisHandle {
+            events,
+            cancel: Arc::new(AtomicBool::new(false)),
+            pending: RefCell::new(Some((g.clone(), depth, event_tx))),
+        }
+    }
+
+    pub fn try_event(&self) -> Option<AnalysisEvent> {
+        #[cfg(target_arch = "wasm32")]
+        if let Some((g, depth, event_tx)) = self.pending.borrow_mut().take() {
+            analyze(&g, depth, &mut analysis::BuiltIn, &event_tx, &self.cancel);
+        }
+        self.events.try_recv().ok()
+    }
+}
+
+impl Drop for AnalysisHandle {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
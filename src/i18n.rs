@@ -0,0 +1,50 @@
+// Translations of the texts of the GUI. The English texts are the keys, the table of a
+// language has a line for each translated text: the English text and the translation,
+// separated by a tab. Texts without a translation are shown in English.
+
+use crate::engine::SAN_FIG_STR;
+
+const GERMAN: &str = include_str!("de.tsv");
+
+// the piece letters of SAN, "S" for Springer, "L" for Läufer, "T" for Turm and "D" for Dame
+const GERMAN_LETTERS: [&str; 7] = ["", "", "S", "L", "T", "D", "K"];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+// the languages with their names in the language
+pub const LANGUAGES: [(&str, Language); 2] = [
+    ("English", Language::English),
+    ("Deutsch", Language::German),
+];
+
+fn table(lang: Language) -> &'static str {
+    match lang {
+        Language::English => "",
+        Language::German => GERMAN,
+    }
+}
+
+// the pairs of English texts and translations of the table of lang
+pub fn translations(lang: Language) -> impl Iterator<Item = (&'static str, &'static str)> {
+    table(lang).lines().filter_map(|line| line.split_once('\t'))
+}
+
+// text in the language lang, or text itself without a translation
+pub fn tr(lang: Language, text: &str) -> &str {
+    translations(lang)
+        .find(|&(english, _)| english == text)
+        .map_or(text, |(_, translated)| translated)
+}
+
+// the piece letters of SAN in the language lang, for engine::Move::localized()
+pub fn piece_letters(lang: Language) -> &'static [&'static str; 7] {
+    match lang {
+        Language::English => &SAN_FIG_STR,
+        Language::German => &GERMAN_LETTERS,
+    }
+}
@@ -0,0 +1,234 @@
+// Games between two instances of the app in a local network, one hosts and the other
+// joins by the IP address of the host. The messages are lines of text over TCP:
+//
+//   tiny-chess 1                the greeting of both sides, with the protocol version
+//   start white 300 2           a new game, the receiver plays White, with 300 s and 2 s
+//                               increment; "start black none" plays without clocks
+//   move e2e4 299500 300000     a move with the milliseconds left of White and Black
+//   draw offer, draw accept, draw decline, resign
+//
+// Both instances run the clocks, each move carries the clocks of the mover. The stream
+// is read in its own thread, the events are polled with Connection::try_event() like the
+// events of the engine in handle.rs. Needs std.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7878;
+const GREETING: &str = "tiny-chess 1";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100); // to notice a cancel
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Start {
+        white: bool,               // the receiver plays White
+        clock: Option<(u64, u64)>, // base time and increment in seconds, see TIME_CONTROLS
+    },
+    Move {
+        uci: String,
+        clocks: [Duration; 2], // time left of White and Black after the move
+    },
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+    Resign,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Start { white, clock } => {
+                let color = if *white { "white" } else { "black" };
+                match clock {
+                    Some((base, inc)) => write!(f, "start {} {} {}", color, base, inc),
+                    None => write!(f, "start {} none", color),
+                }
+            }
+            Message::Move { uci, clocks } => write!(
+                f,
+                "move {} {} {}",
+                uci,
+                clocks[0].as_millis(),
+                clocks[1].as_millis()
+            ),
+            Message::OfferDraw => f.write_str("draw offer"),
+            Message::AcceptDraw => f.write_str("draw accept"),
+            Message::DeclineDraw => f.write_str("draw decline"),
+            Message::Resign => f.write_str("resign"),
+        }
+    }
+}
+
+// a line of the protocol, None for an unknown one
+pub fn parse_message(line: &str) -> Option<Message> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let millis = |w: &str| w.parse().ok().map(Duration::from_millis);
+    Some(match words[..] {
+        ["start", color, "none"] | ["start", color, _, _] => Message::Start {
+            white: match color {
+                "white" => true,
+                "black" => false,
+                _ => return None,
+            },
+            clock: match words[2..] {
+                [base, inc] => Some((base.parse().ok()?, inc.parse().ok()?)),
+                _ => None,
+            },
+        },
+        ["move", uci, white, black] => Message::Move {
+            uci: uci.to_owned(),
+            clocks: [millis(white)?, millis(black)?],
+        },
+        ["draw", "offer"] => Message::OfferDraw,
+        ["draw", "accept"] => Message::AcceptDraw,
+        ["draw", "decline"] => Message::DeclineDraw,
+        ["resign"] => Message::Resign,
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Connected(String), // the address of the opponent
+    Message(Message),
+    Error(String),          // of a send, the connection may still work
+    Closed(Option<String>), // with the error that ended the connection, if any
+}
+
+pub struct Connection {
+    stream: Arc<Mutex<Option<TcpStream>>>, // None until connected
+    events: mpsc::Receiver<Event>,
+    sender: mpsc::Sender<Event>,
+    closed: Arc<AtomicBool>, // ends the wait of the host
+    port: Option<u16>,       // of the host
+}
+
+// greet the opponent and pass its messages as events, until the stream ends
+fn read_stream(stream: TcpStream, shared: &Mutex<Option<TcpStream>>, events: &mpsc::Sender<Event>) {
+    let result = (|| {
+        let peer = stream.peer_addr()?.to_string();
+        let mut writer = stream.try_clone()?;
+        writeln!(writer, "{}", GREETING)?;
+        *shared.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+        let mut lines = BufReader::new(stream).lines();
+        match lines.next().transpose()? {
+            Some(line) if line.trim() == GREETING => {}
+            _ => {
+                return Err(io::Error::other(
+                    "the opponent is no tiny-chess of this version",
+                ))
+            }
+        }
+        let _ = events.send(Event::Connected(peer));
+        for line in lines {
+            let line = line?;
+            match parse_message(&line) {
+                Some(m) => events.send(Event::Message(m)).map_err(io::Error::other)?,
+                None => return Err(io::Error::other(format!("unknown message {}", line))),
+            }
+        }
+        Ok(())
+    })();
+    let _ = events.send(Event::Closed(result.err().map(|e| e.to_string())));
+}
+
+impl Connection {
+    fn new() -> Self {
+        let (sender, events) = mpsc::channel();
+        Connection {
+            stream: Arc::new(Mutex::new(None)),
+            events,
+            sender,
+            closed: Arc::new(AtomicBool::new(false)),
+            port: None,
+        }
+    }
+
+    // wait for the opponent on the port of all interfaces
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let mut connection = Connection::new();
+        connection.port = Some(listener.local_addr()?.port());
+        let (shared, events) = (Arc::clone(&connection.stream), connection.sender.clone());
+        let closed = Arc::clone(&connection.closed);
+        thread::spawn(move || {
+            while !closed.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        return match stream.set_nonblocking(false) {
+                            Ok(()) => read_stream(stream, &shared, &events),
+                            Err(e) => {
+                                let _ = events.send(Event::Closed(Some(e.to_string())));
+                            }
+                        };
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL)
+                    }
+                    Err(e) => {
+                        let _ = events.send(Event::Closed(Some(e.to_string())));
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(connection)
+    }
+
+    // connect to the host at an address like 192.168.1.20, with DEFAULT_PORT if it has none
+    pub fn join(address: &str) -> Self {
+        let connection = Connection::new();
+        let (shared, events) = (Arc::clone(&connection.stream), connection.sender.clone());
+        let address = address.trim().to_owned();
+        thread::spawn(move || {
+            let stream = (address.as_str(), DEFAULT_PORT)
+                .to_socket_addrs()
+                .or_else(|_| address.to_socket_addrs())
+                .and_then(|mut addrs| addrs.next().ok_or(io::ErrorKind::NotFound.into()))
+                .and_then(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT));
+            match stream {
+                Ok(stream) => read_stream(stream, &shared, &events),
+                Err(e) => {
+                    let _ = events.send(Event::Closed(Some(format!("{}: {}", address, e))));
+                }
+            }
+        });
+        connection
+    }
+
+    pub fn send(&self, m: &Message) {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(s) = stream.as_mut() {
+            if let Err(e) = writeln!(s, "{}", m) {
+                let _ = self.sender.send(Event::Error(e.to_string()));
+            }
+        }
+    }
+
+    // the port the host listens on, 0 chooses a free one
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+// the reading thread ends with the stream, and the host stops waiting
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        let stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(s) = stream.as_ref() {
+            let _ = s.shutdown(Shutdown::Both);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// The Salewski Chess Engine as a library, used by the egui frontend in main.rs
+// (C) 2015 - 2032 Dr. Stefan Salewski
+// All rights reserved.
+//
+// Without the default "std" feature the engine compiles with #![no_std] and alloc,
+// e.g. for embedding on a microcontroller:
+// cargo build --lib --no-default-features
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod analysis;
+pub mod book;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod broadcast;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod cli;
+pub mod correspondence;
+pub mod database;
+pub mod diagram;
+#[cfg(feature = "std")]
+pub mod eboard;
+pub mod eco;
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod epd;
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub mod future;
+#[cfg(feature = "std")]
+pub mod handle;
+pub mod i18n;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod lan;
+#[cfg(feature = "lichess")]
+pub mod lichess;
+pub mod persona;
+#[cfg(feature = "std")]
+pub mod puzzle;
+pub mod repertoire;
+#[cfg(all(feature = "server", not(target_arch = "wasm32")))]
+pub mod server;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod tournament;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub mod uci;
+pub mod variant;
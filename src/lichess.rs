@@ -0,0 +1,407 @@
+// Online games on lichess.org with the Board API, authenticated by a personal API token
+// with the scope board:play, see https://lichess.org/account/oauth/token. And the move
+// statistics of the opening explorer, see explore(), and the download of games, see
+// pgn_url().
+//
+// The event stream of the account and the streams of its games are read in their own
+// threads, the requests are sent by short-lived threads as a seek blocks until it is
+// accepted. Everything arrives as an Event, polled with Client::try_event() like the
+// events of the engine in handle.rs. Needs the "lichess" feature.
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+pub const API: &str = "https://lichess.org/api";
+pub const EXPLORER: &str = "https://explorer.lichess.ovh";
+pub const CHESS_COM_API: &str = "https://api.chess.com/pub";
+
+// the names and paths of the databases of the opening explorer
+pub const DATABASES: [(&str, &str); 2] = [("Masters", "masters"), ("Lichess", "lichess")];
+
+// the position of a game, sent with each move and when the game ends
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameState {
+    pub moves: Vec<String>,    // all moves of the game in UCI notation
+    pub clocks: [Duration; 2], // time left of White and Black
+    pub status: String,        // "started" while the game runs, then e.g. "mate" or "resign"
+    pub winner: Option<bool>,  // true for White, None for a draw or an aborted game
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // the stream of the account
+    GameStart {
+        id: String,
+        white: bool, // the user plays White
+    },
+    Challenge {
+        id: String,
+        from: String,
+        speed: String,
+    },
+    // a challenge was canceled or declined
+    ChallengeGone {
+        id: String,
+    },
+    // the stream of a game, the first event is GameFull
+    GameFull {
+        id: String,
+        fen: Option<String>, // None for the start position
+        white: String,       // names of the players
+        black: String,
+        state: GameState,
+    },
+    // the id is set by the stream of the game
+    GameState {
+        id: String,
+        state: GameState,
+    },
+    // of a failed request or a broken stream
+    Error(String),
+}
+
+pub enum Request {
+    Seek { minutes: u32, increment: u32 },
+    CancelSeek,
+    Accept(String),  // the id of a challenge
+    Decline(String), // the id of a challenge
+    Move { game: String, uci: String },
+    Resign(String), // the id of a game
+    Abort(String),  // the id of a game, before both players have moved
+}
+
+fn string(v: &Value) -> String {
+    v.as_str().unwrap_or_default().to_owned()
+}
+
+fn game_state(v: &Value) -> GameState {
+    let millis = |key: &str| Duration::from_millis(v[key].as_u64().unwrap_or(0));
+    GameState {
+        moves: v["moves"]
+            .as_str()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect(),
+        clocks: [millis("wtime"), millis("btime")],
+        status: string(&v["status"]),
+        winner: v["winner"].as_str().map(|w| w == "white"),
+    }
+}
+
+// one line of the event stream of the account or of a game stream; empty lines keep the
+// connection alive, and events which the app does not use are ignored as well
+pub fn parse_event(line: &str) -> Option<Event> {
+    let v: Value = serde_json::from_str(line.trim()).ok()?;
+    let event = match v["type"].as_str()? {
+        "gameStart" => Event::GameStart {
+            id: string(&v["game"]["gameId"]),
+            white: v["game"]["color"] == "white",
+        },
+        "challenge" => Event::Challenge {
+            id: string(&v["challenge"]["id"]),
+            from: string(&v["challenge"]["challenger"]["name"]),
+            speed: string(&v["challenge"]["speed"]),
+        },
+        "challengeCanceled" | "challengeDeclined" => Event::ChallengeGone {
+            id: string(&v["challenge"]["id"]),
+        },
+        "gameFull" => {
+            let fen = v["initialFen"].as_str().filter(|&f| f != "startpos");
+            Event::GameFull {
+                id: string(&v["id"]),
+                fen: fen.map(str::to_owned),
+                white: string(&v["white"]["name"]),
+                black: string(&v["black"]["name"]),
+                state: game_state(&v["state"]),
+            }
+        }
+        "gameState" => Event::GameState {
+            id: String::new(),
+            state: game_state(&v),
+        },
+        _ => return None,
+    };
+    Some(event)
+}
+
+// the message of a failed request, lichess explains most errors in the body
+fn error_text(e: ureq::Error) -> String {
+    match e {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            let v: Value = serde_json::from_str(&body).unwrap_or_default();
+            match v["error"].as_str() {
+                Some(error) => format!("Lichess error {}: {}", code, error),
+                None => format!("Lichess error {}", code),
+            }
+        }
+        ureq::Error::Transport(t) => format!("Lichess is not reachable: {}", t),
+    }
+}
+
+pub struct Client {
+    token: Arc<str>,
+    events: mpsc::Receiver<Event>,
+    event_tx: mpsc::Sender<Event>,
+    closed: Arc<AtomicBool>, // ends the streams at their next line
+    seeks: Arc<AtomicU64>,   // odd while a seek is open, counts Seek and its end
+}
+
+// reads the lines of a stream until it ends or the client is dropped, game is the id of
+// a game stream, or None for the stream of the account, which starts the game streams
+fn read_stream(
+    token: Arc<str>,
+    game: Option<String>,
+    event_tx: mpsc::Sender<Event>,
+    closed: Arc<AtomicBool>,
+) {
+    let url = match &game {
+        Some(id) => format!("{}/board/game/stream/{}", API, id),
+        None => format!("{}/stream/event", API),
+    };
+    let response = ureq::get(&url)
+        .set("Authorization", &format!("Bearer {}", token))
+        .call();
+    let reader = match response {
+        Ok(response) => BufReader::new(response.into_reader()),
+        Err(e) => {
+            let _ = event_tx.send(Event::Error(error_text(e)));
+            return;
+        }
+    };
+    for line in reader.lines() {
+        if closed.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(line) = line else {
+            let _ = event_tx.send(Event::Error("Lichess closed the stream".to_owned()));
+            return;
+        };
+        let Some(mut event) = parse_event(&line) else {
+            continue;
+        };
+        if let (Event::GameState { id, .. }, Some(game)) = (&mut event, &game) {
+            id.clone_from(game);
+        }
+        // the game stream starts after GameStart is sent, so GameFull arrives later
+        let start = match &event {
+            Event::GameStart { id, .. } => Some(id.clone()),
+            _ => None,
+        };
+        if event_tx.send(event).is_err() {
+            return;
+        }
+        if let Some(id) = start {
+            let (token, event_tx, closed) = (token.clone(), event_tx.clone(), closed.clone());
+            thread::spawn(move || read_stream(token, Some(id), event_tx, closed));
+        }
+    }
+}
+
+impl Client {
+    // start the event stream of the account of the token
+    pub fn connect(token: &str) -> Self {
+        let token: Arc<str> = token.trim().into();
+        let (event_tx, events) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let (stream_token, stream_tx, stream_closed) =
+            (token.clone(), event_tx.clone(), closed.clone());
+        thread::spawn(move || read_stream(stream_token, None, stream_tx, stream_closed));
+        Client {
+            token,
+            events,
+            event_tx,
+            closed,
+            seeks: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // requests are sent in the background, a failure arrives as Event::Error
+    pub fn send(&self, r: Request) {
+        let path = match r {
+            Request::Seek { minutes, increment } => {
+                self.seek(minutes, increment);
+                return;
+            }
+            Request::CancelSeek => {
+                let seek = self.seeks.load(Ordering::Relaxed);
+                if seek % 2 == 1 {
+                    let _ = self.seeks.compare_exchange(
+                        seek,
+                        seek + 1,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    );
+                }
+                return;
+            }
+            Request::Accept(id) => format!("challenge/{}/accept", id),
+            Request::Decline(id) => format!("challenge/{}/decline", id),
+            Request::Move { game, uci } => format!("board/game/{}/move/{}", game, uci),
+            Request::Resign(game) => format!("board/game/{}/resign", game),
+            Request::Abort(game) => format!("board/game/{}/abort", game),
+        };
+        let token = self.token.clone();
+        let event_tx = self.event_tx.clone();
+        thread::spawn(move || {
+            let response = ureq::post(&format!("{}/{}", API, path))
+                .set("Authorization", &format!("Bearer {}", token))
+                .call();
+            if let Err(e) = response {
+                let _ = event_tx.send(Event::Error(error_text(e)));
+            }
+        });
+    }
+
+    // the seek is open while its connection is, so the response is read until the game
+    // starts or the seek is canceled; lichess sends a newline every few seconds.
+    // A second seek is ignored while one is open.
+    fn seek(&self, minutes: u32, increment: u32) {
+        if self.seeking() {
+            return;
+        }
+        let seek = self.seeks.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = self.token.clone();
+        let event_tx = self.event_tx.clone();
+        let (seeks, closed) = (self.seeks.clone(), self.closed.clone());
+        thread::spawn(move || {
+            let (minutes, increment) = (minutes.to_string(), increment.to_string());
+            let response = ureq::post(&format!("{}/board/seek", API))
+                .set("Authorization", &format!("Bearer {}", token))
+                .send_form(&[
+                    ("time", minutes.as_str()),
+                    ("increment", increment.as_str()),
+                ]);
+            match response {
+                Ok(response) => {
+                    let mut reader = response.into_reader();
+                    let mut buf = [0; 64];
+                    while seeks.load(Ordering::Relaxed) == seek && !closed.load(Ordering::Relaxed) {
+                        if !matches!(reader.read(&mut buf), Ok(n) if n > 0) {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = event_tx.send(Event::Error(error_text(e)));
+                }
+            }
+            let _ = seeks.compare_exchange(seek, seek + 1, Ordering::Relaxed, Ordering::Relaxed);
+        });
+    }
+
+    pub fn seeking(&self) -> bool {
+        self.seeks.load(Ordering::Relaxed) % 2 == 1
+    }
+
+    pub fn try_event(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+}
+
+// the streams end at their next line, open connections are not waited for
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// the statistics of a position in the opening explorer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Explorer {
+    pub games: [u64; 3],                // won by White, drawn and won by Black
+    pub moves: Vec<(String, [u64; 3])>, // in UCI notation, the most played first
+}
+
+fn results(v: &Value) -> [u64; 3] {
+    ["white", "draws", "black"].map(|key| v[key].as_u64().unwrap_or(0))
+}
+
+pub fn parse_explorer(json: &str) -> Option<Explorer> {
+    let v: Value = serde_json::from_str(json).ok()?;
+    let moves = v["moves"].as_array()?;
+    Some(Explorer {
+        games: results(&v),
+        moves: moves
+            .iter()
+            .map(|m| (string(&m["uci"]), results(m)))
+            .collect(),
+    })
+}
+
+// ask the database of DATABASES about the position in a thread, the result arrives
+// with the receiver; the token is optional
+pub fn explore(
+    database: usize,
+    fen: &str,
+    token: &str,
+) -> mpsc::Receiver<Result<Explorer, String>> {
+    let (tx, rx) = mpsc::channel();
+    let url = format!("{}/{}", EXPLORER, DATABASES[database].1);
+    let (fen, token) = (fen.to_owned(), token.trim().to_owned());
+    thread::spawn(move || {
+        let mut request = ureq::get(&url).query("fen", &fen);
+        if !token.is_empty() {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let result = match request.call() {
+            Ok(response) => response
+                .into_string()
+                .ok()
+                .and_then(|json| parse_explorer(&json))
+                .ok_or_else(|| "Invalid answer of the opening explorer".to_owned()),
+            Err(e) => Err(error_text(e)),
+        };
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+// The PGN download of a game URL: a lichess game like https://lichess.org/q7ZvsdUF, also
+// with the id of a player, the color or the move, i.e. https://lichess.org/q7ZvsdUFe2x7#32,
+// or the monthly archive of a chess.com player from the published data API, i.e.
+// https://api.chess.com/pub/player/hikaru/games/2024/05, or any URL of a .pgn file.
+pub fn pgn_url(url: &str) -> Option<String> {
+    let url = url.trim();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let path = path.split(['#', '?']).next().unwrap_or_default();
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    match (host, &parts[..]) {
+        ("lichess.org" | "www.lichess.org", [id, ..])
+            if (id.len() == 8 || id.len() == 12)
+                && id.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            Some(format!("https://lichess.org/game/export/{}", &id[..8]))
+        }
+        ("api.chess.com", ["pub", "player", player, "games", year, month, ..]) => Some(format!(
+            "{}/player/{}/games/{}/{}/pgn",
+            CHESS_COM_API, player, year, month
+        )),
+        _ if path.ends_with(".pgn") => Some(url.to_owned()),
+        _ => None,
+    }
+}
+
+// download the games of pgn_url() in a thread, the PGN arrives with the receiver
+pub fn fetch_pgn(url: &str) -> mpsc::Receiver<Result<String, String>> {
+    let (tx, rx) = mpsc::channel();
+    let url = url.to_owned();
+    thread::spawn(move || {
+        let result = match ureq::get(&url).call() {
+            Ok(response) => response.into_string().map_err(|e| e.to_string()),
+            Err(ureq::Error::Status(code, _)) => Err(format!("Download error {}: {}", code, url)),
+            Err(ureq::Error::Transport(t)) => Err(format!("{} is not reachable: {}", url, t)),
+        };
+        let _ = tx.send(result);
+    });
+    rx
+}
@@ -3,17 +3,39 @@
 // (C) 2015 - 2032 Dr. Stefan Salewski
 // All rights reserved.
 
-// this is a version with threading, using spawn and channels
+// the engine searches in its own thread, see tiny_chess::handle
+// In the browser the app runs in a canvas of index.html, built with trunk, see README.
 
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 use eframe::egui;
-use std::sync::{mpsc, Arc, Mutex};
-use std::thread;
-//use std::time::Duration;
-
-mod engine;
+#[cfg(not(target_arch = "wasm32"))]
+use rodio::Source;
+use std::collections::{HashMap, VecDeque};
+#[cfg(any(feature = "lichess", not(target_arch = "wasm32")))]
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tiny_chess::analysis::{self, Judgment};
+use tiny_chess::book::Book;
+use tiny_chess::database::{Database, OpeningTree};
+#[cfg(not(target_arch = "wasm32"))]
+use tiny_chess::diagram;
+#[cfg(feature = "eboard")]
+use tiny_chess::eboard::{self, BoardSync};
+use tiny_chess::handle::{AnalysisEvent, AnalysisHandle, Command, EngineHandle, Event};
+use tiny_chess::i18n::{self, tr};
+#[cfg(feature = "lichess")]
+use tiny_chess::lichess::{self, Request};
+#[cfg(not(target_arch = "wasm32"))]
+use tiny_chess::{broadcast, lan};
+use tiny_chess::{correspondence, eco, engine, puzzle, repertoire, variant};
+// std::time panics in the browser
+#[cfg(target_arch = "wasm32")]
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
 
 const ENGINE: u8 = 1;
 const HUMAN: u8 = 0;
@@ -22,152 +44,4057 @@ const FIGURES: [&str; 13] = [
     "♚", "♛", "♜", "♝", "♞", "♟", "", "♙", "♘", "♗", "♖", "♕", "♔",
 ];
 
+// the piece sets of the settings, the first one are the Unicode glyphs of FIGURES
+const PIECE_SETS: [&str; 2] = ["Unicode", "Outline"];
+
+// the time controls of the settings, base time and increment per move in seconds.
+// None plays without clocks, with the engine option MoveTime.
+const TIME_CONTROLS: [(&str, Option<(u64, u64)>); 6] = [
+    ("Move time", None),
+    ("1 + 0", Some((60, 0))),
+    ("3 + 2", Some((180, 2))),
+    ("5 + 0", Some((300, 0))),
+    ("10 + 5", Some((600, 5))),
+    ("15 + 10", Some((900, 10))),
+];
+
+// the hints for the destinations of the selected piece
+const HINTS: [&str; 3] = ["No move hints", "Move hints", "Beginner hints"];
+const HINTS_NONE: usize = 0;
+const HINTS_BEGINNER: usize = 2; // captures are marked in another color
+
+// the tagged squares are shaded, or marked with shapes: a dot for a move, a ring for a
+// capture, and a border for the last move and the selected piece
+const HIGHLIGHTS: [&str; 2] = ["Shaded highlights", "Marker highlights"];
+const HIGHLIGHTS_MARKERS: usize = 1;
+
+// the colors of the markers, from the Okabe-Ito palette for color vision deficiencies
+const MARKER_MOVE: egui::Color32 = egui::Color32::from_rgb(0, 114, 178); // blue
+const MARKER_LAST: egui::Color32 = egui::Color32::from_rgb(230, 159, 0); // orange
+const MARKER_SELECTED: egui::Color32 = egui::Color32::from_rgb(213, 94, 0); // vermillion
+
+const TOUCH_SCALE: f32 = 1.6; // of the widget sizes in touch mode
+
+const PRESENTATION_SCALE: f32 = 2.0; // of the text of the move list in the presentation layout
+
 const STATE_UZ: i32 = -2; // state when engine or human player have made their move, so it's other sides turn
 const STATE_UX: i32 = -1; // stable state, current game is terminated
 const STATE_U0: i32 = 0;
 const STATE_U1: i32 = 1;
 const STATE_U2: i32 = 2;
 const STATE_U3: i32 = 3;
+const STATE_U4: i32 = 4; // a promoting pawn move of the human player awaits the choice of the piece
+const STATE_U5: i32 = 5; // a move of two human players awaits the confirmation
+const STATE_U6: i32 = 6; // a move of the opponent in a puzzle or a drill is due
+#[cfg(any(feature = "lichess", not(target_arch = "wasm32")))]
+const STATE_U7: i32 = 7; // a move of the opponent on lichess or in a LAN game is awaited
+
+const BOOL_TO_ENGINE: [u8; 2] = [HUMAN, ENGINE];
+const BOOL_TO_STATE: [i32; 2] = [STATE_U0, STATE_U2];
+
+// adjudication of engine games: a score beyond ADJUDICATE_WIN for the last ADJUDICATE_PLIES
+// engine moves wins, a score within ADJUDICATE_DRAW draws after ADJUDICATE_DRAW_PLY
+const ADJUDICATE_WIN: i64 = 1000;
+const ADJUDICATE_DRAW: i64 = 10;
+const ADJUDICATE_PLIES: usize = 10;
+const ADJUDICATE_DRAW_PLY: usize = 80;
+
+// the search depth of "Analyze game", about 0.2 seconds for a position
+const ANALYSIS_DEPTH: i64 = 4;
+// with the analysis engine, a few seconds for a position with Stockfish
+const EXTERNAL_ANALYSIS_DEPTH: i64 = 18;
+// the time of the analysis engine for each position of the evaluation bar
+const EVALUATION_TIME: &str = "1000";
+const CRITICAL_MOMENTS: usize = 5;
+
+// the lichess puzzle database, see puzzle.rs, only the first MAX_PUZZLES are read
+const PUZZLE_FILE: &str = "lichess_db_puzzle.csv";
+const MAX_PUZZLES: usize = 20000;
+const PUZZLE_REPLY_DELAY: Duration = Duration::from_millis(600); // also of a drill
+
+const REPERTOIRE_FILE: &str = "repertoire.pgn";
+const DATABASE_FILE: &str = "games.pgn";
+const BROADCAST_DIR: &str = "broadcast"; // the live files for streams, see broadcast.rs
+
+// the games of "Save PGN" are the games of the opening tree
+const SAVED_PREFIX: &str = "tiny-chess-";
+const HUMAN_NAME: &str = "Human"; // the tag of the human player
+const TREE_PLIES: usize = 20;
+#[cfg(not(target_arch = "wasm32"))]
+const IMAGE_FILE: &str = "position.png"; // .svg for a vector image, .gif for a replay
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_DELAY: u64 = 1000; // ms per move of an animated GIF
+
+const MAX_HISTORY: usize = 100; // messages of the status bar
+#[cfg(feature = "lichess")]
+const MAX_EXPLORED: usize = 1000; // positions in the cache of the opening explorer
+
+fn _print_variable_type<K>(_: &K) {
+    println!("{}", std::any::type_name::<K>())
+}
+
+fn _rot_180(b: engine::Board) -> engine::Board {
+    let mut result: engine::Board = [0; 64];
+    for (i, f) in b.iter().enumerate() {
+        result[63 - i] = *f;
+    }
+    result
+}
+
+// the name of a square with its piece for screen readers, i.e. "g1 white knight"
+fn square_label(p: usize, f: engine::FigureID) -> String {
+    const NAMES: [&str; 7] = ["empty", "pawn", "knight", "bishop", "rook", "queen", "king"];
+    let square = engine::square_str(p as engine::Position);
+    match f.signum() {
+        1 => format!("{} white {}", square, NAMES[f as usize]),
+        -1 => format!("{} black {}", square, NAMES[-f as usize]),
+        _ => format!("{} empty", square),
+    }
+}
+
+// The view transform between board positions, which have h1 at 0, and the squares of
+// the screen, counted from the top left. It is its own inverse.
+fn view_transform(rotated: bool, p: usize) -> usize {
+    if rotated {
+        63 - p
+    } else {
+        p
+    }
+}
+
+// the pixels of a diagram, see diagram.rs, the options need the system fonts for the
+// coordinates
+#[cfg(not(target_arch = "wasm32"))]
+fn rasterize(
+    svg: &str,
+    options: &resvg::usvg::Options,
+) -> Result<resvg::tiny_skia::Pixmap, String> {
+    let tree = resvg::usvg::Tree::from_str(svg, options).map_err(|e| e.to_string())?;
+    let size = tree.size().to_int_size();
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| "Invalid image size".to_owned())?;
+    resvg::render(&tree, Default::default(), &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_png(path: &str, svg: &str) -> Result<(), String> {
+    let mut options = resvg::usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let png = rasterize(svg, &options)?
+        .encode_png()
+        .map_err(|e| e.to_string())?;
+    std::fs::write(path, png).map_err(|e| e.to_string())
+}
+
+// an animated GIF of the diagrams, all of the same size, which loops
+#[cfg(not(target_arch = "wasm32"))]
+fn write_gif(path: &str, diagrams: &[String], delay: Duration) -> Result<(), String> {
+    use std::io::Write;
+    let mut options = resvg::usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = None;
+    for svg in diagrams {
+        let mut pixmap = rasterize(svg, &options)?;
+        let (width, height) = (pixmap.width() as u16, pixmap.height() as u16);
+        let encoder = match &mut encoder {
+            Some(encoder) => encoder,
+            None => {
+                let writer = std::io::BufWriter::new(&file);
+                let mut e =
+                    gif::Encoder::new(writer, width, height, &[]).map_err(|e| e.to_string())?;
+                e.set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                encoder.insert(e)
+            }
+        };
+        // the pixels are opaque, premultiplied RGBA is RGBA then
+        let mut frame = gif::Frame::from_rgba_speed(width, height, pixmap.data_mut(), 10);
+        frame.delay = (delay.as_millis() / 10) as u16; // in 1/100 s
+        encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    if let Some(encoder) = encoder {
+        // the trailer, errors of the drop would be lost
+        let mut writer = encoder.into_inner().map_err(|e| e.to_string())?;
+        writer.flush().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// the game string of the "Share game" window with its QR code, computed again when the
+// position_key() or the current_node() of the game changes
+type ShareCode = ((u64, engine::NodeId), String, Option<qrcode::QrCode>);
+
+// dark modules on white, with the quiet zone of 4 modules around the code
+fn qr_code(ui: &mut egui::Ui, code: &qrcode::QrCode) {
+    let (width, module) = (code.width(), 4.0);
+    let side = (width + 8) as f32 * module;
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(side, side), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+    for (i, color) in code.to_colors().into_iter().enumerate() {
+        if color == qrcode::Color::Dark {
+            let (x, y) = ((i % width + 4) as f32, (i / width + 4) as f32);
+            let min = rect.min + egui::vec2(x, y) * module;
+            let square = egui::Rect::from_min_size(min, egui::vec2(module, module));
+            painter.rect_filled(square, 0.0, egui::Color32::BLACK);
+        }
+    }
+}
+
+// the bundled images of the "Outline" piece set
+fn piece_image(f: engine::FigureID) -> egui::ImageSource<'static> {
+    let (white, black) = match f.abs() {
+        engine::PAWN_ID => (
+            egui::include_image!("pieces/wp.svg"),
+            egui::include_image!("pieces/bp.svg"),
+        ),
+        engine::KNIGHT_ID => (
+            egui::include_image!("pieces/wn.svg"),
+            egui::include_image!("pieces/bn.svg"),
+        ),
+        engine::BISHOP_ID => (
+            egui::include_image!("pieces/wb.svg"),
+            egui::include_image!("pieces/bb.svg"),
+        ),
+        engine::ROOK_ID => (
+            egui::include_image!("pieces/wr.svg"),
+            egui::include_image!("pieces/br.svg"),
+        ),
+        engine::QUEEN_ID => (
+            egui::include_image!("pieces/wq.svg"),
+            egui::include_image!("pieces/bq.svg"),
+        ),
+        _ => (
+            egui::include_image!("pieces/wk.svg"),
+            egui::include_image!("pieces/bk.svg"),
+        ),
+    };
+    if f > 0 {
+        white
+    } else {
+        black
+    }
+}
+
+// draw the piece f into rect, with the glyphs or images of PIECE_SETS[piece_set]
+// the colors of the board, for the light and the dark theme of egui.
+// The squares, captures and premoves have a color for light and for dark squares.
+struct Palette {
+    squares: [egui::Color32; 2],
+    capture: [egui::Color32; 2],
+    premove: [egui::Color32; 2],
+    check: egui::Color32,
+    glyph: egui::Color32,
+}
+
+const LIGHT_PALETTE: Palette = Palette {
+    squares: [
+        egui::Color32::from_rgb(255, 255, 255),
+        egui::Color32::from_rgb(205, 205, 205),
+    ],
+    capture: [
+        egui::Color32::from_rgb(255, 215, 150),
+        egui::Color32::from_rgb(215, 170, 110),
+    ],
+    premove: [
+        egui::Color32::from_rgb(245, 190, 190),
+        egui::Color32::from_rgb(205, 150, 150),
+    ],
+    check: egui::Color32::from_rgb(235, 80, 70),
+    glyph: egui::Color32::BLACK,
+};
+
+// darker, but still with enough contrast for the black pieces
+const DARK_PALETTE: Palette = Palette {
+    squares: [
+        egui::Color32::from_rgb(175, 175, 165),
+        egui::Color32::from_rgb(125, 125, 118),
+    ],
+    capture: [
+        egui::Color32::from_rgb(210, 170, 105),
+        egui::Color32::from_rgb(170, 130, 75),
+    ],
+    premove: [
+        egui::Color32::from_rgb(200, 140, 140),
+        egui::Color32::from_rgb(160, 105, 105),
+    ],
+    check: egui::Color32::from_rgb(205, 60, 50),
+    glyph: egui::Color32::from_rgb(10, 10, 10),
+};
+
+fn palette(ui: &egui::Ui) -> &'static Palette {
+    if ui.visuals().dark_mode {
+        &DARK_PALETTE
+    } else {
+        &LIGHT_PALETTE
+    }
+}
+
+// the notations of the moves, in the move list and the messages
+const NOTATIONS: [(&str, engine::Notation); 3] = [
+    ("SAN", engine::Notation::San),
+    ("Long algebraic", engine::Notation::Long),
+    ("Figurine", engine::Notation::Figurine),
+];
+
+// the range of the zoom factor of the UI
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.0;
+
+// the theme settings
+const THEMES: [(&str, egui::ThemePreference); 3] = [
+    ("System theme", egui::ThemePreference::System),
+    ("Light theme", egui::ThemePreference::Light),
+    ("Dark theme", egui::ThemePreference::Dark),
+];
+
+// the seconds since 1970, for file names and the reviews of repertoire lines
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// the color of arrows and circles like on lichess: green, red with Shift, blue with Ctrl,
+// and yellow with Alt
+fn mark_color(modifiers: egui::Modifiers) -> egui::Color32 {
+    let (r, g, b) = if modifiers.shift {
+        (200, 30, 30)
+    } else if modifiers.command {
+        (30, 90, 210)
+    } else if modifiers.alt {
+        (220, 170, 0)
+    } else {
+        (20, 150, 60)
+    };
+    egui::Color32::from_rgba_unmultiplied(r, g, b, 170)
+}
+
+fn draw_piece(ui: &egui::Ui, rect: egui::Rect, f: engine::FigureID, piece_set: usize) {
+    if f == engine::VOID_ID {
+        return;
+    }
+    if piece_set == 0 {
+        ui.painter().text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            FIGURES[(f + 6) as usize],
+            egui::FontId::proportional(rect.height() * 0.9),
+            palette(ui).glyph,
+        );
+    } else {
+        egui::Image::new(piece_image(f)).paint_at(ui, rect.shrink(rect.height() * 0.05));
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Sound {
+    Move,
+    Capture,
+    Check,
+    LowClock,
+    GameEnd,
+}
+
+// short synthesized sounds, played by rodio without blocking the GUI
+#[cfg(not(target_arch = "wasm32"))]
+struct Sounds {
+    _stream: Option<rodio::OutputStream>, // sounds play only while the stream exists
+    handle: Option<rodio::OutputStreamHandle>,
+    muted: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sounds {
+    // without an audio device the game is silent
+    fn new() -> Self {
+        let (stream, handle) = rodio::OutputStream::try_default().ok().unzip();
+        Sounds {
+            _stream: stream,
+            handle,
+            muted: false,
+        }
+    }
+
+    fn play(&self, sound: Sound) {
+        let Some(handle) = self.handle.as_ref().filter(|_| !self.muted) else {
+            return;
+        };
+        // frequency in Hz and duration in ms of the tones
+        let tones: &[(f32, u64)] = match sound {
+            Sound::Move => &[(660.0, 40)],
+            Sound::Capture => &[(440.0, 60), (330.0, 60)],
+            Sound::Check => &[(880.0, 60), (988.0, 80)],
+            Sound::LowClock => &[(1200.0, 30)],
+            Sound::GameEnd => &[(523.0, 120), (659.0, 120), (784.0, 200)],
+        };
+        if let Ok(sink) = rodio::Sink::try_new(handle) {
+            for &(freq, ms) in tones {
+                let tone = rodio::source::SineWave::new(freq)
+                    .take_duration(Duration::from_millis(ms))
+                    .amplify(0.2);
+                sink.append(tone);
+            }
+            sink.detach();
+        }
+    }
+}
+
+// rodio has no audio output in the browser, so the web version is silent
+#[cfg(target_arch = "wasm32")]
+struct Sounds {
+    muted: bool,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Sounds {
+    fn new() -> Self {
+        Sounds { muted: false }
+    }
+
+    fn play(&self, _sound: Sound) {}
+}
+
+// minutes and seconds, and tenths of seconds for the last ten seconds
+fn format_clock(t: Duration) -> String {
+    if t < Duration::from_secs(10) {
+        format!("{:.1}", t.as_secs_f32())
+    } else {
+        format!("{}:{:02}", t.as_secs() / 60, t.as_secs() % 60)
+    }
+}
+
+// the colors of the judgments of the analysis, like on lichess
+fn judgment_color(j: Judgment) -> egui::Color32 {
+    match j {
+        Judgment::Brilliant => egui::Color32::from_rgb(30, 160, 170),
+        Judgment::Good => egui::Color32::from_rgb(60, 150, 60),
+        Judgment::Inaccuracy => egui::Color32::from_rgb(80, 140, 210),
+        Judgment::Mistake => egui::Color32::from_rgb(230, 140, 20),
+        Judgment::Blunder => egui::Color32::from_rgb(210, 40, 40),
+    }
+}
+
+// a NAG as symbol like "!?", or as "$n" without one
+fn nag_text(nag: u8) -> String {
+    match engine::NAG_SYMBOLS.iter().find(|(n, _)| *n == nag) {
+        Some((_, symbol)) => symbol.to_string(),
+        None => format!(" ${}", nag),
+    }
+}
+
+// the moves of the line from start on, the main line of the game or a variation.
+// The variations of a move follow it, indented. A click selects the position after a move.
+fn show_line(
+    ui: &mut egui::Ui,
+    g: &engine::Game,
+    start: engine::NodeId,
+    ply: usize, // of the move of start, 1 for the first move of the game
+    badges: &[(engine::NodeId, Judgment)], // the judgments of the analyzed moves
+    notation: (engine::Notation, &'static [&'static str; 7]), // and the piece letters
+    goto: &mut Option<engine::NodeId>,
+) {
+    let (mut node, mut ply) = (start, ply);
+    while node != engine::ROOT_NODE {
+        let mut variations: &[engine::NodeId] = &[];
+        ui.horizontal_wrapped(|ui| {
+            let mut row_start = true;
+            while node != engine::ROOT_NODE {
+                if ply % 2 == 1 {
+                    ui.label(format!("{}.", ply.div_ceil(2)));
+                } else if row_start {
+                    ui.label(format!("{}...", ply / 2));
+                }
+                row_start = false;
+                let mut text = engine::node_move(g, node)
+                    .localized(notation.0, notation.1)
+                    .to_string();
+                let nags = engine::node_nags(g, node);
+                for &nag in nags {
+                    text.push_str(&nag_text(nag));
+                }
+                let badge = badges.iter().find(|(n, _)| *n == node).map(|&(_, j)| j);
+                if let Some(j) = badge.filter(|j| !nags.contains(&j.nag())) {
+                    text.push_str(&nag_text(j.nag()));
+                }
+                let mut text = egui::RichText::new(text);
+                if let Some(j) = badge {
+                    text = text.color(judgment_color(j));
+                }
+                if ui
+                    .selectable_label(node == engine::current_node(g), text)
+                    .clicked()
+                {
+                    *goto = Some(node);
+                }
+                let comment = engine::node_comment(g, node);
+                if !comment.is_empty() {
+                    ui.label(egui::RichText::new(comment).italics().weak());
+                    row_start = true; // the next move of Black gets its number
+                }
+                // the variations of a main line move end the row
+                let siblings = engine::node_children(g, engine::node_parent(g, node));
+                let main = siblings[0] == node;
+                let children = engine::node_children(g, node);
+                node = children.first().copied().unwrap_or(engine::ROOT_NODE);
+                ply += 1;
+                if main && siblings.len() > 1 {
+                    variations = &siblings[1..];
+                    break;
+                }
+            }
+        });
+        for &v in variations {
+            ui.indent(v, |ui| show_line(ui, g, v, ply - 1, badges, notation, goto));
+        }
+    }
+}
+
+// the app with the restored settings and the saved game
+fn new_app(cc: &eframe::CreationContext<'_>) -> Tabs {
+    // This gives us image support:
+    egui_extras::install_image_loaders(&cc.egui_ctx);
+    let mut app = MyApp::default();
+    if let Some(storage) = cc.storage {
+        if let Some(settings) = eframe::get_value::<Settings>(storage, SETTINGS_KEY) {
+            app.restore(settings);
+            cc.egui_ctx.set_theme(THEMES[app.theme].1);
+            cc.egui_ctx.set_zoom_factor(app.zoom);
+        }
+        app.resume = eframe::get_value::<Option<SavedGame>>(storage, GAME_KEY).flatten();
+        // the correspondence game continues in its own tab
+        let saved = eframe::get_value::<Option<CorrespondenceGame>>(storage, CORRESPONDENCE_KEY);
+        if let Some(saved) = saved.flatten() {
+            let mut game = MyApp::default();
+            game.restore(app.settings());
+            if game.resume_game(&saved.game) {
+                game.correspondence = true;
+                game.conditionals = saved.conditionals;
+                return Tabs {
+                    games: vec![app, game],
+                    current: 1,
+                };
+            }
+            app.msg = "The saved correspondence game is not valid.".to_owned();
+        }
+    }
+    Tabs {
+        games: vec![app],
+        current: 0,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> Result<(), eframe::Error> {
+    // a subcommand drives the engine without a window, see cli.rs
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(tiny_chess::cli::main(&args));
+    }
+    //env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    // The window size, position and maximized state are restored by eframe with its
+    // persistence feature, the inner size is only used for the first start.
+    let options = eframe::NativeOptions {
+        persist_window: true,
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([1200.0, 800.0])
+            .with_title("Tiny chess")
+            .with_app_id("tiny-chess"),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "My egui App",
+        options,
+        Box::new(|cc| Ok(Box::new(new_app(cc)))),
+    )
+}
+
+// the web version draws into the canvas with the id "tiny_chess" of index.html
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use wasm_bindgen::JsCast;
+    wasm_bindgen_futures::spawn_local(async {
+        let canvas = web_sys::window()
+            .and_then(|w| w.document())
+            .and_then(|d| d.get_element_by_id("tiny_chess"))
+            .and_then(|e| e.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+            .expect("index.html has no canvas with the id tiny_chess");
+        let result = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| Ok(Box::new(new_app(cc)))),
+            )
+            .await;
+        if let Err(e) = result {
+            web_sys::console::error_1(&e);
+        }
+    });
+}
+
+// the open games, each with its own engine thread and clocks. Only the shown game is updated,
+// the clocks of the others are charged and the replies of their engines are played when they
+// are shown again.
+struct Tabs {
+    games: Vec<MyApp>,
+    current: usize,
+}
+
+impl eframe::App for Tabs {
+    // the settings and the game of the shown tab
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::App::save(&mut self.games[self.current], storage);
+        let correspondence = self.games.iter().find_map(MyApp::correspondence_game);
+        eframe::set_value(storage, CORRESPONDENCE_KEY, &correspondence);
+    }
+
+    fn auto_save_interval(&self) -> Duration {
+        eframe::App::auto_save_interval(&self.games[self.current])
+    }
+
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        let mut close = false;
+        let lang = self.games[self.current].lang();
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for i in 0..self.games.len() {
+                    ui.selectable_value(
+                        &mut self.current,
+                        i,
+                        format!("{} {}", tr(lang, "Game"), i + 1),
+                    );
+                }
+                if ui
+                    .button("+")
+                    .on_hover_text(tr(lang, "Open a new game"))
+                    .clicked()
+                {
+                    let mut game = MyApp::default();
+                    game.restore(self.games[self.current].settings());
+                    self.games.push(game);
+                    self.current = self.games.len() - 1;
+                }
+                close = self.games.len() > 1 && ui.button(tr(lang, "Close")).clicked();
+            });
+        });
+        if close {
+            self.games.remove(self.current); // stops its engine thread
+            self.current = self.current.min(self.games.len() - 1);
+        }
+        eframe::App::update(&mut self.games[self.current], ctx, frame);
+    }
+}
+
+// the key of the settings in the eframe storage
+const SETTINGS_KEY: &str = "settings";
+
+// the settings of the side panel, saved on exit and restored on startup.
+// Indices out of range, from an older version, are clamped in MyApp::restore().
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Settings {
+    time_control: usize,
+    options: Vec<(String, String)>, // names and values of engine::OPTIONS
+    engine_plays_white: bool,
+    engine_plays_black: bool,
+    rotated: bool,
+    theme: usize,
+    piece_set: usize,
+    hints: usize,
+    #[serde(default)]
+    notation: usize,
+    #[serde(default)]
+    language: usize,
+    #[serde(default)]
+    touch: bool,
+    #[serde(default)]
+    highlight: usize,
+    #[serde(default = "default_zoom")]
+    zoom: f32,
+    muted: bool,
+    #[serde(default)]
+    engine_path: String,
+    #[serde(default)]
+    analysis_engine: String,
+    #[serde(default)]
+    book_path: String,
+    #[serde(default)]
+    lan_address: String,
+    #[serde(default = "default_broadcast_dir")]
+    broadcast_dir: String,
+    #[serde(default)]
+    variant: String, // the name, see variant::by_name()
+    #[serde(default)]
+    thresholds: analysis::Thresholds,
+    #[serde(default = "start_rating")]
+    puzzle_rating: i64,
+    #[serde(default)]
+    reviews: HashMap<String, repertoire::Card>,
+    #[cfg(feature = "lichess")]
+    #[serde(default)]
+    explorer_database: usize,
+    #[cfg(feature = "lichess")]
+    #[serde(default)]
+    explorer_offline: bool,
+}
+
+fn start_rating() -> i64 {
+    puzzle::START_RATING
+}
+
+fn default_zoom() -> f32 {
+    1.0
+}
+
+fn default_broadcast_dir() -> String {
+    BROADCAST_DIR.to_owned()
+}
+
+// a puzzle or a line of a repertoire on the board, the moves of the solver are checked
+// and the moves of the opponent are played
+struct Exercise {
+    kind: Kind,
+    moves: Vec<String>, // in UCI notation
+    solver: usize,      // the solver plays the plies of this parity, counted from 0
+    ply: usize,         // of the next move in moves
+    failed: bool,       // a wrong move was tried, or the solution was shown
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Kind {
+    Puzzle(usize), // index of MyApp::puzzles
+    Line(usize),   // index of Drill::lines
+}
+
+// the loaded repertoire, see repertoire.rs
+struct Drill {
+    start: String,      // the FEN of the start position
+    lines: Vec<String>, // the keys of the lines, the moves in UCI notation
+    white: bool,        // the moves of White are drilled
+}
+
+// the connection to lichess, see tiny_chess::lichess
+#[cfg(feature = "lichess")]
+struct Online {
+    client: lichess::Client,
+    challenges: Vec<(String, String)>, // id and description of the incoming challenges
+    game: Option<String>,              // the id of the running game
+    white: bool,                       // the user plays White in the game
+    names: [String; 2],                // of White and Black
+    clocks: [Duration; 2],             // as of the last move
+}
+
+// the answer of a request of the opening explorer for a database and position_key()
+#[cfg(feature = "lichess")]
+type Exploring = ((usize, u64), Receiver<Result<lichess::Explorer, String>>);
+
+// the connection of a game in the local network, see tiny_chess::lan
+#[cfg(not(target_arch = "wasm32"))]
+struct Lan {
+    connection: lan::Connection,
+    opponent: Option<String>, // the address, None while the host waits
+    playing: bool,            // a game is running
+    white: bool,              // the user plays White, the colors change with each game
+    draw_offered: bool,       // by the opponent
+}
+
+// the key of the unfinished game in the eframe storage
+const GAME_KEY: &str = "game";
+
+// the unfinished game, saved periodically and on exit, and offered to resume on startup
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SavedGame {
+    start: String,      // FEN of the start position
+    moves: Vec<String>, // in UCI notation, including the taken back moves
+    ply: usize,         // the played moves, see engine::game_ply()
+    clocks: [f64; 2],   // seconds left of White and Black
+}
+
+// the key of the correspondence game in the eframe storage
+const CORRESPONDENCE_KEY: &str = "correspondence";
+
+// the untimed correspondence game, kept between the sessions until it is finished
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CorrespondenceGame {
+    game: SavedGame,
+    conditionals: Vec<correspondence::Conditional>,
+}
+
+// the position of the board editor, see engine::set_board()
+struct Editor {
+    board: engine::Board,
+    piece: engine::FigureID, // placed by a click on the board, VOID_ID removes pieces
+    white_to_move: bool,
+    rights: [bool; 4], // castling rights K, Q, k and q
+    ep: String,        // the en passant square like "e6", or empty
+}
+
+impl Editor {
+    fn new(g: &engine::Game) -> Self {
+        let ep = engine::fen(g)
+            .split_whitespace()
+            .nth(3)
+            .unwrap_or("-")
+            .to_owned();
+        Editor {
+            board: engine::get_board(g),
+            piece: engine::PAWN_ID,
+            white_to_move: g.move_counter.is_multiple_of(2),
+            rights: engine::castling_rights(g),
+            ep: if ep == "-" { String::new() } else { ep },
+        }
+    }
+
+    // a click on a square with the selected piece removes it
+    fn place(&mut self, p: usize) {
+        self.board[p] = if self.board[p] == self.piece {
+            engine::VOID_ID
+        } else {
+            self.piece
+        };
+    }
+
+    // the edited position, as a new game
+    fn set_up(&self, g: &mut engine::Game) -> Result<(), String> {
+        let ep = match self.ep.trim() {
+            "" | "-" => None,
+            s => Some(engine::parse_square(s).map_err(|e| e.to_string())?),
+        };
+        let side = if self.white_to_move {
+            engine::COLOR_WHITE
+        } else {
+            engine::COLOR_BLACK
+        };
+        engine::set_board(g, &self.board, side, self.rights, ep).map_err(|e| e.to_string())
+    }
+}
+
+struct MyApp {
+    game: engine::Game, // a clone with a small transposition table, for the rules
+    engine: EngineHandle,
+    engine_path: String,  // of the external UCI engine, empty for the own engine
+    engine_input: String, // the path of "Start engine"
+    book_input: String,   // the path of "Load book"
+    analysis_engine: String, // the UCI engine of the analysis, empty for the own engine
+    book: Option<Arc<Book>>, // the opening book of the engine
+    book_path: String,    // of the book file, empty for none
+    lan_address: String,  // of the host of a LAN game
+    broadcast_dir: String, // of the live files
+    #[cfg(not(target_arch = "wasm32"))]
+    broadcast_files: bool, // the live files are written
+    #[cfg(not(target_arch = "wasm32"))]
+    endpoint: Option<broadcast::Endpoint>, // the live output over HTTP
+    #[cfg(not(target_arch = "wasm32"))]
+    broadcast_key: Option<(engine::NodeId, u64, bool)>, // the position of the last output
+    #[cfg(not(target_arch = "wasm32"))]
+    lan: Option<Lan>,
+    #[cfg(not(target_arch = "wasm32"))]
+    image_file: Option<String>, // the window of the image export is open
+    #[cfg(not(target_arch = "wasm32"))]
+    image_style: diagram::Style,
+    #[cfg(not(target_arch = "wasm32"))]
+    replay_plies: (usize, usize), // the first and the last position of a replay
+    #[cfg(not(target_arch = "wasm32"))]
+    replay_delay: u64,
+    #[cfg(not(target_arch = "wasm32"))]
+    replay: Option<Receiver<Result<String, String>>>, // the GIF written in the background
+    #[cfg(feature = "eboard")]
+    eboard: Option<Box<dyn eboard::Driver>>,
+    #[cfg(feature = "eboard")]
+    eboard_board: Option<engine::Board>, // the pieces on the e-board
+    #[cfg(feature = "eboard")]
+    eboard_differs: Vec<engine::Position>, // the squares of the e-board which differ from the game
+    evaluator: Option<EngineHandle>, // the analysis engine searching for the evaluation bar
+    evaluated: u64,                  // the engine::position_key() searched by the evaluator
+    msg: String,                     // the status, with the last move or an error
+    history: VecDeque<String>,       // the last messages, the newest at the back
+    rotated: bool,
+    options: Vec<engine::OptionValue>, // edited values of engine::OPTIONS
+    options_changed: bool,
+    tagged: engine::Board,
+    state: engine::State,
+    players: [u8; 2],
+    engine_plays_white: bool,
+    engine_plays_black: bool,
+    p0: i32,
+    new_game: bool,
+    bbb: engine::Board,                // the displayed board, see sync_board()
+    drag_from: Option<usize>,          // board position of the piece dragged with the mouse
+    promotion: (i8, i8),               // source and destination of the move in STATE_U4
+    piece_set: usize,                  // index of PIECE_SETS
+    eval: i64,  // centipawns from White's point of view, for the evaluation bar
+    pv: String, // the best line of the engine, while and after it thinks
+    stats: Option<engine::SearchInfo>, // of the last completed iteration of the engine
+    progress: (u64, Duration), // the nodes searched and the time of the engine
+    time_control: usize, // index of TIME_CONTROLS
+    clocks: [Duration; 2], // time left of White and Black
+    clock_history: Vec<[Duration; 2]>, // the clocks after each ply, restored by undo and browsing
+    last_tick: Instant, // when the running clock was updated last
+    game_result: Option<(String, &'static str)>, // reason and PGN result of a finished game
+    show_result: bool, // the game over dialog
+    sounds: Sounds,
+    animations: Vec<(usize, usize, engine::FigureID)>, // pieces sliding from and to
+    animation_count: u32, // animated by egui, the animation ends when it reaches the count
+    dropped: bool,        // the human move was done by drag and drop
+    premove: Vec<i8>,     // source and destination, entered while the engine thinks
+    in_check: Option<usize>, // position of the king of the active player in check
+    hints: usize,         // index of HINTS
+    highlight: usize,     // index of HIGHLIGHTS
+    notation: usize,      // index of NOTATIONS
+    language: usize,      // index of i18n::LANGUAGES
+    zoom: f32,            // the egui zoom factor, applied to the scale of the OS
+    theme: usize,         // index of THEMES
+    resume: Option<SavedGame>, // the game of the last session, while the dialog is shown
+    correspondence: bool, // an untimed game, saved between the sessions
+    conditionals: Vec<correspondence::Conditional>, // played automatically in correspondence mode
+    conditional_input: String, // a line like "if e4 then c5"
+    move_input: String,   // a move typed by the human player
+    fen_input: String,    // the position for "Load FEN"
+    pgn_input: Option<String>, // the text of the "Paste PGN" window while it is open
+    share_input: Option<String>, // the game string to load, while the "Share game" window is open
+    share_code: Option<ShareCode>, // the QR code of the shown game string
+    editor: Option<Editor>, // the board editor, while it is open
+    blindfold: bool,      // the pieces are hidden, moves are still accepted
+    touch: bool,          // larger widgets, a tap on a piece selects or deselects it
+    presentation: bool,   // the layout for a projector, see PRESENTATION_SCALE
+    autoplay_delay: u64,  // milliseconds between the moves of an engine game
+    paused: bool,         // an engine game waits for Resume
+    last_move: Instant,   // when the engine moved last, for autoplay_delay
+    scores: Vec<i64>,     // of the engine moves from White's point of view, for the adjudication
+    auto_rotate: bool,    // two human players see the board from their side
+    confirm_moves: bool,  // two human players confirm each move
+    confirm: (i8, i8, engine::FigureID), // the move to confirm in STATE_U5
+    marks: Vec<(usize, usize, egui::Color32)>, // arrows, or circles with equal squares
+    mark_from: Option<usize>, // start of an arrow, dragged with the secondary button
+    opening: ((engine::NodeId, u64), String), // the ECO name of the current node and position
+    analysis: Option<AnalysisHandle>, // the running analysis of the game
+    analysis_progress: (usize, usize), // the searched position and the number of moves
+    report: Option<analysis::Report>, // the finished analysis, for the move list
+    analyzed: Vec<engine::NodeId>, // the nodes of the moves of the analysis
+    show_report: bool,    // the window of the report
+    thresholds: analysis::Thresholds, // of the judgments of the analysis
+    puzzles: Vec<puzzle::Puzzle>, // read from puzzle_file
+    tried: Vec<bool>,     // for each of puzzles
+    puzzle_file: String,  // the CSV file of "Load puzzles"
+    exercise: Option<Exercise>, // a puzzle or a line of the repertoire drill
+    puzzle_rating: i64,   // of the solver
+    repertoire_file: String, // the PGN of "Load repertoire"
+    drill_black: bool,    // the moves of Black are drilled
+    drill: Option<Drill>,
+    reviews: HashMap<String, repertoire::Card>, // by the keys of Drill::lines
+    database_file: String,                      // the PGN of "Open database"
+    database: Option<Database>,
+    database_query: String,    // the search of the game list
+    database_hits: Vec<usize>, // the games of database found by database_query
+    show_database: bool,       // the window of the game list
+    tree: Option<OpeningTree>, // of the saved games
+    #[cfg(feature = "lichess")]
+    import_url: String, // of "Import from URL"
+    #[cfg(feature = "lichess")]
+    importing: Option<Receiver<Result<String, String>>>, // the download of import_url
+    #[cfg(feature = "lichess")]
+    online: Option<Online>,
+    #[cfg(feature = "lichess")]
+    lichess_token: String, // not saved with the settings, see LICHESS_TOKEN
+    #[cfg(feature = "lichess")]
+    seek: (u32, u32), // minutes and increment in seconds of a seek
+    #[cfg(feature = "lichess")]
+    explorer_database: usize, // index of lichess::DATABASES
+    #[cfg(feature = "lichess")]
+    explorer_offline: bool, // only the cached positions are shown
+    #[cfg(feature = "lichess")]
+    explored: HashMap<(usize, u64), lichess::Explorer>, // by database and position_key()
+    #[cfg(feature = "lichess")]
+    exploring: Option<Exploring>, // the running request of the opening explorer
+}
+
+impl Default for MyApp {
+    fn default() -> Self {
+        let game = engine::new_game();
+        Self {
+            game: game.clone(),
+            engine: EngineHandle::new(game),
+            engine_path: String::new(),
+            engine_input: String::new(),
+            book_input: String::new(),
+            analysis_engine: String::new(),
+            book: None,
+            book_path: String::new(),
+            lan_address: String::new(),
+            broadcast_dir: default_broadcast_dir(),
+            #[cfg(not(target_arch = "wasm32"))]
+            broadcast_files: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            endpoint: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            broadcast_key: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            lan: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            image_file: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            image_style: diagram::Style::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            replay_plies: (0, 0),
+            #[cfg(not(target_arch = "wasm32"))]
+            replay_delay: REPLAY_DELAY,
+            #[cfg(not(target_arch = "wasm32"))]
+            replay: None,
+            #[cfg(feature = "eboard")]
+            eboard: None,
+            #[cfg(feature = "eboard")]
+            eboard_board: None,
+            #[cfg(feature = "eboard")]
+            eboard_differs: Vec::new(),
+            evaluator: None,
+            evaluated: 0,
+            msg: "Tiny chess".to_owned(),
+            history: VecDeque::new(),
+            options: engine::OPTIONS
+                .iter()
+                .map(|o| o.kind.default_value())
+                .collect(),
+            options_changed: false,
+            rotated: true,
+            tagged: [0; 64],
+            players: [HUMAN, ENGINE],
+            p0: -1,
+            state: STATE_UZ,
+            bbb: [0; 64],
+            drag_from: None,
+            promotion: (-1, -1),
+            piece_set: 1,
+            eval: 0,
+            pv: String::new(),
+            stats: None,
+            progress: (0, Duration::ZERO),
+            time_control: 0,
+            clocks: [Duration::ZERO; 2],
+            clock_history: vec![[Duration::ZERO; 2]],
+            last_tick: Instant::now(),
+            game_result: None,
+            show_result: false,
+            sounds: Sounds::new(),
+            animations: Vec::new(),
+            animation_count: 0,
+            dropped: false,
+            premove: Vec::new(),
+            in_check: None,
+            hints: 1,
+            highlight: 0,
+            notation: 0,
+            language: 0,
+            zoom: default_zoom(),
+            theme: 0,
+            resume: None,
+            correspondence: false,
+            conditionals: Vec::new(),
+            conditional_input: String::new(),
+            move_input: String::new(),
+            fen_input: String::new(),
+            pgn_input: None,
+            share_input: None,
+            share_code: None,
+            editor: None,
+            blindfold: false,
+            touch: false,
+            presentation: false,
+            autoplay_delay: 500,
+            paused: false,
+            last_move: Instant::now(),
+            scores: Vec::new(),
+            auto_rotate: false,
+            confirm_moves: false,
+            confirm: (-1, -1, engine::VOID_ID),
+            marks: Vec::new(),
+            mark_from: None,
+            opening: ((engine::ROOT_NODE, 0), String::new()),
+            analysis: None,
+            analysis_progress: (0, 0),
+            report: None,
+            analyzed: Vec::new(),
+            show_report: false,
+            thresholds: analysis::Thresholds::default(),
+            puzzles: Vec::new(),
+            tried: Vec::new(),
+            puzzle_file: PUZZLE_FILE.to_owned(),
+            exercise: None,
+            puzzle_rating: puzzle::START_RATING,
+            repertoire_file: REPERTOIRE_FILE.to_owned(),
+            drill_black: false,
+            drill: None,
+            reviews: HashMap::new(),
+            database_file: DATABASE_FILE.to_owned(),
+            database: None,
+            database_query: String::new(),
+            database_hits: Vec::new(),
+            show_database: false,
+            tree: None,
+            #[cfg(feature = "lichess")]
+            import_url: String::new(),
+            #[cfg(feature = "lichess")]
+            importing: None,
+            #[cfg(feature = "lichess")]
+            online: None,
+            #[cfg(feature = "lichess")]
+            lichess_token: std::env::var("LICHESS_TOKEN").unwrap_or_default(),
+            #[cfg(feature = "lichess")]
+            seek: (10, 5), // the Board API allows rapid and slower games
+            #[cfg(feature = "lichess")]
+            explorer_database: 0,
+            #[cfg(feature = "lichess")]
+            explorer_offline: false,
+            #[cfg(feature = "lichess")]
+            explored: HashMap::new(),
+            #[cfg(feature = "lichess")]
+            exploring: None,
+            new_game: true,
+            engine_plays_white: false,
+            engine_plays_black: true,
+        }
+    }
+}
+
+impl MyApp {
+    fn settings(&self) -> Settings {
+        Settings {
+            time_control: self.time_control,
+            options: engine::OPTIONS
+                .iter()
+                .zip(&self.options)
+                .map(|(o, value)| (o.name.to_string(), value.to_string()))
+                .collect(),
+            engine_plays_white: self.engine_plays_white,
+            engine_plays_black: self.engine_plays_black,
+            rotated: self.rotated,
+            theme: self.theme,
+            piece_set: self.piece_set,
+            hints: self.hints,
+            notation: self.notation,
+            language: self.language,
+            touch: self.touch,
+            highlight: self.highlight,
+            zoom: self.zoom,
+            muted: self.sounds.muted,
+            engine_path: self.engine_path.clone(),
+            analysis_engine: self.analysis_engine.clone(),
+            book_path: self.book_path.clone(),
+            lan_address: self.lan_address.clone(),
+            broadcast_dir: self.broadcast_dir.clone(),
+            variant: engine::variant(&self.game).name().to_string(),
+            thresholds: self.thresholds,
+            puzzle_rating: self.puzzle_rating,
+            reviews: self.reviews.clone(),
+            #[cfg(feature = "lichess")]
+            explorer_database: self.explorer_database,
+            #[cfg(feature = "lichess")]
+            explorer_offline: self.explorer_offline,
+        }
+    }
+
+    // unknown options and invalid values are ignored, the defaults remain
+    fn restore(&mut self, s: Settings) {
+        self.time_control = s.time_control.min(TIME_CONTROLS.len() - 1);
+        for (name, value) in &s.options {
+            if let Some(i) = engine::OPTIONS.iter().position(|o| o.name == name) {
+                if let Ok(v) = engine::OPTIONS[i].kind.parse(value) {
+                    self.options[i] = v;
+                    self.options_changed = true;
+                }
+            }
+        }
+        self.engine_plays_white = s.engine_plays_white;
+        self.engine_plays_black = s.engine_plays_black;
+        self.players = [
+            BOOL_TO_ENGINE[s.engine_plays_white as usize],
+            BOOL_TO_ENGINE[s.engine_plays_black as usize],
+        ];
+        self.rotated = s.rotated;
+        self.theme = s.theme.min(THEMES.len() - 1);
+        self.piece_set = s.piece_set.min(PIECE_SETS.len() - 1);
+        self.hints = s.hints.min(HINTS.len() - 1);
+        self.notation = s.notation.min(NOTATIONS.len() - 1);
+        self.language = s.language.min(i18n::LANGUAGES.len() - 1);
+        self.touch = s.touch;
+        self.highlight = s.highlight.min(HIGHLIGHTS.len() - 1);
+        self.zoom = s.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        self.sounds.muted = s.muted;
+        #[cfg(not(target_arch = "wasm32"))]
+        if !s.engine_path.is_empty() {
+            self.engine_input.clone_from(&s.engine_path);
+            self.set_engine(&s.engine_path);
+        }
+        self.analysis_engine = s.analysis_engine;
+        #[cfg(not(target_arch = "wasm32"))]
+        if !s.book_path.is_empty() {
+            self.book_input.clone_from(&s.book_path);
+            self.load_book(&s.book_path);
+        }
+        self.lan_address = s.lan_address;
+        self.broadcast_dir = s.broadcast_dir;
+        if let Some(v) = variant::by_name(&s.variant) {
+            engine::set_variant(&mut self.game, v);
+        }
+        self.thresholds = s.thresholds;
+        self.puzzle_rating = s.puzzle_rating;
+        self.reviews = s.reviews;
+        #[cfg(feature = "lichess")]
+        {
+            self.explorer_database = s.explorer_database.min(lichess::DATABASES.len() - 1);
+            self.explorer_offline = s.explorer_offline;
+        }
+        self.reset_clocks();
+    }
+
+    // None for a finished game, a puzzle, a correspondence game, or when no move was made yet
+    fn saved_game(&self) -> Option<SavedGame> {
+        let moves = engine::move_list(&self.game);
+        if self.game_result.is_some()
+            || self.exercise.is_some()
+            || self.correspondence
+            || moves.is_empty()
+        {
+            return None;
+        }
+        Some(self.game_record())
+    }
+
+    // None for a finished game, or when correspondence mode is off
+    fn correspondence_game(&self) -> Option<CorrespondenceGame> {
+        if !self.correspondence || self.game_result.is_some() {
+            return None;
+        }
+        Some(CorrespondenceGame {
+            game: self.game_record(),
+            conditionals: self.conditionals.clone(),
+        })
+    }
+
+    fn game_record(&self) -> SavedGame {
+        let mut start = self.game.clone();
+        engine::go_to_ply(&mut start, 0);
+        SavedGame {
+            start: engine::fen(&start),
+            moves: engine::move_list(&self.game)
+                .iter()
+                .map(|m| m.uci().to_string())
+                .collect(),
+            ply: engine::game_ply(&self.game),
+            clocks: self.clocks.map(|c| c.as_secs_f64()),
+        }
+    }
+
+    // replay the saved game, false if it is not valid
+    fn resume_game(&mut self, saved: &SavedGame) -> bool {
+        let mut g = self.game.clone();
+        if engine::set_fen(&mut g, &saved.start).is_err() {
+            return false;
+        }
+        for uci in &saved.moves {
+            let Ok(m) = engine::parse_uci_move(&g, uci) else {
+                return false;
+            };
+            engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+        }
+        engine::go_to_ply(&mut g, saved.ply);
+        self.engine.send(Command::Stop);
+        self.game = g;
+        self.clocks = saved
+            .clocks
+            .map(|secs| Duration::try_from_secs_f64(secs).unwrap_or_default());
+        self.clock_history = vec![self.clocks; saved.ply + 1]; // the earlier clocks are not saved
+        self.last_tick = Instant::now();
+        self.browsed();
+        true
+    }
+
+    // test for checkmate, stalemate and draws, and report the result
+    fn game_over(&mut self) -> bool {
+        let name = engine::variant(&self.game).name();
+        let state = engine::state(&self.game);
+        let result = match state {
+            engine::GameState::Ongoing => return false,
+            // with the name of the variant
+            engine::GameState::VariantWin(_) => state.to_string().replacen("Variant win", name, 1),
+            _ => state.to_string(),
+        };
+        self.msg.push(' ');
+        self.msg.push_str(&result);
+        self.end_game(&result, engine::pgn_result(&self.game));
+        true
+    }
+
+    // end an engine game with a clear score, see ADJUDICATE_WIN
+    fn adjudicate(&mut self) -> bool {
+        if self.players != [ENGINE, ENGINE] || self.scores.len() < ADJUDICATE_PLIES {
+            return false;
+        }
+        let last = &self.scores[self.scores.len() - ADJUDICATE_PLIES..];
+        let (reason, score) = if last.iter().all(|&s| s >= ADJUDICATE_WIN) {
+            ("White wins by adjudication.", "1-0")
+        } else if last.iter().all(|&s| s <= -ADJUDICATE_WIN) {
+            ("Black wins by adjudication.", "0-1")
+        } else if engine::game_ply(&self.game) >= ADJUDICATE_DRAW_PLY
+            && last.iter().all(|s| s.abs() <= ADJUDICATE_DRAW)
+        {
+            ("Draw by adjudication.", "1/2-1/2")
+        } else {
+            return false;
+        };
+        self.msg = reason.to_owned();
+        self.end_game(reason, score);
+        true
+    }
+
+    // the game ended by the rules, resignation or on time, show the result dialog
+    fn end_game(&mut self, reason: &str, score: &'static str) {
+        self.engine.send(Command::Stop);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(lan) = self.lan.as_mut() {
+            lan.playing = false;
+            lan.draw_offered = false;
+        }
+        // the finished game is the main line, which gets the result in game_pgn()
+        while engine::promote_variation(&mut self.game) {}
+        self.game_result = Some((reason.to_owned(), score));
+        self.show_result = true;
+        self.sounds.play(Sound::GameEnd);
+        self.state = STATE_UX;
+    }
+
+    // the whole game with the players and the result, wherever the current position is
+    fn game_pgn(&self) -> String {
+        // an external engine is named by its file
+        let path = std::path::Path::new(&self.engine_path);
+        let engine_name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Tiny chess");
+        let name = |p: u8| if p == ENGINE { engine_name } else { HUMAN_NAME };
+        let mut tags = vec![
+            ("White", name(self.players[0])),
+            ("Black", name(self.players[1])),
+        ];
+        if let Some((_, score)) = &self.game_result {
+            tags.push(("Result", *score));
+        }
+        engine::pgn(&self.game, &tags)
+    }
+
+    // write the game as PGN into the current directory
+    fn save_pgn(&mut self) {
+        let path = format!("{}{}.pgn", SAVED_PREFIX, unix_time());
+        self.msg = match std::fs::write(&path, self.game_pgn()) {
+            Ok(()) => format!("Saved {}", path),
+            Err(e) => format!("Saving {} failed: {}", path, e),
+        };
+    }
+
+    // update the displayed board after moves or a new game
+    fn sync_board(&mut self) -> Vec<engine::SquareChange> {
+        let changes = engine::diff(&self.bbb, &engine::get_board(&self.game));
+        for c in &changes {
+            self.bbb[c.pos as usize] = c.new;
+        }
+        if !changes.is_empty() {
+            self.marks.clear(); // the marks explain the position before a move
+        }
+        if self.evaluator.is_none() {
+            self.eval = engine::evaluate(&self.game); // until the engine reports a search score
+        }
+        let active = 1 - self.game.move_counter as i64 % 2 * 2;
+        self.in_check = engine::is_in_check(&self.game, active)
+            .then(|| self.bbb.iter().position(|&f| f == engine::KING_ID * active))
+            .flatten();
+        changes
+    }
+
+    // the same mark again removes it, with another color it replaces the color
+    fn toggle_mark(&mut self, from: usize, to: usize, color: egui::Color32) {
+        match self.marks.iter().position(|m| (m.0, m.1) == (from, to)) {
+            Some(i) if self.marks[i].2 == color => {
+                self.marks.remove(i);
+            }
+            Some(i) => self.marks[i].2 = color,
+            None => self.marks.push((from, to, color)),
+        }
+    }
+
+    // slide the moved pieces from their old to their new squares, the promoted piece
+    // from the square of the pawn
+    fn animate(&mut self, changes: &[engine::SquareChange]) {
+        self.animations.clear();
+        for to in changes.iter().filter(|c| c.new != engine::VOID_ID) {
+            let from = changes
+                .iter()
+                .find(|c| c.old == to.new && c.new != to.new)
+                .or_else(|| {
+                    changes.iter().find(|c| {
+                        c.old == engine::PAWN_ID * to.new.signum() && c.new == engine::VOID_ID
+                    })
+                });
+            if let Some(from) = from {
+                self.animations
+                    .push((from.pos as usize, to.pos as usize, to.new));
+            }
+        }
+        self.animation_count += 1;
+    }
+
+    fn time_control(&self) -> Option<(Duration, Duration)> {
+        TIME_CONTROLS[self.time_control]
+            .1
+            .map(|(base, inc)| (Duration::from_secs(base), Duration::from_secs(inc)))
+    }
+
+    // the clock of the active player runs while a human or the engine has to move
+    fn ticking(&self) -> bool {
+        self.time_control().is_some()
+            && self.state >= STATE_U0
+            && self.exercise.is_none()
+            && !self.lichess_game()
+            && !self.correspondence
+    }
+
+    fn reset_clocks(&mut self) {
+        let base = self.time_control().map_or(Duration::ZERO, |(base, _)| base);
+        self.clocks = [base; 2];
+        self.clock_history = vec![self.clocks];
+    }
+
+    // the clocks after the move just made, the entries of the taken back moves are dropped
+    fn record_clocks(&mut self) {
+        let ply = engine::game_ply(&self.game);
+        self.clock_history.resize(ply + 1, self.clocks);
+        self.clock_history[ply] = self.clocks;
+    }
+
+    // charge the time since the last update to the active player, who may lose on time
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if self.ticking() {
+            let active = self.game.move_counter as usize % 2;
+            let low = Duration::from_secs(10);
+            if self.clocks[active] > low && self.clocks[active] <= low + (now - self.last_tick) {
+                self.sounds.play(Sound::LowClock);
+            }
+            self.clocks[active] = self.clocks[active].saturating_sub(now - self.last_tick);
+            if self.clocks[active].is_zero() {
+                self.msg = ["White lost on time.", "Black lost on time."][active].to_owned();
+                self.end_game(&self.msg.clone(), ["0-1", "1-0"][active]);
+            }
+        }
+        self.last_tick = now;
+    }
+
+    // the increment for the player who just moved
+    fn add_increment(&mut self, mover: usize) {
+        if let Some((_, inc)) = self.time_control() {
+            self.clocks[mover] += inc;
+        }
+    }
+
+    fn move_sound(&self, m: &engine::Move) {
+        self.sounds.play(if m.check {
+            Sound::Check
+        } else if m.is_capture() {
+            Sound::Capture
+        } else {
+            Sound::Move
+        });
+    }
+
+    // a single human player plays from the bottom, Rotate overrides it until the next change
+    fn orient(&mut self) {
+        if self.players[0] != self.players[1] {
+            self.rotated = self.players[0] == HUMAN;
+        }
+    }
+
+    // a score of the side to move in self.game from White's point of view
+    fn white_view(&self, score: i64) -> i64 {
+        if self.game.move_counter.is_multiple_of(2) {
+            score
+        } else {
+            -score
+        }
+    }
+
+    // take back the moves up to the last one of a human player, so it can be replaced
+    fn undo(&mut self) {
+        if self.online_game() {
+            return;
+        }
+        self.engine.send(Command::Stop);
+        while engine::take_back(&mut self.game).is_some() {
+            let next = self.game.move_counter as usize % 2;
+            if self.players[next] == HUMAN || self.players == [ENGINE, ENGINE] {
+                break;
+            }
+        }
+        self.browsed();
+    }
+
+    // play the taken back moves again, up to the next one of a human player
+    fn redo(&mut self) {
+        if self.online_game() {
+            return;
+        }
+        self.engine.send(Command::Stop);
+        while engine::redo_move(&mut self.game).is_some() {
+            let next = self.game.move_counter as usize % 2;
+            if self.players[next] == HUMAN || self.players == [ENGINE, ENGINE] {
+                break;
+            }
+        }
+        self.browsed();
+    }
+
+    // analyze the moves leading to the current position in a thread, see analysis_window()
+    fn analyze(&mut self) {
+        self.report = None;
+        self.analyzed.clear();
+        let mut node = engine::current_node(&self.game);
+        while node != engine::ROOT_NODE {
+            self.analyzed.push(node);
+            node = engine::node_parent(&self.game, node);
+        }
+        self.analyzed.reverse();
+        self.analysis_progress = (0, engine::game_ply(&self.game));
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.analysis_engine.trim().is_empty() {
+            let path = self.analysis_engine.trim();
+            match AnalysisHandle::external(&self.game, EXTERNAL_ANALYSIS_DEPTH, path) {
+                Ok(handle) => self.analysis = Some(handle),
+                Err(e) => self.msg = format!("Starting {} failed: {}.", path, e),
+            }
+            return;
+        }
+        self.analysis = Some(AnalysisHandle::new(&self.game, ANALYSIS_DEPTH));
+    }
+
+    // the judgments of the analyzed moves, for the move list
+    fn badges(&self) -> Vec<(engine::NodeId, Judgment)> {
+        let Some(report) = &self.report else {
+            return Vec::new();
+        };
+        let judged = self.analyzed.iter().enumerate();
+        judged
+            .filter_map(|(i, &node)| Some((node, report.judgment(i, &self.thresholds)?)))
+            .collect()
+    }
+
+    // the clipboard is pasted into the text field with Ctrl+V, egui can not read it
+    // without a paste event
+    fn pgn_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang();
+        let Some(pgn) = &mut self.pgn_input else {
+            return;
+        };
+        let (mut load, mut cancel) = (false, false);
+        egui::Window::new(tr(lang, "Paste PGN"))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let empty = pgn.is_empty();
+                let text = egui::TextEdit::multiline(pgn)
+                    .hint_text("Ctrl+V")
+                    .desired_rows(10);
+                let response = ui.add(text);
+                if empty {
+                    response.request_focus();
+                }
+                ui.horizontal(|ui| {
+                    load = ui.button(tr(lang, "Load PGN")).clicked();
+                    cancel = ui.button(tr(lang, "Cancel")).clicked();
+                });
+            });
+        if load {
+            let pgn = self.pgn_input.take().unwrap_or_default();
+            self.load_pgn(&pgn);
+        } else if cancel {
+            self.pgn_input = None;
+        }
+    }
+
+    // The game as compact string and as QR code, to transfer it to a phone by scanning
+    // the screen, and the loading of a game string.
+    fn share_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang();
+        let Some(input) = &mut self.share_input else {
+            self.share_code = None;
+            return;
+        };
+        let key = (
+            engine::position_key(&self.game),
+            engine::current_node(&self.game),
+        );
+        if self.share_code.as_ref().map(|(k, ..)| *k) != Some(key) {
+            let text = engine::compact(&self.game);
+            let code = qrcode::QrCode::new(&text).ok();
+            self.share_code = Some((key, text, code));
+        }
+        let (_, text, code) = self.share_code.as_ref().unwrap();
+        let (mut copy, mut load) = (false, false);
+        let mut open = true;
+        egui::Window::new(tr(lang, "Share game"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut text.as_str()));
+                    copy = ui.button(tr(lang, "Copy")).clicked();
+                });
+                if let Some(code) = code {
+                    qr_code(ui, code);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(input);
+                    load = ui.button(tr(lang, "Load game string")).clicked();
+                });
+            });
+        if copy {
+            ctx.copy_text(text.clone());
+            self.msg = "Game copied.".to_owned();
+        }
+        if load {
+            let input = self.share_input.take().unwrap_or_default();
+            self.load_compact(&input);
+        } else if !open {
+            self.share_input = None;
+        }
+    }
+
+    // the current position as PNG or SVG file, or a part of the game as animated GIF, by
+    // the extension of the file name; the GIF is written in the background
+    #[cfg(not(target_arch = "wasm32"))]
+    fn image_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang();
+        if let Some(rx) = &self.replay {
+            match rx.try_recv() {
+                Ok(written) => {
+                    self.replay = None;
+                    self.msg = match written {
+                        Ok(path) => format!("Saved {}", path),
+                        Err(e) => format!("Saving the replay failed: {}", e),
+                    };
+                }
+                Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+                Err(TryRecvError::Disconnected) => self.replay = None,
+            }
+        }
+        let Some(file) = &mut self.image_file else {
+            return;
+        };
+        let style = &mut self.image_style;
+        let (first, last) = &mut self.replay_plies;
+        let delay = &mut self.replay_delay;
+        let plies = engine::move_list(&self.game).len();
+        let idle = self.replay.is_none();
+        let (mut export, mut cancel) = (false, false);
+        egui::Window::new(tr(lang, "Export position image"))
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr(lang, "File"));
+                    ui.text_edit_singleline(file);
+                });
+                ui.add(egui::Slider::new(&mut style.size, 100..=2000).text(tr(lang, "Pixels")));
+                ui.checkbox(&mut style.coordinates, tr(lang, "Coordinates"));
+                ui.checkbox(&mut style.last_move, tr(lang, "Last move"));
+                if file.to_lowercase().ends_with(".gif") {
+                    ui.add(egui::Slider::new(first, 0..=plies).text(tr(lang, "First ply")));
+                    ui.add(egui::Slider::new(last, *first..=plies).text(tr(lang, "Last ply")));
+                    ui.add(egui::Slider::new(delay, 100..=5000).text(tr(lang, "ms per move")));
+                }
+                ui.horizontal(|ui| {
+                    export = ui
+                        .add_enabled(idle, egui::Button::new(tr(lang, "Export")))
+                        .clicked();
+                    cancel = ui.button(tr(lang, "Cancel")).clicked();
+                });
+            });
+        if export {
+            let path = self.image_file.take().unwrap_or_default();
+            let style = diagram::Style {
+                flipped: !self.rotated,
+                ..self.image_style
+            };
+            if path.to_lowercase().ends_with(".gif") {
+                // the diagrams are quick, the pixels and their palettes are not
+                let mut g = self.game.clone();
+                let (first, last) = self.replay_plies;
+                let diagrams: Vec<String> = (first..=last.max(first))
+                    .map(|ply| {
+                        engine::go_to_ply(&mut g, ply);
+                        diagram::svg(&g, &style)
+                    })
+                    .collect();
+                let delay = Duration::from_millis(self.replay_delay);
+                let (tx, rx) = std::sync::mpsc::channel();
+                std::thread::spawn(move || {
+                    let _ = tx.send(write_gif(&path, &diagrams, delay).map(|()| path));
+                });
+                self.replay = Some(rx);
+                self.msg = "Writing the replay.".to_owned();
+                return;
+            }
+            let svg = diagram::svg(&self.game, &style);
+            let written = if path.to_lowercase().ends_with(".svg") {
+                std::fs::write(&path, svg).map_err(|e| e.to_string())
+            } else {
+                write_png(&path, &svg)
+            };
+            self.msg = match written {
+                Ok(()) => format!("Saved {}", path),
+                Err(e) => format!("Saving {} failed: {}", path, e),
+            };
+        } else if cancel {
+            self.image_file = None;
+        }
+    }
+
+    // the progress of the analysis, or its report when it is done
+    fn analysis_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang();
+        if let Some(handle) = &self.analysis {
+            let mut failed = false;
+            while let Some(event) = handle.try_event() {
+                match event {
+                    AnalysisEvent::Progress { ply, plies } => self.analysis_progress = (ply, plies),
+                    AnalysisEvent::Done(report) => {
+                        self.report = Some(report);
+                        self.show_report = true;
+                    }
+                    AnalysisEvent::Error(e) => {
+                        self.msg = format!("{}.", e);
+                        failed = true;
+                    }
+                }
+            }
+            if self.report.is_some() || failed {
+                self.analysis = None;
+            } else {
+                ctx.request_repaint_after(Duration::from_millis(100));
+            }
+        }
+        let mut open = true;
+        let mut cancel = false;
+        let mut goto = None;
+        if self.analysis.is_some() {
+            let (ply, plies) = self.analysis_progress;
+            egui::Window::new(tr(lang, "Analysis"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.add(
+                        egui::ProgressBar::new(ply as f32 / (plies + 1) as f32).text(format!(
+                            "Position {} of {}",
+                            ply + 1,
+                            plies + 1
+                        )),
+                    );
+                    cancel = ui.button(tr(lang, "Cancel")).clicked();
+                });
+        } else if let (true, Some(report)) = (self.show_report, &self.report) {
+            let t = &self.thresholds;
+            let (n, letters) = self.notation();
+            egui::Window::new(tr(lang, "Analysis"))
+                .open(&mut open)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!("Depth {}", report.depth));
+                    egui::Grid::new("report").striped(true).show(ui, |ui| {
+                        ui.label("");
+                        ui.strong(tr(lang, "White"));
+                        ui.strong(tr(lang, "Black"));
+                        ui.end_row();
+                        ui.label(tr(lang, "Average centipawn loss"));
+                        ui.label(report.average_loss(true).to_string());
+                        ui.label(report.average_loss(false).to_string());
+                        ui.end_row();
+                        for (name, judgment) in [
+                            ("Brilliant moves", Judgment::Brilliant),
+                            ("Good moves", Judgment::Good),
+                            ("Inaccuracies", Judgment::Inaccuracy),
+                            ("Mistakes", Judgment::Mistake),
+                            ("Blunders", Judgment::Blunder),
+                        ] {
+                            let name = egui::RichText::new(tr(lang, name));
+                            ui.label(name.color(judgment_color(judgment)));
+                            ui.label(report.count(true, judgment, t).to_string());
+                            ui.label(report.count(false, judgment, t).to_string());
+                            ui.end_row();
+                        }
+                    });
+                    ui.separator();
+                    ui.strong(tr(lang, "Critical moments"));
+                    let moments = report.critical_moments(CRITICAL_MOMENTS, t);
+                    if moments.is_empty() {
+                        ui.label(tr(lang, "None"));
+                    }
+                    for i in moments {
+                        let m = &report.moves[i];
+                        let number = (report.move_counter as usize + i) / 2 + 1;
+                        let number = if m.white {
+                            format!("{}.", number)
+                        } else {
+                            format!("{}...", number)
+                        };
+                        let text = format!(
+                            "{} {} loses {:.2}, {} was best",
+                            number,
+                            m.mv.localized(n, letters),
+                            m.loss as f64 / 100.0,
+                            m.best.localized(n, letters)
+                        );
+                        if ui.link(text).clicked() {
+                            goto = Some(i);
+                        }
+                    }
+                });
+        }
+        if cancel {
+            self.analysis = None;
+        }
+        if !open {
+            self.show_report = false;
+        }
+        // the position before the critical move
+        if let Some(ply) = goto {
+            self.engine.send(Command::Stop);
+            engine::go_to_ply(&mut self.game, ply);
+            self.browsed();
+        }
+    }
+
+    // show the position after undo, redo or a click in the move list, and continue the game
+    fn browsed(&mut self) {
+        self.editor = None;
+        self.scores.clear();
+        self.sync_board();
+        self.tagged = [0; 64];
+        if let Some(ply) = engine::game_ply(&self.game).checked_sub(1) {
+            let m = engine::move_list(&self.game)[ply];
+            self.tagged[m.src as usize] = 2;
+            self.tagged[m.dst as usize] = 2;
+        }
+        if let Some(&clocks) = self.clock_history.get(engine::game_ply(&self.game)) {
+            self.clocks = clocks;
+        }
+        self.last_tick = Instant::now();
+        self.drag_from = None;
+        self.premove.clear();
+        self.game_result = None;
+        self.show_result = false;
+        self.msg = "Tiny chess".to_owned();
+        self.state = STATE_UZ;
+        self.exercise = None;
+    }
+
+    // the moves of the game, a click goes to the position after that move
+    fn move_list(&mut self, ui: &mut egui::Ui) {
+        let mut goto = None;
+        let badges = self.badges();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            let start = engine::current_node(&self.game) == engine::ROOT_NODE;
+            if ui
+                .selectable_label(start, tr(self.lang(), "Start"))
+                .clicked()
+            {
+                goto = Some(engine::ROOT_NODE);
+            }
+            let comment = engine::node_comment(&self.game, engine::ROOT_NODE);
+            if !comment.is_empty() {
+                ui.label(egui::RichText::new(comment).italics().weak());
+            }
+            let main = engine::node_children(&self.game, engine::ROOT_NODE);
+            if let Some(&first) = main.first() {
+                let notation = self.notation();
+                show_line(ui, &self.game, first, 1, &badges, notation, &mut goto);
+            }
+        });
+        if let Some(node) = goto {
+            self.engine.send(Command::Stop);
+            engine::go_to_node(&mut self.game, node);
+            self.browsed();
+        }
+    }
+
+    // the name of the opening of the current position, cached
+    fn update_opening(&mut self) {
+        let key = (
+            engine::current_node(&self.game),
+            engine::position_key(&self.game),
+        );
+        if self.opening.0 != key {
+            let name = eco::opening(&self.game)
+                .map(|o| format!("{} {}", o.eco, o.name))
+                .unwrap_or_default();
+            self.opening = (key, name);
+        }
+    }
+
+    fn lang(&self) -> i18n::Language {
+        i18n::LANGUAGES[self.language].1
+    }
+
+    // the notation of the moves with the piece letters of the language
+    fn notation(&self) -> (engine::Notation, &'static [&'static str; 7]) {
+        (NOTATIONS[self.notation].1, i18n::piece_letters(self.lang()))
+    }
+
+    fn human_move(&mut self, h: i8, p1: i8, promote_to: engine::FigureID) {
+        let (n, letters) = self.notation();
+        let legal = engine::legal_moves(&self.game).any(|m| m.src == h && m.dst == p1);
+        if let (Some(ex), true) = (&self.exercise, legal) {
+            let m = engine::new_move(&self.game, h, p1, promote_to);
+            if self.is_solution(ex, &m) {
+                self.play_human_move(h, p1, promote_to);
+                self.exercise_move_done();
+            } else {
+                self.msg = format!(
+                    "{} is not the solution, try again.",
+                    m.localized(n, letters)
+                );
+                // an arrow shows the move of the repertoire
+                let line = engine::parse_uci_move(&self.game, &ex.moves[ex.ply]);
+                if let (Kind::Line(_), Ok(r)) = (ex.kind, line) {
+                    let (ms, rs) = (m.localized(n, letters), r.localized(n, letters));
+                    self.msg = format!("{} deviates from the repertoire, which plays {}.", ms, rs);
+                    let color = mark_color(egui::Modifiers::NONE);
+                    self.marks = vec![(r.src as usize, r.dst as usize, color)];
+                }
+                self.exercise_failed();
+                self.dropped = false; // the piece is back on its source
+                self.tagged = [0; 64];
+                self.state = STATE_UZ;
+            }
+            return;
+        }
+        if self.confirm_moves && self.players == [HUMAN, HUMAN] && legal {
+            self.confirm = (h, p1, promote_to);
+            self.dropped = false; // the piece is back on its source
+            self.tagged = [0; 64];
+            self.tagged[h as usize] = 2;
+            self.tagged[p1 as usize] = 2;
+            self.state = STATE_U5;
+        } else {
+            self.play_human_move(h, p1, promote_to);
+        }
+    }
+
+    // the answer of a conditional line, when the opponent has just followed it
+    fn conditional_move(&mut self) {
+        if self.state != STATE_U0 || !self.correspondence {
+            return;
+        }
+        if let Some(m) = correspondence::answer(&self.game, &mut self.conditionals) {
+            self.play_human_move(m.src, m.dst, m.promote_to);
+        }
+    }
+
+    fn play_human_move(&mut self, h: i8, p1: i8, promote_to: engine::FigureID) {
+        let mover = self.game.move_counter as usize % 2;
+        let dropped = std::mem::take(&mut self.dropped);
+        let m = match engine::play_move(&mut self.game, h, p1, promote_to) {
+            Ok(token) => {
+                self.add_increment(mover);
+                self.record_clocks();
+                let changes = self.sync_board();
+                if !dropped {
+                    self.animate(&changes); // a dropped piece is already there
+                }
+                token.mv
+            }
+            Err(e) => {
+                self.msg = format!("{}, ignored.", e);
+                self.tagged = [0; 64];
+                self.state = STATE_UZ;
+                return;
+            }
+        };
+        self.tagged = [0; 64];
+        self.tagged[h as usize] = 2;
+        self.tagged[p1 as usize] = 2;
+        self.move_sound(&m);
+        #[cfg(feature = "lichess")]
+        if let Some(Online {
+            client,
+            game: Some(game),
+            ..
+        }) = &self.online
+        {
+            let (game, uci) = (game.clone(), m.uci().to_string());
+            client.send(Request::Move { game, uci });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(lan) = self.lan.as_ref().filter(|l| l.playing) {
+            let uci = m.uci().to_string();
+            let clocks = self.clocks;
+            lan.connection.send(&lan::Message::Move { uci, clocks });
+        }
+        let (n, letters) = self.notation();
+        self.msg = m.localized(n, letters).to_string();
+        self.state = STATE_UZ;
+    }
+
+    // replace the engine by the UCI engine at path, or by the own engine for an empty path
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_engine(&mut self, path: &str) {
+        let path = path.trim();
+        if path.is_empty() {
+            self.engine = EngineHandle::new(engine::new_game());
+            self.msg = "Built-in engine started.".to_owned();
+        } else {
+            match EngineHandle::external(path) {
+                Ok(handle) => {
+                    self.engine = handle;
+                    self.msg = format!("Engine {} started.", path);
+                }
+                Err(e) => {
+                    self.msg = format!("Starting {} failed: {}.", path, e);
+                    return;
+                }
+            }
+        }
+        self.engine_path = path.to_owned();
+        self.options_changed = true; // Hash and MoveTime for the new engine
+        if self.state == STATE_U3 {
+            self.state = STATE_U2; // the new engine searches again
+        }
+    }
+
+    // the Polyglot book at path for the engine, or no book for an empty path
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_book(&mut self, path: &str) {
+        let path = path.trim();
+        if path.is_empty() {
+            self.book = None;
+            self.msg = "The engine plays without a book.".to_owned();
+        } else {
+            let book = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Book::from_bytes(&bytes).map_err(|e| e.to_string()));
+            match book {
+                Ok(book) => {
+                    self.msg = format!("Book with {} positions loaded.", book.positions());
+                    self.book = Some(Arc::new(book));
+                }
+                Err(e) => {
+                    self.msg = format!("Loading {} failed: {}.", path, e);
+                    return;
+                }
+            }
+        }
+        self.book_path = path.to_owned();
+        self.engine.send(Command::SetBook(self.book.clone()));
+    }
+
+    // The book of the engine, and its editor: the moves of the book in the current
+    // position with their weights, a click plays a move. A deleted move takes its line
+    // with it, and the last move of the game is added with the weight 1.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn book_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        ui.horizontal(|ui| {
+            ui.label(tr(lang, "Path"));
+            ui.text_edit_singleline(&mut self.book_input);
+        });
+        ui.horizontal(|ui| {
+            if ui.button(tr(lang, "Load book")).clicked() {
+                self.load_book(&self.book_input.clone());
+            }
+            if ui
+                .add_enabled(self.book.is_some(), egui::Button::new(tr(lang, "No book")))
+                .clicked()
+            {
+                self.load_book("");
+            }
+        });
+        ui.separator();
+        let moves = self.book.as_ref().map(|b| b.moves(&self.game));
+        let moves = moves.unwrap_or_default();
+        let (n, letters) = self.notation();
+        let (mut play, mut weight, mut delete) = (None, None, None);
+        if moves.is_empty() {
+            ui.label(tr(lang, "No book moves in this position."));
+        } else {
+            egui::Grid::new("opening_book")
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Move", "Weight"] {
+                        ui.strong(tr(lang, header));
+                    }
+                    ui.end_row();
+                    for (m, w) in moves {
+                        if ui.link(m.localized(n, letters).to_string()).clicked() {
+                            play = Some(m);
+                        }
+                        let mut w = w;
+                        if ui.add(egui::DragValue::new(&mut w)).changed() {
+                            weight = Some((m, w));
+                        }
+                        if ui.button(tr(lang, "Delete")).clicked() {
+                            delete = Some(m);
+                        }
+                        ui.end_row();
+                    }
+                });
+        }
+        let ply = engine::game_ply(&self.game);
+        let mut add = false;
+        let mut save = false;
+        ui.horizontal(|ui| {
+            add = ui
+                .add_enabled(ply > 0, egui::Button::new(tr(lang, "Add last move")))
+                .clicked();
+            save = ui
+                .add_enabled(
+                    self.book.is_some(),
+                    egui::Button::new(tr(lang, "Save book")),
+                )
+                .clicked();
+        });
+        // a new book for the first added move
+        if add || weight.is_some() || delete.is_some() {
+            let book = Arc::make_mut(self.book.get_or_insert_with(Default::default));
+            if let Some((m, w)) = weight {
+                book.set_weight(&self.game, &m, w);
+            }
+            if let Some(m) = delete {
+                book.remove(&self.game, &m);
+            }
+            if add {
+                let m = engine::move_list(&self.game)[ply - 1];
+                let mut before = self.game.clone();
+                engine::go_to_ply(&mut before, ply - 1);
+                let uci = m.uci().to_string();
+                if book
+                    .moves(&before)
+                    .iter()
+                    .all(|(b, _)| b.uci().to_string() != uci)
+                {
+                    book.set_weight(&before, &m, 1);
+                }
+            }
+            self.engine.send(Command::SetBook(self.book.clone()));
+        }
+        if let Some(m) = play.filter(|_| self.state == STATE_U0 || self.state == STATE_U1) {
+            self.human_move(m.src, m.dst, m.promote_to);
+        }
+        if save {
+            self.save_book();
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_book(&mut self) {
+        let path = self.book_input.trim().to_owned();
+        let Some(book) = &self.book else {
+            return;
+        };
+        if path.is_empty() {
+            self.msg = "The book needs a path.".to_owned();
+            return;
+        }
+        match std::fs::write(&path, book.to_bytes()) {
+            Ok(()) => {
+                self.msg = format!("Book with {} positions saved.", book.positions());
+                self.book_path = path;
+            }
+            Err(e) => self.msg = format!("Saving {} failed: {}.", path, e),
+        }
+    }
+
+    // start or stop the analysis engine searching each position for the evaluation bar
+    #[cfg(not(target_arch = "wasm32"))]
+    fn set_evaluator(&mut self, on: bool) {
+        self.evaluator = None;
+        self.evaluated = 0;
+        let path = self.analysis_engine.trim();
+        if !on || path.is_empty() {
+            return;
+        }
+        match EngineHandle::external(path) {
+            Ok(handle) => {
+                let time = EVALUATION_TIME.to_owned();
+                handle.send(Command::SetOption("MoveTime".to_owned(), time));
+                self.evaluator = Some(handle);
+            }
+            Err(e) => self.msg = format!("Starting {} failed: {}.", path, e),
+        }
+    }
+
+    // the evaluator searches each new position, its score replaces the one of the engine
+    fn evaluate(&mut self) {
+        let Some(evaluator) = &self.evaluator else {
+            return;
+        };
+        let key = engine::position_key(&self.game);
+        let white = self.game.move_counter.is_multiple_of(2);
+        let mut failed = false;
+        while let Some(event) = evaluator.try_event() {
+            match event {
+                Event::BestMove { key: k, result } if k == key => {
+                    self.eval = if white { result.score } else { -result.score };
+                }
+                Event::Error(e) => {
+                    self.msg = format!("{}.", e);
+                    failed = true;
+                }
+                _ => {}
+            }
+        }
+        if failed {
+            self.evaluator = None;
+        } else if key != self.evaluated && engine::state(&self.game) == engine::GameState::Ongoing {
+            self.evaluated = key;
+            let mut g = self.game.clone();
+            engine::set_time_left(&mut g, None); // searches for EVALUATION_TIME
+            evaluator.send(Command::Stop);
+            evaluator.send(Command::SetPosition(g));
+            evaluator.send(Command::Go);
+        }
+    }
+
+    // a game on lichess is running, with the clocks of lichess
+    fn lichess_game(&self) -> bool {
+        #[cfg(feature = "lichess")]
+        let online = self.online.as_ref().is_some_and(|o| o.game.is_some());
+        #[cfg(not(feature = "lichess"))]
+        let online = false;
+        online
+    }
+
+    // a game on lichess or in the local network is running, its moves can not be taken back
+    fn online_game(&self) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        let lan = self.lan.as_ref().is_some_and(|l| l.playing);
+        #[cfg(target_arch = "wasm32")]
+        let lan = false;
+        self.lichess_game() || lan
+    }
+
+    #[cfg(feature = "lichess")]
+    fn lichess_events(&mut self) {
+        let Some(online) = &self.online else {
+            return;
+        };
+        let events: Vec<_> = std::iter::from_fn(|| online.client.try_event()).collect();
+        for event in events {
+            self.lichess_event(event);
+        }
+    }
+
+    #[cfg(feature = "lichess")]
+    fn lichess_event(&mut self, event: lichess::Event) {
+        let Some(online) = self.online.as_mut() else {
+            return;
+        };
+        match event {
+            lichess::Event::GameStart { id, white } if online.game.is_none() => {
+                online.game = Some(id);
+                online.white = white;
+            }
+            lichess::Event::GameFull {
+                id,
+                fen,
+                white,
+                black,
+                state,
+            } if online.game.as_ref() == Some(&id) => {
+                let mut g = self.game.clone();
+                engine::reset_game(&mut g);
+                if let Some(fen) = fen {
+                    if let Err(e) = engine::set_fen(&mut g, &fen) {
+                        self.msg = format!("Invalid FEN: {}.", e);
+                        return;
+                    }
+                }
+                self.msg = format!("{} - {}", white, black);
+                self.rotated = online.white;
+                online.names = [white, black];
+                self.engine.send(Command::Stop);
+                self.game = g;
+                self.new_position();
+                // both players are human, the moves of the opponent arrive from lichess
+                self.players = [HUMAN, HUMAN];
+                self.engine_plays_white = false;
+                self.engine_plays_black = false;
+                self.lichess_state(state);
+            }
+            lichess::Event::GameState { id, state } if online.game.as_ref() == Some(&id) => {
+                self.lichess_state(state);
+            }
+            lichess::Event::Challenge { id, from, speed } => {
+                let text = format!("{} ({})", from, speed);
+                online.challenges.push((id, text));
+            }
+            lichess::Event::ChallengeGone { id } => {
+                online.challenges.retain(|(c, _)| *c != id);
+            }
+            lichess::Event::Error(e) => self.msg = format!("{}.", e),
+            _ => {} // another game of the account
+        }
+    }
+
+    // play the new moves of a lichess game, the own moves are already on the board
+    #[cfg(feature = "lichess")]
+    fn lichess_state(&mut self, state: lichess::GameState) {
+        let ply = engine::game_ply(&self.game);
+        for uci in state.moves.iter().skip(ply) {
+            let Ok(m) = engine::parse_uci_move(&self.game, uci) else {
+                self.msg = format!("Illegal move {} from lichess.", uci);
+                break;
+            };
+            let mv = engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false).mv;
+            let changes = self.sync_board();
+            self.animate(&changes);
+            self.move_sound(&mv);
+            self.tagged = [0; 64];
+            self.tagged[m.src as usize] = 2;
+            self.tagged[m.dst as usize] = 2;
+            let (n, letters) = self.notation();
+            self.msg = mv.localized(n, letters).to_string();
+            self.state = STATE_UZ;
+        }
+        let Some(online) = self.online.as_mut() else {
+            return;
+        };
+        online.clocks = state.clocks;
+        if state.status != "started" && state.status != "created" {
+            online.game = None;
+            if self.game_result.is_none() {
+                let score = match state.winner {
+                    Some(true) => "1-0",
+                    Some(false) => "0-1",
+                    None if state.status == "aborted" => "*",
+                    None => "1/2-1/2",
+                };
+                self.msg = format!("Lichess game over: {}.", state.status);
+                self.end_game(&self.msg.clone(), score);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn lan_events(&mut self) {
+        let Some(lan) = &self.lan else {
+            return;
+        };
+        let events: Vec<_> = std::iter::from_fn(|| lan.connection.try_event()).collect();
+        for event in events {
+            self.lan_event(event);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn lan_event(&mut self, event: lan::Event) {
+        let Some(lan) = self.lan.as_mut() else {
+            return;
+        };
+        match event {
+            lan::Event::Connected(address) => {
+                self.msg = format!("Connected to {}.", address);
+                lan.opponent = Some(address);
+                // the host starts the first game with White
+                if lan.connection.port().is_some() {
+                    let clock = TIME_CONTROLS[self.time_control].1;
+                    let start = lan::Message::Start {
+                        white: false,
+                        clock,
+                    };
+                    lan.connection.send(&start);
+                    self.start_lan_game(true);
+                }
+            }
+            lan::Event::Message(lan::Message::Start { white, clock }) => {
+                let index = TIME_CONTROLS.iter().position(|(_, tc)| *tc == clock);
+                self.time_control = index.unwrap_or(0);
+                self.start_lan_game(white);
+            }
+            lan::Event::Message(lan::Message::Move { uci, clocks }) if lan.playing => {
+                // a buggy or hostile peer must not move our pieces, or play on after the end
+                let white = self.game.move_counter.is_multiple_of(2);
+                if white == lan.white {
+                    self.msg = format!("Protocol error: move {} out of turn.", uci);
+                    return;
+                }
+                if self.game_result.is_some()
+                    || engine::state(&self.game) != engine::GameState::Ongoing
+                {
+                    self.msg = format!("Protocol error: move {} after the end of the game.", uci);
+                    return;
+                }
+                let Ok(m) = engine::parse_uci_move(&self.game, &uci) else {
+                    self.msg = format!("Illegal move {} from the opponent.", uci);
+                    return;
+                };
+                let mv = engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false).mv;
+                self.clocks = clocks;
+                self.record_clocks();
+                self.last_tick = Instant::now();
+                let changes = self.sync_board();
+                self.animate(&changes);
+                self.move_sound(&mv);
+                self.tagged = [0; 64];
+                self.tagged[m.src as usize] = 2;
+                self.tagged[m.dst as usize] = 2;
+                let (n, letters) = self.notation();
+                self.msg = mv.localized(n, letters).to_string();
+                self.state = STATE_UZ;
+            }
+            lan::Event::Message(lan::Message::OfferDraw) if lan.playing => {
+                lan.draw_offered = true;
+                self.msg = "The opponent offers a draw.".to_owned();
+            }
+            lan::Event::Message(lan::Message::AcceptDraw) if lan.playing => {
+                self.msg = "Draw by agreement.".to_owned();
+                self.end_game("Draw by agreement.", "1/2-1/2");
+            }
+            lan::Event::Message(lan::Message::DeclineDraw) => {
+                self.msg = "The opponent declines the draw.".to_owned();
+            }
+            lan::Event::Message(lan::Message::Resign) if lan.playing => {
+                let white = lan.white as usize;
+                self.msg = ["White resigns.", "Black resigns."][white].to_owned();
+                self.end_game(&self.msg.clone(), ["0-1", "1-0"][white]);
+            }
+            lan::Event::Error(e) => self.msg = format!("{}.", e),
+            lan::Event::Closed(error) => {
+                self.lan = None;
+                self.msg = match error {
+                    Some(e) => format!("Connection closed: {}.", e),
+                    None => "The opponent closed the connection.".to_owned(),
+                };
+            }
+            _ => {} // a message for a finished game
+        }
+    }
+
+    // a new LAN game from the start position, both players are human
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_lan_game(&mut self, white: bool) {
+        let Some(lan) = self.lan.as_mut() else {
+            return;
+        };
+        lan.white = white;
+        lan.playing = true;
+        lan.draw_offered = false;
+        self.rotated = white;
+        self.engine.send(Command::Stop);
+        engine::reset_game(&mut self.game);
+        self.new_position();
+        self.players = [HUMAN, HUMAN];
+        self.engine_plays_white = false;
+        self.engine_plays_black = false;
+        self.last_tick = Instant::now();
+    }
+
+    // A move on the e-board is played when the human player is to move, the moves of the
+    // engine or the opponent are made on the e-board by hand.
+    #[cfg(feature = "eboard")]
+    fn eboard_events(&mut self) {
+        let Some(driver) = &self.eboard else {
+            return;
+        };
+        let events: Vec<_> = std::iter::from_fn(|| driver.try_event()).collect();
+        for event in events {
+            match event {
+                eboard::Event::Connected(name) => self.msg = format!("Connected to {}.", name),
+                eboard::Event::Board(board) => self.eboard_board = Some(*board),
+                eboard::Event::Closed(e) => {
+                    self.msg = match e {
+                        Some(e) => format!("{}.", e),
+                        None => "The e-board is disconnected.".to_owned(),
+                    };
+                    self.eboard = None;
+                    self.eboard_board = None;
+                    self.eboard_differs.clear();
+                    return;
+                }
+            }
+        }
+        let Some(board) = self.eboard_board else {
+            return;
+        };
+        self.eboard_differs = match eboard::sync(&self.game, &board) {
+            BoardSync::InSync => Vec::new(),
+            BoardSync::Move(m) if self.state == STATE_U0 || self.state == STATE_U1 => {
+                self.human_move(m.src, m.dst, m.promote_to);
+                Vec::new()
+            }
+            BoardSync::Move(m) => vec![m.src, m.dst],
+            BoardSync::Differs(squares) => squares,
+        };
+    }
+
+    #[cfg(feature = "eboard")]
+    fn eboard_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        if self.eboard.is_none() {
+            if ui.button(tr(lang, "Connect Chessnut Air")).clicked() {
+                self.eboard = Some(Box::new(eboard::Chessnut::connect()));
+                self.msg = "Searching for the e-board.".to_owned();
+            }
+            return;
+        }
+        if self.eboard_board.is_none() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(tr(lang, "Waiting for the e-board"));
+            });
+        } else if self.eboard_differs.is_empty() {
+            ui.label(tr(lang, "The e-board shows the game."));
+        } else {
+            let squares: Vec<String> = self
+                .eboard_differs
+                .iter()
+                .map(|&p| engine::square_str(p))
+                .collect();
+            let text = format!(
+                "{} {}",
+                tr(lang, "The e-board differs at"),
+                squares.join(", ")
+            );
+            ui.colored_label(egui::Color32::RED, text);
+        }
+        if ui.button(tr(lang, "Disconnect")).clicked() {
+            self.eboard = None;
+            self.eboard_board = None;
+            self.eboard_differs.clear();
+        }
+    }
+
+    // the live files and the endpoint follow the game when the position or the result changes
+    #[cfg(not(target_arch = "wasm32"))]
+    fn broadcast(&mut self) {
+        if !self.broadcast_files && self.endpoint.is_none() {
+            return;
+        }
+        let key = (
+            engine::current_node(&self.game),
+            engine::position_key(&self.game),
+            self.game_result.is_some(),
+        );
+        if self.broadcast_key == Some(key) {
+            return;
+        }
+        self.broadcast_key = Some(key);
+        let live = broadcast::Live::new(&self.game, self.game_pgn());
+        if self.broadcast_files {
+            let dir = std::path::Path::new(&self.broadcast_dir);
+            if let Err(e) = broadcast::write_files(dir, &live) {
+                self.msg = format!("Writing {} failed: {}.", self.broadcast_dir, e);
+                self.broadcast_files = false;
+            }
+        }
+        if let Some(endpoint) = &self.endpoint {
+            endpoint.update(live);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn broadcast_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        ui.horizontal(|ui| {
+            ui.label(tr(lang, "Directory"));
+            ui.add_enabled(
+                !self.broadcast_files,
+                egui::TextEdit::singleline(&mut self.broadcast_dir),
+            );
+        });
+        if ui
+            .checkbox(&mut self.broadcast_files, tr(lang, "Write live files"))
+            .changed()
+        {
+            self.broadcast_key = None; // written at once
+        }
+        let mut http = self.endpoint.is_some();
+        if ui.checkbox(&mut http, tr(lang, "HTTP endpoint")).changed() {
+            self.broadcast_key = None;
+            self.endpoint = None;
+            if http {
+                match broadcast::Endpoint::start(broadcast::DEFAULT_PORT) {
+                    Ok(endpoint) => self.endpoint = Some(endpoint),
+                    Err(e) => self.msg = format!("{}.", e),
+                }
+            }
+        }
+        if let Some(endpoint) = &self.endpoint {
+            ui.label(format!("http://127.0.0.1:{}/json", endpoint.port()));
+        }
+    }
+
+    // an untimed game with conditional moves, see correspondence.rs
+    fn correspondence_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        if ui
+            .checkbox(&mut self.correspondence, tr(lang, "Correspondence mode"))
+            .on_hover_text(tr(lang, "Untimed, the game is kept between the sessions"))
+            .changed()
+        {
+            self.time_control = 0;
+            self.reset_clocks();
+            self.conditionals.clear();
+        }
+        if !self.correspondence {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.conditional_input)
+                    .hint_text("if e4 then c5 Nf3 d6"),
+            );
+            if ui.button(tr(lang, "Add")).clicked() {
+                match correspondence::parse_line(&self.game, &self.conditional_input) {
+                    Ok(line) => {
+                        self.conditionals.push(line);
+                        self.conditional_input.clear();
+                    }
+                    Err(e) => self.msg = format!("{}.", e),
+                }
+            }
+        });
+        let mut delete = None;
+        for (i, line) in self.conditionals.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}. {}", line.ply / 2 + 1, line.san));
+                if ui.button(tr(lang, "Delete")).clicked() {
+                    delete = Some(i);
+                }
+            });
+        }
+        if let Some(i) = delete {
+            self.conditionals.remove(i);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn lan_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        let Some(lan) = self.lan.as_mut() else {
+            ui.horizontal(|ui| {
+                ui.label(tr(lang, "Address"));
+                ui.text_edit_singleline(&mut self.lan_address);
+            });
+            ui.horizontal(|ui| {
+                let address = !self.lan_address.trim().is_empty();
+                if ui
+                    .add_enabled(address, egui::Button::new(tr(lang, "Join")))
+                    .clicked()
+                {
+                    self.lan = Some(Lan {
+                        connection: lan::Connection::join(&self.lan_address),
+                        opponent: None,
+                        playing: false,
+                        white: false,
+                        draw_offered: false,
+                    });
+                    self.msg = format!("Connecting to {}.", self.lan_address.trim());
+                }
+                if ui.button(tr(lang, "Host")).clicked() {
+                    match lan::Connection::host(lan::DEFAULT_PORT) {
+                        Ok(connection) => {
+                            self.lan = Some(Lan {
+                                connection,
+                                opponent: None,
+                                playing: false,
+                                white: true,
+                                draw_offered: false,
+                            });
+                            self.msg = format!("Waiting on port {}.", lan::DEFAULT_PORT);
+                        }
+                        Err(e) => self.msg = format!("Hosting failed: {}.", e),
+                    }
+                }
+            });
+            return;
+        };
+        let Some(opponent) = &lan.opponent else {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                if ui.button(tr(lang, "Cancel")).clicked() {
+                    self.lan = None;
+                }
+            });
+            return;
+        };
+        ui.label(opponent);
+        let (playing, draw_offered) = (lan.playing, lan.draw_offered);
+        let mut clicked = None;
+        ui.horizontal(|ui| {
+            let mut button =
+                |text: &'static str| ui.button(tr(lang, text)).clicked().then_some(text);
+            clicked = if !playing {
+                // the colors change, with the time control of the settings
+                button("New game").or(button("Disconnect"))
+            } else if draw_offered {
+                button("Accept draw")
+                    .or(button("Decline"))
+                    .or(button("Resign"))
+            } else {
+                button("Offer draw").or(button("Resign"))
+            };
+        });
+        let white = lan.white;
+        let message = match clicked {
+            Some("New game") => {
+                let clock = TIME_CONTROLS[self.time_control].1;
+                lan.connection.send(&lan::Message::Start { white, clock });
+                self.start_lan_game(!white);
+                return;
+            }
+            Some("Disconnect") => {
+                self.lan = None;
+                return;
+            }
+            Some("Accept draw") => lan::Message::AcceptDraw,
+            Some("Decline") => lan::Message::DeclineDraw,
+            Some("Offer draw") => lan::Message::OfferDraw,
+            Some("Resign") => lan::Message::Resign,
+            _ => return,
+        };
+        lan.connection.send(&message);
+        match message {
+            lan::Message::AcceptDraw => {
+                self.msg = "Draw by agreement.".to_owned();
+                self.end_game("Draw by agreement.", "1/2-1/2");
+            }
+            lan::Message::DeclineDraw => lan.draw_offered = false,
+            lan::Message::OfferDraw => self.msg = "Draw offered.".to_owned(),
+            _ => {
+                let white = white as usize;
+                self.msg = ["Black resigns.", "White resigns."][white].to_owned();
+                self.end_game(&self.msg.clone(), ["1-0", "0-1"][white]);
+            }
+        }
+    }
+
+    // the moves of the current position in the opening explorer, a click plays a move
+    #[cfg(feature = "lichess")]
+    fn explorer_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("explorer_database").show_index(
+                ui,
+                &mut self.explorer_database,
+                lichess::DATABASES.len(),
+                |i| tr(lang, lichess::DATABASES[i].0),
+            );
+            ui.checkbox(&mut self.explorer_offline, tr(lang, "Offline"));
+        });
+        let answer = self
+            .exploring
+            .as_ref()
+            .map(|(key, rx)| (*key, rx.try_recv()));
+        match answer {
+            Some((key, Ok(Ok(explorer)))) => {
+                if self.explored.len() == MAX_EXPLORED {
+                    self.explored.clear();
+                }
+                self.explored.insert(key, explorer);
+                self.exploring = None;
+            }
+            Some((_, Ok(Err(e)))) => {
+                self.msg = format!("{}.", e);
+                self.exploring = None;
+            }
+            Some((_, Err(TryRecvError::Empty))) => {
+                ui.ctx().request_repaint_after(Duration::from_millis(100));
+            }
+            Some((_, Err(TryRecvError::Disconnected))) => self.exploring = None,
+            None => {}
+        }
+        let key = (self.explorer_database, engine::position_key(&self.game));
+        let Some(explorer) = self.explored.get(&key) else {
+            if self.explorer_offline {
+                ui.label(tr(lang, "Not in the cache."));
+            } else if self.exploring.is_none() {
+                let fen = engine::fen(&self.game);
+                let rx = lichess::explore(self.explorer_database, &fen, &self.lichess_token);
+                self.exploring = Some((key, rx));
+            }
+            if self.exploring.is_some() {
+                ui.spinner();
+            }
+            return;
+        };
+        let (n, letters) = self.notation();
+        let mut play = None;
+        egui::Grid::new("explorer").striped(true).show(ui, |ui| {
+            for header in ["Move", "Games", "White", "Draw", "Black"] {
+                ui.strong(tr(lang, header));
+            }
+            ui.end_row();
+            for (uci, results) in &explorer.moves {
+                let Ok(m) = engine::parse_uci_move(&self.game, uci) else {
+                    continue;
+                };
+                let games: u64 = results.iter().sum();
+                if ui.link(m.localized(n, letters).to_string()).clicked() {
+                    play = Some(m);
+                }
+                ui.label(games.to_string());
+                for r in results {
+                    ui.label(format!("{:.0} %", 100.0 * *r as f64 / games.max(1) as f64));
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(m) = play.filter(|_| self.state == STATE_U0 || self.state == STATE_U1) {
+            self.human_move(m.src, m.dst, m.promote_to);
+        }
+    }
+
+    #[cfg(feature = "lichess")]
+    fn lichess_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        let Some(online) = self.online.as_mut() else {
+            ui.horizontal(|ui| {
+                ui.label(tr(lang, "API token"));
+                ui.add(egui::TextEdit::singleline(&mut self.lichess_token).password(true));
+            });
+            let token = !self.lichess_token.trim().is_empty();
+            if ui
+                .add_enabled(token, egui::Button::new(tr(lang, "Connect")))
+                .clicked()
+            {
+                self.online = Some(Online {
+                    client: lichess::Client::connect(&self.lichess_token),
+                    challenges: Vec::new(),
+                    game: None,
+                    white: true,
+                    names: Default::default(),
+                    clocks: [Duration::ZERO; 2],
+                });
+                self.msg = "Connected to lichess.".to_owned();
+            }
+            return;
+        };
+        if let Some(game) = &online.game {
+            ui.label(format!("{} - {}", online.names[0], online.names[1]));
+            for (i, name) in ["White", "Black"].iter().enumerate() {
+                let clock = format_clock(online.clocks[i]);
+                ui.monospace(format!("{} {}", tr(lang, name), clock));
+            }
+            ui.horizontal(|ui| {
+                if ui.button(tr(lang, "Resign")).clicked() {
+                    online.client.send(Request::Resign(game.clone()));
+                }
+                if ui.button(tr(lang, "Abort")).clicked() {
+                    online.client.send(Request::Abort(game.clone()));
+                }
+            });
+            return;
+        }
+        ui.horizontal(|ui| {
+            let (minutes, increment) = &mut self.seek;
+            ui.add(egui::DragValue::new(minutes).range(1..=180).suffix(" min"));
+            ui.add(egui::DragValue::new(increment).range(0..=180).suffix(" s"));
+            if online.client.seeking() {
+                ui.spinner();
+                if ui.button(tr(lang, "Cancel")).clicked() {
+                    online.client.send(Request::CancelSeek);
+                }
+            } else if ui.button(tr(lang, "Seek game")).clicked() {
+                let (minutes, increment) = (*minutes, *increment);
+                online.client.send(Request::Seek { minutes, increment });
+            }
+        });
+        let mut answered = None;
+        for (id, text) in &online.challenges {
+            ui.horizontal(|ui| {
+                ui.label(text);
+                if ui.button(tr(lang, "Accept")).clicked() {
+                    online.client.send(Request::Accept(id.clone()));
+                    answered = Some(id.clone());
+                }
+                if ui.button(tr(lang, "Decline")).clicked() {
+                    online.client.send(Request::Decline(id.clone()));
+                    answered = Some(id.clone());
+                }
+            });
+        }
+        if let Some(id) = answered {
+            online.challenges.retain(|(c, _)| *c != id);
+        }
+        if ui.button(tr(lang, "Disconnect")).clicked() {
+            self.online = None;
+        }
+    }
+
+    fn load_puzzles(&mut self) {
+        let file = std::fs::File::open(&self.puzzle_file);
+        match file.and_then(|f| puzzle::read_puzzles(std::io::BufReader::new(f), MAX_PUZZLES)) {
+            Ok(puzzles) if !puzzles.is_empty() => {
+                self.tried = vec![false; puzzles.len()];
+                self.puzzles = puzzles;
+                self.next_puzzle();
+            }
+            Ok(_) => self.msg = format!("No puzzles in {}.", self.puzzle_file),
+            Err(e) => self.msg = format!("Loading {} failed: {}.", self.puzzle_file, e),
+        }
+    }
+
+    // the untried puzzle with the rating closest to the rating of the solver
+    fn next_puzzle(&mut self) {
+        let untried = (0..self.puzzles.len()).filter(|&i| !self.tried[i]);
+        let Some(index) =
+            untried.min_by_key(|&i| (self.puzzles[i].rating - self.puzzle_rating).abs())
+        else {
+            self.msg = "All puzzles were tried.".to_owned();
+            return;
+        };
+        self.tried[index] = true;
+        let p = &self.puzzles[index];
+        let mut g = self.game.clone();
+        match puzzle::set_puzzle(&mut g, p) {
+            Ok(()) => {
+                self.msg = format!("Puzzle {}, rating {}", p.id, p.rating);
+                // the solver plays the second move
+                let moves = p.moves.clone();
+                self.start_exercise(g, Kind::Puzzle(index), moves, 1);
+            }
+            Err(e) => self.msg = format!("Invalid puzzle {}: {}.", p.id, e),
+        }
+    }
+
+    fn load_repertoire(&mut self) {
+        let pgn = match std::fs::read_to_string(&self.repertoire_file) {
+            Ok(pgn) => pgn,
+            Err(e) => {
+                self.msg = format!("Loading {} failed: {}.", self.repertoire_file, e);
+                return;
+            }
+        };
+        let mut g = self.game.clone();
+        if let Err(e) = engine::set_pgn(&mut g, &pgn) {
+            self.msg = format!("Invalid repertoire: {}.", e);
+            return;
+        }
+        let lines = repertoire::lines(&g);
+        if lines.is_empty() {
+            self.msg = format!("No moves in {}.", self.repertoire_file);
+            return;
+        }
+        engine::go_to_ply(&mut g, 0);
+        self.drill = Some(Drill {
+            start: engine::fen(&g),
+            lines: lines
+                .iter()
+                .map(|line| repertoire::line_key(line))
+                .collect(),
+            white: !self.drill_black,
+        });
+        self.next_line();
+    }
+
+    // the review state of the lines of the drill
+    fn cards(&self) -> Vec<repertoire::Card> {
+        let lines = self.drill.as_ref().map_or(&[][..], |d| &d.lines[..]);
+        let card = |key: &String| self.reviews.get(key).copied().unwrap_or_default();
+        lines.iter().map(card).collect()
+    }
+
+    // the line of the repertoire that is due first
+    fn next_line(&mut self) {
+        let cards = self.cards();
+        let (Some(drill), Some(index)) = (&self.drill, repertoire::next_card(&cards)) else {
+            return;
+        };
+        let mut g = self.game.clone();
+        if engine::set_fen(&mut g, &drill.start).is_err() {
+            return; // it was checked by set_pgn()
+        }
+        // the solver plays the even plies when the drilled player moves first
+        let solver = (g.move_counter.is_multiple_of(2) != drill.white) as usize;
+        let moves = drill.lines[index].split(' ').map(String::from).collect();
+        let due = repertoire::due_cards(&cards, unix_time());
+        self.msg = format!("Line {} of {}, {} due", index + 1, cards.len(), due);
+        self.start_exercise(g, Kind::Line(index), moves, solver);
+    }
+
+    // g is the start position, the solver plays the plies of the parity solver
+    fn start_exercise(&mut self, g: engine::Game, kind: Kind, moves: Vec<String>, solver: usize) {
+        self.engine.send(Command::Stop);
+        self.game = g;
+        self.new_position();
+        // the solver plays from the bottom of the board
+        self.rotated = (self.game.move_counter as usize + solver).is_multiple_of(2);
+        self.last_move = Instant::now();
+        self.exercise = Some(Exercise {
+            kind,
+            moves,
+            solver,
+            ply: 0,
+            failed: false,
+        });
+    }
+
+    // m is the next move of the exercise, a puzzle accepts any checkmate
+    fn is_solution(&self, ex: &Exercise, m: &engine::Move) -> bool {
+        match ex.kind {
+            Kind::Puzzle(i) => puzzle::is_solution(&self.game, &self.puzzles[i], ex.ply, m),
+            Kind::Line(_) => m.uci().to_string() == ex.moves[ex.ply],
+        }
+    }
+
+    // the first wrong move of a puzzle costs rating, a line is reviewed at its end
+    fn exercise_failed(&mut self) {
+        if let Some(ex) = self.exercise.as_mut().filter(|ex| !ex.failed) {
+            ex.failed = true;
+            if let Kind::Puzzle(i) = ex.kind {
+                let rating = self.puzzles[i].rating;
+                self.puzzle_rating = puzzle::new_rating(self.puzzle_rating, rating, false);
+            }
+        }
+    }
+
+    // after a move of the exercise, by the solver, the opponent or "Show solution"
+    fn exercise_move_done(&mut self) {
+        let Some(ex) = self.exercise.as_mut() else {
+            return;
+        };
+        ex.ply += 1;
+        self.last_move = Instant::now();
+        if ex.ply < ex.moves.len() {
+            return;
+        }
+        let result = if ex.failed { "Done" } else { "Solved" };
+        match ex.kind {
+            Kind::Puzzle(i) => {
+                if !ex.failed {
+                    let rating = self.puzzles[i].rating;
+                    self.puzzle_rating = puzzle::new_rating(self.puzzle_rating, rating, true);
+                }
+                self.msg = format!("{}! Puzzle rating {}", result, self.puzzle_rating);
+            }
+            Kind::Line(i) => {
+                let Some(drill) = &self.drill else {
+                    return;
+                };
+                let now = unix_time();
+                let card = self.reviews.entry(drill.lines[i].clone()).or_default();
+                repertoire::review(card, now, !ex.failed);
+                let days = (card.due - now) / repertoire::DAY;
+                self.msg = format!("{}! The line is due again in {} days.", result, days);
+            }
+        }
+    }
+
+    // the next move of the exercise, of the opponent or of the solution
+    fn exercise_move(&mut self) {
+        let Some(ex) = &self.exercise else {
+            return;
+        };
+        let uci = ex.moves.get(ex.ply);
+        let Some(m) = uci.and_then(|uci| engine::parse_uci_move(&self.game, uci).ok()) else {
+            return;
+        };
+        engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false);
+        let changes = self.sync_board();
+        self.animate(&changes);
+        self.move_sound(&m);
+        self.tagged = [0; 64];
+        self.tagged[m.src as usize] = 2;
+        self.tagged[m.dst as usize] = 2;
+        self.exercise_move_done();
+        self.state = STATE_UZ;
+    }
+
+    // start from self.game, for a new game or a loaded position
+    fn new_position(&mut self) {
+        self.editor = None;
+        self.exercise = None;
+        self.analysis = None;
+        self.report = None;
+        self.show_report = false;
+        self.scores.clear();
+        self.pv.clear();
+        self.premove.clear();
+        self.conditionals.clear();
+        self.game_result = None;
+        self.show_result = false;
+        self.reset_clocks();
+        self.state = STATE_UZ;
+        self.tagged = [0; 64];
+        self.sync_board();
+    }
+
+    fn load_fen(&mut self) {
+        let mut g = self.game.clone();
+        match engine::set_fen(&mut g, &self.fen_input) {
+            Ok(()) => {
+                self.engine.send(Command::Stop);
+                self.game = g;
+                self.new_position();
+                self.msg = "Position loaded.".to_owned();
+            }
+            Err(e) => self.msg = format!("Invalid FEN: {}.", e),
+        }
+    }
+    // a game copied from lichess or a chat, at the end of its main line
+    fn load_pgn(&mut self, pgn: &str) {
+        if pgn.trim().is_empty() {
+            return;
+        }
+        let mut g = self.game.clone();
+        match engine::set_pgn(&mut g, pgn) {
+            Ok(_) => {
+                self.engine.send(Command::Stop);
+                self.game = g;
+                self.new_position();
+                self.msg = "Game loaded.".to_owned();
+            }
+            Err(e) => self.msg = format!("Invalid PGN: {}.", e),
+        }
+    }
+
+    // a game string of engine::compact(), i.e. from the QR code on another device
+    fn load_compact(&mut self, s: &str) {
+        let mut g = self.game.clone();
+        match engine::set_compact(&mut g, s) {
+            Ok(()) => {
+                self.engine.send(Command::Stop);
+                self.game = g;
+                self.new_position();
+                self.msg = "Game loaded.".to_owned();
+            }
+            Err(e) => self.msg = format!("{}.", e),
+        }
+    }
+
+    // a PGN file with many games, only their tags are read for the game list
+    fn open_database(&mut self) {
+        match std::fs::read_to_string(&self.database_file) {
+            Ok(pgn) => {
+                let database = Database::new(pgn);
+                if database.games.is_empty() {
+                    self.msg = format!("No games in {}.", self.database_file);
+                    return;
+                }
+                self.msg = format!("{} games in {}.", database.games.len(), self.database_file);
+                self.show_games(database);
+            }
+            Err(e) => self.msg = format!("Loading {} failed: {}.", self.database_file, e),
+        }
+    }
+
+    // the game list of the database, with the current search
+    fn show_games(&mut self, database: Database) {
+        self.database_hits = database.search(&self.database_query);
+        self.database = Some(database);
+        self.show_database = true;
+    }
+
+    // a lichess game or the games of a PGN URL, see lichess::pgn_url()
+    #[cfg(feature = "lichess")]
+    fn import_game(&mut self) {
+        match lichess::pgn_url(&self.import_url) {
+            Some(url) => {
+                self.msg = format!("Downloading {}.", url);
+                self.importing = Some(lichess::fetch_pgn(&url));
+            }
+            None => self.msg = "Not a game URL of lichess or chess.com.".to_owned(),
+        }
+    }
+
+    // a single game is loaded, more games are shown in the game list
+    #[cfg(feature = "lichess")]
+    fn poll_import(&mut self, ctx: &egui::Context) {
+        let Some(rx) = &self.importing else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(Ok(pgn)) => {
+                self.importing = None;
+                match engine::split_pgn(&pgn).len() {
+                    0 => self.msg = "No games downloaded.".to_owned(),
+                    1 => self.load_pgn(&pgn),
+                    n => {
+                        self.msg = format!("{} games downloaded.", n);
+                        self.show_games(Database::new(pgn));
+                    }
+                }
+            }
+            Ok(Err(e)) => {
+                self.importing = None;
+                self.msg = format!("{}.", e);
+            }
+            Err(TryRecvError::Empty) => ctx.request_repaint_after(Duration::from_millis(100)),
+            Err(TryRecvError::Disconnected) => self.importing = None,
+        }
+    }
 
-const BOOL_TO_ENGINE: [u8; 2] = [HUMAN, ENGINE];
-const BOOL_TO_STATE: [i32; 2] = [STATE_U0, STATE_U2];
+    fn load_database_game(&mut self, index: usize) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let mut g = self.game.clone();
+        match database.load(&mut g, index) {
+            Ok(_) => {
+                self.engine.send(Command::Stop);
+                self.game = g;
+                self.new_position();
+                self.msg = "Game loaded.".to_owned();
+            }
+            Err(e) => self.msg = format!("Invalid PGN: {}.", e),
+        }
+    }
 
-fn _print_variable_type<K>(_: &K) {
-    println!("{}", std::any::type_name::<K>())
-}
+    // the games of the database found by the search, a click loads a game
+    fn database_window(&mut self, ctx: &egui::Context) {
+        let lang = self.lang();
+        let Some(database) = &self.database else {
+            return;
+        };
+        let mut open = self.show_database;
+        let mut searched = false;
+        let mut load = None;
+        let hits = &self.database_hits;
+        let query = &mut self.database_query;
+        egui::Window::new(tr(lang, "Database"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr(lang, "Search"));
+                    searched = ui.text_edit_singleline(query).changed();
+                });
+                ui.label(format!("{} of {} games", hits.len(), database.games.len()));
+                ui.separator();
+                let height = ui.spacing().interact_size.y;
+                // only the visible rows of a large database are shown
+                egui::ScrollArea::vertical().show_rows(ui, height, hits.len(), |ui, rows| {
+                    for &i in &hits[rows] {
+                        let h = &database.games[i];
+                        let text = format!(
+                            "{} - {}  {}  {}  {}  {}",
+                            h.white, h.black, h.result, h.eco, h.date, h.event
+                        );
+                        if ui.selectable_label(false, text).clicked() {
+                            load = Some(i);
+                        }
+                    }
+                });
+            });
+        if searched {
+            self.database_hits = database.search(&self.database_query);
+        }
+        self.show_database = open;
+        if let Some(i) = load {
+            self.load_database_game(i);
+        }
+    }
 
-fn _rot_180(b: engine::Board) -> engine::Board {
-    let mut result: engine::Board = [0; 64];
-    for (i, f) in b.iter().enumerate() {
-        result[63 - i] = *f;
+    // the saved games in the current directory, from the view of the human player
+    fn build_tree(&mut self) {
+        let entries = match std::fs::read_dir(".") {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.msg = format!("Reading the saved games failed: {}.", e);
+                return;
+            }
+        };
+        let mut tree = OpeningTree::new();
+        let mut games = 0;
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let saved = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(SAVED_PREFIX) && name.ends_with(".pgn"));
+            let Some(pgn) = saved.then(|| std::fs::read_to_string(&path).ok()).flatten() else {
+                continue;
+            };
+            for game in engine::split_pgn(&pgn) {
+                if let Ok(true) = tree.add_game(game, HUMAN_NAME, TREE_PLIES) {
+                    games += 1;
+                }
+            }
+        }
+        self.msg = format!("{} games in the opening tree.", games);
+        self.tree = Some(tree);
     }
-    result
-}
 
-fn main() -> Result<(), eframe::Error> {
-    //env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-    let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1200.0, 800.0]),
-        ..Default::default()
-    };
-    eframe::run_native(
-        "My egui App",
-        options,
-        Box::new(|cc| {
-            // This gives us image support:
-            egui_extras::install_image_loaders(&cc.egui_ctx);
-            //Box::<MyApp>::default()
-            Ok(Box::<MyApp>::default())
-        }),
-    )
-}
+    // the moves of the saved games in the current position, a click plays a move
+    fn tree_ui(&mut self, ui: &mut egui::Ui) {
+        let lang = self.lang();
+        if ui.button(tr(lang, "Build tree")).clicked() {
+            self.build_tree();
+        }
+        let Some(tree) = &self.tree else {
+            return;
+        };
+        let moves = tree.moves(&self.game);
+        if moves.is_empty() {
+            ui.label(tr(lang, "No games in this position."));
+            return;
+        }
+        let (n, letters) = self.notation();
+        let mut play = None;
+        egui::Grid::new("opening_tree")
+            .striped(true)
+            .show(ui, |ui| {
+                for header in ["Move", "Games", "Score"] {
+                    ui.strong(tr(lang, header));
+                }
+                ui.end_row();
+                for t in moves {
+                    let Ok(m) = engine::parse_uci_move(&self.game, &t.uci) else {
+                        continue;
+                    };
+                    if ui.link(m.localized(n, letters).to_string()).clicked() {
+                        play = Some(m);
+                    }
+                    ui.label(t.games().to_string());
+                    ui.label(format!("{:.0} %", 100.0 * t.score()));
+                    ui.end_row();
+                }
+            });
+        if let Some(m) = play.filter(|_| self.state == STATE_U0 || self.state == STATE_U1) {
+            self.human_move(m.src, m.dst, m.promote_to);
+        }
+    }
 
-struct MyApp {
-    game: Arc<Mutex<engine::Game>>,
-    msg: String,
-    rotated: bool,
-    time_per_move: f32,
-    tagged: engine::Board,
-    state: engine::State,
-    players: [u8; 2],
-    engine_plays_white: bool,
-    engine_plays_black: bool,
-    p0: i32,
-    new_game: bool,
-    bbb: engine::Board,
-    rx: Option<mpsc::Receiver<engine::Move>>,
-}
+    fn edit_board(&mut self) {
+        self.engine.send(Command::Stop);
+        self.editor = Some(Editor::new(&self.game));
+        self.premove.clear();
+        self.tagged = [0; 64];
+        self.in_check = None;
+        self.state = STATE_UX;
+        self.msg = "Edit the board.".to_owned();
+    }
 
-impl Default for MyApp {
-    fn default() -> Self {
-        Self {
-            game: Arc::new(Mutex::new(engine::new_game())),
-            msg: "Tiny chess".to_owned(),
-            time_per_move: 1.5,
-            rotated: true,
-            tagged: [0; 64],
-            players: [HUMAN, ENGINE],
-            p0: -1,
-            state: STATE_UZ,
-            bbb: [0; 64],
-            new_game: true,
-            engine_plays_white: false,
-            engine_plays_black: true,
-            rx: None, // Initialize receiver as None
+    // a typed move in SAN like "Nf3" or in UCI notation like "g1f3"
+    fn typed_move(&mut self) {
+        let text = std::mem::take(&mut self.move_input);
+        if text.trim().is_empty() {
+            return;
         }
+        if self.state != STATE_U0 && self.state != STATE_U1 {
+            self.msg = "Not your turn.".to_owned();
+            return;
+        }
+        let m = engine::parse_san(&self.game, &text)
+            .or_else(|e| engine::parse_uci_move(&self.game, &text).map_err(|_| e));
+        match m {
+            Ok(m) => self.human_move(m.src, m.dst, m.promote_to),
+            Err(e) => self.msg = format!("{}: {}.", text.trim(), e),
+        }
+    }
+
+    fn engine_move(&mut self, m: engine::SearchResult) {
+        self.tagged = [0; 64];
+        self.tagged[m.src as usize] = 2;
+        self.tagged[m.dst as usize] = 2;
+        let eval = self.white_view(m.score);
+        self.add_increment(self.game.move_counter as usize % 2);
+        let mv = engine::do_move(
+            &mut self.game,
+            m.src as i8,
+            m.dst as i8,
+            m.promote_to,
+            false,
+        )
+        .mv;
+        self.record_clocks();
+        let changes = self.sync_board();
+        self.animate(&changes);
+        self.move_sound(&mv);
+        if self.evaluator.is_none() {
+            self.eval = eval;
+        }
+        self.scores.push(eval);
+        self.last_move = Instant::now();
+        let (n, letters) = self.notation();
+        self.msg = format!("{} (score: {})", mv.localized(n, letters), m.score);
+        if m.score > engine::KING_VALUE_DIV_2 as i64 {
+            self.msg.push_str(&format!(
+                " Checkmate in {}",
+                (engine::KING_VALUE as i64 - m.score) / 2
+            ));
+        }
+        self.state = STATE_UZ;
     }
 }
 
 impl eframe::App for MyApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SETTINGS_KEY, &self.settings());
+        // a game of the last session is kept until the dialog is answered
+        if self.resume.is_none() {
+            eframe::set_value(storage, GAME_KEY, &self.saved_game());
+        }
+    }
+
+    // the game survives a crash, at the loss of the last few seconds
+    fn auto_save_interval(&self) -> Duration {
+        Duration::from_secs(5)
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.set_pixels_per_point(1.5);
-        if let Ok(ref mut mutex) = self.game.try_lock() {
-            if self.new_game {
-                engine::reset_game(mutex);
-                self.new_game = false;
-                self.state = STATE_UZ;
-                self.tagged = [0; 64];
+        let lang = self.lang();
+        // Ctrl+scroll zooms, egui itself handles Ctrl+plus, Ctrl+minus and Ctrl+0
+        let zoom_delta = ctx.input(|i| i.zoom_delta());
+        if zoom_delta != 1.0 {
+            ctx.set_zoom_factor((ctx.zoom_factor() * zoom_delta).clamp(MIN_ZOOM, MAX_ZOOM));
+        }
+        if ctx.dragged_id().is_none() {
+            self.zoom = ctx.zoom_factor(); // but not while the zoom slider is dragged
+        }
+        // larger buttons and spacing for fingers in touch mode
+        let scale = if self.touch { TOUCH_SCALE } else { 1.0 };
+        ctx.style_mut(|style| {
+            let default = egui::style::Spacing::default();
+            style.spacing.interact_size = default.interact_size * scale;
+            style.spacing.button_padding = default.button_padding * scale;
+            style.spacing.item_spacing = default.item_spacing * scale;
+            style.spacing.icon_width = default.icon_width * scale;
+        });
+        if self.new_game {
+            self.engine.send(Command::Stop);
+            engine::reset_game(&mut self.game);
+            self.new_position();
+            self.new_game = false;
+        }
+        self.tick();
+        if self.ticking() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if self.options_changed {
+            for (o, value) in engine::OPTIONS.iter().zip(&self.options) {
+                self.engine
+                    .send(Command::SetOption(o.name.to_string(), value.to_string()));
             }
-            self.bbb = engine::get_board(mutex);
-            mutex.secs_per_move = self.time_per_move;
+            self.engine.send(Command::SetBook(self.book.clone())); // for a new engine
+            self.options_changed = false;
         }
-
-        let mut x: i8 = -1;
-        let mut y: i8 = -1;
-        egui::SidePanel::left("side_panel")
-            .min_width(200.0)
-            .show(ctx, |ui| {
-                ui.ctx()
-                    .send_viewport_cmd(egui::ViewportCommand::Title(self.msg.clone()));
-                ui.heading(self.msg.clone());
-                ui.add(egui::Slider::new(&mut self.time_per_move, 0.1..=5.0).text("Sec/move"));
-                if ui.button("Rotate").clicked() {
-                    self.rotated ^= true;
-                    self.tagged.reverse();
-                }
-                if ui.button("Print movelist").clicked() {
-                    engine::print_move_list(&self.game.lock().unwrap());
+        while let Some(event) = self.engine.try_event() {
+            match event {
+                Event::BestMove { key, result }
+                    if self.state == STATE_U3 && key == engine::position_key(&self.game) =>
+                {
+                    self.engine_move(result)
                 }
-                if ui.button("New Game").clicked() {
-                    self.new_game = true;
+                Event::Info(info) if self.state == STATE_U3 => {
+                    if self.evaluator.is_none() {
+                        self.eval = self.white_view(info.score);
+                    }
+                    let (n, letters) = self.notation();
+                    let line = info.pv.iter().map(|m| m.localized(n, letters).to_string());
+                    let line: Vec<String> = line.collect();
+                    self.pv = format!("Depth {}: {}", info.depth, line.join(" "));
+                    self.progress = (info.nodes, info.time);
+                    self.stats = Some(info);
                 }
-                if ui
-                    .checkbox(&mut self.engine_plays_white, "Engine plays white")
-                    .changed()
-                {
-                    self.players[0] = BOOL_TO_ENGINE[self.engine_plays_white as usize];
-                    self.state = STATE_UZ;
+                Event::Progress { nodes, time } if self.state == STATE_U3 => {
+                    self.progress = (nodes, time);
                 }
-                if ui
-                    .checkbox(&mut self.engine_plays_black, "Engine plays black")
-                    .changed()
-                {
-                    self.players[1] = BOOL_TO_ENGINE[self.engine_plays_black as usize];
-                    self.state = STATE_UZ;
+                Event::OptionError(e) => self.msg = format!("{}.", e),
+                Event::Error(e) => self.msg = format!("{}.", e),
+                _ => {} // progress, or the reply for an old position
+            }
+        }
+        self.evaluate();
+        if self.evaluator.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        #[cfg(feature = "lichess")]
+        self.lichess_events();
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.lan.is_some() {
+            self.lan_events();
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        self.broadcast();
+        #[cfg(feature = "eboard")]
+        if self.eboard.is_some() {
+            self.eboard_events();
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        // the status bar spans the window below the side panel
+        if self.history.back() != Some(&self.msg) {
+            if self.history.len() == MAX_HISTORY {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.msg.clone());
+        }
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.menu_button(tr(lang, "History"), |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for msg in self.history.iter().rev() {
+                                ui.label(tr(lang, msg));
+                            }
+                        });
+                });
+                ui.separator();
+                if self.state == STATE_U2 || self.state == STATE_U3 {
+                    ui.spinner();
                 }
-                ui.image(egui::include_image!("ferris.png"));
+                // a live region, screen readers announce the moves, checks and results
+                let status = ui.strong(tr(lang, &self.msg));
+                ui.ctx().accesskit_node_builder(status.id, |node| {
+                    node.set_live(egui::accesskit::Live::Polite);
+                });
             });
+        });
+        let mut x: i8 = -1;
+        let mut y: i8 = -1;
+        // F11 toggles fullscreen, F5 the presentation layout, which Escape leaves as well
+        let (f11, f5, escape) = ctx.input(|i| {
+            let pressed = |key| i.key_pressed(key);
+            (
+                pressed(egui::Key::F11),
+                pressed(egui::Key::F5),
+                pressed(egui::Key::Escape),
+            )
+        });
+        if f11 {
+            let fullscreen = ctx.input(|i| i.viewport().fullscreen.unwrap_or(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(!fullscreen));
+        }
+        if f5 || escape && self.presentation {
+            self.presentation ^= true;
+        }
+        // Ctrl+V outside of the text fields loads a game from the clipboard
+        let pasted = ctx.input(|i| {
+            i.events.iter().find_map(|e| match e {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        });
+        if let Some(pgn) = pasted.filter(|_| !ctx.wants_keyboard_input()) {
+            self.load_pgn(&pgn);
+        }
+        self.update_opening();
+        if self.presentation {
+            // for a projector: a large move list without the settings
+            egui::SidePanel::left("presentation_panel")
+                .min_width(300.0)
+                .show(ctx, |ui| {
+                    for font in ui.style_mut().text_styles.values_mut() {
+                        font.size *= PRESENTATION_SCALE;
+                    }
+                    ui.label(egui::RichText::new(&self.opening.1).italics());
+                    if ui.button(tr(lang, "Leave presentation")).clicked() {
+                        self.presentation = false;
+                    }
+                    self.move_list(ui);
+                });
+        } else {
+            egui::SidePanel::left("side_panel")
+                .min_width(200.0)
+                .show(ctx, |ui| {
+                    ui.label(&self.pv);
+                    ui.label(egui::RichText::new(&self.opening.1).italics());
+                    if ui.button(tr(lang, "Presentation")).clicked() {
+                        self.presentation = true;
+                    }
+                    #[cfg(feature = "lichess")]
+                    ui.collapsing(tr(lang, "Opening explorer"), |ui| self.explorer_ui(ui));
+                    ui.collapsing(tr(lang, "Opening tree"), |ui| self.tree_ui(ui));
+                    ui.collapsing(tr(lang, "Engine statistics"), |ui| {
+                        let (nodes, time) = self.progress;
+                        let nps = nodes as u128 * 1000 / time.as_millis().max(1);
+                        let (depth, seldepth, hashfull) = self
+                            .stats
+                            .as_ref()
+                            .map_or((0, 0, 0), |s| (s.depth, s.seldepth, s.hashfull));
+                        egui::Grid::new("engine_statistics").show(ui, |ui| {
+                            for (name, value) in [
+                                ("Depth", format!("{}/{}", depth, seldepth)),
+                                ("Nodes", nodes.to_string()),
+                                ("Nodes per second", nps.to_string()),
+                                ("Hash full", format!("{:.1} %", hashfull as f32 / 10.0)),
+                                ("Time", format!("{:.1} s", time.as_secs_f32())),
+                            ] {
+                                ui.label(tr(lang, name));
+                                ui.monospace(value);
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if self.time_control().is_some() {
+                        let active = self.game.move_counter as usize % 2;
+                        for (i, name) in ["White", "Black"].iter().enumerate() {
+                            let text =
+                                format!("{} {}", tr(lang, name), format_clock(self.clocks[i]));
+                            let text = egui::RichText::new(text).monospace().size(20.0);
+                            ui.label(if i == active && self.ticking() {
+                                text.strong()
+                            } else {
+                                text.weak()
+                            });
+                        }
+                    }
+                    ui.checkbox(&mut self.sounds.muted, tr(lang, "Mute"));
+                    if egui::ComboBox::from_label(tr(lang, "Time control"))
+                        .show_index(ui, &mut self.time_control, TIME_CONTROLS.len(), |i| {
+                            TIME_CONTROLS[i].0
+                        })
+                        .changed()
+                    {
+                        self.reset_clocks();
+                    }
+                    let mut index = variant::VARIANTS
+                        .iter()
+                        .position(|v| v.name() == engine::variant(&self.game).name())
+                        .unwrap_or(0);
+                    if egui::ComboBox::from_label(tr(lang, "Variant"))
+                        .show_index(ui, &mut index, variant::VARIANTS.len(), |i| {
+                            tr(lang, variant::VARIANTS[i].name())
+                        })
+                        .changed()
+                    {
+                        // the new rules start with a new game
+                        engine::set_variant(&mut self.game, variant::VARIANTS[index]);
+                        self.new_game = true;
+                    }
+                    for (o, value) in engine::OPTIONS.iter().zip(&mut self.options) {
+                        let response = match (o.kind, value) {
+                            (
+                                engine::OptionType::Spin { min, max, .. },
+                                engine::OptionValue::Spin(v),
+                            ) => ui.add(egui::Slider::new(v, min..=max).text(o.name)),
+                            (_, engine::OptionValue::Check(v)) => ui.checkbox(v, o.name),
+                            (
+                                engine::OptionType::Combo { values, .. },
+                                engine::OptionValue::String(v),
+                            ) => {
+                                let mut changed = false;
+                                let selected = tr(lang, v).to_string();
+                                let mut response = egui::ComboBox::from_label(o.name)
+                                    .selected_text(selected)
+                                    .show_ui(ui, |ui| {
+                                        for &value in values {
+                                            changed |= ui
+                                                .selectable_value(
+                                                    v,
+                                                    value.to_string(),
+                                                    tr(lang, value),
+                                                )
+                                                .changed();
+                                        }
+                                    })
+                                    .response;
+                                if changed {
+                                    response.mark_changed();
+                                }
+                                response
+                            }
+                            (_, engine::OptionValue::String(v)) => {
+                                ui.horizontal(|ui| {
+                                    ui.label(o.name);
+                                    ui.text_edit_singleline(v)
+                                })
+                                .inner
+                            }
+                            _ => unreachable!(),
+                        };
+                        // apply when editing is done, as a new Hash size reallocates the table
+                        if response.drag_stopped()
+                            || response.lost_focus()
+                            || response.changed() && !response.dragged() && !response.has_focus()
+                        {
+                            self.options_changed = true;
+                        }
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ui.collapsing(tr(lang, "UCI engine"), |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(tr(lang, "Path"));
+                            ui.text_edit_singleline(&mut self.engine_input);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button(tr(lang, "Start engine")).clicked() {
+                                self.set_engine(&self.engine_input.clone());
+                            }
+                            let external = !self.engine_path.is_empty();
+                            if ui
+                                .add_enabled(
+                                    external,
+                                    egui::Button::new(tr(lang, "Built-in engine")),
+                                )
+                                .clicked()
+                            {
+                                self.set_engine("");
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label(tr(lang, "Analysis engine"));
+                            let response = ui.text_edit_singleline(&mut self.analysis_engine);
+                            if response.lost_focus() && self.evaluator.is_some() {
+                                self.set_evaluator(true); // restart with the new path
+                            }
+                        });
+                        let mut on = self.evaluator.is_some();
+                        let enabled = !self.analysis_engine.trim().is_empty();
+                        let checkbox = egui::Checkbox::new(&mut on, tr(lang, "Engine evaluation"));
+                        if ui.add_enabled(enabled, checkbox).changed() {
+                            self.set_evaluator(on);
+                        }
+                    });
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ui.collapsing(tr(lang, "Opening book"), |ui| self.book_ui(ui));
+                    egui::ComboBox::from_label(tr(lang, "Hints")).show_index(
+                        ui,
+                        &mut self.hints,
+                        HINTS.len(),
+                        |i| tr(lang, HINTS[i]),
+                    );
+                    egui::ComboBox::from_label(tr(lang, "Highlights")).show_index(
+                        ui,
+                        &mut self.highlight,
+                        HIGHLIGHTS.len(),
+                        |i| tr(lang, HIGHLIGHTS[i]),
+                    );
+                    egui::ComboBox::from_label(tr(lang, "Language")).show_index(
+                        ui,
+                        &mut self.language,
+                        i18n::LANGUAGES.len(),
+                        |i| i18n::LANGUAGES[i].0,
+                    );
+                    egui::ComboBox::from_label(tr(lang, "Notation")).show_index(
+                        ui,
+                        &mut self.notation,
+                        NOTATIONS.len(),
+                        |i| tr(lang, NOTATIONS[i].0),
+                    );
+                    if egui::ComboBox::from_label(tr(lang, "Theme"))
+                        .show_index(ui, &mut self.theme, THEMES.len(), |i| tr(lang, THEMES[i].0))
+                        .changed()
+                    {
+                        ui.ctx().set_theme(THEMES[self.theme].1);
+                    }
+                    // applied when dragging ends, the slider would move with the zoomed panel
+                    let response = ui.add(
+                        egui::Slider::new(&mut self.zoom, MIN_ZOOM..=MAX_ZOOM)
+                            .text(tr(lang, "Zoom"))
+                            .fixed_decimals(2),
+                    );
+                    if response.drag_stopped() || response.changed() && !response.dragged() {
+                        ui.ctx().set_zoom_factor(self.zoom);
+                    }
+                    egui::ComboBox::from_label(tr(lang, "Pieces")).show_index(
+                        ui,
+                        &mut self.piece_set,
+                        PIECE_SETS.len(),
+                        |i| PIECE_SETS[i],
+                    );
+                    ui.checkbox(&mut self.blindfold, tr(lang, "Blindfold"));
+                    ui.checkbox(&mut self.touch, tr(lang, "Touch mode"));
+                    if ui.button(tr(lang, "Rotate")).clicked() {
+                        self.rotated ^= true;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label(tr(lang, "Move"));
+                        let response = ui.text_edit_singleline(&mut self.move_input);
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            self.typed_move();
+                            response.request_focus(); // for the next move
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("FEN");
+                        ui.text_edit_singleline(&mut self.fen_input);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Load FEN")).clicked() {
+                            self.load_fen();
+                        }
+                        if ui.button(tr(lang, "Copy FEN")).clicked() {
+                            self.fen_input = engine::fen(&self.game);
+                            ui.ctx().copy_text(self.fen_input.clone());
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Copy game as PGN")).clicked() {
+                            ui.ctx().copy_text(self.game_pgn());
+                            self.msg = "Game copied.".to_owned();
+                        }
+                        if ui.button(tr(lang, "Paste PGN")).clicked() {
+                            self.pgn_input = Some(String::new());
+                        }
+                        if ui.button(tr(lang, "Share game")).clicked() {
+                            self.share_input = Some(String::new());
+                        }
+                    });
+                    #[cfg(feature = "lichess")]
+                    ui.horizontal(|ui| {
+                        self.poll_import(ui.ctx());
+                        ui.label("URL");
+                        ui.text_edit_singleline(&mut self.import_url);
+                        let idle = self.importing.is_none();
+                        if ui
+                            .add_enabled(idle, egui::Button::new(tr(lang, "Import from URL")))
+                            .clicked()
+                        {
+                            self.import_game();
+                        }
+                        if !idle {
+                            ui.spinner();
+                        }
+                    });
+                    if ui.button(tr(lang, "Open in lichess analysis")).clicked() {
+                        let url = engine::lichess_analysis_url(&self.game);
+                        ui.ctx().open_url(egui::OpenUrl::new_tab(url));
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button(tr(lang, "Export position image")).clicked() {
+                        self.image_file.get_or_insert_with(|| IMAGE_FILE.to_owned());
+                        self.replay_plies = (0, engine::move_list(&self.game).len());
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Undo")).clicked() {
+                            self.undo();
+                        }
+                        if ui.button(tr(lang, "Redo")).clicked() {
+                            self.redo();
+                        }
+                    });
+                    if ui
+                        .add_enabled(
+                            self.editor.is_none(),
+                            egui::Button::new(tr(lang, "Edit board")),
+                        )
+                        .clicked()
+                    {
+                        self.edit_board();
+                    }
+                    let can_analyze = self.analysis.is_none() && engine::game_ply(&self.game) > 0;
+                    if ui
+                        .add_enabled(can_analyze, egui::Button::new(tr(lang, "Analyze game")))
+                        .clicked()
+                    {
+                        self.analyze();
+                    }
+                    ui.collapsing(tr(lang, "Analysis thresholds"), |ui| {
+                        let t = &mut self.thresholds;
+                        for (value, text) in [
+                            (&mut t.inaccuracy, "Inaccuracy ?! (cp)"),
+                            (&mut t.mistake, "Mistake ? (cp)"),
+                            (&mut t.blunder, "Blunder ?? (cp)"),
+                            (&mut t.good, "Good ! after a loss of (cp)"),
+                        ] {
+                            ui.add(egui::Slider::new(value, 10..=1000).text(tr(lang, text)));
+                        }
+                    });
+                    ui.collapsing(tr(lang, "Puzzles"), |ui| {
+                        ui.label(format!("Puzzle rating {}", self.puzzle_rating));
+                        ui.horizontal(|ui| {
+                            ui.label(tr(lang, "File"));
+                            ui.text_edit_singleline(&mut self.puzzle_file);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button(tr(lang, "Load puzzles")).clicked() {
+                                self.load_puzzles();
+                            }
+                            let loaded = !self.puzzles.is_empty();
+                            if ui
+                                .add_enabled(loaded, egui::Button::new(tr(lang, "Next puzzle")))
+                                .clicked()
+                            {
+                                self.next_puzzle();
+                            }
+                        });
+                        if let Some(Kind::Puzzle(i)) = self.exercise.as_ref().map(|ex| ex.kind) {
+                            let themes = self.puzzles[i].themes.join(", ");
+                            ui.label(egui::RichText::new(themes).weak());
+                        }
+                    });
+                    ui.collapsing(tr(lang, "Repertoire drill"), |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("PGN");
+                            ui.text_edit_singleline(&mut self.repertoire_file);
+                        });
+                        ui.checkbox(&mut self.drill_black, tr(lang, "Drill the moves of Black"));
+                        ui.horizontal(|ui| {
+                            if ui.button(tr(lang, "Load repertoire")).clicked() {
+                                self.load_repertoire();
+                            }
+                            if ui
+                                .add_enabled(
+                                    self.drill.is_some(),
+                                    egui::Button::new(tr(lang, "Next line")),
+                                )
+                                .clicked()
+                            {
+                                self.next_line();
+                            }
+                        });
+                        if self.drill.is_some() {
+                            let cards = self.cards();
+                            let due = repertoire::due_cards(&cards, unix_time());
+                            ui.label(format!("{} lines, {} due", cards.len(), due));
+                        }
+                    });
+                    ui.collapsing(tr(lang, "Database"), |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("PGN");
+                            ui.text_edit_singleline(&mut self.database_file);
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button(tr(lang, "Open database")).clicked() {
+                                self.open_database();
+                            }
+                            if ui
+                                .add_enabled(
+                                    self.database.is_some(),
+                                    egui::Button::new(tr(lang, "Game list")),
+                                )
+                                .clicked()
+                            {
+                                self.show_database = true;
+                            }
+                        });
+                    });
+                    #[cfg(feature = "lichess")]
+                    ui.collapsing("Lichess", |ui| self.lichess_ui(ui));
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ui.collapsing(tr(lang, "LAN game"), |ui| self.lan_ui(ui));
+                    ui.collapsing(tr(lang, "Correspondence"), |ui| self.correspondence_ui(ui));
+                    #[cfg(not(target_arch = "wasm32"))]
+                    ui.collapsing(tr(lang, "Broadcast"), |ui| self.broadcast_ui(ui));
+                    #[cfg(feature = "eboard")]
+                    ui.collapsing(tr(lang, "E-board"), |ui| self.eboard_ui(ui));
+                    // the solver is to move
+                    let solving = self.exercise.is_some()
+                        && (self.state == STATE_U0 || self.state == STATE_U1);
+                    if ui
+                        .add_enabled(solving, egui::Button::new(tr(lang, "Show solution")))
+                        .clicked()
+                    {
+                        self.exercise_failed();
+                        self.exercise_move();
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "New Game")).clicked() {
+                            self.new_game = true;
+                        }
+                        // the human player to move resigns
+                        let active = self.game.move_counter as usize % 2;
+                        let can_resign = self.game_result.is_none()
+                            && self.players[active] == HUMAN
+                            && !self.online_game(); // see lichess_ui()
+                        if ui
+                            .add_enabled(can_resign, egui::Button::new(tr(lang, "Resign")))
+                            .clicked()
+                        {
+                            self.msg = ["White resigns.", "Black resigns."][active].to_owned();
+                            self.end_game(&self.msg.clone(), ["0-1", "1-0"][active]);
+                        }
+                    });
+                    if ui
+                        .checkbox(&mut self.engine_plays_white, tr(lang, "Engine plays white"))
+                        .changed()
+                    {
+                        self.players[0] = BOOL_TO_ENGINE[self.engine_plays_white as usize];
+                        self.orient();
+                        if self.state != STATE_UX {
+                            self.state = STATE_UZ;
+                        }
+                    }
+                    if ui
+                        .checkbox(&mut self.engine_plays_black, tr(lang, "Engine plays black"))
+                        .changed()
+                    {
+                        self.players[1] = BOOL_TO_ENGINE[self.engine_plays_black as usize];
+                        self.orient();
+                        if self.state != STATE_UX {
+                            self.state = STATE_UZ;
+                        }
+                    }
+                    if self.players == [HUMAN, HUMAN] {
+                        ui.checkbox(&mut self.auto_rotate, tr(lang, "Rotate after each move"));
+                        ui.checkbox(&mut self.confirm_moves, tr(lang, "Confirm moves"));
+                    }
+                    if self.players == [ENGINE, ENGINE] {
+                        ui.add(
+                            egui::Slider::new(&mut self.autoplay_delay, 0..=5000)
+                                .text(tr(lang, "Delay (ms)")),
+                        );
+                        let label = if self.paused { "Resume" } else { "Pause" };
+                        if ui.button(tr(lang, label)).clicked() {
+                            self.paused ^= true;
+                        }
+                    }
+                    ui.image(egui::include_image!("ferris.png"));
+                    // the variation of the current position
+                    let (promote, delete) = ui
+                        .horizontal(|ui| {
+                            let promote = ui.button(tr(lang, "Promote variation")).clicked();
+                            (promote, ui.button(tr(lang, "Delete variation")).clicked())
+                        })
+                        .inner;
+                    if promote {
+                        engine::promote_variation(&mut self.game);
+                    }
+                    if delete {
+                        self.engine.send(Command::Stop);
+                        if engine::delete_variation(&mut self.game) {
+                            self.browsed();
+                        }
+                    }
+                    // the annotation of the move leading to the current position
+                    let current = engine::current_node(&self.game);
+                    ui.horizontal(|ui| {
+                        let nags = engine::node_nags(&self.game, current);
+                        let mut nag = nags.first().copied().unwrap_or(0);
+                        let old = nag;
+                        ui.add_enabled_ui(current != engine::ROOT_NODE, |ui| {
+                            let text = if nag == 0 {
+                                "-".to_owned()
+                            } else {
+                                nag_text(nag)
+                            };
+                            egui::ComboBox::from_id_salt("nag")
+                                .width(40.0)
+                                .selected_text(text)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut nag, 0, "-");
+                                    for (n, symbol) in engine::NAG_SYMBOLS {
+                                        ui.selectable_value(&mut nag, n, symbol);
+                                    }
+                                });
+                        });
+                        if nag != old {
+                            let nags: &[u8] = if nag == 0 { &[] } else { &[nag] };
+                            engine::set_node_nags(&mut self.game, current, nags);
+                        }
+                        let mut comment = engine::node_comment(&self.game, current).to_owned();
+                        let response = ui.text_edit_singleline(&mut comment);
+                        if response.on_hover_text(tr(lang, "Comment")).changed() {
+                            engine::set_node_comment(&mut self.game, current, &comment);
+                        }
+                    });
+                    self.move_list(ui);
+                });
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
-            if self.state == STATE_U2 {
-                ui.ctx().send_viewport_cmd(egui::ViewportCommand::Title(
-                    " ... one moment please, reply is:".to_owned(),
-                ));
-            }
             let available_size = ui.available_size();
             let central_panel_rect = ui.min_rect();
             let center_x = central_panel_rect.center().x;
             let center_y = central_panel_rect.center().y;
             let mut responses = Vec::new();
             let board_size = available_size.min_elem();
-            let square_size = board_size / 8.0;
+            let square_size = board_size / 9.0; // half a square margin for the coordinates
             let board_top_left = egui::Pos2 {
                 x: center_x - (4.0 * square_size),
                 y: center_y - (4.0 * square_size),
             };
+            let hidden = self.blindfold && self.editor.is_none(); // the editor shows the pieces
             for row in 0..8 {
                 for col in 0..8 {
-                    let p = col + row * 8;
-                    let t = &self.tagged[p];
+                    let gp = view_transform(self.rotated, col + row * 8); // the game position
+                    let markers = self.highlight == HIGHLIGHTS_MARKERS;
+                    let t = &if markers { 0 } else { self.tagged[gp] };
                     let h: u8;
                     if *t == 2 {
                         h = 25;
@@ -176,10 +4103,19 @@ impl eframe::App for MyApp {
                     } else {
                         h = 0;
                     }
-                    let color = if (row + col) % 2 == 0 {
-                        egui::Color32::from_rgb(255, 255, 255 - h)
+                    let colors = palette(ui);
+                    let dark = (row + col) % 2; // dark squares are at index 1 of the palette
+                    let color = if self.in_check == Some(gp) {
+                        colors.check
+                    } else if *t == -1 && self.touch {
+                        ui.visuals().selection.bg_fill // the selected piece
+                    } else if *t == 3 {
+                        colors.capture[dark]
+                    } else if self.premove.contains(&(gp as i8)) {
+                        colors.premove[dark]
                     } else {
-                        egui::Color32::from_rgb(205, 205, 205 - h)
+                        let [r, g, b, _] = colors.squares[dark].to_array();
+                        egui::Color32::from_rgb(r, g, b.saturating_sub(h)) // yellowish tags
                     };
                     let top_left = egui::Pos2 {
                         x: board_top_left.x + (col as f32 * square_size),
@@ -190,123 +4126,610 @@ impl eframe::App for MyApp {
                         y: top_left.y + square_size,
                     };
                     let rect = egui::Rect::from_two_pos(top_left, bottom_right);
-                    let response = ui.allocate_rect(rect, egui::Sense::click());
-                    let (r, c) = if self.rotated {
-                        (7 - row, 7 - col)
-                    } else {
-                        (row, col)
-                    };
-                    responses.push((response, rect, color, c, r));
+                    let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
+                    let label = square_label(gp, if hidden { 0 } else { self.bbb[gp] });
+                    response.widget_info(|| {
+                        egui::WidgetInfo::labeled(egui::WidgetType::Button, true, &label)
+                    });
+                    responses.push((response, rect, color, gp % 8, gp / 8));
+                }
+            }
+            // Keyboard play: Tab focuses the board, the arrow keys move the focus between
+            // the squares, and Enter or Space clicks the focused square.
+            if let Some(i) = responses.iter().position(|r| r.0.has_focus()) {
+                let (col, row) = (i % 8, i / 8);
+                let mut target = None;
+                ui.input_mut(|input| {
+                    for (key, possible, to) in [
+                        (egui::Key::ArrowLeft, col > 0, i.wrapping_sub(1)),
+                        (egui::Key::ArrowRight, col < 7, i + 1),
+                        (egui::Key::ArrowUp, row > 0, i.wrapping_sub(8)),
+                        (egui::Key::ArrowDown, row < 7, i + 8),
+                    ] {
+                        // consumed also at the edge, where egui would leave the board
+                        if input.consume_key(egui::Modifiers::NONE, key) && possible {
+                            target = Some(to);
+                        }
+                    }
+                });
+                if let Some(to) = target {
+                    responses[to].0.request_focus();
                 }
             }
             let painter = ui.painter();
+            let rotated = self.rotated;
+            let center = |p: engine::Position| {
+                let p = view_transform(rotated, p as usize) as f32;
+                board_top_left + egui::vec2(p % 8.0 + 0.5, (p / 8.0).floor() + 0.5) * square_size
+            };
+            // the moves slide in 150 ms
+            let count = self.animation_count as f32;
+            let animated =
+                ui.ctx()
+                    .animate_value_with_time(egui::Id::new("move animation"), count, 0.15);
+            let progress = 1.0 - (count - animated).clamp(0.0, 1.0);
+            // files a-h below and ranks 1-8 left of the board
+            let font = egui::FontId::proportional(square_size * 0.3);
+            let text_color = ui.visuals().text_color();
+            for i in 0..8 {
+                let offset = (i as f32 + 0.5) * square_size;
+                let file = view_transform(self.rotated, i) % 8;
+                painter.text(
+                    board_top_left + egui::vec2(offset, 8.0 * square_size + 2.0),
+                    egui::Align2::CENTER_TOP,
+                    ((b'h' - file as u8) as char).to_string(),
+                    font.clone(),
+                    text_color,
+                );
+                let rank = view_transform(self.rotated, i * 8) / 8;
+                painter.text(
+                    board_top_left + egui::vec2(-4.0, offset),
+                    egui::Align2::RIGHT_CENTER,
+                    (rank + 1).to_string(),
+                    font.clone(),
+                    text_color,
+                );
+            }
+            let pointer = ui.ctx().pointer_interact_pos();
+            let mut dropped = false;
+            let mut marked = false;
+            let mut hovered = None;
+            let color_of_mark = mark_color(ui.input(|i| i.modifiers));
             for (response, rect, color, col, row) in responses {
+                let p = col + row * 8;
                 if response.clicked() {
                     x = col as i8;
                     y = row as i8;
                 }
+                // the secondary button marks a square, or draws an arrow when dragged
+                if response.secondary_clicked() {
+                    self.toggle_mark(p, p, color_of_mark);
+                }
+                if response.drag_started_by(egui::PointerButton::Secondary) {
+                    self.mark_from = Some(p);
+                }
+                marked |= response.drag_stopped_by(egui::PointerButton::Secondary);
+                // dragging a piece selects it as source, dropping it selects the destination
+                if response.drag_started_by(egui::PointerButton::Primary)
+                    && (self.state == STATE_U0 || self.state == STATE_U1)
+                    && self.bbb[p] != 0
+                {
+                    self.state = STATE_U0;
+                    self.drag_from = Some(p);
+                    x = col as i8;
+                    y = row as i8;
+                }
+                dropped |= response.drag_stopped_by(egui::PointerButton::Primary);
+                if pointer.is_some_and(|pos| rect.contains(pos)) {
+                    hovered = Some((col, row));
+                }
                 painter.rect_filled(rect, 0.0, color);
-                let text_pos = rect.center();
-                let piece = FIGURES[(self.bbb[col + row * 8] + 6) as usize];
-                painter.text(
-                    text_pos,
-                    egui::Align2::CENTER_CENTER,
-                    piece,
-                    egui::FontId::proportional(square_size * 0.9),
-                    egui::Color32::BLACK,
+                let sliding = progress < 1.0 && self.animations.iter().any(|a| a.1 == p);
+                if self.drag_from != Some(p) && !sliding && !hidden {
+                    draw_piece(ui, rect, self.bbb[p], self.piece_set);
+                }
+                if self.highlight == HIGHLIGHTS_MARKERS {
+                    let (center, border) = (rect.center(), egui::StrokeKind::Inside);
+                    let ring = egui::Stroke::new(square_size * 0.06, MARKER_MOVE);
+                    match self.tagged[p] {
+                        1 if self.bbb[p] == 0 => {
+                            painter.circle_filled(center, square_size * 0.12, MARKER_MOVE);
+                        }
+                        1 | 3 => {
+                            painter.circle_stroke(center, square_size * 0.44, ring);
+                        }
+                        2 => {
+                            let stroke = egui::Stroke::new(square_size * 0.06, MARKER_LAST);
+                            painter.rect_stroke(rect, 0.0, stroke, border);
+                        }
+                        -1 => {
+                            let stroke = egui::Stroke::new(square_size * 0.1, MARKER_SELECTED);
+                            painter.rect_stroke(rect, 0.0, stroke, border);
+                        }
+                        _ => {}
+                    }
+                }
+                if response.has_focus() {
+                    let stroke =
+                        egui::Stroke::new(square_size * 0.05, ui.visuals().selection.stroke.color);
+                    painter.rect_stroke(rect, 0.0, stroke, egui::StrokeKind::Inside);
+                }
+            }
+            if progress < 1.0 && !hidden {
+                for &(from, to, f) in &self.animations {
+                    let pos = center(from as engine::Position)
+                        .lerp(center(to as engine::Position), progress);
+                    let rect = egui::Rect::from_center_size(pos, egui::Vec2::splat(square_size));
+                    draw_piece(ui, rect, f, self.piece_set);
+                }
+            }
+            // an arrow for the last move
+            if let (None, Some(ply)) = (&self.editor, engine::game_ply(&self.game).checked_sub(1)) {
+                let m = engine::move_list(&self.game)[ply];
+                let from = center(m.src);
+                painter.arrow(
+                    from,
+                    center(m.dst) - from,
+                    egui::Stroke::new(
+                        square_size * 0.08,
+                        egui::Color32::from_rgba_unmultiplied(0, 90, 200, 150),
+                    ),
                 );
             }
+            if marked {
+                if let (Some(from), Some((col, row))) = (self.mark_from.take(), hovered) {
+                    self.toggle_mark(from, col + row * 8, color_of_mark);
+                }
+            }
+            for &(from, to, color) in &self.marks {
+                let (from, to) = (
+                    center(from as engine::Position),
+                    center(to as engine::Position),
+                );
+                if from == to {
+                    let stroke = egui::Stroke::new(square_size * 0.07, color);
+                    painter.circle_stroke(from, square_size * 0.45, stroke);
+                } else {
+                    painter.arrow(
+                        from,
+                        to - from,
+                        egui::Stroke::new(square_size * 0.12, color),
+                    );
+                }
+            }
+            // the evaluation bar right of the board, White's part is on White's side
+            let bar = egui::Rect::from_min_size(
+                board_top_left + egui::vec2(8.1 * square_size, 0.0),
+                egui::vec2(0.25 * square_size, 8.0 * square_size),
+            );
+            let white = 1.0 / (1.0 + (-self.eval as f32 / 400.0).exp());
+            let split = if self.rotated {
+                bar.bottom() - white * bar.height()
+            } else {
+                bar.top() + white * bar.height()
+            };
+            painter.rect_filled(bar, 0.0, egui::Color32::from_gray(40));
+            let white_part = if self.rotated {
+                egui::Rect::from_x_y_ranges(bar.x_range(), split..=bar.bottom())
+            } else {
+                egui::Rect::from_x_y_ranges(bar.x_range(), bar.top()..=split)
+            };
+            painter.rect_filled(white_part, 0.0, egui::Color32::from_gray(235));
+            painter.rect_stroke(
+                bar,
+                0.0,
+                egui::Stroke::new(1.0, egui::Color32::GRAY),
+                egui::StrokeKind::Inside,
+            );
+            painter.text(
+                bar.center_bottom() + egui::vec2(0.0, 2.0),
+                egui::Align2::CENTER_TOP,
+                format!("{:+.1}", self.eval as f32 / 100.0),
+                font.clone(),
+                text_color,
+            );
+            // the captured pieces on the side of their captor, with the material advantage
+            let mut captured = engine::captured_pieces(&self.game);
+            captured.sort_by_key(|f| -f.abs());
+            let balance = engine::material_balance(&self.game);
+            let size = square_size * 0.4;
+            for color in [engine::COLOR_WHITE, engine::COLOR_BLACK] {
+                let bottom = (color == engine::COLOR_WHITE) == self.rotated;
+                let y = if bottom {
+                    board_top_left.y + 8.0 * square_size - size
+                } else {
+                    board_top_left.y
+                };
+                let mut x = board_top_left.x + 8.5 * square_size;
+                for &f in captured.iter().filter(|f| f.signum() == -color) {
+                    let rect = egui::Rect::from_min_size(egui::pos2(x, y), egui::Vec2::splat(size));
+                    draw_piece(ui, rect, f, self.piece_set);
+                    x += size * 0.6;
+                }
+                if balance * color > 0 {
+                    painter.text(
+                        egui::pos2(x + size * 0.5, y + size * 0.5),
+                        egui::Align2::LEFT_CENTER,
+                        format!("+{}", balance * color),
+                        font.clone(),
+                        text_color,
+                    );
+                }
+            }
+            if let (Some(from), Some(pos), false) = (self.drag_from, pointer, hidden) {
+                let rect = egui::Rect::from_center_size(pos, egui::Vec2::splat(square_size));
+                draw_piece(ui, rect, self.bbb[from], self.piece_set);
+            }
+            if dropped {
+                if let (Some(from), Some((col, row))) = (self.drag_from, hovered) {
+                    if col + row * 8 != from && self.state == STATE_U1 {
+                        x = col as i8;
+                        y = row as i8;
+                        self.dropped = true;
+                    }
+                }
+                self.drag_from = None;
+            }
             if self.state == STATE_U3 {
                 ui.ctx().request_repaint();
             }
         });
+        if let (true, Some((reason, score))) = (self.show_result, self.game_result.clone()) {
+            let mut rematch = false;
+            let mut save = false;
+            egui::Window::new(tr(lang, "Game over"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(tr(lang, &reason));
+                    ui.heading(score);
+                    ui.horizontal(|ui| {
+                        rematch = ui.button(tr(lang, "Rematch (swap colors)")).clicked();
+                        if ui.button(tr(lang, "Analyze")).clicked() {
+                            self.show_result = false; // the move list browses the game
+                            self.analyze();
+                        }
+                        save = ui.button(tr(lang, "Save PGN")).clicked();
+                    });
+                });
+            if save {
+                self.save_pgn();
+            }
+            if rematch {
+                self.players.swap(0, 1);
+                self.engine_plays_white = self.players[0] == ENGINE;
+                self.engine_plays_black = self.players[1] == ENGINE;
+                self.orient();
+                self.new_game = true;
+            }
+        }
+        self.pgn_window(ctx);
+        self.share_window(ctx);
+        self.database_window(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.image_window(ctx);
+        self.analysis_window(ctx);
+        if let Some(saved) = self.resume.take() {
+            let mut answer = None;
+            egui::Window::new(tr(lang, "Unfinished game"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Resume the game of the last session, after {} moves?",
+                        saved.ply.div_ceil(2)
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Resume")).clicked() {
+                            answer = Some(true);
+                        }
+                        if ui.button(tr(lang, "New game")).clicked() {
+                            answer = Some(false);
+                        }
+                    });
+                });
+            match answer {
+                Some(true) if !self.resume_game(&saved) => {
+                    self.msg = "The saved game is not valid.".to_owned()
+                }
+                Some(_) => {}
+                None => self.resume = Some(saved),
+            }
+        }
+        if let Some(editor) = self.editor.as_mut() {
+            let mut done = None;
+            egui::Window::new(tr(lang, "Board editor"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+                .show(ctx, |ui| {
+                    for color in [engine::COLOR_WHITE, engine::COLOR_BLACK] {
+                        ui.horizontal(|ui| {
+                            for id in (engine::PAWN_ID..=engine::KING_ID).rev() {
+                                let f = id * color;
+                                let button = if self.piece_set == 0 {
+                                    let glyph = FIGURES[(f + 6) as usize];
+                                    egui::Button::new(egui::RichText::new(glyph).size(30.0))
+                                } else {
+                                    egui::Button::image(
+                                        egui::Image::new(piece_image(f))
+                                            .fit_to_exact_size(egui::Vec2::splat(30.0)),
+                                    )
+                                };
+                                if ui.add(button.selected(editor.piece == f)).clicked() {
+                                    editor.piece = f;
+                                }
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        let empty = editor.piece == engine::VOID_ID;
+                        if ui.selectable_label(empty, "Remove pieces").clicked() {
+                            editor.piece = engine::VOID_ID;
+                        }
+                        if ui.button(tr(lang, "Clear board")).clicked() {
+                            editor.board = [engine::VOID_ID; 64];
+                        }
+                        if ui.button(tr(lang, "Start position")).clicked() {
+                            let mut g = self.game.clone();
+                            engine::reset_game(&mut g);
+                            *editor = Editor::new(&g);
+                        }
+                    });
+                    ui.checkbox(&mut editor.white_to_move, tr(lang, "White to move"));
+                    ui.horizontal(|ui| {
+                        ui.label(tr(lang, "Castling"));
+                        for (right, name) in editor.rights.iter_mut().zip(["K", "Q", "k", "q"]) {
+                            ui.checkbox(right, name);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(tr(lang, "En passant"));
+                        ui.text_edit_singleline(&mut editor.ep);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Done")).clicked() {
+                            done = Some(true);
+                        }
+                        if ui.button(tr(lang, "Cancel")).clicked() {
+                            done = Some(false);
+                        }
+                    });
+                });
+            self.bbb = editor.board;
+            match done {
+                Some(true) => {
+                    let mut g = self.game.clone();
+                    match editor.set_up(&mut g) {
+                        Ok(()) => {
+                            self.game = g;
+                            self.new_position();
+                            self.msg = "Position set up.".to_owned();
+                        }
+                        Err(e) => self.msg = format!("Invalid position: {}.", e),
+                    }
+                }
+                Some(false) => self.browsed(), // continues the game
+                None => {}
+            }
+        }
+        if self.state == STATE_U4 {
+            let (h, p1) = self.promotion;
+            let color = self.bbb[h as usize].signum();
+            let mut choice = None;
+            egui::Window::new(tr(lang, "Promotion"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        for id in [
+                            engine::QUEEN_ID,
+                            engine::ROOK_ID,
+                            engine::BISHOP_ID,
+                            engine::KNIGHT_ID,
+                        ] {
+                            let response = if self.piece_set == 0 {
+                                let glyph = FIGURES[(id * color + 6) as usize];
+                                ui.button(egui::RichText::new(glyph).size(40.0))
+                            } else {
+                                let image = egui::Image::new(piece_image(id * color))
+                                    .fit_to_exact_size(egui::Vec2::splat(40.0));
+                                ui.add(egui::Button::image(image))
+                            };
+                            if response.clicked() {
+                                choice = Some(id);
+                            }
+                        }
+                    });
+                    if ui.button(tr(lang, "Cancel")).clicked() {
+                        choice = Some(engine::VOID_ID);
+                    }
+                });
+            match choice {
+                Some(engine::VOID_ID) => {
+                    self.tagged = [0; 64];
+                    self.state = STATE_UZ;
+                }
+                Some(id) => self.human_move(h, p1, id),
+                None => {}
+            }
+        }
+
+        if self.state == STATE_U5 {
+            let (h, p1, promote_to) = self.confirm;
+            let m = engine::new_move(&self.game, h, p1, promote_to);
+            let mut confirmed = None;
+            egui::Window::new(tr(lang, "Confirm move"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .show(ctx, |ui| {
+                    let (n, letters) = self.notation();
+                    ui.heading(m.localized(n, letters).to_string());
+                    ui.horizontal(|ui| {
+                        if ui.button(tr(lang, "Confirm")).clicked() {
+                            confirmed = Some(true);
+                        }
+                        if ui.button(tr(lang, "Cancel")).clicked() {
+                            confirmed = Some(false);
+                        }
+                    });
+                });
+            match confirmed {
+                Some(true) => self.play_human_move(h, p1, promote_to),
+                Some(false) => {
+                    self.tagged = [0; 64];
+                    self.state = STATE_UZ;
+                }
+                None => {}
+            }
+        }
 
-        if self.state == STATE_UX {
+        // a tap on another piece of the color of the selected piece, see touch
+        let reselect = self.state == STATE_U1 && x >= 0 && {
+            let (p, p0) = ((x + y * 8) as usize, self.p0 as usize);
+            p != p0 && self.bbb[p] * self.bbb[p0] > 0
+        };
+        if let Some(editor) = self.editor.as_mut() {
+            if x >= 0 {
+                editor.place((x + y * 8) as usize);
+                self.bbb = editor.board;
+            }
+        } else if self.state == STATE_UX {
             // game terminated
         } else if self.state == STATE_UZ {
-            let next = self.game.lock().unwrap().move_counter as usize % 2;
+            // an exercise alternates between the moves of the opponent and of the solver
+            if let Some(ex) = &self.exercise {
+                self.state = if ex.ply == ex.moves.len() {
+                    STATE_UX
+                } else if ex.ply % 2 == ex.solver {
+                    STATE_U0
+                } else {
+                    STATE_U6
+                };
+                return;
+            }
+            if self.game_over() || self.adjudicate() {
+                self.premove.clear();
+                self.state = STATE_UX;
+                return;
+            }
+            // the moves of the opponent arrive with the stream of the game
+            #[cfg(feature = "lichess")]
+            if let Some(online) = self.online.as_ref().filter(|o| o.game.is_some()) {
+                let white = self.game.move_counter.is_multiple_of(2);
+                self.state = if white == online.white {
+                    STATE_U0
+                } else {
+                    STATE_U7
+                };
+                self.conditional_move();
+                return;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(lan) = self.lan.as_ref().filter(|l| l.playing) {
+                let white = self.game.move_counter.is_multiple_of(2);
+                self.state = if white == lan.white {
+                    STATE_U0
+                } else {
+                    STATE_U7
+                };
+                self.conditional_move();
+                return;
+            }
+            // an engine game waits between the moves, or while it is paused
+            if self.players == [ENGINE, ENGINE] {
+                let delay = Duration::from_millis(self.autoplay_delay);
+                let wait = delay.saturating_sub(self.last_move.elapsed());
+                if self.paused {
+                    return;
+                } else if !wait.is_zero() {
+                    ctx.request_repaint_after(wait);
+                    return;
+                }
+            }
+            let next = self.game.move_counter as usize % 2;
+            if self.players == [HUMAN, HUMAN] && self.auto_rotate {
+                self.rotated = next == 0;
+            }
             self.state = BOOL_TO_STATE[self.players[next] as usize];
-        } else if self.state == STATE_U0 && x >= 0 {
+            // while browsing the game the engine waits, so the taken back moves are kept
+            if self.state == STATE_U2
+                && engine::game_ply(&self.game) < engine::move_list(&self.game).len()
+            {
+                self.msg = "Engine waits, Redo continues.".to_owned();
+                self.state = STATE_UX;
+            }
+            // the premove is validated only now, in the position after the engine move
+            if self.state == STATE_U0 && self.premove.len() == 2 {
+                let (h, p1) = (self.premove[0], self.premove[1]);
+                self.premove.clear();
+                self.dropped = true; // the reply of the engine is still sliding
+                self.human_move(h, p1, engine::QUEEN_ID);
+            }
+            self.premove.clear();
+            self.conditional_move();
+        } else if self.state == STATE_U3 && x >= 0 {
+            // a premove: source and destination, a click on the source again cancels it
+            let p = x + y * 8;
+            let human = self.game.move_counter as i64 % 2 * 2 - 1; // color of the human
+            if (self.premove.is_empty() && self.bbb[p as usize].signum() == human)
+                || (self.premove.len() == 1 && self.premove[0] != p)
+            {
+                self.premove.push(p);
+            } else {
+                self.premove.clear();
+            }
+        } else if self.state == STATE_U1 && x >= 0 && self.touch && x + y * 8 == self.p0 as i8 {
+            // in touch mode a tap on the selected piece deselects it
+            self.tagged = [0; 64];
+            self.state = STATE_U0;
+        } else if (self.state == STATE_U0 || self.touch && reselect) && x >= 0 {
+            // in touch mode a tap on another piece of the same color selects it
             self.p0 = (x + y * 8) as i32;
             let h = self.p0 as i64;
             self.tagged = [0; 64];
-            for i in engine::tag(&mut self.game.lock().unwrap(), h) {
-                self.tagged[i.di as usize] = 1;
+            for m in engine::legal_moves_from(&self.game, h as engine::Position) {
+                let capture = self.hints == HINTS_BEGINNER && m.is_capture();
+                if self.hints != HINTS_NONE {
+                    self.tagged[m.dst as usize] = if capture { 3 } else { 1 };
+                }
             }
             self.tagged[h as usize] = -1;
-            if self.rotated {
-                self.tagged.reverse();
-            }
             self.state = STATE_U1;
         } else if self.state == STATE_U1 && x >= 0 {
             let p1 = x + y * 8;
-            let h = self.p0;
-            if h == p1 as i32
-                || !engine::move_is_valid2(&mut self.game.lock().unwrap(), h as i64, p1 as i64)
+            let h = self.p0 as i8;
+            if self.bbb[h as usize].abs() == engine::PAWN_ID
+                && !(8..56).contains(&p1)
+                && engine::move_is_valid2(&self.game, h as i64, p1 as i64)
             {
-                self.msg = "invalid move, ignored.".to_owned();
-                self.tagged = [0; 64];
-                self.state = STATE_UZ;
-                return;
+                self.promotion = (h, p1);
+                self.state = STATE_U4;
+            } else {
+                self.human_move(h, p1, engine::VOID_ID);
             }
-            let flag = engine::do_move(&mut self.game.lock().unwrap(), h as i8, p1 as i8, false);
-            self.tagged = [0; 64];
-            self.tagged[h as usize] = 2;
-            self.tagged[p1 as usize] = 2;
-            if self.rotated {
-                self.tagged.reverse();
+        } else if self.state == STATE_U6 {
+            let wait = PUZZLE_REPLY_DELAY.saturating_sub(self.last_move.elapsed());
+            if wait.is_zero() {
+                self.exercise_move();
+            } else {
+                ctx.request_repaint_after(wait);
             }
-            self.msg = engine::move_to_str(&mut self.game.lock().unwrap(), h as i8, p1 as i8, flag);
-            self.state = STATE_UZ;
         } else if self.state == STATE_U2 {
             self.state = STATE_U3;
-            let (tx, rx) = mpsc::channel(); // Create a new channel
-            self.rx = Some(rx); // Store the receiver in the struct
-            let game_clone = self.game.clone();
-            thread::spawn(move || {
-                let m = engine::reply(&mut game_clone.lock().unwrap());
-                tx.send(m).unwrap();
-            });
-        } else if self.state == STATE_U3 {
-            // Check if the thread has finished
-            if let Some(rx) = &self.rx {
-                if let Ok(m) = rx.try_recv() {
-                    self.tagged = [0; 64];
-                    self.tagged[m.src as usize] = 2;
-                    self.tagged[m.dst as usize] = 2;
-                    if self.rotated {
-                        self.tagged.reverse();
-                    }
-                    let flag = engine::do_move(
-                        &mut self.game.lock().unwrap(),
-                        m.src as i8,
-                        m.dst as i8,
-                        false,
-                    );
-                    self.msg = engine::move_to_str(
-                        &mut self.game.lock().unwrap(),
-                        m.src as i8,
-                        m.dst as i8,
-                        flag,
-                    ) + &format!(" (score: {})", m.score);
-                    if m.score == engine::KING_VALUE as i64 {
-                        self.msg.push_str(" Checkmate, game terminated!");
-                        self.state = STATE_UX;
-                        return;
-                    } else if m.score > engine::KING_VALUE_DIV_2 as i64 {
-                        self.msg.push_str(&format!(
-                            " Checkmate in {}",
-                            (engine::KING_VALUE as i64 - m.score) / 2
-                        ));
-                    }
-                    self.state = STATE_UZ;
-                    self.rx = None; // Reset the receiver
-                } else {
-                    // If the thread has not finished, keep the state as STATE_U3
-                    // self.state = STATE_U3;
-                    // ctx.request_repaint_after(Duration::from_millis(100));
-                }
-            }
+            self.stats = None;
+            self.progress = (0, Duration::ZERO);
+            let active = self.game.move_counter as usize % 2;
+            let time_left = self
+                .time_control()
+                .map(|(_, inc)| (self.clocks[active], inc));
+            engine::set_time_left(&mut self.game, time_left);
+            self.engine.send(Command::SetPosition(self.game.clone()));
+            self.engine.send(Command::Go);
         }
     }
 }
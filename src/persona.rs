@@ -0,0 +1,74 @@
+// Weak personas, selected by the engine option Persona: instead of the search, reply()
+// picks the move by a simple strategy. Great for absolute beginners, and for testing a
+// frontend with fast and unpredictable replies. All personas play legal moves only.
+
+use crate::engine::{self, Game, Move};
+use alloc::vec::Vec;
+
+pub const FULL: &str = "Full"; // the search, no persona
+
+// the values of the option Persona
+pub const PERSONAS: [&str; 4] = [FULL, "Random", "Greedy", "Materialist"];
+
+// a xorshift generator, seeded by the position and the time
+struct Random(u64);
+
+impl Random {
+    fn new(g: &Game) -> Self {
+        let time = engine::now(g).as_nanos() as u64;
+        Random((engine::position_key(g) ^ time) | 1)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+
+    fn pick(&mut self, moves: &[Move]) -> Move {
+        moves[self.below(moves.len())]
+    }
+}
+
+// None for the full strength search, or when the game is over
+pub(crate) fn choose(g: &mut Game, persona: &str) -> Option<Move> {
+    let moves: Vec<Move> = engine::legal_moves(g).collect();
+    if persona == FULL || moves.is_empty() {
+        return None;
+    }
+    let mut random = Random::new(g);
+    match persona {
+        // any capture, even of a defended piece
+        "Greedy" => {
+            let captures: Vec<Move> = moves.iter().copied().filter(|m| m.is_capture()).collect();
+            if captures.is_empty() {
+                Some(random.pick(&moves))
+            } else {
+                Some(random.pick(&captures))
+            }
+        }
+        // the most material after the own move, the replies are not seen
+        "Materialist" => {
+            let color = engine::active_color(g);
+            let material: Vec<i64> = moves
+                .iter()
+                .map(|m| {
+                    let token = engine::do_move(g, m.src, m.dst, m.promote_to, true);
+                    let material = engine::material_balance(g) * color;
+                    engine::undo_move(g, token);
+                    material
+                })
+                .collect();
+            let best = material.iter().max().copied();
+            let best: Vec<Move> = moves
+                .iter()
+                .zip(&material)
+                .filter(|&(_, &m)| Some(m) == best)
+                .map(|(&m, _)| m)
+                .collect();
+            Some(random.pick(&best))
+        }
+        _ => Some(random.pick(&moves)),
+    }
+}
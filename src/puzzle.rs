@@ -0,0 +1,110 @@
+// Tactics puzzles from the lichess puzzle database, a CSV file with the fields
+// PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+// The FEN is the position before the move of the opponent, the first of the UCI moves,
+// the solution follows. Needs std for reading the file and for the rating.
+
+use crate::engine::{self, FenError, Game, GameState, Move, SanError};
+use std::fmt;
+use std::io::{self, BufRead};
+
+pub const START_RATING: i64 = 1500;
+const K_FACTOR: f64 = 32.0; // the largest change of the rating for a puzzle
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    pub id: String,
+    pub fen: String,
+    pub moves: Vec<String>, // in UCI notation, starting with the move of the opponent
+    pub rating: i64,
+    pub themes: Vec<String>, // i.e. "mateIn2"
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    Fields,
+    Rating,
+    Fen(FenError),
+    Move(String, SanError),
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::Fields => f.write_str("missing fields in puzzle"),
+            PuzzleError::Rating => f.write_str("invalid rating of puzzle"),
+            PuzzleError::Fen(e) => e.fmt(f),
+            PuzzleError::Move(uci, e) => write!(f, "{} in puzzle: {}", e, uci),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
+
+// a line of the CSV file, the moves are checked by set_puzzle()
+pub fn parse_puzzle(line: &str) -> Result<Puzzle, PuzzleError> {
+    let fields: Vec<&str> = line.trim().split(',').collect();
+    if fields.len() < 4 || fields[2].split_whitespace().count() < 2 {
+        return Err(PuzzleError::Fields);
+    }
+    Ok(Puzzle {
+        id: fields[0].to_string(),
+        fen: fields[1].to_string(),
+        moves: fields[2].split_whitespace().map(String::from).collect(),
+        rating: fields[3].parse().map_err(|_| PuzzleError::Rating)?,
+        themes: fields.get(7).map_or(Vec::new(), |t| {
+            t.split_whitespace().map(String::from).collect()
+        }),
+    })
+}
+
+// The puzzles of a CSV file, at most max of them. The header and invalid lines are skipped.
+pub fn read_puzzles(reader: impl BufRead, max: usize) -> io::Result<Vec<Puzzle>> {
+    let mut puzzles = Vec::new();
+    for line in reader.lines() {
+        if puzzles.len() >= max {
+            break;
+        }
+        if let Ok(p) = parse_puzzle(&line?) {
+            puzzles.push(p);
+        }
+    }
+    Ok(puzzles)
+}
+
+// Set up the position of the puzzle, before the move of the opponent. g is only modified
+// when all moves are legal.
+pub fn set_puzzle(g: &mut Game, p: &Puzzle) -> Result<(), PuzzleError> {
+    let mut check = g.clone();
+    engine::set_fen(&mut check, &p.fen).map_err(PuzzleError::Fen)?;
+    for uci in &p.moves {
+        let m =
+            engine::parse_uci_move(&check, uci).map_err(|e| PuzzleError::Move(uci.clone(), e))?;
+        engine::do_move(&mut check, m.src, m.dst, m.promote_to, false);
+    }
+    engine::set_fen(g, &p.fen).map_err(PuzzleError::Fen)
+}
+
+// The move with index ply of the puzzle, in the position g before it.
+pub fn puzzle_move(g: &Game, p: &Puzzle, ply: usize) -> Option<Move> {
+    engine::parse_uci_move(g, p.moves.get(ply)?).ok()
+}
+
+// m in the position g solves the move with index ply of the puzzle: it is the move of the
+// solution, or a checkmate like on lichess
+pub fn is_solution(g: &Game, p: &Puzzle, ply: usize, m: &Move) -> bool {
+    if let Some(s) = puzzle_move(g, p, ply) {
+        if (s.src, s.dst, s.promote_to.abs()) == (m.src, m.dst, m.promote_to.abs()) {
+            return true;
+        }
+    }
+    let mut after = g.clone();
+    engine::do_move(&mut after, m.src, m.dst, m.promote_to, false);
+    matches!(engine::state(&after), GameState::Checkmate(_))
+}
+
+// the Elo rating of the solver after a puzzle
+pub fn new_rating(rating: i64, puzzle_rating: i64, solved: bool) -> i64 {
+    let expected = 1.0 / (1.0 + 10f64.powf((puzzle_rating - rating) as f64 / 400.0));
+    let score = if solved { 1.0 } else { 0.0 };
+    rating + (K_FACTOR * (score - expected)).round() as i64
+}
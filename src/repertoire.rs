@@ -0,0 +1,65 @@
+// An opening repertoire for a drill: the lines of a PGN with variations, read with
+// engine::set_pgn(), from the start to the end of each variation. The lines are reviewed
+// with spaced repetition in Leitner boxes, a line in box n is due again after 2^(n-1) days.
+
+use crate::engine::{self, Game, Move, NodeId, ROOT_NODE};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+pub const DAY: u64 = 24 * 60 * 60; // the times are in seconds
+const MAX_BOX: u32 = 8; // an interval of 128 days
+
+// the review state of a line, a new line is due at once
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Card {
+    pub level: u32, // the Leitner box, 0 for a new or failed line
+    pub due: u64,   // the time of the next review
+}
+
+// the lines of the variation tree of g, in the order of the PGN
+pub fn lines(g: &Game) -> Vec<Vec<Move>> {
+    let mut result = Vec::new();
+    let mut line = Vec::new();
+    collect_lines(g, ROOT_NODE, &mut line, &mut result);
+    result
+}
+
+fn collect_lines(g: &Game, node: NodeId, line: &mut Vec<Move>, result: &mut Vec<Vec<Move>>) {
+    let children = engine::node_children(g, node);
+    if children.is_empty() && !line.is_empty() {
+        result.push(line.clone());
+    }
+    for &child in children {
+        line.push(engine::node_move(g, child));
+        collect_lines(g, child, line, result);
+        line.pop();
+    }
+}
+
+// the moves of a line in UCI notation, the key of its Card
+pub fn line_key(line: &[Move]) -> String {
+    let moves: Vec<String> = line.iter().map(|m| m.uci().to_string()).collect();
+    moves.join(" ")
+}
+
+// Move the card to the next box after a correct review, or back to the first box.
+pub fn review(card: &mut Card, now: u64, correct: bool) {
+    if correct {
+        card.level = (card.level + 1).min(MAX_BOX);
+        card.due = now + DAY * (1 << (card.level - 1));
+    } else {
+        card.level = 0;
+        card.due = now;
+    }
+}
+
+// the index of the card due first, the lowest box first for equal times
+pub fn next_card(cards: &[Card]) -> Option<usize> {
+    (0..cards.len()).min_by_key(|&i| (cards[i].due, cards[i].level))
+}
+
+// the number of cards due at now
+pub fn due_cards(cards: &[Card], now: u64) -> usize {
+    cards.iter().filter(|c| c.due <= now).count()
+}
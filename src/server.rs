@@ -0,0 +1,446 @@
+// The engine as the backend of web and mobile frontends, with a small JSON API over HTTP
+// and WebSocket. Needs the "server" feature:
+//
+//   cargo run --release --features server -- --serve 127.0.0.1:8080
+//
+// The HTTP API keeps one position, the bodies are JSON objects:
+//
+//   GET  /position   the position: {"start": ..., "moves": [...], "fen": ..., "state": ...,
+//                    "result": ...}, the start position with the moves played from it
+//   POST /position   {"fen": ..., "moves": ["e2e4", "e7e5"]}, both optional, answered
+//                    with the position
+//   POST /move       {"move": "Nf3"} in SAN or UCI notation, answered with the position
+//   POST /bestmove   {"movetime": 1000}, optional, the search without playing the move:
+//                    {"bestmove": "g1f3", "san": "Nf3", "score": 31}
+//
+// A WebSocket connection, e.g. to ws://127.0.0.1:8080/ws, has its own position and engine
+// thread, and streams the analysis. At most MAX_WEBSOCKETS connections are open at a
+// time. The messages are JSON objects with a type:
+//
+//   {"type": "position", "fen": ..., "moves": [...]}  answered with the position
+//   {"type": "move", "move": "Nf3"}                   answered with the position
+//   {"type": "go", "movetime": 1000}                  an info message for each depth,
+//                                                     then the bestmove message
+//   {"type": "analyze"}                               the same for the longest MoveTime
+//   {"type": "stop"}                                  ends the search with bestmove
+//
+// Errors are answered with {"error": ...}, with status 400 for HTTP. The requests run in
+// their own threads, the searches of HTTP requests one after the other.
+
+use crate::cli;
+use crate::engine::{self, Game, SearchInfo, SearchResult};
+use crate::handle::{Command, EngineHandle, Event};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+// for the messages of a WebSocket and the events of its engine
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// the limits of a request
+const MAX_HEADER: usize = 16 * 1024;
+const MAX_BODY: usize = 1024 * 1024;
+
+// each WebSocket connection has its own engine thread with a table of WEBSOCKET_HASH_MB,
+// further connections are refused with status 503
+const MAX_WEBSOCKETS: usize = 4;
+const WEBSOCKET_HASH_MB: i64 = 64;
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>, // the names in lowercase
+    body: String,
+    head: Vec<u8>, // the bytes read, replayed for the WebSocket handshake
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        let header = self.headers.iter().find(|(n, _)| n == name);
+        header.map(|(_, value)| value.as_str())
+    }
+
+    fn is_websocket(&self) -> bool {
+        self.header("upgrade")
+            .is_some_and(|u| u.eq_ignore_ascii_case("websocket"))
+    }
+}
+
+fn invalid(text: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, text)
+}
+
+// the request line and the headers, and the body unless the request is a WebSocket
+// handshake
+fn read_request(reader: &mut BufReader<TcpStream>) -> io::Result<Request> {
+    let mut head = Vec::new();
+    let mut lines = Vec::new();
+    loop {
+        let start = head.len();
+        if reader.read_until(b'\n', &mut head)? == 0 || head.len() > MAX_HEADER {
+            return Err(invalid("incomplete request"));
+        }
+        let line = String::from_utf8_lossy(&head[start..])
+            .trim_end()
+            .to_owned();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    let mut request_line = lines.first().map_or("", |l| l.as_str()).split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_owned();
+    let path = request_line.next().unwrap_or_default().to_owned();
+    let headers = lines.iter().skip(1).filter_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        Some((name.trim().to_ascii_lowercase(), value.trim().to_owned()))
+    });
+    let mut request = Request {
+        method,
+        path,
+        headers: headers.collect(),
+        body: String::new(),
+        head,
+    };
+    if !request.is_websocket() {
+        let length: usize = request
+            .header("content-length")
+            .map_or(Ok(0), str::parse)
+            .map_err(|_| invalid("invalid Content-Length"))?;
+        if length > MAX_BODY {
+            return Err(invalid("request too large"));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        request.body = String::from_utf8(body).map_err(|_| invalid("body not UTF-8"))?;
+    } else {
+        request.head.extend_from_slice(reader.buffer());
+    }
+    Ok(request)
+}
+
+// a stream which returns the bytes of the request first, so tungstenite can read the
+// handshake that was already read by read_request()
+struct Replay {
+    head: io::Cursor<Vec<u8>>,
+    stream: TcpStream,
+}
+
+impl Read for Replay {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.head.read(buf)? {
+            0 => self.stream.read(buf),
+            n => Ok(n),
+        }
+    }
+}
+
+impl Write for Replay {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+fn state(g: &Game) -> &'static str {
+    engine::state(g).name()
+}
+
+// the start position and the moves played from it, and the current position
+fn position_json(g: &Game) -> Value {
+    let ply = engine::game_ply(g);
+    let moves: Vec<String> = engine::move_list(g)[..ply]
+        .iter()
+        .map(|m| m.uci().to_string())
+        .collect();
+    let mut start = g.clone();
+    engine::go_to_ply(&mut start, 0);
+    json!({
+        "start": engine::fen(&start),
+        "moves": moves,
+        "fen": engine::fen(g),
+        "state": state(g),
+        "result": engine::pgn_result(g),
+    })
+}
+
+// the start position and the moves of a position message, the start position by default
+fn set_position(g: &mut Game, message: &Value) -> Result<(), String> {
+    let mut new = engine::new_game_with_hash(1); // for the rules only
+    if let Some(fen) = message["fen"].as_str() {
+        engine::set_fen(&mut new, fen).map_err(|e| e.to_string())?;
+    }
+    for m in message["moves"].as_array().into_iter().flatten() {
+        play_move(&mut new, m)?;
+    }
+    engine::set_position(g, &new);
+    Ok(())
+}
+
+// a move in SAN or UCI notation
+fn play_move(g: &mut Game, m: &Value) -> Result<(), String> {
+    let text = m.as_str().ok_or("the move is no string")?;
+    let m = engine::parse_san(g, text)
+        .or_else(|e| engine::parse_uci_move(g, text).map_err(|_| e))
+        .map_err(|e| format!("{}: {}", text, e))?;
+    engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    Ok(())
+}
+
+fn info_json(info: &SearchInfo) -> Value {
+    let pv: Vec<String> = info.pv.iter().map(|m| m.uci().to_string()).collect();
+    json!({
+        "type": "info",
+        "depth": info.depth,
+        "seldepth": info.seldepth,
+        "score": info.score,
+        "uci_score": cli::uci_score(info.score),
+        "nodes": info.nodes,
+        "time": info.time.as_millis() as u64,
+        "pv": pv,
+    })
+}
+
+// the move of the search in the position g, with its SAN
+fn best_move_json(g: &mut Game, result: &SearchResult) -> Value {
+    let m = engine::legal_moves(g).find(|m| {
+        (m.src as i64, m.dst as i64, m.promote_to) == (result.src, result.dst, result.promote_to)
+    });
+    let Some(m) = m else {
+        return json!({ "type": "bestmove", "bestmove": Value::Null, "state": state(g) });
+    };
+    let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    let san = token.mv.to_string();
+    engine::undo_move(g, token);
+    json!({
+        "type": "bestmove",
+        "bestmove": m.uci().to_string(),
+        "san": san,
+        "score": result.score,
+        "uci_score": cli::uci_score(result.score),
+    })
+}
+
+fn error_json(e: &str) -> Value {
+    json!({ "error": e })
+}
+
+fn set_move_time(g: &mut Game, message: &Value) -> Result<(), String> {
+    match message.get("movetime") {
+        Some(time) => {
+            engine::set_option(g, "MoveTime", &time.to_string()).map_err(|e| e.to_string())
+        }
+        None => Ok(()),
+    }
+}
+
+// the status and the answer of a request of the HTTP API
+fn answer(request: &Request, session: &Mutex<Game>) -> (u16, Value) {
+    let body = if request.body.trim().is_empty() {
+        json!({})
+    } else {
+        match serde_json::from_str(&request.body) {
+            Ok(body) => body,
+            Err(e) => return (400, error_json(&e.to_string())),
+        }
+    };
+    let mut g = session.lock().unwrap_or_else(|e| e.into_inner());
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/position") => Ok(position_json(&g)),
+        ("POST", "/position") => set_position(&mut g, &body).map(|()| position_json(&g)),
+        ("POST", "/move") => play_move(&mut g, &body["move"]).map(|()| position_json(&g)),
+        ("POST", "/bestmove") => set_move_time(&mut g, &body).map(|()| {
+            let result = engine::reply(&mut g, &mut ());
+            best_move_json(&mut g, &result)
+        }),
+        _ => return (404, error_json("not found")),
+    };
+    match result {
+        Ok(answer) => (200, answer),
+        Err(e) => (400, error_json(&e)),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        503 => "Service Unavailable",
+        _ => "Not Found",
+    };
+    // frontends on other origins may use the API
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// the errors of tungstenite are large, they are io errors of the connection here
+fn send(ws: &mut WebSocket<Replay>, message: Value) -> io::Result<()> {
+    ws.send(Message::text(message.to_string()))
+        .map_err(io::Error::other)
+}
+
+// the messages of a WebSocket connection, with its own engine thread
+fn websocket(mut ws: WebSocket<Replay>) -> io::Result<()> {
+    ws.get_mut().stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut g = engine::new_game_with_hash(1);
+    let engine = EngineHandle::new(engine::new_game_with_hash(WEBSOCKET_HASH_MB));
+    let mut searching = false;
+    loop {
+        let message = match ws.read() {
+            Ok(Message::Text(text)) => Some(text.to_string()),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => None, // pings are answered by tungstenite
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                None
+            }
+            Err(tungstenite::Error::ConnectionClosed) => break,
+            Err(e) => return Err(io::Error::other(e)),
+        };
+        if let Some(text) = message {
+            let message: Value = match serde_json::from_str(&text) {
+                Ok(message) => message,
+                Err(e) => {
+                    send(&mut ws, error_json(&e.to_string()))?;
+                    continue;
+                }
+            };
+            let kind = message["type"].as_str().unwrap_or_default();
+            let answer = match kind {
+                "position" | "move" | "go" | "analyze" if searching => {
+                    Err("a search is running".to_owned())
+                }
+                "position" => set_position(&mut g, &message).map(|()| Some(position_json(&g))),
+                "move" => play_move(&mut g, &message["move"]).map(|()| Some(position_json(&g))),
+                "go" | "analyze" => {
+                    let time = match kind {
+                        "analyze" => json!(9000),
+                        _ => message.get("movetime").cloned().unwrap_or(json!(1500)),
+                    };
+                    // checked on a copy, the engine thread reports no errors of Go
+                    set_move_time(&mut g.clone(), &json!({ "movetime": time })).map(|()| {
+                        let time = time.to_string();
+                        engine.send(Command::SetOption("MoveTime".to_owned(), time));
+                        engine.send(Command::SetPosition(g.clone()));
+                        engine.send(Command::Go);
+                        searching = true;
+                        None
+                    })
+                }
+                "stop" => {
+                    engine.send(Command::Stop);
+                    Ok(None)
+                }
+                _ => Err(format!("unknown type {}", kind)),
+            };
+            match answer {
+                Ok(Some(answer)) => send(&mut ws, answer)?,
+                Ok(None) => {}
+                Err(e) => send(&mut ws, error_json(&e))?,
+            }
+        }
+        while let Some(event) = engine.try_event() {
+            match event {
+                Event::Info(info) => send(&mut ws, info_json(&info))?,
+                Event::BestMove { key, result } if key == engine::position_key(&g) => {
+                    searching = false;
+                    send(&mut ws, best_move_json(&mut g, &result))?;
+                }
+                Event::Error(e) => send(&mut ws, error_json(&e))?,
+                _ => {}
+            }
+        }
+    }
+    engine.send(Command::Stop);
+    Ok(())
+}
+
+// the count of the open WebSocket connections, with one for the owner
+struct WebSocketSlot<'a>(&'a AtomicUsize);
+
+impl<'a> WebSocketSlot<'a> {
+    fn take(count: &'a AtomicUsize) -> Option<Self> {
+        count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < MAX_WEBSOCKETS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| WebSocketSlot(count))
+    }
+}
+
+impl Drop for WebSocketSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn connection(
+    stream: TcpStream,
+    session: &Mutex<Game>,
+    websockets: &AtomicUsize,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+    let mut stream = reader.into_inner();
+    if request.is_websocket() {
+        let Some(_slot) = WebSocketSlot::take(websockets) else {
+            let body = error_json("too many WebSocket connections").to_string();
+            return respond(&mut stream, 503, &body);
+        };
+        let replay = Replay {
+            head: io::Cursor::new(request.head),
+            stream,
+        };
+        let ws = tungstenite::accept(replay).map_err(|e| invalid(&e.to_string()))?;
+        return websocket(ws);
+    }
+    if request.method == "OPTIONS" {
+        return respond(&mut stream, 204, "");
+    }
+    let (status, body) = answer(&request, session);
+    respond(&mut stream, status, &body.to_string())
+}
+
+// answer the connections of the listener, each in its own thread
+pub fn run(listener: TcpListener) {
+    let session = Arc::new(Mutex::new(engine::new_game()));
+    let websockets = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        let session = Arc::clone(&session);
+        let websockets = Arc::clone(&websockets);
+        thread::spawn(move || connection(stream, &session, &websockets));
+    }
+}
+
+pub fn serve(address: &str, out: &mut dyn Write) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    writeln!(out, "Listening on http://{}", listener.local_addr()?)?;
+    run(listener);
+    Ok(())
+}
@@ -0,0 +1,392 @@
+// Matches between two engines: the own engine with its options, e.g. another Hash size,
+// or external UCI engines, see uci.rs. The engines alternate colors and play each opening
+// of the book twice, the games are adjudicated like the engine games of the GUI. Needs
+// std, and processes for the external engines.
+
+use crate::engine::{self, Game, GameState, Move};
+use crate::uci;
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+enum Player {
+    BuiltIn(Box<Game>), // with its own transposition table
+    External(uci::Engine),
+}
+
+pub struct Engine {
+    pub name: String,
+    player: Player,
+}
+
+impl Engine {
+    // the own engine with options like [("Hash", "64")]
+    pub fn built_in(name: &str, options: &[(String, String)]) -> Result<Self, String> {
+        let mut g = Box::new(engine::new_game());
+        for (option, value) in options {
+            engine::set_option(&mut g, option, value)
+                .map_err(|e| format!("{} {}: {}", option, value, e))?;
+        }
+        Ok(Engine {
+            name: name.to_owned(),
+            player: Player::BuiltIn(g),
+        })
+    }
+
+    // the UCI engine of the executable at path, the options are checked by the engine
+    pub fn external(name: &str, path: &str, options: &[(String, String)]) -> Result<Self, String> {
+        let mut external =
+            uci::Engine::spawn(path).map_err(|e| format!("Starting {} failed: {}", path, e))?;
+        external.handshake()?;
+        for (option, value) in options {
+            let option = external
+                .option(option)
+                .ok_or_else(|| format!("{} has no option {}", name, option))?
+                .to_owned();
+            external.send(&format!("setoption name {} value {}", option, value))?;
+        }
+        Ok(Engine {
+            name: name.to_owned(),
+            player: Player::External(external),
+        })
+    }
+
+    // the move in the position g and its score for the side to move, with the clocks
+    // of White and Black
+    fn play(
+        &mut self,
+        g: &mut Game,
+        clocks: [Duration; 2],
+        increment: Duration,
+    ) -> Result<(Move, i64), String> {
+        match &mut self.player {
+            Player::BuiltIn(own) => {
+                engine::set_position(own, g);
+                let active = g.move_counter as usize % 2;
+                engine::set_time_left(own, Some((clocks[active], increment)));
+                let result = engine::reply(own, &mut ());
+                let m = engine::legal_moves(g).find(|m| {
+                    (m.src as i64, m.dst as i64, m.promote_to)
+                        == (result.src, result.dst, result.promote_to)
+                });
+                let m = m.ok_or_else(|| "no legal move".to_owned())?;
+                Ok((m, result.score))
+            }
+            Player::External(external) => {
+                let go = format!(
+                    "go wtime {} btime {} winc {2} binc {2}",
+                    clocks[0].as_millis(),
+                    clocks[1].as_millis(),
+                    increment.as_millis()
+                );
+                let mut score = 0;
+                let m = external.go(g, &go, &mut |info| score = info.score, &mut || false)?;
+                Ok((m, score))
+            }
+        }
+    }
+}
+
+// the start of the games, a position with the moves played from it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<String>, // in UCI notation
+}
+
+// the main lines of the games of a PGN book, invalid games are skipped
+pub fn pgn_openings(pgn: &str) -> Vec<Opening> {
+    let mut g = engine::new_game_with_hash(1);
+    let mut openings = Vec::new();
+    for game in engine::split_pgn(pgn) {
+        if engine::set_pgn(&mut g, game).is_err() {
+            continue;
+        }
+        let moves = engine::move_list(&g);
+        let moves = moves[..engine::game_ply(&g)].iter();
+        let moves = moves.map(|m| m.uci().to_string()).collect();
+        engine::go_to_ply(&mut g, 0);
+        openings.push(Opening {
+            fen: engine::fen(&g),
+            moves,
+        });
+    }
+    openings
+}
+
+// the positions of an EPD book, the operations are ignored
+pub fn epd_openings(epd: &str) -> Vec<Opening> {
+    let mut g = engine::new_game_with_hash(1);
+    let lines = epd.lines().filter(|l| !l.trim_start().starts_with('#'));
+    let fens = lines.map(|l| l.split_whitespace().take(4).collect::<Vec<_>>().join(" "));
+    fens.filter(|fen| engine::set_fen(&mut g, fen).is_ok())
+        .map(|fen| Opening {
+            fen,
+            moves: Vec::new(),
+        })
+        .collect()
+}
+
+// A score beyond win_score of both engines for the last win_plies moves wins, a score
+// within draw_score draws after draw_ply, and a game is drawn after max_plies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Adjudication {
+    pub win_score: i64,
+    pub win_plies: usize,
+    pub draw_score: i64,
+    pub draw_ply: usize,
+    pub max_plies: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            win_score: 1000,
+            win_plies: 10,
+            draw_score: 10,
+            draw_ply: 80,
+            max_plies: 400,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub games: usize,
+    pub tc: (Duration, Duration), // the time for the game and the increment per move
+    pub openings: Vec<Opening>,   // the start position for all games when empty
+    pub adjudication: Adjudication,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    pub white: usize, // the index of the engine playing White
+    pub result: &'static str,
+    pub reason: String,
+    pub pgn: String,
+}
+
+impl GameRecord {
+    // the points of the engine with the index i
+    pub fn points(&self, i: usize) -> f64 {
+        match (self.result, self.white == i) {
+            ("1-0", true) | ("0-1", false) => 1.0,
+            ("1-0", false) | ("0-1", true) => 0.0,
+            _ => 0.5,
+        }
+    }
+}
+
+// the result of the scores of the moves from White's point of view
+fn adjudicate(
+    scores: &[i64],
+    ply: usize,
+    a: &Adjudication,
+) -> Option<(&'static str, &'static str)> {
+    let last = scores.get(scores.len().checked_sub(a.win_plies)?..)?;
+    if last.iter().all(|&s| s >= a.win_score) {
+        Some(("White wins by adjudication.", "1-0"))
+    } else if last.iter().all(|&s| s <= -a.win_score) {
+        Some(("Black wins by adjudication.", "0-1"))
+    } else if ply >= a.draw_ply && last.iter().all(|s| s.abs() <= a.draw_score) {
+        Some(("Draw by adjudication.", "1/2-1/2"))
+    } else {
+        None
+    }
+}
+
+fn play_game(
+    g: &mut Game,
+    engines: &mut [Engine; 2],
+    white: usize,
+    opening: Option<&Opening>,
+    m: &Match,
+) -> (String, &'static str) {
+    engine::reset_game(g);
+    if let Some(opening) = opening {
+        let _ = engine::set_fen(g, &opening.fen);
+        for uci in &opening.moves {
+            let Ok(m) = engine::parse_uci_move(g, uci) else {
+                break;
+            };
+            engine::do_move(g, m.src, m.dst, m.promote_to, false);
+        }
+    }
+    let (time, increment) = m.tc;
+    let mut clocks = [time; 2];
+    let mut scores = Vec::new();
+    let colors = ["White", "Black"];
+    loop {
+        let ply = engine::game_ply(g);
+        let state = engine::state(g);
+        if state != GameState::Ongoing {
+            return (state.to_string(), engine::pgn_result(g));
+        }
+        if let Some((reason, result)) = adjudicate(&scores, ply, &m.adjudication) {
+            return (reason.to_owned(), result);
+        }
+        if ply >= m.adjudication.max_plies {
+            return ("Draw by the move limit.".to_owned(), "1/2-1/2");
+        }
+        let active = g.move_counter as usize % 2;
+        let player = &mut engines[if active == 0 { white } else { 1 - white }];
+        let loss = if active == 0 { "0-1" } else { "1-0" };
+        let start = Instant::now();
+        let (mv, score) = match player.play(g, clocks, increment) {
+            Ok(played) => played,
+            Err(e) => return (format!("{} failed: {}.", player.name, e), loss),
+        };
+        let elapsed = start.elapsed();
+        if elapsed > clocks[active] {
+            return (format!("{} lost on time.", colors[active]), loss);
+        }
+        clocks[active] = clocks[active] - elapsed + increment;
+        scores.push(if active == 0 { score } else { -score });
+        engine::do_move(g, mv.src, mv.dst, mv.promote_to, false);
+    }
+}
+
+// Play the games of the match, on_game is called after each game and ends the match by
+// returning false. The first engine plays White in the even games, two games in turn
+// start with the same opening.
+pub fn play_match(
+    m: &Match,
+    engines: &mut [Engine; 2],
+    on_game: &mut dyn FnMut(&GameRecord) -> bool,
+) -> Vec<GameRecord> {
+    let mut g = engine::new_game_with_hash(1);
+    let mut records = Vec::new();
+    for i in 0..m.games {
+        let white = i % 2;
+        let opening = m.openings.get((i / 2) % m.openings.len().max(1));
+        let (reason, result) = play_game(&mut g, engines, white, opening, m);
+        let round = (i + 1).to_string();
+        let tags = [
+            ("Event", "Tiny chess match"),
+            ("Round", round.as_str()),
+            ("White", engines[white].name.as_str()),
+            ("Black", engines[1 - white].name.as_str()),
+            ("Result", result),
+            ("Termination", reason.as_str()),
+        ];
+        let record = GameRecord {
+            white,
+            result,
+            pgn: engine::pgn(&g, &tags),
+            reason,
+        };
+        let go_on = on_game(&record);
+        records.push(record);
+        if !go_on {
+            break;
+        }
+    }
+    records
+}
+
+// the Elo difference of a score between 0 and 1, None for a score of 0 or 1
+pub fn elo_difference(score: f64) -> Option<f64> {
+    (score > 0.0 && score < 1.0).then(|| 400.0 * (score / (1.0 - score)).log10())
+}
+
+// added to the count of each result by Sprt::llr()
+const PSEUDO_COUNT: f64 = 0.5;
+
+// the expected score of an Elo difference
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// The sequential probability ratio test of the Elo difference of the first engine, i.e.
+// a patched version against the original: H0 is a difference of elo0 and H1 of elo1,
+// alpha and beta are the probabilities to accept H1 and H0 falsely.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Continue,
+    AcceptH0, // the patch fails
+    AcceptH1, // the patch passes
+}
+
+impl Sprt {
+    // the log-likelihood ratio below which H0 and above which H1 is accepted
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // The log-likelihood ratio of the results of the first engine, approximated with the
+    // mean and the variance of the scores of the games like in the GSPRT of Fishtest. The
+    // frequencies get PSEUDO_COUNT games of each result, so the variance of a run of only
+    // wins or only draws is not 0, and the test still decides.
+    pub fn llr(&self, wins: usize, draws: usize, losses: usize) -> f64 {
+        let n = (wins + draws + losses) as f64;
+        if n == 0.0 {
+            return 0.0;
+        }
+        let [w, d, l] = [wins, draws, losses].map(|r| r as f64 + PSEUDO_COUNT);
+        let total = w + d + l;
+        let score = (w + d / 2.0) / total;
+        let variance = (w + d / 4.0) / total - score * score;
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        n * (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance)
+    }
+
+    pub fn decision(&self, llr: f64) -> Decision {
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Decision::AcceptH0
+        } else if llr >= upper {
+            Decision::AcceptH1
+        } else {
+            Decision::Continue
+        }
+    }
+}
+
+// the wins, draws, losses and points of both engines, and the Elo difference of the first
+pub fn results_table(names: [&str; 2], records: &[GameRecord]) -> String {
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(6);
+    let mut table = format!(
+        "{:width$} {:>5} {:>5} {:>5} {:>6} {:>6}\n",
+        "Engine", "Games", "Wins", "Draws", "Losses", "Points"
+    );
+    for (i, name) in names.iter().enumerate() {
+        let points: Vec<f64> = records.iter().map(|r| r.points(i)).collect();
+        let count = |p: f64| points.iter().filter(|&&q| q == p).count();
+        let _ = writeln!(
+            table,
+            "{:width$} {:>5} {:>5} {:>5} {:>6} {:>6.1}",
+            name,
+            records.len(),
+            count(1.0),
+            count(0.5),
+            count(0.0),
+            points.iter().sum::<f64>()
+        );
+    }
+    let score = records.iter().map(|r| r.points(0)).sum::<f64>() / records.len().max(1) as f64;
+    if let Some(elo) = elo_difference(score) {
+        let _ = writeln!(table, "Elo difference of {}: {:+.0}", names[0], elo);
+    }
+    table
+}
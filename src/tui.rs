@@ -0,0 +1,274 @@
+// A terminal frontend: the board in Unicode, the moves typed in SAN or UCI notation, and
+// the engine in its own thread like in the GUI. It needs no display server, so it works
+// over SSH as well.
+//
+// cargo run --release --features tui --bin tiny-chess-tui
+
+use ratatui::crossterm::event::{self, Event as Input, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+use tiny_chess::engine;
+use tiny_chess::handle::{Command, EngineHandle, Event};
+
+// the filled glyphs by VOID_ID..=KING_ID, colored for White and Black
+const PIECES: [&str; 7] = [" ", "♟", "♞", "♝", "♜", "♛", "♚"];
+const LIGHT: Color = Color::Rgb(240, 217, 181);
+const DARK: Color = Color::Rgb(181, 136, 99);
+const LAST_MOVE: Color = Color::Rgb(205, 210, 106);
+
+// for the keys and the events of the engine
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const HELP: &str = "Moves like Nf3 or g1f3, and new, undo, go, flip or quit";
+
+struct Tui {
+    game: engine::Game, // a clone for the rules, the engine thread has its own
+    engine: EngineHandle,
+    engine_side: usize, // the engine plays White (0) or Black (1)
+    thinking: bool,
+    white_below: bool,
+    input: String,
+    msg: String,
+}
+
+impl Tui {
+    fn new() -> Self {
+        let game = engine::new_game();
+        Tui {
+            game: game.clone(),
+            engine: EngineHandle::new(game),
+            engine_side: 1,
+            thinking: false,
+            white_below: true,
+            input: String::new(),
+            msg: HELP.to_owned(),
+        }
+    }
+
+    fn side_to_move(&self) -> usize {
+        self.game.move_counter as usize % 2
+    }
+
+    // the result, or None while the game goes on
+    fn result(&self) -> Option<engine::GameState> {
+        Some(engine::state(&self.game)).filter(|s| *s != engine::GameState::Ongoing)
+    }
+
+    // the engine searches when it is to move
+    fn start_engine(&mut self) {
+        if self.thinking || self.side_to_move() != self.engine_side || self.result().is_some() {
+            return;
+        }
+        self.engine.send(Command::SetPosition(self.game.clone()));
+        self.engine.send(Command::Go);
+        self.thinking = true;
+    }
+
+    fn engine_events(&mut self) {
+        while let Some(event) = self.engine.try_event() {
+            match event {
+                Event::BestMove { key, result }
+                    if self.thinking && key == engine::position_key(&self.game) =>
+                {
+                    self.thinking = false;
+                    let m = engine::do_move(
+                        &mut self.game,
+                        result.src as i8,
+                        result.dst as i8,
+                        result.promote_to,
+                        false,
+                    )
+                    .mv;
+                    self.msg = format!("{} (score: {})", m, result.score);
+                    if let Some(result) = self.result() {
+                        self.msg = format!("{} {}", self.msg, result);
+                    }
+                }
+                Event::OptionError(e) => self.msg = format!("{}.", e),
+                Event::Error(e) => self.msg = format!("{}.", e),
+                _ => {} // the progress, or the reply for an old position
+            }
+        }
+    }
+
+    fn stop_engine(&mut self) {
+        self.engine.send(Command::Stop);
+        self.thinking = false;
+    }
+
+    // a move or a command of the input line, false to quit
+    fn enter(&mut self) -> bool {
+        let text = std::mem::take(&mut self.input);
+        match text.trim() {
+            "" => {}
+            "quit" | "q" => return false,
+            "new" => {
+                self.stop_engine();
+                engine::reset_game(&mut self.game);
+                self.msg = "New game.".to_owned();
+            }
+            // back to the last position with the human to move
+            "undo" => {
+                self.stop_engine();
+                engine::take_back(&mut self.game);
+                if self.side_to_move() == self.engine_side {
+                    engine::take_back(&mut self.game);
+                }
+                self.msg = "Move taken back.".to_owned();
+            }
+            // the engine plays the side to move
+            "go" => self.engine_side = self.side_to_move(),
+            "flip" => self.white_below ^= true,
+            text => {
+                if self.thinking {
+                    self.msg = "Not your turn.".to_owned();
+                } else if self.result().is_some() {
+                    self.msg = "The game is over, type new.".to_owned();
+                } else {
+                    let m = engine::parse_san(&self.game, text)
+                        .or_else(|e| engine::parse_uci_move(&self.game, text).map_err(|_| e));
+                    self.msg = match m {
+                        Ok(m) => {
+                            let m =
+                                engine::do_move(&mut self.game, m.src, m.dst, m.promote_to, false)
+                                    .mv;
+                            let result = self.result().map(|r| r.to_string()).unwrap_or_default();
+                            format!("{} {}", m, result).trim_end().to_owned()
+                        }
+                        Err(e) => format!("{}: {}.", text, e),
+                    };
+                }
+            }
+        }
+        true
+    }
+
+    // the ranks from the top, each square three columns wide
+    fn board_lines(&self) -> Vec<Line<'static>> {
+        let board = engine::get_board(&self.game);
+        let ply = engine::game_ply(&self.game);
+        let last = ply.checked_sub(1).map(|i| engine::move_list(&self.game)[i]);
+        // board positions have h1 at 0, see view_transform() of the GUI
+        let position = |row: usize, col: usize| {
+            let s = row * 8 + col;
+            if self.white_below {
+                63 - s
+            } else {
+                s
+            }
+        };
+        let mut lines = Vec::new();
+        for row in 0..8 {
+            let rank = position(row, 0) / 8 + 1;
+            let mut spans = vec![Span::raw(format!("{} ", rank))];
+            for col in 0..8 {
+                let p = position(row, col);
+                let f = board[p];
+                let moved = last.is_some_and(|m| m.src as usize == p || m.dst as usize == p);
+                let bg = if moved {
+                    LAST_MOVE
+                } else if (p / 8 + p % 8) % 2 == 0 {
+                    LIGHT
+                } else {
+                    DARK
+                };
+                let fg = if f > 0 { Color::White } else { Color::Black };
+                let piece = format!(" {} ", PIECES[f.unsigned_abs() as usize]);
+                spans.push(Span::styled(piece, Style::new().fg(fg).bg(bg).bold()));
+            }
+            lines.push(Line::from(spans));
+        }
+        let files: String = (0..8)
+            .map(|col| {
+                let file = b'h' - (position(0, col) % 8) as u8;
+                format!(" {} ", file as char)
+            })
+            .collect();
+        lines.push(Line::from(format!("  {}", files)));
+        lines
+    }
+
+    // the moves of the game in pairs, numbered from the start position
+    fn move_lines(&self) -> Vec<Line<'static>> {
+        let ply = engine::game_ply(&self.game);
+        let moves = engine::move_list(&self.game);
+        let pairs = moves[..ply].chunks(2).enumerate();
+        let pairs = pairs.map(|(i, pair)| {
+            let pair: Vec<String> = pair.iter().map(|m| m.to_string()).collect();
+            Line::from(format!("{}. {}", i + 1, pair.join(" ")))
+        });
+        pairs.collect()
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [top, input, status] = Layout::vertical([
+            Constraint::Min(11),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [board, moves] =
+            Layout::horizontal([Constraint::Length(28), Constraint::Min(20)]).areas(top);
+        let title = if self.thinking { "Thinking" } else { "Board" };
+        frame.render_widget(
+            Paragraph::new(self.board_lines()).block(Block::bordered().title(title)),
+            board,
+        );
+        // the last moves remain visible
+        let lines = self.move_lines();
+        let rows = moves.height.saturating_sub(2) as usize;
+        let skip = lines.len().saturating_sub(rows);
+        frame.render_widget(
+            Paragraph::new(lines[skip..].to_vec()).block(Block::bordered().title("Moves")),
+            moves,
+        );
+        frame.render_widget(
+            Paragraph::new(self.input.as_str()).block(Block::bordered().title("Move")),
+            input,
+        );
+        frame.set_cursor_position((input.x + 1 + self.input.chars().count() as u16, input.y + 1));
+        frame.render_widget(
+            Paragraph::new(self.msg.as_str()).wrap(Wrap { trim: true }),
+            status,
+        );
+    }
+}
+
+fn run(terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+    let mut tui = Tui::new();
+    loop {
+        terminal.draw(|frame| tui.draw(frame))?;
+        tui.engine_events();
+        tui.start_engine();
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        let Input::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Enter if !tui.enter() => return Ok(()),
+            KeyCode::Backspace => {
+                tui.input.pop();
+            }
+            KeyCode::Char(c) => tui.input.push(c),
+            _ => {}
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,300 @@
+// The Universal Chess Interface of external engines like Stockfish, which run as child
+// processes, for games behind an EngineHandle, see handle::EngineHandle::external(),
+// and as a backend of the analysis. Only the commands and answers used by the GUI are
+// translated.
+
+use crate::analysis::{AnalysisObserver, Backend};
+use crate::engine::{self, Game, Move, OptionValue, SearchInfo};
+use core::fmt::Write as _;
+use core::str::SplitWhitespace;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// for the answers to uci and isready
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// the stop requests are polled while the engine searches
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// for the exit after quit, then the engine is killed
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// the position command for the current position of g: the start position of the game
+// and the moves played since
+pub fn position_command(g: &mut Game) -> String {
+    let ply = engine::game_ply(g);
+    let moves = engine::move_list(g);
+    engine::go_to_ply(g, 0);
+    let mut command = format!("position fen {}", engine::fen(g));
+    engine::go_to_ply(g, ply);
+    if ply > 0 {
+        command.push_str(" moves");
+        for m in &moves[..ply] {
+            let _ = write!(command, " {}", m.uci());
+        }
+    }
+    command
+}
+
+// The go command with the clock of engine::set_time_left(), which is given for both
+// players as the GUI knows only the time of the active one. Without a clock the engine
+// searches for the option MoveTime.
+pub fn go_command(g: &Game) -> String {
+    match engine::time_left(g) {
+        Some((left, increment)) => format!(
+            "go wtime {0} btime {0} winc {1} binc {1}",
+            left.as_millis(),
+            increment.as_millis()
+        ),
+        None => {
+            let ms = match engine::get_option(g, "MoveTime") {
+                Some(OptionValue::Spin(ms)) => *ms,
+                _ => 1000,
+            };
+            format!("go movetime {}", ms)
+        }
+    }
+}
+
+// the names of the options in the answer to the uci command, e.g. Hash from
+// "option name Hash type spin default 16 min 1 max 33554432"
+pub fn parse_option(line: &str) -> Option<String> {
+    let rest = line.trim().strip_prefix("option name ")?;
+    let name = rest.split(" type ").next()?.trim();
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+fn number(words: &mut SplitWhitespace) -> i64 {
+    words.next().and_then(|w| w.parse().ok()).unwrap_or(0)
+}
+
+// An info line with a score and a principal variation, like
+// "info depth 12 seldepth 17 score cp 31 nodes 48012 time 61 hashfull 12 pv e2e4 e7e5".
+// The moves of the pv are checked in g, which is restored. Other info lines, and the
+// lines of secondary variations with MultiPV, give None.
+pub fn parse_info(g: &mut Game, line: &str) -> Option<SearchInfo> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "info" {
+        return None;
+    }
+    let (mut depth, mut seldepth, mut nodes, mut time, mut hashfull) = (0, 0, 0, 0, 0);
+    let (mut score, mut pv) = (None, Vec::new());
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => depth = number(&mut words),
+            "seldepth" => seldepth = number(&mut words),
+            "nodes" => nodes = number(&mut words),
+            "time" => time = number(&mut words),
+            "hashfull" => hashfull = number(&mut words),
+            "multipv" if number(&mut words) > 1 => return None,
+            // mate in n moves like the scores of the own engine, KING_VALUE for a mate
+            // with the next move, less one for each ply until the mate
+            "score" => {
+                let kind = words.next();
+                let n = number(&mut words);
+                let king = engine::KING_VALUE as i64;
+                score = match kind {
+                    Some("cp") => Some(n),
+                    Some("mate") if n > 0 => Some(king - 2 * n + 2),
+                    Some("mate") => Some(-(king + 2 * n + 1)),
+                    _ => None,
+                };
+            }
+            "pv" => pv = words.by_ref().collect(), // the last field
+            "string" => return None,
+            _ => {}
+        }
+    }
+    let score = score?;
+    let mut tokens = Vec::new();
+    for uci in pv {
+        let Ok(m) = engine::parse_uci_move(g, uci) else {
+            break;
+        };
+        tokens.push(engine::do_move(g, m.src, m.dst, m.promote_to, false));
+    }
+    let pv: Vec<_> = tokens.iter().map(|t| t.mv).collect();
+    while let Some(token) = tokens.pop() {
+        engine::undo_move(g, token);
+    }
+    Some(SearchInfo {
+        depth,
+        best_move: *pv.first()?,
+        pv,
+        score,
+        nodes: nodes as u64,
+        time: Duration::from_millis(time as u64),
+        seldepth,
+        hashfull: hashfull as u32,
+    })
+}
+
+// the move of "bestmove e2e4 ponder e7e5" in UCI notation
+pub fn parse_bestmove(line: &str) -> Option<&str> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "bestmove" {
+        return None;
+    }
+    words.next()
+}
+
+// a running external engine, which quits when it is dropped
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: mpsc::Receiver<String>, // of the output, read by a thread
+    options: Vec<String>,          // the names announced in the handshake
+    error: Option<String>,         // why the last search failed
+}
+
+impl Engine {
+    // start the executable at path, handshake() has to follow
+    pub fn spawn(path: &str) -> std::io::Result<Self> {
+        let mut child = std::process::Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = child.stdout.take().expect("piped stdout");
+        let (line_tx, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if line_tx.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Engine {
+            child,
+            stdin,
+            lines,
+            options: Vec::new(),
+            error: None,
+        })
+    }
+
+    // the engine announces its options, and has to be ready within HANDSHAKE_TIMEOUT
+    pub fn handshake(&mut self) -> Result<(), String> {
+        self.send("uci")?;
+        loop {
+            let line = self
+                .line(HANDSHAKE_TIMEOUT)?
+                .ok_or("The engine does not answer")?;
+            if line.trim() == "uciok" {
+                break;
+            }
+            self.options.extend(parse_option(&line));
+        }
+        self.send("isready")?;
+        loop {
+            let line = self
+                .line(HANDSHAKE_TIMEOUT)?
+                .ok_or("The engine does not answer")?;
+            if line.trim() == "readyok" {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).map_err(|e| format!("The engine terminated: {}", e))
+    }
+
+    // the next line of the output, None after the timeout
+    fn line(&self, timeout: Duration) -> Result<Option<String>, String> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(Some(line)),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err("The engine terminated".to_owned()),
+        }
+    }
+
+    // the announced name of an option, which is case insensitive
+    pub fn option(&self, name: &str) -> Option<&str> {
+        let name = name.trim();
+        self.options
+            .iter()
+            .find(|o| o.eq_ignore_ascii_case(name))
+            .map(|o| o.as_str())
+    }
+
+    // Search the current position of g with the go command until the engine answers with
+    // its best move. The info lines are passed to on_info, and stop is polled: when it
+    // returns true the search is stopped, the engine still answers with a move.
+    pub fn go(
+        &mut self,
+        g: &mut Game,
+        go: &str,
+        on_info: &mut dyn FnMut(SearchInfo),
+        stop: &mut dyn FnMut() -> bool,
+    ) -> Result<Move, String> {
+        self.send(&position_command(g))?;
+        self.send(go)?;
+        let mut stopped = false;
+        loop {
+            if !stopped && stop() {
+                self.send("stop")?;
+                stopped = true;
+            }
+            let Some(line) = self.line(POLL_INTERVAL)? else {
+                continue;
+            };
+            if let Some(info) = parse_info(g, &line) {
+                on_info(info);
+            } else if let Some(uci) = parse_bestmove(&line) {
+                return engine::parse_uci_move(g, uci)
+                    .map_err(|e| format!("Invalid move {} of the engine: {}", uci, e));
+            }
+        }
+    }
+
+    // why the last search of the analysis failed
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // an engine which ignores quit must not block the GUI, which drops its handle
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while let Ok(None) = self.child.try_wait() {
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+// the analysis searches each position to the depth of the engine
+impl Backend for Engine {
+    fn search(
+        &mut self,
+        g: &mut Game,
+        depth: i64,
+        observer: &mut dyn AnalysisObserver,
+    ) -> Option<SearchInfo> {
+        let mut last = None;
+        let mut canceled = false;
+        let go = format!("go depth {}", depth);
+        let result = self.go(g, &go, &mut |info| last = Some(info), &mut || {
+            canceled = canceled || observer.should_stop();
+            canceled
+        });
+        if let Err(e) = result {
+            self.error = Some(e);
+            return None;
+        }
+        if canceled {
+            None
+        } else {
+            last
+        }
+    }
+}
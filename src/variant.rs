@@ -0,0 +1,172 @@
+// Chess variants. A Variant changes the rules of the engine at a few points: the start
+// position and the valid setups, the pseudo moves of a figure, a win by a rule of the
+// variant, the draw by insufficient material and the static evaluation. The default methods are the rules of
+// standard chess, so a variant only implements its differences. Checkmate, stalemate,
+// castling and the other draw rules stay with the engine. A game keeps its variant, see
+// engine::set_variant(), and the name is the Variant tag of PGN.
+
+use crate::engine::{self, Board, Color, Game, Kks, Position, SetupError, KK};
+use crate::engine::{COLOR_BLACK, COLOR_WHITE, KING_ID, KING_VALUE, PAWN_ID, VOID_ID};
+
+pub trait Variant: Sync {
+    // as in the PGN Variant tag of lichess, i.e. "King of the Hill"
+    fn name(&self) -> &'static str;
+
+    fn start_fen(&self) -> &'static str {
+        engine::START_FEN
+    }
+
+    // the kings and pawns of a position for engine::set_board()
+    fn check_setup(&self, pieces: &Board) -> Result<(), SetupError> {
+        engine::check_setup(pieces)
+    }
+
+    // append the pseudo moves of the figure at kk.si to s, castlings are added by the
+    // engine. The search generates all en passant captures and tests them later.
+    fn walk(&self, g: &Game, kk: KK, s: &mut Kks, all_en_passant: bool) {
+        engine::walk_figure(g, kk, s, all_en_passant)
+    }
+
+    // the color which has won by a rule of the variant on board, tested by engine::state()
+    // and by the search after each move; a move which leaves the own king in check is
+    // still invalid, so the test must not hold then
+    fn winner(&self, _g: &Game, _board: &Board) -> Option<Color> {
+        None
+    }
+
+    // no side can win anymore, see engine::state()
+    fn insufficient_material(&self, g: &Game) -> bool {
+        engine::insufficient_material(g)
+    }
+
+    // the static evaluation of the search in centipawns, from White's view
+    fn evaluate(&self, g: &Game) -> i16 {
+        engine::plain_evaluate_board(g)
+    }
+}
+
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+// A king which reaches one of the four center squares wins, when it is not in check there.
+pub struct KingOfTheHill;
+
+const HILL: [Position; 4] = [27, 28, 35, 36]; // e4, d4, e5 and d5
+const HILL_BONUS: [i16; 4] = [80, 40, 20, 10]; // by the distance of a king to the hill
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn winner(&self, g: &Game, board: &Board) -> Option<Color> {
+        let king = HILL
+            .into_iter()
+            .find(|&p| board[p as usize].abs() == KING_ID)?;
+        let color = board[king as usize].signum();
+        (!engine::is_attacked_on(g, board, king, -color)).then_some(color)
+    }
+
+    // bare kings can still reach the hill
+    fn insufficient_material(&self, _g: &Game) -> bool {
+        false
+    }
+
+    // the kings are drawn to the center, even in the middlegame
+    fn evaluate(&self, g: &Game) -> i16 {
+        let mut result = engine::plain_evaluate_board(g);
+        let board = engine::get_board(g);
+        for (c, bonus) in [(COLOR_WHITE, 1), (COLOR_BLACK, -1)] {
+            if let Some(p) = board.iter().position(|&f| f == KING_ID * c) {
+                let (file, rank) = ((p % 8) as i16, (p / 8) as i16);
+                let distance = |i: i16| (3 - i).max(i - 4).max(0);
+                result += bonus * HILL_BONUS[distance(file).max(distance(rank)) as usize];
+            }
+        }
+        result
+    }
+}
+
+// White has 36 pawns and no king, Black the usual army. Black wins by capturing all of
+// them, White by checkmate. The pawns on the first rank can do a double step as well,
+// without en passant.
+pub struct Horde;
+
+const HORDE_PAWN_BONUS: i16 = 8; // the pawns of the horde protect each other
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    fn check_setup(&self, pieces: &Board) -> Result<(), SetupError> {
+        if pieces.iter().filter(|&&f| f == KING_ID).count() != 0
+            || pieces.iter().filter(|&&f| f == -KING_ID).count() != 1
+        {
+            return Err(SetupError::MissingKing);
+        }
+        // the first rank is 0..8, the last 56..64
+        if (56..64).any(|p| pieces[p].abs() == PAWN_ID) || (0..8).any(|p| pieces[p] == -PAWN_ID) {
+            return Err(SetupError::PawnOnBackRank);
+        }
+        Ok(())
+    }
+
+    fn walk(&self, g: &Game, kk: KK, s: &mut Kks, all_en_passant: bool) {
+        engine::walk_figure(g, kk, s, all_en_passant);
+        let board = engine::get_board(g);
+        let (step, jump) = (kk.si as usize + 8, kk.si as usize + 16);
+        if kk.sf as i64 == PAWN_ID
+            && kk.si < 8
+            && kk.s >= 0
+            && board[step] == VOID_ID
+            && board[jump] == VOID_ID
+        {
+            let mut kk = kk;
+            kk.di = jump as Position;
+            kk.df = VOID_ID as i8;
+            s.push(kk);
+        }
+    }
+
+    fn winner(&self, _g: &Game, board: &Board) -> Option<Color> {
+        board.iter().all(|&f| f <= 0).then_some(COLOR_BLACK)
+    }
+
+    // Black can still capture the last pieces of White
+    fn insufficient_material(&self, _g: &Game) -> bool {
+        false
+    }
+
+    // the missing white king is not a loss
+    fn evaluate(&self, g: &Game) -> i16 {
+        let pawns = engine::get_board(g)
+            .iter()
+            .filter(|&&f| f == PAWN_ID)
+            .count() as i16;
+        engine::plain_evaluate_board(g) + KING_VALUE + HORDE_PAWN_BONUS * pawns
+    }
+}
+
+pub static STANDARD: &dyn Variant = &Standard;
+pub static KING_OF_THE_HILL: &dyn Variant = &KingOfTheHill;
+pub static HORDE: &dyn Variant = &Horde;
+
+pub static VARIANTS: [&dyn Variant; 3] = [STANDARD, KING_OF_THE_HILL, HORDE];
+
+// the variant of a PGN Variant tag, case is ignored
+pub fn by_name(name: &str) -> Option<&'static dyn Variant> {
+    VARIANTS
+        .iter()
+        .copied()
+        .find(|v| v.name().eq_ignore_ascii_case(name.trim()))
+}
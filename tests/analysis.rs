@@ -0,0 +1,89 @@
+// The post-game analysis, on a short game with a blunder.
+
+mod common;
+
+use tiny_chess::analysis::{self, Judgment, Thresholds};
+use tiny_chess::engine;
+
+fn scholars_mate() -> engine::Game {
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]);
+    g
+}
+
+#[derive(Default)]
+struct Canceler {
+    positions: Vec<usize>,
+}
+
+impl analysis::AnalysisObserver for Canceler {
+    fn on_position(&mut self, ply: usize, plies: usize) {
+        assert_eq!(plies, 7);
+        self.positions.push(ply);
+    }
+    fn should_stop(&mut self) -> bool {
+        self.positions.len() > 2
+    }
+}
+
+#[test]
+fn analyze_game() {
+    let g = scholars_mate();
+    let t = Thresholds::default();
+    let report = analysis::analyze_game(&g, 3, &mut ()).unwrap();
+    assert_eq!(report.moves.len(), 7);
+    let blunder = &report.moves[5];
+    assert_eq!(blunder.mv.to_string(), "Nf6");
+    assert!(!blunder.white);
+    assert_eq!(report.judgment(5, &t), Some(Judgment::Blunder));
+    assert_eq!(report.count(false, Judgment::Blunder, &t), 1);
+    assert_eq!(report.count(true, Judgment::Blunder, &t), 0);
+    assert!(report.average_loss(false) > report.average_loss(true));
+    assert_eq!(report.critical_moments(3, &t), vec![5]);
+    let mate = &report.moves[6];
+    assert_eq!((mate.loss, mate.score > 900), (0, true));
+    // the best move after a mistake
+    assert_eq!(report.judgment(6, &t), Some(Judgment::Good));
+    // the game is unchanged
+    assert_eq!(engine::game_ply(&g), 7);
+
+    let mut canceler = Canceler::default();
+    assert!(analysis::analyze_game(&g, 3, &mut canceler).is_none());
+    assert_eq!(canceler.positions, vec![0, 1, 2]);
+}
+
+#[test]
+fn judgment() {
+    let t = Thresholds::default();
+    assert_eq!(analysis::judgment(0, &t), None);
+    assert_eq!(analysis::judgment(49, &t), None);
+    assert_eq!(analysis::judgment(50, &t), Some(Judgment::Inaccuracy));
+    assert_eq!(analysis::judgment(100, &t), Some(Judgment::Mistake));
+    assert_eq!(analysis::judgment(300, &t), Some(Judgment::Blunder));
+    let strict = Thresholds {
+        inaccuracy: 20,
+        ..t
+    };
+    assert_eq!(analysis::judgment(20, &strict), Some(Judgment::Inaccuracy));
+
+    // a queen sacrifice, captured by the king
+    let mut g = common::small_game();
+    engine::set_fen(&mut g, "6rk/6pp/8/8/8/3Q4/8/6K1 w - - 0 1").unwrap();
+    common::play_san(&mut g, &["Qxh7+", "Kxh7"]);
+    let moves = engine::move_list(&g).into_iter().enumerate();
+    let moves = moves.map(|(ply, mv)| analysis::AnalyzedMove {
+        mv,
+        best: mv,
+        white: ply == 0,
+        score: 0,
+        loss: 0,
+    });
+    let report = analysis::Report {
+        depth: 1,
+        move_counter: 0,
+        moves: moves.collect(),
+    };
+    assert_eq!(report.judgment(0, &t), Some(Judgment::Brilliant));
+    assert_eq!(report.judgment(1, &t), None);
+    assert_eq!(Judgment::Brilliant.nag(), 3);
+}
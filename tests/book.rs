@@ -0,0 +1,180 @@
+// Opening books in the format of Polyglot: the encoding of the moves, a book built from
+// PGN games and read back from its bytes, and the engine playing from a book.
+
+mod common;
+
+use std::sync::Arc;
+use tiny_chess::book::{self, Book, BookError, Builder, Filter, Results};
+use tiny_chess::engine::{self, Game};
+
+const PGN: &str = r#"[White "A"]
+[Black "B"]
+[WhiteElo "2400"]
+[BlackElo "2300"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. O-O 1-0
+
+[White "C"]
+[Black "D"]
+[WhiteElo "2500"]
+[BlackElo "2450"]
+[Result "1/2-1/2"]
+
+1. e4 c5 2. Nf3 d6 1/2-1/2
+
+[White "E"]
+[Black "F"]
+[WhiteElo "1500"]
+[BlackElo "1600"]
+[Result "0-1"]
+
+1. d4 d5 2. c4 e6 0-1
+
+[White "G"]
+[Black "H"]
+[Result "*"]
+
+1. c4 *
+"#;
+
+fn book_moves(book: &Book, g: &Game) -> Vec<(String, u16)> {
+    let moves = book.moves(g).into_iter();
+    moves.map(|(m, w)| (m.uci().to_string(), w)).collect()
+}
+
+#[test]
+fn encoding() {
+    let mut g = common::small_game();
+    let e4 = engine::parse_san(&g, "e4").unwrap();
+    // e2 is 12, e4 is 28
+    assert_eq!(book::encode(&e4), 12 << 6 | 28);
+    engine::set_fen(&mut g, "r3k3/1P6/8/8/8/8/8/4K2R w Kq - 0 1").unwrap();
+    let castling = engine::parse_san(&g, "O-O").unwrap();
+    // e1h1
+    assert_eq!(book::encode(&castling), 4 << 6 | 7);
+    let promotion = engine::parse_uci_move(&g, "b7a8n").unwrap();
+    assert_eq!(book::encode(&promotion), 1 << 12 | 49 << 6 | 56);
+}
+
+#[test]
+fn polyglot_keys() {
+    // the examples of the Polyglot book format, with castling and en passant
+    let mut g = common::small_game();
+    assert_eq!(engine::polyglot_key(&g), 0x463b96181691fc9c);
+    let keys = [
+        ("e4", 0x823c9b50fd114196),
+        ("d5", 0x0756b94461c50fb0),
+        ("e5", 0x662fafb965db29d4),
+        ("f5", 0x22a48b5a8e47ff78),
+        ("Ke2", 0x652a607ca3f242c1),
+        ("Kf7", 0x00fdd303c946bdd9),
+    ];
+    for (san, key) in keys {
+        common::play_san(&mut g, &[san]);
+        assert_eq!(engine::polyglot_key(&g), key, "after {}", san);
+    }
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["a4", "b5", "h4", "b4", "c4"]);
+    assert_eq!(engine::polyglot_key(&g), 0x3c8123ea7b067637);
+    common::play_san(&mut g, &["bxc3", "Ra3"]);
+    assert_eq!(engine::polyglot_key(&g), 0x5c3f9b829b279560);
+}
+
+#[test]
+fn build_and_read() {
+    let mut builder = Builder::new(Filter::default());
+    // the unfinished game is skipped
+    assert_eq!(builder.add_pgn(PGN), 3);
+    let bytes = builder.book().to_bytes();
+    assert_eq!(bytes.len() % 16, 0);
+    let book = Book::from_bytes(&bytes).unwrap();
+    assert_eq!(book, builder.book());
+    let mut g = common::small_game();
+    // 2 points for the win, 1 for the draw, and d4 lost
+    assert_eq!(book_moves(&book, &g), [("e2e4".to_owned(), 3)]);
+    common::play_san(&mut g, &["e4"]);
+    // the moves of the losing player of the first game are skipped
+    assert_eq!(book_moves(&book, &g), [("c7c5".to_owned(), 1)]);
+    common::play_san(&mut g, &["e5", "Nf3", "Nc6", "Bc4", "Nf6"]);
+    // castling is found by its Polyglot encoding
+    assert_eq!(book_moves(&book, &g), [("e1g1".to_owned(), 2)]);
+    assert_eq!(
+        Book::from_bytes(&bytes[1..]),
+        Err(BookError::Size(bytes.len() - 1))
+    );
+}
+
+#[test]
+fn filter() {
+    let mut builder = Builder::new(Filter {
+        plies: 1,
+        min_rating: 2400,
+        results: Results::All,
+    });
+    assert_eq!(builder.add_pgn(PGN), 1);
+    let book = builder.book();
+    assert_eq!(book.entries().len(), 1);
+    assert_eq!(book.positions(), 1);
+    let mut builder = Builder::new(Filter {
+        results: Results::Wins,
+        ..Filter::default()
+    });
+    assert_eq!(builder.add_pgn(PGN), 2);
+    let g = common::small_game();
+    let moves = book_moves(&builder.book(), &g);
+    assert_eq!(moves, [("e2e4".to_owned(), 2)]);
+}
+
+#[test]
+fn engine_plays_the_book() {
+    let mut builder = Builder::new(Filter::default());
+    builder.add_pgn(PGN);
+    let book = builder.book();
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["e4"]);
+    assert_eq!(
+        book.pick(&g, 12345).map(|m| m.uci().to_string()),
+        Some("c7c5".to_owned())
+    );
+    engine::set_option(&mut g, "MoveTime", "100").unwrap();
+    engine::set_book(&mut g, Some(Arc::new(book)));
+    let result = engine::reply(&mut g, &mut ());
+    assert_eq!((result.src, result.dst), (53, 37)); // c7c5
+                                                    // out of the book the engine searches
+    common::play_san(&mut g, &["a6"]);
+    let result = engine::reply(&mut g, &mut ());
+    assert!(
+        engine::legal_moves(&g).any(|m| (m.src as i64, m.dst as i64) == (result.src, result.dst))
+    );
+}
+
+#[test]
+fn editing() {
+    let mut builder = Builder::new(Filter {
+        results: Results::All,
+        ..Filter::default()
+    });
+    builder.add_pgn(PGN);
+    let mut book = builder.book();
+    let mut g = common::small_game();
+    let e4 = engine::parse_san(&g, "e4").unwrap();
+    let d4 = engine::parse_san(&g, "d4").unwrap();
+    let c4 = engine::parse_san(&g, "c4").unwrap();
+    book.remove(&g, &e4);
+    book.set_weight(&g, &d4, 7);
+    book.set_weight(&g, &c4, 5);
+    assert_eq!(
+        book_moves(&book, &g),
+        [("d2d4".to_owned(), 7), ("c2c4".to_owned(), 5)]
+    );
+    // the line of e4 is deleted, the one of d4 is kept
+    common::play_san(&mut g, &["d4"]);
+    assert_eq!(book_moves(&book, &g), [("d7d5".to_owned(), 2)]);
+    engine::take_back(&mut g);
+    common::play_san(&mut g, &["e4"]);
+    assert!(book_moves(&book, &g).is_empty());
+    assert_eq!(book.positions(), 4);
+    let book = Book::from_bytes(&book.to_bytes()).unwrap();
+    assert_eq!(book.positions(), 4);
+}
@@ -0,0 +1,70 @@
+// The live output of the game as files and over HTTP.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use tiny_chess::broadcast::{self, Endpoint, Live};
+use tiny_chess::engine;
+
+fn game(moves: &[&str]) -> engine::Game {
+    let mut g = common::small_game();
+    common::play_san(&mut g, moves);
+    g
+}
+
+#[test]
+fn live() {
+    let g = game(&[]);
+    let live = Live::new(&g, "*\n".to_owned());
+    assert_eq!(live.last_move, "");
+    assert_eq!(live.fen, engine::fen(&g));
+    let g = game(&["e4", "e5", "Qh5", "Nc6", "Bc4", "Nf6", "Qxf7#"]);
+    assert_eq!(Live::new(&g, String::new()).last_move, "4. Qxf7#");
+    let g = game(&["e4", "c5"]);
+    let live = Live::new(&g, "[White \"A \\\"B\\\"\"]\n\n1. e4 c5 *\n".to_owned());
+    assert_eq!(live.last_move, "1... c5");
+    assert_eq!(
+        live.json(),
+        format!(
+            "{{\"fen\": \"{}\", \"pgn\": \"[White \\\"A \\\\\\\"B\\\\\\\"\\\"]\\n\\n1. e4 c5 *\\n\", \"lastMove\": \"1... c5\"}}",
+            live.fen
+        )
+    );
+}
+
+#[test]
+fn files() {
+    let dir = std::env::temp_dir().join(format!("tiny-chess-broadcast-{}", std::process::id()));
+    let live = Live::new(&game(&["d4"]), "1. d4 *\n".to_owned());
+    broadcast::write_files(&dir, &live).unwrap();
+    let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("live.fen"), live.fen);
+    assert_eq!(read("live.pgn"), "1. d4 *\n");
+    assert_eq!(read("live.txt"), "1. d4");
+    assert_eq!(read("live.json"), live.json());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn endpoint() {
+    let endpoint = Endpoint::start(0).unwrap();
+    let get = |path: &str| {
+        let mut stream = TcpStream::connect(("127.0.0.1", endpoint.port())).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    endpoint.update(Live::new(&game(&["Nf3"]), "1. Nf3 *\n".to_owned()));
+    // a client which sends nothing does not block the others
+    let _idle = TcpStream::connect(("127.0.0.1", endpoint.port())).unwrap();
+    let response = get("/move");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+    assert!(response.ends_with("\r\n\r\n1. Nf3"));
+    assert!(get("/pgn").ends_with("\r\n\r\n1. Nf3 *\n"));
+    assert!(get("/json").contains("\"lastMove\": \"1. Nf3\""));
+    assert!(get("/board").starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
@@ -0,0 +1,134 @@
+// The subcommands of the tiny-chess binary for scripts, see src/cli.rs.
+
+use std::time::Duration;
+use tiny_chess::book::{Filter, Results};
+use tiny_chess::cli::{self, Cli, CliError};
+use tiny_chess::engine;
+
+fn args(line: &str) -> Vec<String> {
+    line.split_whitespace().map(str::to_owned).collect()
+}
+
+#[test]
+fn arguments() {
+    assert_eq!(
+        cli::parse_args(&args("perft --depth 6")).unwrap(),
+        Cli::Perft {
+            fen: None,
+            depth: 6
+        }
+    );
+    assert_eq!(
+        cli::parse_args(&args("selfplay --games 100 --tc 1+0.1")).unwrap(),
+        Cli::Selfplay {
+            games: 100,
+            tc: (Duration::from_secs(60), Duration::from_millis(100)),
+            pgn: None,
+        }
+    );
+    assert_eq!(
+        cli::parse_args(&args(
+            "match --engine2 ./stockfish --options1 Hash=64,MoveTime=500"
+        ))
+        .unwrap(),
+        Cli::Match {
+            engines: ["builtin".to_owned(), "./stockfish".to_owned()],
+            options: [
+                vec![
+                    ("Hash".to_owned(), "64".to_owned()),
+                    ("MoveTime".to_owned(), "500".to_owned())
+                ],
+                Vec::new()
+            ],
+            games: 10,
+            tc: (Duration::from_secs(60), Duration::from_millis(100)),
+            book: None,
+            pgn: None,
+            sprt: None,
+        }
+    );
+    assert_eq!(
+        cli::parse_args(&args("book games.pgn --plies 12 --results wins")).unwrap(),
+        Cli::Book {
+            path: "games.pgn".to_owned(),
+            out: "book.bin".to_owned(),
+            filter: Filter {
+                plies: 12,
+                min_rating: 0,
+                results: Results::Wins,
+            },
+        }
+    );
+    assert_eq!(cli::parse_options("Hash"), None);
+    let sprt = cli::parse_sprt("elo1=10,alpha=0.01").unwrap();
+    assert_eq!(
+        (sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta),
+        (0.0, 10.0, 0.01, 0.05)
+    );
+    assert_eq!(cli::parse_sprt("elo0=5,elo1=0"), None);
+    assert!(matches!(
+        cli::parse_args(&args("match --sprt elo1=3")).unwrap(),
+        Cli::Match {
+            games: 100_000,
+            sprt: Some(_),
+            ..
+        }
+    ));
+    let analyze = vec![
+        "analyze".to_owned(),
+        "--fen".to_owned(),
+        "8/8/8/8/8/8/8/K6k w - - 0 1".to_owned(),
+    ];
+    assert!(matches!(
+        cli::parse_args(&analyze).unwrap(),
+        Cli::Analyze {
+            fen: Some(_),
+            depth: 10
+        }
+    ));
+    assert_eq!(
+        cli::parse_tc("0.5"),
+        Some((Duration::from_secs(30), Duration::ZERO))
+    );
+    assert_eq!(cli::parse_tc("1+x"), None);
+
+    let error = |line| cli::parse_args(&args(line)).unwrap_err();
+    assert!(matches!(error("play"), CliError::UnknownCommand(_)));
+    assert!(matches!(error("perft --games 3"), CliError::UnknownFlag(_)));
+    assert!(matches!(error("perft --depth"), CliError::MissingValue(_)));
+    assert!(matches!(
+        error("perft --depth x"),
+        CliError::InvalidValue(..)
+    ));
+}
+
+#[test]
+fn output() {
+    // the move tables are on the heap, a Game fits on the stack of a test thread
+    assert!(std::mem::size_of::<engine::Game>() < 16 * 1024);
+    let mut g = engine::new_game();
+    assert_eq!(cli::perft(&mut g, 3), 8902);
+
+    let mut out = Vec::new();
+    let perft = cli::parse_args(&args("perft --depth 2")).unwrap();
+    cli::run(&perft, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("perft 1 nodes 20 time "));
+    assert!(text.contains("perft 2 nodes 400 time "));
+
+    // a mate in one
+    let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1".to_owned();
+    let analyze = Cli::Analyze {
+        fen: Some(fen),
+        depth: 3,
+    };
+    let mut out = Vec::new();
+    cli::run(&analyze, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("score mate 1"), "{}", text);
+    assert!(text.ends_with("bestmove a1a8\n"), "{}", text);
+    assert_eq!(cli::uci_score(-31), "cp -31");
+    let king = engine::KING_VALUE as i64;
+    assert_eq!(cli::uci_score(king - 2), "mate 2");
+    assert_eq!(cli::uci_score(1 - king), "mate -1");
+}
@@ -0,0 +1,139 @@
+// Invariants of the move generation, shared by the proptest suite in tests/ and the
+// fuzz target in fuzz/. Each check panics with the FEN of the offending position.
+// And small helpers of the tests, not every test uses all of this module.
+#![allow(dead_code)]
+
+use tiny_chess::engine::{self, Game, GameState, Move};
+
+// a game for the rules only, with the smallest transposition table
+pub fn small_game() -> Game {
+    engine::new_game_with_hash(1)
+}
+
+// play the moves in SAN, which must be valid
+pub fn play_san(g: &mut Game, moves: &[&str]) {
+    for san in moves {
+        let m = engine::parse_san(g, san)
+            .unwrap_or_else(|e| panic!("{} in {}: {:?}", san, engine::fen(g), e));
+        engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    }
+}
+
+// move_is_valid2() must agree with legal_moves(), and the SAN and UCI strings of
+// all valid moves must be unique and parse back to the same move.
+pub fn check_position(g: &Game) {
+    let fen = engine::fen(g);
+    let moves: Vec<Move> = engine::legal_moves(g).collect();
+    for si in 0..64 {
+        for di in 0..64 {
+            let listed = moves.iter().any(|m| m.src == si && m.dst == di);
+            assert_eq!(
+                engine::move_is_valid2(g, si as i64, di as i64),
+                listed,
+                "move_is_valid2 {} {} disagrees with legal_moves in {}",
+                si,
+                di,
+                fen
+            );
+        }
+    }
+    let mut san: Vec<String> = moves.iter().map(|m| m.to_string()).collect();
+    let mut uci: Vec<String> = moves.iter().map(|m| m.uci().to_string()).collect();
+    san.sort();
+    san.dedup();
+    uci.sort();
+    uci.dedup();
+    assert_eq!(san.len(), moves.len(), "ambiguous SAN in {}", fen);
+    for m in &moves {
+        let parsed = engine::parse_san(g, &m.to_string());
+        assert_eq!(parsed, Ok(*m), "SAN {} not parsed in {}", m, fen);
+        let parsed = engine::parse_uci_move(g, &m.uci().to_string());
+        assert_eq!(parsed, Ok(*m), "UCI {} not parsed in {}", m.uci(), fen);
+    }
+    assert_eq!(uci.len(), moves.len(), "duplicated UCI in {}", fen);
+    match engine::state(g) {
+        GameState::Checkmate(_) | GameState::Stalemate => {
+            assert!(moves.is_empty(), "game over with valid moves in {}", fen)
+        }
+        _ => assert!(!moves.is_empty(), "no valid moves in {}", fen),
+    }
+}
+
+// do_move() followed by undo_move() must restore the position, key and state
+pub fn check_undo(g: &mut Game, m: Move) {
+    let fen = engine::fen(g);
+    let moves: Vec<String> = engine::legal_moves(g).map(|m| m.to_string()).collect();
+    let state = engine::state(g);
+    let key = engine::position_key(g);
+    let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    assert_ne!(
+        engine::position_key(g),
+        key,
+        "same key after {} in {}",
+        m,
+        fen
+    );
+    engine::undo_move(g, token);
+    assert_eq!(engine::fen(g), fen, "undo of {} failed", m);
+    assert_eq!(engine::position_key(g), key, "key changed by undo of {}", m);
+    let again: Vec<String> = engine::legal_moves(g).map(|m| m.to_string()).collect();
+    assert_eq!(
+        moves, again,
+        "valid moves changed by undo of {} in {}",
+        m, fen
+    );
+    assert_eq!(
+        engine::state(g),
+        state,
+        "state changed by undo of {} in {}",
+        m,
+        fen
+    );
+}
+
+// Play a game from the start position, each choice selects one of the valid moves.
+// Invariants are checked before each move, and finally the FEN round trip.
+pub fn play_random_game(g: &mut Game, choices: impl Iterator<Item = usize>) {
+    engine::reset_game(g);
+    for c in choices {
+        check_position(g);
+        let moves: Vec<Move> = engine::legal_moves(g).collect();
+        if moves.is_empty() {
+            break;
+        }
+        let m = moves[c % moves.len()];
+        let fen = engine::fen(g);
+        check_undo(g, m);
+        let done = engine::do_move(g, m.src, m.dst, m.promote_to, false).mv;
+        assert_eq!(
+            (done.piece, done.captured, done.kind, done.check),
+            (m.piece, m.captured, m.kind, m.check),
+            "do_move() differs from legal_moves() for {} in {}",
+            m,
+            fen
+        );
+        assert_eq!(
+            done.mate,
+            matches!(engine::state(g), GameState::Checkmate(_)),
+            "mate flag of {} in {}",
+            done,
+            fen
+        );
+    }
+    let fen = engine::fen(g);
+    let moves: Vec<String> = engine::legal_moves(g).map(|m| m.to_string()).collect();
+    let key = engine::position_key(g);
+    engine::set_fen(g, &fen).unwrap();
+    assert_eq!(engine::fen(g), fen);
+    assert_eq!(
+        engine::position_key(g),
+        key,
+        "key changed by FEN round trip"
+    );
+    let again: Vec<String> = engine::legal_moves(g).map(|m| m.to_string()).collect();
+    assert_eq!(
+        moves, again,
+        "valid moves changed by FEN round trip in {}",
+        fen
+    );
+}
@@ -0,0 +1,60 @@
+// Conditional moves of correspondence games.
+
+mod common;
+
+use tiny_chess::correspondence::{self, Conditional, LineError};
+use tiny_chess::engine::{self, SanError};
+
+#[test]
+fn parse_line() {
+    let g = common::small_game();
+    let line = correspondence::parse_line(&g, "if e4 then c5, and 2. Nf3 then d6").unwrap();
+    assert_eq!(line.ply, 0);
+    assert_eq!(line.moves, ["e2e4", "c7c5", "g1f3", "d7d6"]);
+    assert_eq!(line.san, "e4 c5 Nf3 d6");
+    assert_eq!(
+        correspondence::parse_line(&g, "e4"),
+        Err(LineError::NoAnswer)
+    );
+    assert_eq!(
+        correspondence::parse_line(&g, "e4 e4"),
+        Err(LineError::Move(2, SanError::IllegalMove))
+    );
+}
+
+#[test]
+fn answer() {
+    let mut g = common::small_game();
+    let mut lines: Vec<Conditional> = ["e4 c5 Nf3 d6", "d4 Nf6"]
+        .iter()
+        .map(|text| correspondence::parse_line(&g, text).unwrap())
+        .collect();
+    assert_eq!(correspondence::answer(&g, &mut lines), None);
+    assert_eq!(lines.len(), 2);
+    common::play_san(&mut g, &["e4"]);
+    let m = correspondence::answer(&g, &mut lines).unwrap();
+    assert_eq!(m.uci().to_string(), "c7c5");
+    // the line d4 was left
+    assert_eq!(lines.len(), 1);
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    assert_eq!(correspondence::answer(&g, &mut lines), None);
+    // not while browsing
+    engine::take_back(&mut g);
+    common::play_san(&mut g, &["c5", "Nf3"]);
+    engine::take_back(&mut g);
+    assert_eq!(correspondence::answer(&g, &mut lines), None);
+    engine::redo_move(&mut g);
+    let m = correspondence::answer(&g, &mut lines).unwrap();
+    assert_eq!(m.uci().to_string(), "d7d6");
+    // the last answer was played
+    assert!(lines.is_empty());
+}
+
+#[test]
+fn divergence() {
+    let mut g = common::small_game();
+    let mut lines = vec![correspondence::parse_line(&g, "e4 e5 Nf3 Nc6").unwrap()];
+    common::play_san(&mut g, &["e4", "e5", "Bc4"]);
+    assert_eq!(correspondence::answer(&g, &mut lines), None);
+    assert!(lines.is_empty());
+}
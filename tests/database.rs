@@ -0,0 +1,105 @@
+// The game list of a PGN database, its search and the loading of a game, and the opening
+// tree of a player.
+
+use tiny_chess::database::{Database, OpeningTree};
+use tiny_chess::engine;
+
+const PGN: &str = r#"[Event "Casual"]
+[White "Fool"]
+[Black "Smart"]
+[Result "0-1"]
+[Date "2023.05.01"]
+
+1. f3 e5 2. g4 Qh4# 0-1
+
+[Event "World Championship"]
+[White "Carlsen, Magnus"]
+[Black "Nepomniachtchi, Ian"]
+[ECO "C88"]
+[Result "1/2-1/2"]
+[Date "2021.11.26"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1/2-1/2
+
+[White "Carlsen, Magnus"]
+[Black "Broken"]
+
+1. e4 e5 2. Ke3 *
+"#;
+
+#[test]
+fn headers() {
+    let db = Database::new(PGN.to_string());
+    assert_eq!(db.games.len(), 3);
+    let h = &db.games[1];
+    assert_eq!(h.white, "Carlsen, Magnus");
+    assert_eq!(h.black, "Nepomniachtchi, Ian");
+    assert_eq!(h.eco, "C88");
+    assert_eq!(h.result, "1/2-1/2");
+    assert_eq!(h.date, "2021.11.26");
+    assert_eq!(h.event, "World Championship");
+    assert_eq!(db.games[2].event, "");
+    assert!(db.pgn(0).starts_with("[Event \"Casual\"]"));
+    assert!(db.pgn(0).trim_end().ends_with("Qh4# 0-1"));
+}
+
+#[test]
+fn search() {
+    let db = Database::new(PGN.to_string());
+    assert_eq!(db.search(""), [0, 1, 2]);
+    assert_eq!(db.search("carlsen"), [1, 2]);
+    assert_eq!(db.search("CARLSEN 1/2"), [1]);
+    assert_eq!(db.search("c88 2021"), [1]);
+    assert_eq!(db.search("kasparov"), Vec::<usize>::new());
+}
+
+#[test]
+fn load() {
+    let db = Database::new(PGN.to_string());
+    let mut g = engine::new_game();
+    let tags = db.load(&mut g, 0).unwrap();
+    assert!(tags.contains(&("White".to_string(), "Fool".to_string())));
+    assert_eq!(
+        engine::state(&g),
+        engine::GameState::Checkmate(engine::COLOR_BLACK)
+    );
+    db.load(&mut g, 1).unwrap();
+    assert_eq!(engine::game_ply(&g), 6);
+    // the moves of a game are only checked when it is loaded
+    assert!(db.load(&mut g, 2).is_err());
+    assert_eq!(engine::game_ply(&g), 6);
+}
+
+#[test]
+fn opening_tree() {
+    let games = [
+        "[White \"Human\"]\n[Black \"Tiny chess\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0",
+        "[White \"Human\"]\n[Black \"Tiny chess\"]\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2",
+        "[White \"Tiny chess\"]\n[Black \"Human\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0",
+        "[White \"Human\"]\n[Black \"Tiny chess\"]\n[Result \"*\"]\n\n1. d4 *",
+        "[White \"Tiny chess\"]\n[Black \"Stockfish\"]\n[Result \"0-1\"]\n\n1. d4 0-1",
+    ];
+    let mut tree = OpeningTree::new();
+    let added: Vec<bool> = games
+        .iter()
+        .map(|pgn| tree.add_game(pgn, "Human", 2).unwrap())
+        .collect();
+    assert_eq!(added, [true, true, true, false, false]);
+    let mut g = engine::new_game();
+    let moves = tree.moves(&g);
+    assert_eq!(moves.len(), 1);
+    assert_eq!(moves[0].uci, "e2e4");
+    assert_eq!((moves[0].wins, moves[0].draws, moves[0].losses), (1, 1, 1));
+    assert_eq!(moves[0].score(), 0.5);
+    let m = engine::parse_san(&g, "e4").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    let moves = tree.moves(&g);
+    assert_eq!(moves.len(), 2);
+    assert_eq!((moves[0].uci.as_str(), moves[0].games()), ("e7e5", 2));
+    assert_eq!((moves[1].uci.as_str(), moves[1].score()), ("c7c5", 0.5));
+    // only the first plies are added
+    let m = engine::parse_san(&g, "e5").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    assert!(tree.moves(&g).is_empty());
+    assert!(tree.add_game("1. e4 e5 2. Ke3 *", "Human", 2).is_err());
+}
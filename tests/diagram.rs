@@ -0,0 +1,36 @@
+// Diagrams of positions as SVG.
+
+mod common;
+
+use tiny_chess::diagram::{self, Style};
+
+#[test]
+fn svg() {
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["e4"]);
+
+    let svg = diagram::svg(&g, &Style::default());
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"400\" height=\"400\" viewBox=\"0 0 380 380\">"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert_eq!(svg.matches("<use ").count(), 32);
+    assert_eq!(svg.matches("<g id=").count(), 12);
+    assert!(svg.contains("<use href=\"#wn\" transform=\"translate(290 315)\"/>"));
+    assert!(svg.contains("<use href=\"#wp\" transform=\"translate(200 180)\"/>"));
+    // the dark squares, a1 is one of them, and the last move
+    assert_eq!(svg.matches("fill=\"#cdcdcd\"").count(), 32);
+    assert!(svg.contains("<rect x=\"20\" y=\"315\" width=\"45\" height=\"45\" fill=\"#cdcdcd\"/>"));
+    assert_eq!(svg.matches("fill-opacity").count(), 2);
+    assert!(svg.contains(">a</text>") && svg.contains(">8</text>"));
+
+    let style = Style {
+        size: 800,
+        coordinates: false,
+        last_move: false,
+        flipped: true,
+    };
+    let svg = diagram::svg(&g, &style);
+    assert!(svg.contains("viewBox=\"0 0 360 360\""));
+    assert!(svg.contains("<use href=\"#wp\" transform=\"translate(135 135)\"/>"));
+    assert!(svg.contains("<use href=\"#bk\" transform=\"translate(135 315)\"/>"));
+    assert!(!svg.contains("fill-opacity") && !svg.contains("<text"));
+}
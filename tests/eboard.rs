@@ -0,0 +1,49 @@
+// The board data of the Chessnut Air and the sync of a physical board with the game.
+
+use tiny_chess::eboard::{self, BoardSync};
+use tiny_chess::engine;
+
+// the start position, from h8 to a1 with the low nibble first
+fn start_data() -> Vec<u8> {
+    let mut data = vec![0x01, 0x24, 0x58, 0x23, 0x31, 0x85];
+    data.extend([0x44; 4]);
+    data.extend([0x00; 16]);
+    data.extend([0x77; 4]);
+    data.extend([0xa6, 0xc9, 0x9b, 0x6a]);
+    data.extend([0; 4]); // a time stamp
+    data
+}
+
+#[test]
+fn chessnut_board() {
+    let g = engine::new_game();
+    let data = start_data();
+    assert_eq!(eboard::chessnut_board(&data), Some(engine::get_board(&g)));
+    assert_eq!(eboard::chessnut_board(&data[..20]), None);
+    assert_eq!(eboard::chessnut_board(&[0x2a; 38]), None);
+    let mut wrong = data.clone();
+    wrong[2] = 0xd8; // no piece has the code 13
+    assert_eq!(eboard::chessnut_board(&wrong), None);
+}
+
+#[test]
+fn sync() {
+    let mut g = engine::new_game();
+    let mut data = start_data();
+    let board = eboard::chessnut_board(&data).unwrap();
+    assert_eq!(eboard::sync(&g, &board), BoardSync::InSync);
+    // the pawn is lifted from e2
+    data[2 + 24 + 1] = 0x07;
+    let board = eboard::chessnut_board(&data).unwrap();
+    let e2 = engine::parse_san(&g, "e4").unwrap().src;
+    assert_eq!(eboard::sync(&g, &board), BoardSync::Differs(vec![e2]));
+    // and put onto e4
+    data[2 + 16 + 1] = 0x70;
+    let board = eboard::chessnut_board(&data).unwrap();
+    let BoardSync::Move(m) = eboard::sync(&g, &board) else {
+        panic!("no move");
+    };
+    assert_eq!(m.uci().to_string(), "e2e4");
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    assert_eq!(eboard::sync(&g, &board), BoardSync::InSync);
+}
@@ -0,0 +1,39 @@
+// The table of the opening names.
+
+mod common;
+
+use tiny_chess::eco;
+use tiny_chess::engine;
+
+#[test]
+fn table() {
+    let mut g = common::small_game();
+    for (opening, moves) in eco::openings() {
+        assert_eq!(opening.eco.len(), 3, "{}", opening.name);
+        engine::go_to_ply(&mut g, 0);
+        common::play_san(&mut g, &moves);
+    }
+}
+
+#[test]
+fn opening() {
+    let mut g = common::small_game();
+    assert_eq!(eco::opening(&g), None);
+    common::play_san(
+        &mut g,
+        &[
+            "e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6", "h3",
+        ],
+    );
+    let najdorf = eco::opening(&g).unwrap();
+    assert_eq!(najdorf.eco, "B90");
+    assert_eq!(najdorf.name, "Sicilian Defense: Najdorf Variation");
+    engine::go_to_ply(&mut g, 9);
+    assert_eq!(eco::opening(&g).unwrap().name, "Sicilian Defense: Open");
+    engine::go_to_ply(&mut g, 1);
+    assert_eq!(eco::opening(&g).unwrap().name, "King's Pawn Game");
+
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    engine::do_move(&mut g, 11, 27, engine::VOID_ID, false);
+    assert_eq!(eco::opening(&g), None);
+}
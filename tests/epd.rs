@@ -0,0 +1,83 @@
+// Test suites in the Extended Position Description, see src/epd.rs.
+
+use tiny_chess::cli::{self, Cli};
+use tiny_chess::engine;
+use tiny_chess::epd::{self, EpdError};
+
+const SUITE: &str = "\
+# the first positions of Win at Chess
+2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";
+8/7p/5k2/5p2/p1p2P2/Pr1pPK2/1P1R3P/8 b - - bm Rxb2; id \"WAC.002\";
+
+r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - am Ng5;
+8/8/8/8/8/8/8/K6k w - -
+";
+
+#[test]
+fn read_suite() {
+    let tests = epd::read_epd(SUITE.as_bytes()).unwrap();
+    assert_eq!(tests.len(), 4);
+    let (line, first) = &tests[0];
+    let first = first.as_ref().unwrap();
+    assert_eq!(*line, 2);
+    assert_eq!(first.id, "WAC.001");
+    assert_eq!(first.best, ["Qg6"]);
+    assert_eq!(
+        first.fen,
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+    );
+    let (line, avoid) = &tests[2];
+    assert_eq!(*line, 5);
+    assert_eq!(avoid.as_ref().unwrap().avoid, ["Ng5"]);
+    assert_eq!(tests[3].1, Err(EpdError::NoMoves));
+    assert_eq!(epd::parse_epd("8/8/8/8 w"), Err(EpdError::Fields));
+
+    // the moves are checked, a solution is a best move and no avoid move
+    let mut g = engine::new_game();
+    let first = tests[0].1.clone().unwrap();
+    epd::set_test(&mut g, &first).unwrap();
+    let qg6 = engine::parse_san(&g, "Qg6").unwrap();
+    let qf4 = engine::parse_san(&g, "Qf4").unwrap();
+    assert!(epd::is_solution(&g, &first, &qg6));
+    assert!(!epd::is_solution(&g, &first, &qf4));
+    let avoid = tests[2].1.clone().unwrap();
+    epd::set_test(&mut g, &avoid).unwrap();
+    let ng5 = engine::parse_san(&g, "Ng5").unwrap();
+    let bc4 = engine::parse_san(&g, "Bc4").unwrap();
+    assert!(!epd::is_solution(&g, &avoid, &ng5));
+    assert!(epd::is_solution(&g, &avoid, &bc4));
+    let mut bad = first.clone();
+    bad.best = vec!["Qh8".to_string()];
+    assert_eq!(
+        epd::set_test(&mut g, &bad),
+        Err(EpdError::Move(
+            "Qh8".to_string(),
+            engine::SanError::IllegalMove
+        ))
+    );
+}
+
+#[test]
+fn run_suite() {
+    let path = std::env::temp_dir().join("tiny-chess-test.epd");
+    let suite = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";\nbroken\n";
+    std::fs::write(&path, suite).unwrap();
+    let args: Vec<String> = ["epd", path.to_str().unwrap(), "--time", "200"]
+        .map(String::from)
+        .to_vec();
+    let cli = cli::parse_args(&args).unwrap();
+    assert_eq!(
+        cli,
+        Cli::Epd {
+            path: path.to_str().unwrap().to_owned(),
+            time: 200
+        }
+    );
+    let mut out = Vec::new();
+    cli::run(&cli, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("mate: Ra8# solved"), "{}", text);
+    assert!(text.contains("line 2: missing fields in EPD"), "{}", text);
+    assert!(text.contains("Solved 1 of 1 (100.0 %)"), "{}", text);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,92 @@
+// The static evaluation and its breakdown into terms.
+
+mod common;
+
+use tiny_chess::engine;
+
+#[test]
+fn evaluation_breakdown() {
+    let mut g = engine::new_game();
+    let e = engine::evaluate_detailed(&g);
+    assert_eq!(e.white, e.black);
+    assert_eq!(e.total(), 0);
+    assert_eq!(
+        e.white.material,
+        8 * 100 + 2 * 300 + 2 * 300 + 2 * 500 + 900
+    );
+    assert_eq!(e.white.mobility, 20);
+    assert_eq!(e.white.king_safety, 0);
+
+    // White lost the knight on b1 and castling rights
+    engine::set_fen(
+        &mut g,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w Kkq - 0 1",
+    )
+    .unwrap();
+    let e = engine::evaluate_detailed(&g);
+    assert_eq!(e.black.material - e.white.material, 300);
+    assert_eq!(e.white.king_safety, -2);
+    assert_eq!(e.black.king_safety, 0);
+    assert_eq!(e.white.pawn_structure, e.black.pawn_structure);
+    assert!(e.total() < -250);
+}
+
+#[test]
+fn quick_evaluation() {
+    let mut g = engine::new_game();
+    assert_eq!(engine::evaluate(&g), 0);
+    // the point of view is White's, whoever is to move
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    assert!(engine::evaluate(&g) > 800);
+    engine::set_fen(&mut g, "3qk3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert!(engine::evaluate(&g) < -800);
+}
+
+#[test]
+fn material() {
+    let mut g = common::small_game();
+    assert_eq!(engine::material_balance(&g), 0);
+    common::play_san(&mut g, &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qxa2", "Rxa2"]);
+    assert_eq!(engine::material_balance(&g), 8);
+    let captured = engine::captured_pieces(&g);
+    let pawn = engine::PAWN_ID;
+    assert_eq!(captured, [-pawn, pawn, pawn, -engine::QUEEN_ID]);
+    engine::take_back(&mut g);
+    assert_eq!(engine::material_balance(&g), -1);
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    assert_eq!(engine::material_balance(&g), 9);
+    assert!(engine::captured_pieces(&g).is_empty());
+}
+
+#[test]
+fn personalities() {
+    let mut g = common::small_game();
+    assert_eq!(
+        engine::get_option(&g, "Personality").unwrap().to_string(),
+        "Balanced"
+    );
+    assert!(engine::set_option(&mut g, "Personality", "Reckless").is_err());
+    let evaluate = |g: &mut engine::Game, personality: &str| {
+        engine::set_option(g, "Personality", personality).unwrap();
+        engine::evaluate(g)
+    };
+    assert_eq!(evaluate(&mut g, "aggressive"), 0);
+
+    // a knight next to the black king
+    engine::set_fen(&mut g, "6k1/5ppp/5N2/8/8/8/5PPP/6K1 b - - 0 1").unwrap();
+    assert!(evaluate(&mut g, "Aggressive") > evaluate(&mut g, "Balanced"));
+
+    // a pawn down in the opening counts less for a gambit player
+    engine::set_fen(
+        &mut g,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1",
+    )
+    .unwrap();
+    let balanced = evaluate(&mut g, "Balanced");
+    assert!(balanced < 0 && evaluate(&mut g, "Gambit") > balanced);
+    // the breakdown stays the plain evaluation
+    engine::set_option(&mut g, "Personality", "Positional").unwrap();
+    assert!(engine::evaluate_detailed(&g).total() < -50);
+    let m = engine::reply(&mut g, &mut ());
+    assert!(engine::move_is_valid2(&g, m.src, m.dst));
+}
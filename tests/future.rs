@@ -0,0 +1,64 @@
+// The async search, run with a minimal executor. Needs the "async" feature:
+// cargo test --features async
+#![cfg(feature = "async")]
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+use tiny_chess::engine;
+use tiny_chess::future::{search, Limits};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(f: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut f = pin!(f);
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(r) => return r,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn async_search() {
+    let mut g = engine::new_game();
+    engine::set_fen(&mut g, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let limits = Limits {
+        move_time: Some(Duration::from_millis(200)),
+        depth: None,
+    };
+    let m = block_on(search(&g, limits)).unwrap();
+    assert_eq!((m.src, m.dst), (7, 63)); // Ra8#
+
+    // a depth limit, and cancellation of a long search
+    engine::reset_game(&mut g);
+    let limits = Limits {
+        move_time: Some(Duration::from_secs(9)),
+        depth: Some(2),
+    };
+    let start = Instant::now();
+    assert!(block_on(search(&g, limits)).is_some());
+    let f = search(
+        &g,
+        Limits {
+            depth: None,
+            ..limits
+        },
+    );
+    thread::sleep(Duration::from_millis(300));
+    f.cancel();
+    assert!(block_on(f).is_some());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
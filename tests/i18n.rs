@@ -0,0 +1,42 @@
+// The translations of the GUI texts.
+
+use tiny_chess::engine::{self, Notation};
+use tiny_chess::i18n::{self, Language};
+
+#[test]
+fn tables() {
+    for (_, lang) in i18n::LANGUAGES {
+        for (english, translated) in i18n::translations(lang) {
+            assert!(!english.is_empty() && !translated.is_empty());
+            assert!(!translated.contains('\t'), "{}", english);
+        }
+    }
+    assert_eq!(i18n::translations(Language::English).count(), 0);
+    assert!(i18n::translations(Language::German).count() > 100);
+}
+
+#[test]
+fn tr() {
+    assert_eq!(i18n::tr(Language::English, "Undo"), "Undo");
+    assert_eq!(i18n::tr(Language::German, "Undo"), "Zurück");
+    assert_eq!(
+        i18n::tr(Language::German, "Stalemate, draw."),
+        "Patt, remis."
+    );
+    // no translation
+    assert_eq!(
+        i18n::tr(Language::German, "e4 (score: 20)"),
+        "e4 (score: 20)"
+    );
+}
+
+#[test]
+fn piece_letters() {
+    let g = engine::new_game();
+    let m = engine::parse_san(&g, "Nf3").unwrap();
+    let german = i18n::piece_letters(Language::German);
+    assert_eq!(m.localized(Notation::San, german).to_string(), "Sf3");
+    assert_eq!(m.localized(Notation::Long, german).to_string(), "Sg1-f3");
+    let english = i18n::piece_letters(Language::English);
+    assert_eq!(m.localized(Notation::San, english).to_string(), "Nf3");
+}
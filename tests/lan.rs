@@ -0,0 +1,70 @@
+// Games over the local network, see src/lan.rs.
+
+use std::time::{Duration, Instant};
+use tiny_chess::lan::{self, Connection, Event, Message};
+
+#[test]
+fn messages() {
+    let messages = [
+        Message::Start {
+            white: true,
+            clock: Some((300, 2)),
+        },
+        Message::Start {
+            white: false,
+            clock: None,
+        },
+        Message::Move {
+            uci: "e7e8q".to_owned(),
+            clocks: [Duration::from_millis(299_500), Duration::from_secs(300)],
+        },
+        Message::OfferDraw,
+        Message::AcceptDraw,
+        Message::DeclineDraw,
+        Message::Resign,
+    ];
+    for m in messages {
+        assert_eq!(lan::parse_message(&m.to_string()), Some(m));
+    }
+    assert_eq!(
+        lan::parse_message("move e2e4 299500 300000"),
+        Some(Message::Move {
+            uci: "e2e4".to_owned(),
+            clocks: [Duration::from_millis(299_500), Duration::from_secs(300)],
+        })
+    );
+    assert_eq!(lan::parse_message("start red none"), None);
+    assert_eq!(lan::parse_message("move e2e4 -1 0"), None);
+    assert_eq!(lan::parse_message("hello"), None);
+}
+
+// the next event, waiting up to a few seconds
+fn event(c: &Connection) -> Event {
+    let start = Instant::now();
+    loop {
+        if let Some(event) = c.try_event() {
+            return event;
+        }
+        assert!(start.elapsed() < Duration::from_secs(5), "no event");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn connection() {
+    let host = Connection::host(0).unwrap();
+    let port = host.port().unwrap();
+    let guest = Connection::join(&format!("127.0.0.1:{}", port));
+    assert!(matches!(event(&host), Event::Connected(_)));
+    assert!(matches!(event(&guest), Event::Connected(_)));
+    let start = Message::Start {
+        white: false,
+        clock: None,
+    };
+    host.send(&start);
+    assert_eq!(event(&guest), Event::Message(start));
+    guest.send(&Message::OfferDraw);
+    assert_eq!(event(&host), Event::Message(Message::OfferDraw));
+    drop(guest);
+    assert_eq!(event(&host), Event::Closed(None));
+}
@@ -0,0 +1,94 @@
+// The events of the lichess Board API streams, the answers of the opening explorer and the
+// downloads of games. Needs the "lichess" feature:
+// cargo test --features lichess
+#![cfg(feature = "lichess")]
+
+use std::time::Duration;
+use tiny_chess::lichess::{parse_event, parse_explorer, pgn_url, Event, GameState};
+
+#[test]
+fn account_events() {
+    let line = r#"{"type":"gameStart","game":{"gameId":"rCRw1AuO","color":"black","fen":"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"}}"#;
+    assert_eq!(
+        parse_event(line),
+        Some(Event::GameStart {
+            id: "rCRw1AuO".to_owned(),
+            white: false,
+        })
+    );
+    let line = r#"{"type":"challenge","challenge":{"id":"7pGLxJ4F","challenger":{"id":"bobby","name":"Bobby"},"speed":"rapid","variant":{"key":"standard"}}}"#;
+    assert_eq!(
+        parse_event(line),
+        Some(Event::Challenge {
+            id: "7pGLxJ4F".to_owned(),
+            from: "Bobby".to_owned(),
+            speed: "rapid".to_owned(),
+        })
+    );
+    let line = r#"{"type":"challengeDeclined","challenge":{"id":"7pGLxJ4F"}}"#;
+    assert_eq!(
+        parse_event(line),
+        Some(Event::ChallengeGone {
+            id: "7pGLxJ4F".to_owned(),
+        })
+    );
+    // keep-alive lines, unknown events and garbage
+    assert_eq!(parse_event(""), None);
+    assert_eq!(parse_event(r#"{"type":"chatLine","text":"hi"}"#), None);
+    assert_eq!(parse_event("{"), None);
+}
+
+#[test]
+fn game_events() {
+    let line = r#"{"type":"gameFull","id":"rCRw1AuO","initialFen":"startpos","white":{"id":"alice","name":"Alice"},"black":{"id":"bobby","name":"Bobby"},"state":{"type":"gameState","moves":"e2e4 c7c5","wtime":600000,"btime":598000,"winc":5000,"binc":5000,"status":"started"}}"#;
+    let state = GameState {
+        moves: vec!["e2e4".to_owned(), "c7c5".to_owned()],
+        clocks: [Duration::from_secs(600), Duration::from_secs(598)],
+        status: "started".to_owned(),
+        winner: None,
+    };
+    assert_eq!(
+        parse_event(line),
+        Some(Event::GameFull {
+            id: "rCRw1AuO".to_owned(),
+            fen: None,
+            white: "Alice".to_owned(),
+            black: "Bobby".to_owned(),
+            state,
+        })
+    );
+    let line = r#"{"type":"gameState","moves":"f2f3 e7e5 g2g4 d8h4","wtime":590000,"btime":595000,"status":"mate","winner":"black"}"#;
+    let Some(Event::GameState { state, .. }) = parse_event(line) else {
+        panic!("no game state");
+    };
+    assert_eq!(state.moves.len(), 4);
+    assert_eq!(state.status, "mate");
+    assert_eq!(state.winner, Some(false));
+}
+
+#[test]
+fn explorer() {
+    let json = r#"{"white":1212,"draws":160,"black":1406,"moves":[{"uci":"e2e4","san":"e4","averageRating":2400,"white":600,"draws":80,"black":700,"game":null},{"uci":"d2d4","san":"d4","averageRating":2410,"white":612,"draws":80,"black":706,"game":null}],"topGames":[],"opening":null}"#;
+    let e = parse_explorer(json).unwrap();
+    assert_eq!(e.games, [1212, 160, 1406]);
+    assert_eq!(e.moves.len(), 2);
+    assert_eq!(e.moves[1], ("d2d4".to_owned(), [612, 80, 706]));
+    assert_eq!(parse_explorer("{}"), None);
+}
+
+#[test]
+fn game_urls() {
+    let export = Some("https://lichess.org/game/export/q7ZvsdUF".to_owned());
+    assert_eq!(pgn_url("https://lichess.org/q7ZvsdUF"), export);
+    assert_eq!(pgn_url(" lichess.org/q7ZvsdUFe2x7/black#32 "), export);
+    assert_eq!(pgn_url("https://lichess.org/study/x"), None);
+    let archive = "https://api.chess.com/pub/player/hikaru/games/2024/05";
+    assert_eq!(pgn_url(archive), Some(format!("{}/pgn", archive)));
+    assert_eq!(
+        pgn_url(&format!("{}/pgn", archive)),
+        Some(format!("{}/pgn", archive))
+    );
+    let file = "https://example.com/wch2021.pgn";
+    assert_eq!(pgn_url(file), Some(file.to_owned()));
+    assert_eq!(pgn_url("https://www.chess.com/game/live/123"), None);
+}
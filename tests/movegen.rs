@@ -0,0 +1,291 @@
+// Property based tests of the move generation, playing random games with proptest.
+// The engine allocates a large transposition table, so all cases share one game.
+
+mod common;
+
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestRunner};
+use std::cell::RefCell;
+use tiny_chess::engine;
+
+#[test]
+fn random_games_keep_invariants() {
+    let game = RefCell::new(engine::new_game());
+    let mut runner = TestRunner::new(Config::with_cases(64));
+    runner
+        .run(&prop::collection::vec(any::<usize>(), 0..160), |choices| {
+            common::play_random_game(&mut game.borrow_mut(), choices.into_iter());
+            Ok(())
+        })
+        .unwrap();
+}
+
+#[test]
+fn start_position() {
+    let g = engine::new_game();
+    common::check_position(&g);
+    assert_eq!(engine::legal_moves(&g).count(), 20);
+    assert_eq!(engine::state(&g), engine::GameState::Ongoing);
+}
+
+#[test]
+fn transpositions_have_same_key() {
+    let mut g = engine::new_game();
+    let mut keys = Vec::new();
+    for line in [["g1f3", "g8f6", "b1c3"], ["b1c3", "g8f6", "g1f3"]] {
+        engine::reset_game(&mut g);
+        for m in line {
+            let src = engine::parse_square(&m[0..2]).unwrap();
+            let dst = engine::parse_square(&m[2..4]).unwrap();
+            engine::play_move(&mut g, src, dst, engine::VOID_ID).unwrap();
+        }
+        keys.push(engine::position_key(&g));
+    }
+    assert_eq!(keys[0], keys[1]);
+    engine::set_fen(
+        &mut g,
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+    )
+    .unwrap();
+    let with_ep = engine::position_key(&g);
+    engine::set_fen(
+        &mut g,
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3",
+    )
+    .unwrap();
+    assert_ne!(engine::position_key(&g), with_ep);
+}
+
+#[test]
+fn cloned_game_is_independent() {
+    let mut g = engine::new_game();
+    let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+    engine::set_fen(&mut g, fen).unwrap();
+    let mut fork = g.clone();
+    engine::set_option(&mut fork, "MoveTime", "200").unwrap();
+    let m = engine::reply(&mut fork, &mut ());
+    let mv = engine::do_move(&mut fork, m.src as i8, m.dst as i8, m.promote_to, false).mv;
+    assert_eq!(mv.to_string(), "Ra8#");
+    assert_eq!(engine::fen(&g), fen);
+    common::check_position(&fork);
+    engine::play_move(&mut g, 1, 9, engine::VOID_ID).unwrap();
+    assert_eq!(
+        engine::state(&fork),
+        engine::GameState::Checkmate(engine::COLOR_WHITE)
+    );
+    let state = engine::state(&fork);
+    assert_eq!(state.to_string(), "Checkmate, white wins.");
+    assert_eq!(state.name(), "checkmate");
+    let draw = engine::GameState::DrawRepetition;
+    assert_eq!(draw.to_string(), "Draw by threefold repetition.");
+}
+
+#[test]
+fn attack_queries() {
+    let mut g = engine::new_game();
+    let sq = |s| engine::parse_square(s).unwrap();
+    let squares = |v: Vec<i8>| v.into_iter().map(engine::square_str).collect::<Vec<_>>();
+    assert_eq!(
+        squares(engine::attackers_of(&g, sq("f3"))),
+        ["g1", "g2", "e2"]
+    );
+    assert!(engine::is_square_attacked(
+        &g,
+        sq("f3"),
+        engine::COLOR_WHITE
+    ));
+    assert!(!engine::is_square_attacked(
+        &g,
+        sq("f3"),
+        engine::COLOR_BLACK
+    ));
+    assert!(!engine::is_square_attacked(
+        &g,
+        sq("e4"),
+        engine::COLOR_WHITE
+    ));
+    assert!(engine::attackers_of(&g, 64).is_empty());
+    engine::set_fen(&mut g, "4k3/8/8/3q4/8/5N2/8/R3K3 w - - 0 1").unwrap();
+    assert_eq!(squares(engine::attackers_of(&g, sq("d4"))), ["f3", "d5"]);
+    assert_eq!(squares(engine::attackers_of(&g, sq("a5"))), ["a1", "d5"]);
+}
+
+#[test]
+fn checks_and_pins() {
+    let mut g = engine::new_game();
+    let squares = |v: Vec<i8>| v.into_iter().map(engine::square_str).collect::<Vec<_>>();
+    assert!(engine::checkers(&g).is_empty());
+    assert!(engine::pinned_pieces(&g).is_empty());
+    // the knight on d7 is pinned by the bishop on b5, the rook on e2 gives check
+    engine::set_fen(&mut g, "r2qk2r/3n4/8/1B6/8/8/4R3/4K3 b - - 0 1").unwrap();
+    assert!(engine::is_in_check(&g, engine::COLOR_BLACK));
+    assert!(!engine::is_in_check(&g, engine::COLOR_WHITE));
+    assert_eq!(squares(engine::checkers(&g)), ["e2"]);
+    assert_eq!(squares(engine::pinned_pieces(&g)), ["d7"]);
+    // a double check, and two pawns between king and rook are not pinned
+    engine::set_fen(&mut g, "r7/1n6/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+    assert!(engine::is_in_check(&g, engine::COLOR_WHITE));
+    assert_eq!(squares(engine::checkers(&g)), ["b7", "a8"]);
+    assert!(engine::pinned_pieces(&g).is_empty());
+}
+
+#[test]
+fn board_diff() {
+    let mut g = engine::new_game();
+    let changed = |g: &mut engine::Game, fen: &str, uci: &str| {
+        engine::set_fen(g, fen).unwrap();
+        let prev = engine::get_board(g);
+        let m = engine::parse_uci_move(g, uci).unwrap();
+        engine::do_move(g, m.src, m.dst, m.promote_to, false);
+        let changes = engine::diff(&prev, &engine::get_board(g));
+        for c in &changes {
+            assert_eq!(
+                (prev[c.pos as usize], engine::get_board(g)[c.pos as usize]),
+                (c.old, c.new)
+            );
+        }
+        let mut squares: Vec<String> = changes.iter().map(|c| engine::square_str(c.pos)).collect();
+        squares.sort();
+        squares
+    };
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(changed(&mut g, start, "e2e4"), ["e2", "e4"]);
+    let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1";
+    assert_eq!(changed(&mut g, fen, "e5d6"), ["d5", "d6", "e5"]);
+    assert_eq!(changed(&mut g, fen, "e1c1"), ["a1", "c1", "d1", "e1"]);
+    assert!(engine::diff(&engine::get_board(&g), &engine::get_board(&g)).is_empty());
+}
+
+#[test]
+fn invalid_move_reasons() {
+    use engine::MoveError::*;
+    let mut g = engine::new_game();
+    let play = |g: &mut engine::Game, fen: &str, m: &str| {
+        engine::set_fen(g, fen).unwrap();
+        let src = engine::parse_square(&m[0..2]).unwrap();
+        let dst = engine::parse_square(&m[2..4]).unwrap_or(64);
+        engine::play_move(g, src, dst, engine::VOID_ID).err()
+    };
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(play(&mut g, start, "e2e4"), None);
+    assert_eq!(play(&mut g, start, "e2e5"), Some(WrongPattern));
+    assert_eq!(play(&mut g, start, "e2d3"), Some(WrongPattern));
+    assert_eq!(play(&mut g, start, "a1a3"), Some(BlockedPath));
+    assert_eq!(play(&mut g, start, "b1d2"), Some(BlockedPath));
+    assert_eq!(play(&mut g, start, "c1e3"), Some(BlockedPath));
+    assert_eq!(play(&mut g, start, "e1g1"), Some(BlockedPath));
+    assert_eq!(play(&mut g, start, "e7e5"), Some(NotYourPiece));
+    assert_eq!(play(&mut g, start, "e4e5"), Some(NoPiece));
+    assert_eq!(play(&mut g, start, "e2xx"), Some(InvalidSquare));
+    let pin = "4k3/8/8/1b6/8/3N4/8/5K2 w - - 0 1";
+    assert_eq!(play(&mut g, pin, "d3e5"), Some(Pinned));
+    let check = "4k3/8/8/8/8/8/4r3/R3K3 w Q - 0 1";
+    assert_eq!(play(&mut g, check, "a1a2"), Some(LeavesKingInCheck));
+    assert_eq!(play(&mut g, check, "e1d2"), Some(LeavesKingInCheck));
+    assert_eq!(play(&mut g, check, "e1c1"), Some(LeavesKingInCheck));
+    assert_eq!(play(&mut g, check, "e1e2"), None);
+    let no_rights = "4k3/8/8/8/8/8/8/R3K3 w - - 0 1";
+    assert_eq!(play(&mut g, no_rights, "e1c1"), Some(WrongPattern));
+    engine::reset_game(&mut g);
+    assert_eq!(
+        engine::play_move(&mut g, 11, 27, engine::KING_ID).err(),
+        Some(InvalidPromotion)
+    );
+}
+
+#[test]
+fn move_navigation() {
+    let mut g = engine::new_game();
+    for uci in ["e2e4", "e7e5", "g1f3", "b8c6"] {
+        let m = engine::parse_uci_move(&g, uci).unwrap();
+        engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    }
+    let fen = engine::fen(&g);
+    let san = |g: &engine::Game| -> Vec<String> {
+        engine::move_list(g).iter().map(|m| m.to_string()).collect()
+    };
+    assert_eq!(san(&g), ["e4", "e5", "Nf3", "Nc6"]);
+    engine::go_to_ply(&mut g, 1);
+    assert_eq!(engine::game_ply(&g), 1);
+    assert_eq!(
+        engine::fen(&g),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+    assert_eq!(san(&g), ["e4", "e5", "Nf3", "Nc6"]);
+    engine::go_to_ply(&mut g, 4);
+    assert_eq!(engine::fen(&g), fen);
+    assert_eq!(engine::redo_move(&mut g), None);
+    // the next move of the line keeps the rest, another move starts a variation
+    engine::go_to_ply(&mut g, 0);
+    let m = engine::parse_uci_move(&g, "e2e4").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    assert_eq!(san(&g), ["e4", "e5", "Nf3", "Nc6"]);
+    let m = engine::parse_uci_move(&g, "c7c5").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    assert_eq!(san(&g), ["e4", "c5"]);
+    assert_eq!(
+        engine::take_back(&mut g).map(|m| m.to_string()),
+        Some("c5".into())
+    );
+    assert_eq!(engine::game_ply(&g), 1);
+    let main: Vec<String> = engine::node_children(&g, engine::current_node(&g))
+        .iter()
+        .map(|&n| engine::node_move(&g, n).to_string())
+        .collect();
+    assert_eq!(main, ["e5", "c5"]);
+    engine::reset_game(&mut g);
+    assert!(engine::move_list(&g).is_empty());
+    assert_eq!(engine::take_back(&mut g), None);
+}
+
+#[test]
+fn variation_tree() {
+    let mut g = engine::new_game();
+    let play = |g: &mut engine::Game, uci: &str| {
+        let m = engine::parse_uci_move(g, uci).unwrap();
+        engine::do_move(g, m.src, m.dst, m.promote_to, false);
+    };
+    let san = |g: &engine::Game| -> Vec<String> {
+        engine::move_list(g).iter().map(|m| m.to_string()).collect()
+    };
+    for uci in ["e2e4", "e7e5", "g1f3"] {
+        play(&mut g, uci);
+    }
+    let nf3 = engine::current_node(&g);
+    engine::go_to_ply(&mut g, 1);
+    play(&mut g, "c7c5");
+    play(&mut g, "g1f3");
+    assert_eq!(san(&g), ["e4", "c5", "Nf3"]);
+    // a move which is only tried leaves the tree unchanged
+    let token = engine::do_move(&mut g, 57, 42, engine::VOID_ID, false);
+    engine::undo_move(&mut g, token);
+    assert_eq!(san(&g), ["e4", "c5", "Nf3"]);
+    assert!(engine::node_children(&g, engine::current_node(&g)).is_empty());
+    // the main line is kept, the variation can become the main line
+    engine::go_to_node(&mut g, nf3);
+    assert_eq!(san(&g), ["e4", "e5", "Nf3"]);
+    assert!(!engine::promote_variation(&mut g));
+    engine::go_to_ply(&mut g, 1);
+    engine::redo_move(&mut g);
+    engine::go_to_ply(&mut g, 2);
+    let e4 = engine::node_parent(&g, engine::current_node(&g));
+    let first = |g: &engine::Game| engine::node_move(g, engine::node_children(g, e4)[0]);
+    assert_eq!(first(&g).to_string(), "e5");
+    engine::go_to_ply(&mut g, 0);
+    play(&mut g, "e2e4");
+    play(&mut g, "c7c5");
+    assert!(engine::promote_variation(&mut g));
+    assert_eq!(first(&g).to_string(), "c5");
+    // deleting the old main line, now a variation, goes to its branch point
+    engine::take_back(&mut g);
+    play(&mut g, "e7e5");
+    assert!(engine::delete_variation(&mut g));
+    assert_eq!(engine::current_node(&g), e4);
+    assert_eq!(engine::node_children(&g, e4).len(), 1);
+    assert_eq!(san(&g), ["e4", "c5", "Nf3"]);
+    assert!(!engine::delete_variation(&mut g));
+    // a node of the deleted variation is no position of the game any more
+    engine::go_to_node(&mut g, nf3);
+    assert_eq!(engine::current_node(&g), e4);
+    assert!(engine::node_children(&g, nf3).is_empty());
+}
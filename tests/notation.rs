@@ -0,0 +1,358 @@
+// Parsing of move notations. The engine allocates a large transposition table,
+// so a single game is used for all cases.
+
+mod common;
+
+use tiny_chess::engine::{self, SanError};
+use tiny_chess::variant;
+
+#[test]
+fn san() {
+    let mut g = engine::new_game();
+    let san = |g: &engine::Game, s: &str| engine::parse_san(g, s).map(|m| m.to_string());
+    assert_eq!(san(&g, "e4"), Ok("e4".to_string()));
+    assert_eq!(san(&g, "Nf3"), Ok("Nf3".to_string()));
+    assert_eq!(san(&g, "Ng1f3"), Ok("Nf3".to_string()));
+    assert_eq!(san(&g, "e5"), Err(SanError::IllegalMove));
+    assert_eq!(san(&g, "Zz9"), Err(SanError::Syntax));
+    assert_eq!(san(&g, ""), Err(SanError::Syntax));
+
+    engine::set_fen(
+        &mut g,
+        "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/2N5/PPP1PPPP/R1BQKBNR b KQkq - 2 2",
+    )
+    .unwrap();
+    assert_eq!(san(&g, "Nd7"), Err(SanError::AmbiguousMove));
+    assert_eq!(san(&g, "Nbd7"), Ok("Nbd7".to_string()));
+    assert_eq!(san(&g, "Nfd7!?"), Ok("Nfd7".to_string()));
+
+    engine::set_fen(&mut g, "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(san(&g, "O-O"), Ok("O-O".to_string()));
+    assert_eq!(san(&g, "0-0-0"), Ok("O-O-O".to_string()));
+    assert_eq!(san(&g, "bxa8=N"), Ok("bxa8=N".to_string()));
+    assert_eq!(san(&g, "bxa8"), Ok("bxa8=Q+".to_string()));
+    assert_eq!(san(&g, "b8=R+"), Ok("b8=R+".to_string()));
+}
+
+#[test]
+fn uci() {
+    let mut g = engine::new_game();
+    let uci = |g: &engine::Game, s: &str| engine::parse_uci_move(g, s).map(|m| m.uci().to_string());
+    assert_eq!(uci(&g, "e2e4"), Ok("e2e4".to_string()));
+    assert_eq!(uci(&g, "g1f3"), Ok("g1f3".to_string()));
+    assert_eq!(uci(&g, "e2e5"), Err(SanError::IllegalMove));
+    assert_eq!(uci(&g, "e2e4q"), Err(SanError::IllegalMove));
+    assert_eq!(uci(&g, "e2"), Err(SanError::Syntax));
+    assert_eq!(uci(&g, "e2e4k"), Err(SanError::Syntax));
+
+    engine::set_fen(&mut g, "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(uci(&g, "e1g1"), Ok("e1g1".to_string()));
+    assert_eq!(uci(&g, "b7a8n"), Ok("b7a8n".to_string()));
+    assert_eq!(uci(&g, "b7b8"), Ok("b7b8q".to_string()));
+    assert_eq!(
+        engine::parse_uci_move(&g, "e1c1").unwrap().to_string(),
+        "O-O-O"
+    );
+}
+
+#[test]
+fn notation() {
+    use engine::Notation::{Figurine, Long, San};
+    let mut g = engine::new_game();
+    let show =
+        |g: &engine::Game, s: &str, n| engine::parse_san(g, s).unwrap().notation(n).to_string();
+    assert_eq!(show(&g, "Nf3", San), "Nf3");
+    assert_eq!(show(&g, "Nf3", Long), "Ng1-f3");
+    assert_eq!(show(&g, "Nf3", Figurine), "\u{2658}f3");
+    assert_eq!(show(&g, "e4", Long), "e2-e4");
+
+    engine::set_fen(&mut g, "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(show(&g, "O-O", Long), "O-O");
+    assert_eq!(show(&g, "bxa8=N", Long), "b7xa8=N");
+    assert_eq!(show(&g, "bxa8=N", Figurine), "bxa8=\u{2658}");
+    assert_eq!(show(&g, "Rxa8+", Long), "Ra1xa8+");
+    assert_eq!(show(&g, "Rxa8+", Figurine), "\u{2656}xa8+");
+}
+
+#[test]
+fn epd() {
+    let mut g = engine::new_game();
+    let line = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+    let epd = engine::set_epd(&mut g, line).unwrap();
+    assert_eq!(epd.best_moves.len(), 1);
+    assert_eq!(epd.best_moves[0].to_string(), "Qg6");
+    assert_eq!(epd.id.as_deref(), Some("WAC.001"));
+    assert_eq!(engine::epd(&g, &epd), line);
+
+    let line = "4k3/8/8/8/8/8/4P3/4K3 w - - am Kd1 Kf1; ce -15; hmvc 7; fmvn 40; c0 \"a; b\";";
+    let epd = engine::set_epd(&mut g, line).unwrap();
+    assert_eq!(epd.avoid_moves.len(), 2);
+    assert_eq!(epd.centipawns, Some(-15));
+    assert_eq!(engine::fen(&g), "4k3/8/8/8/8/8/4P3/4K3 w - - 7 40");
+    assert_eq!(engine::epd(&g, &epd), line);
+
+    let bad = "4k3/8/8/8/8/8/4P3/4K3 w - - bm Ke3;";
+    assert_eq!(
+        engine::set_epd(&mut g, bad),
+        Err(engine::EpdError::Move(SanError::IllegalMove))
+    );
+    let bad = "4k3/8/8/8/8/8/4P3/4K3 w - - id \"x;";
+    assert_eq!(engine::set_epd(&mut g, bad), Err(engine::EpdError::Syntax));
+}
+
+#[test]
+fn setup() {
+    use engine::{FenError, SetupError};
+    let mut g = engine::new_game();
+    let fen = |g: &mut engine::Game, s: &str| engine::set_fen(g, s).map(|_| engine::fen(g));
+    let err = |e| Err(FenError::Setup(e));
+    assert_eq!(
+        fen(&mut g, "8/8/8/8/8/8/8/4K3 w - - 0 1"),
+        err(SetupError::MissingKing)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/8/8/8/8/2K1K3 w - - 0 1"),
+        err(SetupError::MissingKing)
+    );
+    assert_eq!(
+        fen(&mut g, "4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+        err(SetupError::PawnOnBackRank)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/8/8/8/8/p3K3 b - - 0 1"),
+        err(SetupError::PawnOnBackRank)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/8/8/8/8/4K2r b - - 0 1"),
+        err(SetupError::OpponentInCheck)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/8/8/8/8/4K3 w K - 0 1"),
+        err(SetupError::InvalidCastling)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/3pP3/8/8/8/4K3 w - e6 0 1"),
+        err(SetupError::InvalidEnPassant)
+    );
+    assert_eq!(
+        fen(&mut g, "4k3/8/8/3pP3/8/8/8/4K3 b - d6 0 1"),
+        err(SetupError::InvalidEnPassant)
+    );
+    // a zero in a rank is no count of empty squares
+    for bad in [
+        "rnbqkbnr/pppppppp/80/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "4k3/8/8/08/8/8/8/4K3 w - - 0 1",
+    ] {
+        assert_eq!(fen(&mut g, bad), Err(FenError::InvalidBoard));
+    }
+    // a failed setup keeps the previous position
+    assert_eq!(
+        engine::fen(&g),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    );
+    for ok in [
+        "4k3/8/8/3pP3/8/8/8/4K2r w - d6 0 1",
+        "r3k3/8/8/8/8/8/8/4K2R b Kq - 0 1",
+        "4k2r/8/8/8/8/8/8/R3K3 w Qk - 0 1",
+    ] {
+        assert_eq!(fen(&mut g, ok), Ok(ok.to_string()));
+    }
+
+    let mut board = [engine::VOID_ID; 64];
+    board[3] = engine::KING_ID;
+    board[59] = -engine::KING_ID;
+    board[11] = engine::QUEEN_ID;
+    assert_eq!(
+        engine::set_board(&mut g, &board, engine::COLOR_BLACK, [false; 4], None),
+        Ok(())
+    );
+    assert_eq!(engine::fen(&g), "4k3/8/8/8/8/8/4Q3/4K3 b - - 0 1");
+    assert_eq!(
+        engine::set_board(&mut g, &board, engine::COLOR_WHITE, [false; 4], None),
+        Err(SetupError::OpponentInCheck)
+    );
+    board[12] = 7;
+    assert_eq!(
+        engine::set_board(&mut g, &board, engine::COLOR_BLACK, [false; 4], None),
+        Err(SetupError::InvalidPiece)
+    );
+}
+
+#[test]
+fn pgn() {
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["f3", "e5", "g4", "Qh4#"]);
+    assert_eq!(engine::pgn_result(&g), "0-1");
+    let pgn = engine::pgn(&g, &[("White", "Fool"), ("Annotator", "\"tiny\"")]);
+    assert!(pgn.starts_with("[Event \"?\"]\n"));
+    assert!(pgn.contains("[White \"Fool\"]\n[Black \"?\"]\n[Result \"0-1\"]\n"));
+    assert!(pgn.contains("[Annotator \"\\\"tiny\\\"\"]\n"));
+    assert!(!pgn.contains("[FEN"));
+    assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+    // the whole game is exported, wherever the current position is, a set up position too
+    engine::take_back(&mut g);
+    assert!(engine::pgn(&g, &[]).ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+    let pgn = engine::pgn(&g, &[("Result", "1-0")]);
+    assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 1-0\n"));
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/8/R3K3 b - - 0 40").unwrap();
+    let m = engine::parse_san(&g, "Kd7").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    let pgn = engine::pgn(&g, &[]);
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b - - 0 40\"]\n"));
+    assert!(pgn.ends_with("\n\n40... Kd7 *\n"));
+    // long games are wrapped
+    engine::reset_game(&mut g);
+    while engine::game_ply(&g) < 60 && engine::state(&g) == engine::GameState::Ongoing {
+        let m = engine::legal_moves(&g).last().unwrap();
+        engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    }
+    let pgn = engine::pgn(&g, &[]);
+    assert!(pgn.lines().skip(8).count() > 1);
+    assert!(pgn.lines().all(|l| l.len() < 80));
+}
+
+#[test]
+fn lichess_analysis_url() {
+    let mut g = common::small_game();
+    assert_eq!(
+        engine::lichess_analysis_url(&g),
+        "https://lichess.org/analysis/rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR_w_KQkq_-_0_1"
+    );
+    common::play_san(&mut g, &["e4", "e5", "Nf3"]);
+    let url = engine::lichess_analysis_url(&g);
+    assert!(url.starts_with("https://lichess.org/analysis/pgn/%5BEvent%20%22%3F%22%5D%0A"));
+    assert!(url.ends_with("%0A%0A1.%20e4%20e5%202.%20Nf3%20%2A%0A"));
+    // the game, wherever the current position is
+    engine::go_to_ply(&mut g, 0);
+    assert_eq!(engine::lichess_analysis_url(&g), url);
+}
+
+#[test]
+fn pgn_annotations() {
+    let mut g = common::small_game();
+    common::play_san(&mut g, &["e4", "e5", "Nf3"]);
+    let nf3 = engine::current_node(&g);
+    let e5 = engine::node_parent(&g, nf3);
+    engine::set_node_nags(&mut g, nf3, &[1]);
+    engine::set_node_comment(&mut g, e5, "the {open} game");
+    engine::set_node_comment(&mut g, engine::ROOT_NODE, "A classic");
+    let pgn = engine::pgn(&g, &[("White", "Stefan")]);
+    assert!(pgn.ends_with("\n\n{A classic} 1. e4 e5 {the {open) game} 2. Nf3 $1 *\n"));
+    // the PGN is read back with its tags and annotations
+    let mut h = engine::new_game();
+    let tags = engine::set_pgn(&mut h, &pgn).unwrap();
+    assert!(tags.contains(&("White".to_string(), "Stefan".to_string())));
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    let nf3 = engine::current_node(&h);
+    assert_eq!(engine::node_nags(&h, nf3), [1]);
+    let e5 = engine::node_parent(&h, nf3);
+    assert_eq!(engine::node_comment(&h, e5), "the {open) game");
+    assert_eq!(engine::node_comment(&h, engine::ROOT_NODE), "A classic");
+    // variations, symbols and move numbers without space
+    let text = "1.e4 c5!? (1...e5 2.Nf3 (2.f4 exf4) Nc6) ; Sicilian\n2.Nf3 $14 d6 3.O-O?? 1-0";
+    assert_eq!(
+        engine::set_pgn(&mut h, text),
+        Err(engine::PgnError::Move(
+            "3.O-O??".to_string(),
+            engine::SanError::IllegalMove
+        ))
+    );
+    assert_eq!(engine::node_nags(&h, engine::current_node(&h)), [1]);
+    let text = text.replace("3.O-O??", "3.d4");
+    engine::set_pgn(&mut h, &text).unwrap();
+    let san: Vec<String> = engine::move_list(&h)
+        .iter()
+        .map(|m| m.to_string())
+        .collect();
+    assert_eq!(san, ["e4", "c5", "Nf3", "d6", "d4"]);
+    let e4 = engine::node_children(&h, engine::ROOT_NODE)[0];
+    let replies = engine::node_children(&h, e4);
+    assert_eq!(engine::node_move(&h, replies[1]).to_string(), "e5");
+    assert_eq!(engine::node_nags(&h, replies[0]), [5]);
+    assert_eq!(engine::node_comment(&h, replies[0]), "Sicilian");
+    let nf3 = engine::node_children(&h, replies[1])[0];
+    assert_eq!(engine::node_children(&h, nf3).len(), 1);
+    let f4 = engine::node_children(&h, engine::node_parent(&h, nf3))[1];
+    assert_eq!(engine::node_move(&h, f4).to_string(), "f4");
+    assert_eq!(
+        engine::set_pgn(&mut h, "1. e4 (1. d4"),
+        Err(engine::PgnError::Variation)
+    );
+    assert_eq!(
+        engine::set_pgn(&mut h, "[White Stefan]\n1. e4"),
+        Err(engine::PgnError::Tag)
+    );
+}
+
+#[test]
+fn pgn_variations() {
+    let text = "1. e4 c5 (1... e5 2. Nf3 (2. f4 exf4) 2... Nc6) 2. Nf3 $14 {Sicilian} 2... d6 *";
+    let mut g = engine::new_game();
+    engine::set_pgn(&mut g, text).unwrap();
+    assert!(engine::pgn(&g, &[]).ends_with(&format!("\n\n{}\n", text)));
+    // the current position is in a variation
+    let e4 = engine::node_children(&g, engine::ROOT_NODE)[0];
+    let e5 = engine::node_children(&g, e4)[1];
+    let f4 = engine::node_children(&g, e5)[1];
+    engine::go_to_node(&mut g, f4);
+    assert_eq!(engine::game_ply(&g), 3);
+    assert!(engine::pgn(&g, &[]).ends_with(&format!("\n\n{}\n", text)));
+    engine::go_to_ply(&mut g, 0);
+    assert!(engine::pgn(&g, &[]).ends_with(&format!("\n\n{}\n", text)));
+}
+
+#[test]
+fn compact() {
+    let mut g = common::small_game();
+    assert_eq!(engine::compact(&g), "");
+    common::play_san(&mut g, &["e4", "e5", "Nf3"]);
+    let s = engine::compact(&g);
+    assert_eq!(s.len(), 4);
+    assert!(s
+        .bytes()
+        .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+    let mut h = engine::new_game();
+    engine::set_compact(&mut h, &s).unwrap();
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    assert_eq!(engine::game_ply(&h), 3);
+
+    // only the moves up to the current position
+    engine::go_to_ply(&mut g, 1);
+    engine::set_compact(&mut h, &engine::compact(&g)).unwrap();
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+
+    let fen = "r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    engine::set_fen(&mut g, fen).unwrap();
+    let m = engine::parse_san(&g, "bxa8=N").unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    engine::set_compact(&mut h, &engine::compact(&g)).unwrap();
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    engine::go_to_ply(&mut h, 0);
+    assert_eq!(engine::fen(&h), fen);
+
+    assert_eq!(
+        engine::set_compact(&mut h, "e4!"),
+        Err(engine::CompactError::Base64)
+    );
+    assert_eq!(
+        engine::set_compact(&mut h, "AAD_"),
+        Err(engine::CompactError::Move(2))
+    );
+    assert_eq!(engine::fen(&h), fen);
+
+    // the variant is part of the string, its start position is not
+    engine::set_variant(&mut g, variant::HORDE);
+    assert_eq!(engine::compact(&g).len(), 3);
+    engine::set_variant(&mut g, variant::KING_OF_THE_HILL);
+    common::play_san(&mut g, &["e4", "e5"]);
+    engine::set_compact(&mut h, &engine::compact(&g)).unwrap();
+    assert_eq!(engine::variant(&h).name(), variant::KING_OF_THE_HILL.name());
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    engine::set_compact(&mut h, &s).unwrap();
+    assert_eq!(engine::variant(&h).name(), variant::STANDARD.name());
+    // the bytes 0xfe 0x09
+    assert_eq!(
+        engine::set_compact(&mut h, "_gk"),
+        Err(engine::CompactError::Variant(9))
+    );
+}
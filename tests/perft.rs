@@ -0,0 +1,54 @@
+// Perft node counts of well known test positions, see
+// https://www.chessprogramming.org/Perft_Results
+// The engine allocates a large transposition table, so all positions share one game.
+
+use tiny_chess::engine::{self, Game};
+
+fn perft(g: &mut Game, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves: Vec<engine::Move> = engine::legal_moves(g).collect();
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for m in moves {
+        let token = engine::do_move(g, m.src, m.dst, m.promote_to, false);
+        nodes += perft(g, depth - 1);
+        engine::undo_move(g, token);
+    }
+    nodes
+}
+
+#[test]
+fn perft_positions() {
+    let mut g = engine::new_game();
+    for (fen, depth, nodes) in [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            3,
+            8902,
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            2,
+            2039,
+        ),
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3, 2812),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            2,
+            264,
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            2,
+            1486,
+        ),
+    ] {
+        engine::set_fen(&mut g, fen).unwrap();
+        assert_eq!(perft(&mut g, depth), nodes, "{}", fen);
+        assert_eq!(engine::fen(&g), fen);
+    }
+}
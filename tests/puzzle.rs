@@ -0,0 +1,68 @@
+// Puzzles in the CSV format of the lichess puzzle database.
+
+use tiny_chess::engine;
+use tiny_chess::puzzle::{self, PuzzleError};
+
+const CSV: &str = "\
+PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00008,r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24,f2g3 e6e7 b2b1 b3c1 b1c1 h6c1,1913,75,94,6230,crushing hangingPiece long middlegame,https://lichess.org/787zsVup/black#48,
+0000D,5rk1/1p3ppp/pq3b2/8/8/1P1Q1N2/P4PPP/3R2K1 w - - 2 27,d3d6 f8d8 d6d8 f6d8,1504,74,96,27540,advantage endgame short,https://lichess.org/F8M8OS71#53,
+broken line
+";
+
+#[test]
+fn read_puzzles() {
+    let puzzles = puzzle::read_puzzles(CSV.as_bytes(), 10).unwrap();
+    assert_eq!(puzzles.len(), 2);
+    assert_eq!(puzzles[0].id, "00008");
+    assert_eq!(puzzles[0].rating, 1913);
+    assert_eq!(puzzles[0].moves.len(), 6);
+    assert_eq!(puzzles[1].themes, ["advantage", "endgame", "short"]);
+    assert_eq!(puzzle::read_puzzles(CSV.as_bytes(), 1).unwrap().len(), 1);
+    assert_eq!(puzzle::parse_puzzle("1,2,3"), Err(PuzzleError::Fields));
+    assert_eq!(
+        puzzle::parse_puzzle("1,8/8/8/8/8/8/8/8 w - - 0 1,a1a2 a2a3,x"),
+        Err(PuzzleError::Rating)
+    );
+
+    // the moves are legal
+    let mut g = engine::new_game();
+    for p in &puzzles {
+        puzzle::set_puzzle(&mut g, p).unwrap();
+        assert_eq!(engine::fen(&g), p.fen);
+    }
+    let mut bad = puzzles[1].clone();
+    bad.moves[1] = "f8f1".to_string();
+    assert_eq!(
+        puzzle::set_puzzle(&mut g, &bad),
+        Err(PuzzleError::Move(
+            "f8f1".to_string(),
+            engine::SanError::IllegalMove
+        ))
+    );
+    assert_eq!(engine::fen(&g), puzzles[1].fen);
+}
+
+#[test]
+fn solution() {
+    // a back rank mate with either rook
+    let p =
+        puzzle::parse_puzzle("1,6k1/5ppp/8/8/8/8/5PPP/R3R1K1 b - - 0 1,g8h8 a1a8,1000").unwrap();
+    let mut g = engine::new_game();
+    puzzle::set_puzzle(&mut g, &p).unwrap();
+    let m = puzzle::puzzle_move(&g, &p, 0).unwrap();
+    engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    for (uci, solves) in [("a1a8", true), ("e1e8", true), ("a1a7", false)] {
+        let m = engine::parse_uci_move(&g, uci).unwrap();
+        assert_eq!(puzzle::is_solution(&g, &p, 1, &m), solves, "{}", uci);
+    }
+    assert_eq!(puzzle::puzzle_move(&g, &p, 2), None);
+}
+
+#[test]
+fn rating() {
+    assert_eq!(puzzle::new_rating(1500, 1500, true), 1516);
+    assert_eq!(puzzle::new_rating(1500, 1500, false), 1484);
+    assert!(puzzle::new_rating(1500, 2500, true) > 1530);
+    assert!(puzzle::new_rating(1500, 500, false) < 1470);
+}
@@ -0,0 +1,63 @@
+// The lines of a repertoire and their spaced repetition.
+
+use tiny_chess::engine;
+use tiny_chess::repertoire::{self, Card, DAY};
+
+#[test]
+fn lines() {
+    let mut g = engine::new_game();
+    engine::set_pgn(&mut g, "1. e4 e5 (1... c5 2. Nf3) 2. Nf3 Nc6 (2... d6) *").unwrap();
+    let keys: Vec<String> = repertoire::lines(&g)
+        .iter()
+        .map(|line| repertoire::line_key(line))
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            "e2e4 e7e5 g1f3 b8c6",
+            "e2e4 e7e5 g1f3 d7d6",
+            "e2e4 c7c5 g1f3"
+        ]
+    );
+    assert!(repertoire::lines(&engine::new_game()).is_empty());
+}
+
+#[test]
+fn review() {
+    let mut card = Card::default();
+    let now = 1000 * DAY;
+    repertoire::review(&mut card, now, true);
+    assert_eq!(
+        card,
+        Card {
+            level: 1,
+            due: now + DAY
+        }
+    );
+    repertoire::review(&mut card, now, true);
+    assert_eq!(
+        card,
+        Card {
+            level: 2,
+            due: now + 2 * DAY
+        }
+    );
+    for _ in 0..20 {
+        repertoire::review(&mut card, now, true);
+    }
+    assert_eq!(card.due, now + 128 * DAY);
+    repertoire::review(&mut card, now, false);
+    assert_eq!(card, Card { level: 0, due: now });
+
+    let cards = [
+        Card {
+            level: 3,
+            due: now + DAY,
+        },
+        Card { level: 1, due: now },
+        Card { level: 0, due: now },
+    ];
+    assert_eq!(repertoire::next_card(&cards), Some(2));
+    assert_eq!(repertoire::due_cards(&cards, now), 2);
+    assert_eq!(repertoire::next_card(&[]), None);
+}
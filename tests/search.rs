@@ -0,0 +1,195 @@
+// The search, followed with a SearchObserver.
+
+mod common;
+
+use tiny_chess::engine;
+
+#[derive(Default)]
+struct Recorder {
+    depths: Vec<i64>,
+    pvs: Vec<String>,
+    pv: Vec<engine::Move>, // of the last iteration
+    info: Option<engine::SearchInfo>,
+    batches: u64,
+}
+
+impl engine::SearchObserver for Recorder {
+    fn on_depth_complete(&mut self, info: &engine::SearchInfo) {
+        self.depths.push(info.depth);
+        self.pv = info.pv.clone();
+        self.info = Some(info.clone());
+    }
+    fn on_new_pv(&mut self, info: &engine::SearchInfo) {
+        self.pvs.push(info.best_move.to_string());
+    }
+    fn on_node_batch(&mut self, _nodes: u64) {
+        self.batches += 1;
+    }
+}
+
+#[test]
+fn search_observer() {
+    // a small table, so a short search fills a measurable part of it for hashfull
+    let mut g = common::small_game();
+    engine::set_option(&mut g, "MoveTime", "500").unwrap();
+    let mut r = Recorder::default();
+    let m = engine::reply(&mut g, &mut r);
+    assert!(!r.depths.is_empty());
+    assert_eq!(r.depths, (1..=r.depths.len() as i64).collect::<Vec<_>>());
+    let best = engine::new_move(&g, m.src as i8, m.dst as i8, m.promote_to);
+    assert_eq!(r.pvs.last(), Some(&best.to_string()));
+    assert!(r.batches > 0);
+    let info = r.info.unwrap();
+    assert!(info.seldepth >= info.depth);
+    assert!(info.hashfull > 0 && info.hashfull <= 1000);
+    assert!(info.nps() > 0);
+    // the principal variation is a legal line, and the game is unchanged
+    assert_eq!(r.pv[0].uci().to_string(), best.uci().to_string());
+    assert!(r.pv.len() <= r.depths.len());
+    for m in &r.pv {
+        let san = engine::parse_san(&g, &m.to_string()).unwrap();
+        assert_eq!(san.uci().to_string(), m.uci().to_string());
+        engine::do_move(&mut g, m.src, m.dst, m.promote_to, false);
+    }
+    assert_eq!(engine::game_ply(&g), r.pv.len());
+}
+
+#[test]
+fn options() {
+    use engine::{OptionError, OptionValue};
+    let mut g = engine::new_game();
+    assert_eq!(
+        engine::get_option(&g, "MoveTime"),
+        Some(&OptionValue::Spin(1500))
+    );
+    assert_eq!(engine::set_option(&mut g, "movetime", "300"), Ok(()));
+    assert_eq!(
+        engine::get_option(&g, "MoveTime"),
+        Some(&OptionValue::Spin(300))
+    );
+    assert_eq!(
+        engine::set_option(&mut g, "MoveTime", "0"),
+        Err(OptionError::InvalidValue)
+    );
+    assert_eq!(
+        engine::set_option(&mut g, "MoveTime", "x"),
+        Err(OptionError::InvalidValue)
+    );
+    assert_eq!(
+        engine::set_option(&mut g, "Ponder", "true"),
+        Err(OptionError::UnknownOption)
+    );
+    assert_eq!(engine::get_option(&g, "Ponder"), None);
+    assert_eq!(engine::set_option(&mut g, "Hash", "16"), Ok(()));
+    assert_eq!(engine::get_option(&g, "Hash").unwrap().to_string(), "16");
+    // the search still works with the small table
+    engine::set_fen(&mut g, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    let m = engine::reply(&mut g, &mut ());
+    let mv = engine::new_move(&g, m.src as i8, m.dst as i8, m.promote_to);
+    assert_eq!(mv.uci().to_string(), "a1a8");
+}
+
+#[test]
+fn personas() {
+    use engine::{OptionError, OptionValue};
+    let mut g = common::small_game();
+    assert_eq!(
+        engine::get_option(&g, "Persona"),
+        Some(&OptionValue::String("Full".to_string()))
+    );
+    assert_eq!(
+        engine::set_option(&mut g, "Persona", "Strong"),
+        Err(OptionError::InvalidValue)
+    );
+    assert_eq!(engine::set_option(&mut g, "persona", "random"), Ok(()));
+    assert_eq!(
+        engine::get_option(&g, "Persona").unwrap().to_string(),
+        "Random"
+    );
+    // legal moves, and not always the same
+    let mut replies = Vec::new();
+    for _ in 0..20 {
+        let m = engine::reply(&mut g, &mut ());
+        assert!(engine::move_is_valid2(&g, m.src, m.dst));
+        replies.push((m.src, m.dst));
+    }
+    replies.sort();
+    replies.dedup();
+    assert!(replies.len() > 1);
+
+    // the defended pawn is captured anyway
+    engine::set_option(&mut g, "Persona", "Greedy").unwrap();
+    engine::set_fen(&mut g, "4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+    let m = engine::reply(&mut g, &mut ());
+    assert_eq!((m.src, m.dst), (4, 36)); // Qxd5
+
+    // the rook is worth more than the knight, and a queen more than a knight
+    engine::set_option(&mut g, "Persona", "Materialist").unwrap();
+    engine::set_fen(&mut g, "k7/8/8/1n1r4/2Q5/8/8/4K3 w - - 0 1").unwrap();
+    let m = engine::reply(&mut g, &mut ());
+    assert_eq!((m.src, m.dst), (29, 36)); // Qxd5
+    engine::set_fen(&mut g, "k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let m = engine::reply(&mut g, &mut ());
+    assert_eq!((m.dst, m.promote_to), (59, engine::QUEEN_ID)); // e8=Q
+    assert!(m.score > 0);
+}
+
+#[test]
+fn engine_handle() {
+    use std::time::{Duration, Instant};
+    use tiny_chess::handle::{Command, EngineHandle, Event};
+    let mut position = engine::new_game();
+    let handle = EngineHandle::new(engine::new_game());
+    let best_move = |position: &engine::Game| loop {
+        match handle.wait_event() {
+            Some(Event::BestMove { key, result }) => {
+                assert_eq!(key, engine::position_key(position));
+                let m = engine::new_move(position, result.src as i8, result.dst as i8, 0);
+                return m.uci().to_string();
+            }
+            Some(_) => {}
+            None => panic!("engine thread terminated"),
+        }
+    };
+    handle.send(Command::SetOption("MoveTime".into(), "200".into()));
+    engine::set_fen(&mut position, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    handle.send(Command::SetPosition(position.clone()));
+    handle.send(Command::Go);
+    assert_eq!(best_move(&position), "a1a8");
+
+    // a long search ends early with Stop
+    handle.send(Command::SetOption("MoveTime".into(), "9000".into()));
+    engine::reset_game(&mut position);
+    handle.send(Command::SetPosition(position.clone()));
+    let start = Instant::now();
+    handle.send(Command::Go);
+    std::thread::sleep(Duration::from_millis(300));
+    handle.send(Command::Stop);
+    best_move(&position);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    handle.send(Command::SetOption("Nonsense".into(), "1".into()));
+    assert!(matches!(
+        handle.wait_event(),
+        Some(Event::OptionError(engine::OptionError::UnknownOption))
+    ));
+}
+
+#[test]
+fn clock_time_management() {
+    use std::time::{Duration, Instant};
+    let mut g = engine::new_game();
+    engine::set_option(&mut g, "MoveTime", "9000").unwrap();
+    // with 3 seconds on the clock the engine must not use the 9 seconds of MoveTime
+    engine::set_time_left(&mut g, Some((Duration::from_secs(3), Duration::ZERO)));
+    let start = Instant::now();
+    engine::reply(&mut g, &mut ());
+    assert!(start.elapsed() < Duration::from_millis(1500));
+    // set_position() copies the clock
+    let mut copy = g.clone();
+    engine::set_time_left(&mut copy, None);
+    engine::set_position(&mut copy, &g);
+    let start = Instant::now();
+    engine::reply(&mut copy, &mut ());
+    assert!(start.elapsed() < Duration::from_millis(1500));
+}
@@ -0,0 +1,48 @@
+// A game serialized with serde and read back. Needs the "serde" feature:
+// cargo test --features serde
+#![cfg(feature = "serde")]
+
+mod common;
+
+use tiny_chess::engine::{self, Game, GameState};
+use tiny_chess::variant;
+
+fn round_trip(g: &Game) -> Game {
+    serde_json::from_str(&serde_json::to_string(g).unwrap()).unwrap()
+}
+
+#[test]
+fn moves_and_history() {
+    let mut g = engine::new_game();
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/4P3/4K2R w K - 0 30").unwrap();
+    common::play_san(
+        &mut g,
+        &["Rh2", "Kd8", "Rh1", "Ke8", "Rh2", "Kd8", "Rh1", "Kd7"],
+    );
+    engine::take_back(&mut g);
+    let mut h = round_trip(&g);
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    assert_eq!(engine::move_list(&h), engine::move_list(&g));
+    assert_eq!(engine::game_ply(&h), 7);
+    // the castling right is lost, and the repetitions are known
+    assert_eq!(engine::castling_rights(&h), [false; 4]);
+    // the taken back move can be played again
+    assert_eq!(engine::redo_move(&mut h).unwrap().to_string(), "Kd7");
+    engine::take_back(&mut h);
+    common::play_san(&mut h, &["Ke8"]);
+    assert_eq!(engine::state(&h), GameState::DrawRepetition);
+}
+
+#[test]
+fn variant() {
+    let mut g = engine::new_game();
+    engine::set_variant(&mut g, variant::KING_OF_THE_HILL);
+    common::play_san(&mut g, &["e4"]);
+    let h = round_trip(&g);
+    assert_eq!(engine::variant(&h).name(), variant::KING_OF_THE_HILL.name());
+    assert_eq!(engine::fen(&h), engine::fen(&g));
+    // an unknown variant is an error, not a standard game
+    let json = serde_json::to_string(&g).unwrap();
+    let json = json.replace(variant::KING_OF_THE_HILL.name(), "Crazyhouse");
+    assert!(serde_json::from_str::<Game>(&json).is_err());
+}
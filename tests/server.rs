@@ -0,0 +1,103 @@
+// The JSON API of the server mode over HTTP and WebSocket, see src/server.rs. Needs the
+// "server" feature:
+// cargo test --features server
+#![cfg(feature = "server")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use tiny_chess::server;
+use tungstenite::Message;
+
+fn start() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || server::run(listener));
+    address
+}
+
+// the status and the body of the answer
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+    let status = answer[9..12].parse().unwrap();
+    let body = answer.split_once("\r\n\r\n").unwrap().1.to_owned();
+    (status, body)
+}
+
+#[test]
+fn http() {
+    let address = start();
+    let (status, body) = request(address, "POST", "/position", r#"{"moves": ["e2e4"]}"#);
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""moves":["e2e4"]"#), "{}", body);
+    let (status, body) = request(address, "POST", "/move", r#"{"move": "e5"}"#);
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""moves":["e2e4","e7e5"]"#), "{}", body);
+    let (_, body) = request(address, "GET", "/position", "");
+    assert!(body.contains(r#""state":"ongoing""#), "{}", body);
+    let (status, body) = request(address, "POST", "/bestmove", r#"{"movetime": 100}"#);
+    assert_eq!(status, 200);
+    assert!(body.contains(r#""bestmove":"#), "{}", body);
+
+    let (status, body) = request(address, "POST", "/move", r#"{"move": "Ke3"}"#);
+    assert_eq!(status, 400);
+    assert!(body.contains(r#""error":"#), "{}", body);
+    assert_eq!(request(address, "POST", "/move", "{").0, 400);
+    assert_eq!(request(address, "GET", "/unknown", "").0, 404);
+    assert_eq!(request(address, "OPTIONS", "/bestmove", "").0, 204);
+}
+
+#[test]
+fn websocket() {
+    let address = start();
+    let (mut ws, _) = tungstenite::connect(format!("ws://{}/ws", address)).unwrap();
+    let mut send = |text: &str| ws.send(Message::text(text)).unwrap();
+    // a mate in one
+    send(r#"{"type": "position", "fen": "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"}"#);
+    send(r#"{"type": "go", "movetime": 500}"#);
+    let mut infos = 0;
+    loop {
+        let Message::Text(text) = ws.read().unwrap() else {
+            continue;
+        };
+        if text.contains(r#""type":"info""#) {
+            infos += 1;
+        } else if text.contains(r#""type":"bestmove""#) {
+            assert!(text.contains(r#""bestmove":"a1a8""#), "{}", text);
+            assert!(text.contains(r#""san":"Ra8#""#), "{}", text);
+            break;
+        } else {
+            assert!(text.contains(r#""fen":"#), "{}", text);
+        }
+    }
+    assert!(infos > 0);
+    ws.send(Message::text(r#"{"type": "unknown"}"#)).unwrap();
+    let answer = ws.read().unwrap().into_text().unwrap();
+    assert!(answer.contains(r#""error":"#), "{}", answer);
+}
+
+#[test]
+fn websocket_limit() {
+    let address = start();
+    let url = format!("ws://{}/ws", address);
+    let open: Vec<_> = (0..4)
+        .map(|_| tungstenite::connect(&url).unwrap())
+        .collect();
+    match tungstenite::connect(&url) {
+        Err(tungstenite::Error::Http(response)) => assert_eq!(response.status(), 503),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    drop(open);
+}
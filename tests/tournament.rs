@@ -0,0 +1,114 @@
+// Matches between engines, see src/tournament.rs.
+
+use std::time::Duration;
+use tiny_chess::engine;
+use tiny_chess::tournament::{
+    self, Adjudication, Decision, Engine, GameRecord, Match, Opening, Sprt,
+};
+
+const BOOK: &str = "\
+[Event \"Ruy Lopez\"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 *
+
+[Event \"Sicilian\"]
+[Result \"*\"]
+
+1. e4 c5 *
+";
+
+#[test]
+fn openings() {
+    assert_eq!(engine::split_pgn(BOOK).len(), 2);
+    let openings = tournament::pgn_openings(BOOK);
+    assert_eq!(openings.len(), 2);
+    assert_eq!(openings[0].fen, engine::fen(&engine::new_game()));
+    assert_eq!(openings[0].moves, ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5"]);
+    assert_eq!(openings[1].moves, ["e2e4", "c7c5"]);
+
+    let epd = "# a comment\n8/8/8/8/8/8/8/K6k w - - bm Ka2;\ninvalid\n";
+    assert_eq!(
+        tournament::epd_openings(epd),
+        [Opening {
+            fen: "8/8/8/8/8/8/8/K6k w - -".to_owned(),
+            moves: Vec::new()
+        }]
+    );
+}
+
+#[test]
+fn results() {
+    let record = |white, result| GameRecord {
+        white,
+        result,
+        reason: String::new(),
+        pgn: String::new(),
+    };
+    let records = [record(0, "1-0"), record(1, "1-0"), record(0, "1/2-1/2")];
+    assert_eq!(records[1].points(0), 0.0);
+    assert_eq!(records[1].points(1), 1.0);
+    let table = tournament::results_table(["A", "B"], &records);
+    assert!(
+        table.contains("A          3     1     1      1    1.5"),
+        "{}",
+        table
+    );
+    assert!(table.ends_with("Elo difference of A: +0\n"), "{}", table);
+    assert_eq!(tournament::elo_difference(1.0), None);
+    assert!((tournament::elo_difference(0.75).unwrap() - 190.8).abs() < 0.1);
+}
+
+#[test]
+fn built_in_match() {
+    let options = [("MoveTime".to_owned(), "100".to_owned())];
+    let mut engines = [
+        Engine::built_in("Fast", &options).unwrap(),
+        Engine::built_in("Default", &[]).unwrap(),
+    ];
+    let m = Match {
+        games: 2,
+        tc: (Duration::from_secs(5), Duration::from_millis(50)),
+        openings: tournament::pgn_openings(BOOK),
+        adjudication: Adjudication {
+            max_plies: 16,
+            ..Default::default()
+        },
+    };
+    let mut finished = 0;
+    let records = tournament::play_match(&m, &mut engines, &mut |_| {
+        finished += 1;
+        true
+    });
+    assert_eq!(finished, 2);
+    assert_eq!((records[0].white, records[1].white), (0, 1));
+    for (record, white) in records.iter().zip(["Fast", "Default"]) {
+        assert!(record.pgn.contains(&format!("[White \"{}\"]", white)));
+        assert!(
+            record.pgn.contains("1. e4 e5 2. Nf3 Nc6 3. Bb5"),
+            "{}",
+            record.pgn
+        );
+        assert!(record.pgn.contains(record.result));
+    }
+    assert!(Engine::built_in("Invalid", &[("Hash".to_owned(), "x".to_owned())]).is_err());
+}
+
+#[test]
+fn sprt() {
+    let sprt = Sprt::default();
+    let (lower, upper) = sprt.bounds();
+    assert!((lower + 2.94).abs() < 0.01 && (upper - 2.94).abs() < 0.01);
+    assert_eq!(sprt.llr(0, 0, 0), 0.0);
+    // the results do not vary, the pseudo counts decide anyway
+    let wins = sprt.llr(100, 0, 0);
+    assert_eq!(sprt.decision(wins), Decision::AcceptH1, "{}", wins);
+    assert_eq!(sprt.decision(sprt.llr(10, 0, 0)), Decision::Continue);
+    let draws = sprt.llr(0, 300, 0);
+    assert_eq!(sprt.decision(draws), Decision::AcceptH0, "{}", draws);
+    // a score of 60 % passes, one of 40 % fails
+    let pass = sprt.llr(600, 200, 400);
+    let fail = sprt.llr(400, 200, 600);
+    assert_eq!(sprt.decision(pass), Decision::AcceptH1, "{}", pass);
+    assert_eq!(sprt.decision(fail), Decision::AcceptH0, "{}", fail);
+    assert_eq!(sprt.decision(sprt.llr(51, 0, 49)), Decision::Continue);
+}
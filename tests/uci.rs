@@ -0,0 +1,85 @@
+// The translation of the commands and answers of external UCI engines.
+
+mod common;
+
+use tiny_chess::{engine, uci};
+
+#[test]
+fn commands() {
+    let mut g = common::small_game();
+    let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    assert_eq!(
+        uci::position_command(&mut g),
+        format!("position fen {}", start)
+    );
+    common::play_san(&mut g, &["e4", "e5"]);
+    let key = engine::position_key(&g);
+    assert_eq!(
+        uci::position_command(&mut g),
+        format!("position fen {} moves e2e4 e7e5", start)
+    );
+    assert_eq!(engine::position_key(&g), key);
+
+    engine::set_option(&mut g, "MoveTime", "500").unwrap();
+    assert_eq!(uci::go_command(&g), "go movetime 500");
+    let clock = (
+        std::time::Duration::from_secs(60),
+        std::time::Duration::from_secs(2),
+    );
+    engine::set_time_left(&mut g, Some(clock));
+    assert_eq!(
+        uci::go_command(&g),
+        "go wtime 60000 btime 60000 winc 2000 binc 2000"
+    );
+}
+
+#[test]
+fn answers() {
+    let line = "option name Hash type spin default 16 min 1 max 33554432";
+    assert_eq!(uci::parse_option(line), Some("Hash".to_owned()));
+    let line = "option name Clear Hash type button";
+    assert_eq!(uci::parse_option(line), Some("Clear Hash".to_owned()));
+    assert_eq!(uci::parse_option("id name Stockfish"), None);
+
+    assert_eq!(
+        uci::parse_bestmove("bestmove e2e4 ponder e7e5"),
+        Some("e2e4")
+    );
+    assert_eq!(uci::parse_bestmove("info depth 1"), None);
+
+    let mut g = engine::new_game();
+    let line = "info depth 12 seldepth 17 multipv 1 score cp 31 nodes 48012 nps 787000 hashfull 12 time 61 pv e2e4 e7e5 g1f3";
+    let info = uci::parse_info(&mut g, line).unwrap();
+    assert_eq!((info.depth, info.seldepth, info.score), (12, 17, 31));
+    assert_eq!((info.nodes, info.hashfull), (48012, 12));
+    assert_eq!(info.time, std::time::Duration::from_millis(61));
+    let pv: Vec<String> = info.pv.iter().map(|m| m.uci().to_string()).collect();
+    assert_eq!(pv, ["e2e4", "e7e5", "g1f3"]);
+    assert_eq!(info.best_move.uci().to_string(), "e2e4");
+    assert_eq!(engine::game_ply(&g), 0); // the pv was taken back
+
+    // mate in 2 like the scores of the own engine
+    let line = "info depth 5 score mate 2 pv d2d4";
+    let info = uci::parse_info(&mut g, line).unwrap();
+    assert_eq!(info.score, engine::KING_VALUE as i64 - 2);
+    let line = "info depth 5 score mate -2 pv d2d4";
+    assert_eq!(
+        uci::parse_info(&mut g, line).unwrap().score,
+        3 - engine::KING_VALUE as i64
+    );
+
+    // no pv, a secondary variation, a string
+    assert!(uci::parse_info(&mut g, "info depth 3 currmove e2e4 currmovenumber 1").is_none());
+    assert!(uci::parse_info(&mut g, "info multipv 2 score cp 10 pv d2d4").is_none());
+    assert!(uci::parse_info(&mut g, "info string NNUE enabled").is_none());
+}
+
+// cat echoes quit instead of exiting, the engine is killed when dropped
+#[cfg(unix)]
+#[test]
+fn quit_timeout() {
+    let engine = uci::Engine::spawn("cat").unwrap();
+    let start = std::time::Instant::now();
+    drop(engine);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
@@ -0,0 +1,136 @@
+// The variant framework, with a variant of the test: the side which captures the queen
+// of the opponent wins.
+
+mod common;
+
+use tiny_chess::engine::{self, Board, Color, FenError, Game, GameState, SetupError};
+use tiny_chess::variant::{self, Variant};
+
+struct QueenHunt;
+
+impl Variant for QueenHunt {
+    fn name(&self) -> &'static str {
+        "Queen Hunt"
+    }
+
+    fn winner(&self, _g: &Game, board: &Board) -> Option<Color> {
+        let has_queen = |c: Color| board.contains(&(engine::QUEEN_ID * c));
+        match (
+            has_queen(engine::COLOR_WHITE),
+            has_queen(engine::COLOR_BLACK),
+        ) {
+            (true, false) => Some(engine::COLOR_WHITE),
+            (false, true) => Some(engine::COLOR_BLACK),
+            _ => None,
+        }
+    }
+}
+
+static QUEEN_HUNT: QueenHunt = QueenHunt;
+
+#[test]
+fn standard() {
+    let mut g = engine::new_game();
+    assert_eq!(engine::variant(&g).name(), "Standard");
+    assert!(variant::by_name("standard").is_some());
+    assert!(variant::by_name("Queen Hunt").is_none());
+    engine::set_pgn(&mut g, "[Variant \"Standard\"]\n\n1. e4 *").unwrap();
+    assert!(!engine::pgn(&g, &[]).contains("Variant"));
+}
+
+#[test]
+fn own_variant() {
+    let mut g = engine::new_game();
+    engine::set_variant(&mut g, &QUEEN_HUNT);
+    assert_eq!(engine::fen(&g), engine::fen(&engine::new_game()));
+    engine::set_fen(
+        &mut g,
+        "rnb1kbnr/ppp1pppp/8/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 3",
+    )
+    .unwrap();
+    assert_eq!(engine::state(&g), GameState::Ongoing);
+    let m = engine::reply(&mut g, &mut ());
+    assert_eq!((m.src, m.dst), (21, 36)); // Nxd5 wins at once
+    assert!(m.score > engine::SURE_CHECKMATE as i64);
+    engine::do_move(&mut g, m.src as i8, m.dst as i8, m.promote_to, false);
+    assert_eq!(
+        engine::state(&g),
+        GameState::VariantWin(engine::COLOR_WHITE)
+    );
+    assert_eq!(engine::pgn_result(&g), "1-0");
+    assert!(engine::pgn(&g, &[]).contains("[Variant \"Queen Hunt\"]"));
+
+    // a game without Variant tag is standard chess
+    engine::set_pgn(&mut g, "1. e4 *").unwrap();
+    assert_eq!(engine::variant(&g).name(), "Standard");
+}
+
+#[test]
+fn king_of_the_hill() {
+    let mut g = engine::new_game();
+    engine::set_pgn(
+        &mut g,
+        "[Variant \"King of the Hill\"]\n[SetUp \"1\"]\n[FEN \"k7/8/8/8/8/4K3/8/8 w - - 0 1\"]\n\n*",
+    )
+    .unwrap();
+    assert_eq!(engine::variant(&g).name(), "King of the Hill");
+    assert_eq!(engine::state(&g), GameState::Ongoing);
+    // a bare king is no draw on the hill
+    let m = engine::reply(&mut g, &mut ());
+    assert!([27, 28].contains(&m.dst)); // e4 or d4
+    assert!(m.score > engine::SURE_CHECKMATE as i64);
+    engine::do_move(&mut g, m.src as i8, m.dst as i8, m.promote_to, false);
+    assert_eq!(
+        engine::state(&g),
+        GameState::VariantWin(engine::COLOR_WHITE)
+    );
+    let pgn = engine::pgn(&g, &[]);
+    assert!(pgn.contains("[Variant \"King of the Hill\"]") && pgn.contains("1-0"));
+
+    // a king in check on the hill has not won yet
+    engine::set_fen(&mut g, "k7/8/8/8/4K3/8/8/4r3 w - - 0 1").unwrap();
+    assert_eq!(engine::state(&g), GameState::Ongoing);
+
+    // the variant stays for a new game
+    engine::reset_game(&mut g);
+    assert_eq!(engine::variant(&g).name(), "King of the Hill");
+    assert!(engine::evaluate(&g).abs() < 50);
+}
+
+#[test]
+fn horde() {
+    let mut g = common::small_game();
+    // White has no king in Horde
+    let fen = "4k3/8/8/8/8/8/8/4P3 w - - 0 1";
+    assert_eq!(
+        engine::set_fen(&mut g, fen),
+        Err(FenError::Setup(SetupError::MissingKing))
+    );
+    engine::set_variant(&mut g, variant::HORDE);
+    assert_eq!(engine::fen(&g), variant::HORDE.start_fen());
+    assert_eq!(engine::state(&g), GameState::Ongoing);
+    let m = engine::reply(&mut g, &mut ()); // with more than 32 pieces
+    assert!(engine::move_is_valid2(&g, m.src, m.dst));
+
+    // a double step from the first rank, without en passant
+    engine::set_fen(&mut g, fen).unwrap();
+    assert!(!engine::is_in_check(&g, engine::COLOR_WHITE));
+    assert!(engine::move_is_valid2(&g, 3, 19)); // e1e3
+    engine::do_move(&mut g, 3, 19, 0, false);
+    assert_eq!(engine::fen(&g), "4k3/8/8/8/8/4P3/8/8 b - - 0 1");
+
+    // Black wins by capturing the last piece of White
+    engine::set_fen(&mut g, "4k3/8/8/8/8/8/3q4/4P3 b - - 0 1").unwrap();
+    let m = engine::reply(&mut g, &mut ());
+    assert_eq!((m.src, m.dst), (12, 3)); // Qxe1
+    engine::do_move(&mut g, m.src as i8, m.dst as i8, m.promote_to, false);
+    assert_eq!(
+        engine::state(&g),
+        GameState::VariantWin(engine::COLOR_BLACK)
+    );
+    let pgn = engine::pgn(&g, &[]);
+    assert!(pgn.contains("[Variant \"Horde\"]") && pgn.contains("0-1"));
+    engine::set_pgn(&mut g, &pgn).unwrap();
+    assert_eq!(engine::variant(&g).name(), "Horde");
+    assert_eq!(engine::pgn_result(&g), "0-1");
+}